@@ -7,17 +7,18 @@ const MESH_SPHERE: u64 = 2;
 use agate_engine::{
     core::{
         CHUNK_RESOLUTION, CHUNK_SIZE, Completer,
-        entity::{BoundingBox, CollisionResponse},
+        entity::{BoundingBox, CollisionResponse, MaterialKind},
         geometry::{EdgeJoin, Face, Mesh, Shape3},
     },
     render::{
         app::{App, MeshInitData, ObjectInitData, TextureInitData},
-        storage::textures::ResizeStrategy,
+        storage::textures::{ResizeStrategy, TextureKind},
         vertex::DefaultVertexType,
     },
 };
 use image::imageops::FilterType;
 use nalgebra::{UnitQuaternion, UnitVector3, Vector3};
+use wgpu::FrontFace;
 
 fn main() {
     agate_engine::init_logging(log::LevelFilter::Debug);
@@ -34,14 +35,18 @@ fn main() {
         image: image::load_from_memory(include_bytes!("assets/white-marble-2048x2048.png"))
             .unwrap(),
         resize: ResizeStrategy::Stretch(FilterType::Gaussian),
+        kind: TextureKind::Color,
+        size: None,
     });
 
     let penguin_model_completer = app
-        .add_obj_model("examples/rover/assets/PenguinBaseMesh.obj")
+        .add_obj_model("examples/rover/assets/PenguinBaseMesh.obj", FrontFace::Ccw)
         .unwrap();
     let penguin_texture_completer = app.add_texture(TextureInitData {
         image: image::load_from_memory(include_bytes!("assets/Penguin Diffuse Color.png")).unwrap(),
         resize: ResizeStrategy::Stretch(FilterType::Gaussian),
+        kind: TextureKind::Color,
+        size: None,
     });
 
     // app.add_player(PlayerInitData {
@@ -58,6 +63,9 @@ fn main() {
     //     translation: Vector3::new(0.0, 10.0, 0.0),
     //     response: CollisionResponse::Inelastic(1.0),
     //     mass: 100.0,
+    //     friction: 0.0,
+    //     yaw: 0.0,
+    //     pitch: 0.0,
     // });
 
     app.add_object(ObjectInitData {
@@ -71,12 +79,17 @@ fn main() {
         rotation: UnitQuaternion::identity(),
         translation: Vector3::zeros(),
         response: CollisionResponse::Inelastic(0.9),
+        friction: 0.0,
+        lifetime: None,
+        material: MaterialKind::Opaque,
+        emissive: 0.0,
     });
 
+    let mut penguins = vec![];
     for i in -3..4 {
         for j in -3..4 {
             for k in -3..4 {
-                app.add_object(ObjectInitData {
+                penguins.push(ObjectInitData {
                     mesh_id: penguin_model_completer.clone(),
                     texture_id: penguin_texture_completer.clone(),
                     velocity: Vector3::new(1.0, 1.0, 1.0),
@@ -93,10 +106,15 @@ fn main() {
                     ),
                     translation: Vector3::new(10.0 * i as f32, 10.0 * j as f32, 10.0 * k as f32),
                     response: CollisionResponse::Inelastic(0.9),
+                    friction: 0.0,
+                    lifetime: None,
+                    material: MaterialKind::Opaque,
+                    emissive: 0.0,
                 });
             }
         }
     }
+    app.add_objects(penguins);
 
     App::start(&mut app);
 }
@@ -279,18 +297,22 @@ fn get_sample_meshes() -> Vec<MeshInitData<DefaultVertexType>> {
         MeshInitData {
             vertices: cube2_mesh.vertices().to_vec(),
             indices: cube2_mesh.indices().to_vec(),
+            front_face: FrontFace::Ccw,
         },
         MeshInitData {
             vertices: roundish_mesh.vertices().to_vec(),
             indices: roundish_mesh.indices().to_vec(),
+            front_face: FrontFace::Ccw,
         },
         MeshInitData {
             vertices: sphere_mesh.vertices().to_vec(),
             indices: sphere_mesh.indices().to_vec(),
+            front_face: FrontFace::Ccw,
         },
         MeshInitData {
             vertices: ground.vertices().to_vec(),
             indices: ground.indices().to_vec(),
+            front_face: FrontFace::Ccw,
         },
     ]
 }
@@ -7,6 +7,7 @@ use std::hash::Hash;
 
 pub mod assets;
 pub mod camera;
+pub mod console;
 mod constants;
 pub mod continuous;
 mod data_types;
@@ -15,6 +16,8 @@ pub mod geometry;
 pub mod input;
 mod lifecycle;
 pub mod lights;
+pub mod logging;
+pub mod palette;
 pub mod prefabs;
 pub mod world;
 
@@ -26,7 +29,7 @@ pub use lifecycle::{
 
 pub use constants::*;
 
-pub use data_types::{Completer, CompleterError};
+pub use data_types::{Completer, CompleterError, IdBank, SimClock};
 
 pub trait Instanced<I> {
     fn instance(&self) -> I;
@@ -39,3 +42,7 @@ pub trait Unique<U: Hash + Eq + PartialEq> {
 pub trait Meshed<U: Hash + Eq + PartialEq> {
     fn mesh_id(&self) -> &U;
 }
+
+pub trait Textured<U: Hash + Eq + PartialEq> {
+    fn texture_id(&self) -> &U;
+}
@@ -1,3 +1,8 @@
+use winit::window::Icon;
+
+/// Width and height of ICON in pixels.
+pub const ICON_SIZE: (u32, u32) = (8, 8);
+
 // 8x8x4, WxHxRGBA
 #[rustfmt::skip]
 pub const ICON: [u8; 256] = [
@@ -66,3 +71,61 @@ pub const ICON: [u8; 256] = [
     0, 0, 0, 0,
     0, 0, 0, 0,
 ];
+
+#[derive(Debug, PartialEq)]
+pub enum AssetError {
+    /// The buffer doesn't hold exactly width * height RGBA pixels.
+    SizeMismatch { expected: usize, actual: usize },
+
+    /// Width * height RGBA pixels don't fit in memory.
+    TooLarge { width: u32, height: u32 },
+
+    /// The pixels were rejected by winit, with its reason.
+    BadIcon(String),
+}
+
+/// The built-in ICON as a window icon.
+pub fn load_icon() -> Result<Icon, AssetError> {
+    icon_from_rgba(&ICON, ICON_SIZE.0, ICON_SIZE.1)
+}
+
+/// Window icon from the RGBA bytes of a [width] x [height] image, checking their length first.
+pub fn icon_from_rgba(rgba: &[u8], width: u32, height: u32) -> Result<Icon, AssetError> {
+    let expected = (width as usize)
+        .checked_mul(height as usize)
+        .and_then(|pixels| pixels.checked_mul(4))
+        .ok_or(AssetError::TooLarge { width, height })?;
+    if rgba.len() != expected {
+        return Err(AssetError::SizeMismatch {
+            expected,
+            actual: rgba.len(),
+        });
+    }
+    Icon::from_rgba(rgba.to_vec(), width, height).map_err(|e| AssetError::BadIcon(e.to_string()))
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use crate::core::assets::{AssetError, ICON, icon_from_rgba, load_icon};
+
+    #[test]
+    fn icon_length_must_match_size() {
+        assert!(load_icon().is_ok());
+        assert!(icon_from_rgba(&[255; 2 * 3 * 4], 2, 3).is_ok());
+
+        assert_eq!(
+            icon_from_rgba(&ICON, 16, 16).unwrap_err(),
+            AssetError::SizeMismatch {
+                expected: 1024,
+                actual: 256
+            }
+        );
+        assert_eq!(
+            icon_from_rgba(&ICON[..255], 8, 8).unwrap_err(),
+            AssetError::SizeMismatch {
+                expected: 256,
+                actual: 255
+            }
+        );
+    }
+}
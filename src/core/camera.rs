@@ -1,6 +1,7 @@
-use std::{collections::HashMap, f32::consts::PI};
+use std::collections::HashMap;
 
 use nalgebra::{Matrix4, Point3, Rotation3, UnitVector3, Vector3};
+use rand::{Rng, SeedableRng, rngs::StdRng};
 use wgpu::{
     BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, Buffer, BufferUsages, Device,
     Queue,
@@ -8,17 +9,215 @@ use wgpu::{
 };
 use winit::keyboard::KeyCode;
 
-use crate::{Float, core::CAMERA_SPEED};
+use crate::{
+    Float,
+    core::{
+        CAMERA_SHAKE_ANGLE, CAMERA_SPEED, DEFAULT_FAR, DEFAULT_FOVY, DEFAULT_NEAR, EDITOR_ZOOM_STEP,
+    },
+};
 
+/// Object safe, so ActiveState can hold any camera as a Box<dyn Camera>.
 pub trait Camera {
+    fn position(&self) -> &Vector3<Float>;
+    fn get_up(&self) -> &UnitVector3<Float>;
+    fn get_right(&self) -> &UnitVector3<Float>;
+    fn get_center(&self) -> &UnitVector3<Float>;
+    /// Projection times view, as last computed by update.
+    fn view_proj(&self) -> &Matrix4<Float>;
+
     fn look_up(&mut self, amount: f32);
     fn look_ccw(&mut self, amount: f32);
     fn update(&mut self, keys_pressed: &HashMap<KeyCode, bool>, dt: f32);
     fn update_gpu(&mut self, queue: &mut Queue);
     fn bind_group(&self) -> &BindGroup;
+
+    /// Movement settings, for cameras which are moved with CameraControls.
+    fn controls_mut(&mut self) -> Option<&mut CameraControls> {
+        None
+    }
+
+    /// Shake applied on top of the camera's view, for cameras which support it.
+    fn shake_mut(&mut self) -> Option<&mut CameraShake> {
+        None
+    }
+
+    /// Cursor dragged by [dx], [dy] physical pixels (y down) with the middle mouse button held.
+    /// Ignored by default.
+    fn pan(&mut self, _dx: f32, _dy: f32) {}
+
+    /// Scroll wheel moved by [lines], positive away from the user. Ignored by default.
+    fn zoom(&mut self, _lines: f32) {}
+
+    /// Moves the camera to [position] and turns it to face [target]. Ignored by default.
+    fn look_at(&mut self, _position: &Vector3<Float>, _target: &Vector3<Float>) {}
+
+    /// Moves the camera back along its view direction until the sphere at [center] with [radius] fits in
+    /// view, facing its center. Ignored by default.
+    fn frame(&mut self, _center: &Vector3<Float>, _radius: Float) {}
+
+    /// Viewport resized to [width] by [height] physical pixels. Ignored by default.
+    fn resize(&mut self, _width: f32, _height: f32) {}
+}
+
+/// Distance from a sphere's center at which a view with the given half field of view tangents fits all of
+/// the sphere, by its narrower angle.
+pub fn framing_distance(radius: f32, tan_half_fov_x: f32, tan_half_fov_y: f32) -> f32 {
+    let tan = tan_half_fov_x.min(tan_half_fov_y);
+    // radius / sin of the half angle
+    radius * (1.0 + tan * tan).sqrt() / tan
+}
+
+/// Movement settings for NoClipCamera.
+#[derive(Debug, Clone)]
+pub struct CameraControls {
+    /// Units per second.
+    pub speed: f32,
+    /// Speed is multiplied by this while ControlLeft is held.
+    pub sprint_multiplier: f32,
+    /// Replaces [speed] with one proportional to [reference_distance] when both are set.
+    pub distance_scaling: Option<DistanceScaling>,
+    /// Distance to the nearest geometry (i.e. from a depth readback) or a manual zoom level, in units.
+    pub reference_distance: Option<f32>,
+    /// Lowest height the camera can be moved down to. A camera already below it can still move up, and
+    /// isn't pushed up to it.
+    pub min_y: Option<f32>,
+}
+
+/// Movement speed proportional to a reference distance, so the camera moves slowly near objects and
+/// quickly in open space.
+#[derive(Debug, Clone)]
+pub struct DistanceScaling {
+    /// Units per second per unit of reference distance.
+    pub speed_per_unit: f32,
+    pub min_speed: f32,
+    pub max_speed: f32,
+}
+
+impl CameraControls {
+    /// Movement speed before sprinting, scaled by the reference distance if configured.
+    pub fn base_speed(&self) -> f32 {
+        match (&self.distance_scaling, self.reference_distance) {
+            (Some(scaling), Some(distance)) => {
+                (distance * scaling.speed_per_unit).clamp(scaling.min_speed, scaling.max_speed)
+            }
+            _ => self.speed,
+        }
+    }
+
+    /// Height after moving from [from_y] to [to_y], stopping at [min_y] if moving down past it.
+    pub fn clamp_descent(&self, from_y: f32, to_y: f32) -> f32 {
+        match self.min_y {
+            Some(min_y) if to_y < from_y && to_y < min_y => min_y.min(from_y),
+            _ => to_y,
+        }
+    }
+
+    /// Movement speed given the currently pressed keys.
+    pub fn current_speed(&self, keys_pressed: &HashMap<KeyCode, bool>) -> f32 {
+        if *keys_pressed.get(&KeyCode::ControlLeft).unwrap_or(&false) {
+            self.base_speed() * self.sprint_multiplier
+        } else {
+            self.base_speed()
+        }
+    }
+}
+
+impl Default for CameraControls {
+    fn default() -> Self {
+        Self {
+            speed: CAMERA_SPEED,
+            sprint_multiplier: 20.0,
+            distance_scaling: None,
+            reference_distance: None,
+            min_y: None,
+        }
+    }
+}
+
+/// Decaying random offset added to a camera's view, i.e. for impacts and explosions.
+///
+/// Only the rendered view is shaken, the camera's own position and orientation are never modified.
+#[derive(Debug, Clone)]
+pub struct CameraShake {
+    intensity: f32,
+    /// Seconds
+    duration: f32,
+    remaining: f32,
+    rng: StdRng,
+
+    // generated
+    offset: Vector3<f32>,
+    /// (up, ccw) in radians
+    angles: (f32, f32),
+}
+
+impl Default for CameraShake {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CameraShake {
+    pub fn new() -> Self {
+        Self::with_rng(StdRng::from_os_rng())
+    }
+
+    /// Produces the same offsets for the same seed and sequence of updates.
+    pub fn with_seed(seed: u64) -> Self {
+        Self::with_rng(StdRng::seed_from_u64(seed))
+    }
+
+    fn with_rng(rng: StdRng) -> Self {
+        Self {
+            intensity: 0.0,
+            duration: 0.0,
+            remaining: 0.0,
+            rng,
+            offset: Vector3::zeros(),
+            angles: (0.0, 0.0),
+        }
+    }
+
+    /// Starts a shake which moves the view by up to [intensity] units, decaying linearly to nothing over
+    /// [duration] seconds. Replaces any shake in progress.
+    pub fn shake(&mut self, intensity: f32, duration: f32) {
+        self.intensity = intensity;
+        self.duration = duration;
+        self.remaining = duration;
+    }
+
+    /// Current maximum offset along each axis.
+    pub fn magnitude(&self) -> f32 {
+        if self.duration <= 0.0 {
+            0.0
+        } else {
+            self.intensity * self.remaining / self.duration
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.remaining > 0.0
+    }
+
+    pub fn offset(&self) -> &Vector3<f32> {
+        &self.offset
+    }
+
+    /// Advances the shake by [dt] seconds and picks new random offsets.
+    pub fn update(&mut self, dt: f32) {
+        self.remaining = (self.remaining - dt).max(0.0);
+        if !self.is_active() {
+            self.offset = Vector3::zeros();
+            self.angles = (0.0, 0.0);
+            return;
+        }
+        let magnitude = self.magnitude();
+        let mut random = || self.rng.random_range(-1.0..=1.0) * magnitude;
+        self.offset = Vector3::new(random(), random(), random());
+        self.angles = (random() * CAMERA_SHAKE_ANGLE, random() * CAMERA_SHAKE_ANGLE);
+    }
 }
 
-// TODO: Find out why up and right vectors seem to point at the negative of the correct direction.
 #[derive(Debug, Clone)]
 pub struct NoClipCamera {
     buffer: Buffer,
@@ -31,6 +230,8 @@ pub struct NoClipCamera {
     center: UnitVector3<Float>,
 
     projection: Projection,
+    controls: CameraControls,
+    shake: CameraShake,
 
     // generated
     view_proj: nalgebra::Matrix4<f32>,
@@ -46,18 +247,18 @@ impl NoClipCamera {
         roll: f32,
         projection: Projection,
     ) -> Self {
-        let roll = roll + PI;
         let (sin_yaw, cos_yaw) = yaw.sin_cos();
         let (sin_pitch, cos_pitch) = pitch.sin_cos();
 
-        let center = Vector3::new(cos_pitch * cos_yaw, sin_pitch, cos_pitch * sin_yaw) + position;
+        // a direction, the view matrix adds the position
+        let center = Vector3::new(cos_pitch * cos_yaw, sin_pitch, cos_pitch * sin_yaw);
         let up = Rotation3::from_axis_angle(
             &UnitVector3::new_normalize(Vector3::new(0.0, 0.0, 1.0)),
             roll,
         ) * Vector3::new(0.0, 1.0, 0.0);
         let right = center.cross(&up);
 
-        let view = Matrix4::look_at_rh(&position.into(), &(center.into()), &up);
+        let view = Matrix4::look_at_rh(&position.into(), &(center + position).into(), &up);
         let view_proj: Matrix4<f32> = (projection.projection() * view).into();
 
         let buffer = device.create_buffer_init(&BufferInitDescriptor {
@@ -82,6 +283,8 @@ impl NoClipCamera {
             center: UnitVector3::new_normalize(center),
 
             projection,
+            controls: CameraControls::default(),
+            shake: CameraShake::new(),
             bind_group,
             buffer,
             view_proj,
@@ -108,7 +311,7 @@ impl NoClipCamera {
         self.translate(&(amount * direction));
     }
     pub fn right(&mut self, amount: f32) {
-        self.translate(&(-amount * *self.right));
+        self.translate(&(amount * *self.right));
     }
 
     pub fn roll_ccw(&mut self, amount: f32) {
@@ -117,11 +320,16 @@ impl NoClipCamera {
         self.right = rot * self.right;
     }
 
+    /// View from the camera's position and orientation, with the shake on top.
     fn create_view(&self) -> Matrix4<f32> {
+        let (up, ccw) = self.shake.angles;
+        let rot = Rotation3::from_axis_angle(&self.right, up)
+            * Rotation3::from_axis_angle(&self.up, -ccw);
+        let position = self.position + self.shake.offset;
         Matrix4::look_at_rh(
-            &(self.position.into()),
-            &(Into::<Point3<f32>>::into(*self.center) + self.position),
-            &self.up,
+            &(position.into()),
+            &(Into::<Point3<f32>>::into(rot * *self.center) + position),
+            &(rot * *self.up),
         )
     }
 
@@ -130,37 +338,60 @@ impl NoClipCamera {
         self.view_proj = self.projection.projection() * self.create_view();
     }
 
-    pub fn view_proj(&self) -> &nalgebra::Matrix4<Float> {
-        &self.view_proj
+    pub fn controls(&self) -> &CameraControls {
+        &self.controls
+    }
+
+    pub fn set_controls(&mut self, controls: CameraControls) {
+        self.controls = controls;
+    }
+
+    pub fn shake(&self) -> &CameraShake {
+        &self.shake
+    }
+
+    /// Replaces the shake, i.e. with a seeded one.
+    pub fn set_shake(&mut self, shake: CameraShake) {
+        self.shake = shake;
+    }
+}
+
+impl Camera for NoClipCamera {
+    fn controls_mut(&mut self) -> Option<&mut CameraControls> {
+        Some(&mut self.controls)
+    }
+
+    fn shake_mut(&mut self) -> Option<&mut CameraShake> {
+        Some(&mut self.shake)
     }
 
-    pub fn position(&self) -> &Vector3<Float> {
+    fn position(&self) -> &Vector3<Float> {
         &self.position
     }
 
-    pub fn get_up(&self) -> &UnitVector3<Float> {
+    fn get_up(&self) -> &UnitVector3<Float> {
         &self.up
     }
-    pub fn get_right(&self) -> &UnitVector3<Float> {
+    fn get_right(&self) -> &UnitVector3<Float> {
         &self.right
     }
-    pub fn get_center(&self) -> &UnitVector3<Float> {
+    fn get_center(&self) -> &UnitVector3<Float> {
         &self.center
     }
-}
-
-impl Camera for NoClipCamera {
+    fn view_proj(&self) -> &Matrix4<Float> {
+        &self.view_proj
+    }
     fn bind_group(&self) -> &BindGroup {
         &self.bind_group
     }
     fn look_up(&mut self, amount: f32) {
-        let rot = Rotation3::from_axis_angle(&self.right, -amount);
+        let rot = Rotation3::from_axis_angle(&self.right, amount);
 
         self.up = rot * self.up;
         self.center = rot * self.center;
     }
     fn look_ccw(&mut self, amount: f32) {
-        let rot = Rotation3::from_axis_angle(&self.up, amount);
+        let rot = Rotation3::from_axis_angle(&self.up, -amount);
 
         self.right = rot * self.right;
         self.center = rot * self.center;
@@ -170,53 +401,48 @@ impl Camera for NoClipCamera {
         let mut camera_right: f32 = 0.0;
         let mut yaw_ccw: f32 = 0.0;
         let mut fly: f32 = 0.0;
-        let mut fly_speed: f32 = CAMERA_SPEED;
+        let fly_speed = self.controls.current_speed(keys_pressed);
         let mut roll_ccw: f32 = 0.0;
 
-        if let Some(p) = keys_pressed.get(&KeyCode::KeyW) {
-            if *p {
-                camera_forward += 1.0;
-            }
+        if let Some(p) = keys_pressed.get(&KeyCode::KeyW)
+            && *p
+        {
+            camera_forward += 1.0;
         }
-        if let Some(p) = keys_pressed.get(&KeyCode::KeyS) {
-            if *p {
-                camera_forward -= 1.0;
-            }
-        }
-        if let Some(p) = keys_pressed.get(&KeyCode::KeyA) {
-            if *p {
-                camera_right -= 1.0;
-            }
+        if let Some(p) = keys_pressed.get(&KeyCode::KeyS)
+            && *p
+        {
+            camera_forward -= 1.0;
         }
-        if let Some(p) = keys_pressed.get(&KeyCode::KeyD) {
-            if *p {
-                camera_right += 1.0;
-            }
+        if let Some(p) = keys_pressed.get(&KeyCode::KeyA)
+            && *p
+        {
+            camera_right -= 1.0;
         }
-        if let Some(p) = keys_pressed.get(&KeyCode::KeyQ) {
-            if *p {
-                roll_ccw += 0.0025;
-            }
+        if let Some(p) = keys_pressed.get(&KeyCode::KeyD)
+            && *p
+        {
+            camera_right += 1.0;
         }
-        if let Some(p) = keys_pressed.get(&KeyCode::KeyE) {
-            if *p {
-                roll_ccw -= 0.0025;
-            }
+        if let Some(p) = keys_pressed.get(&KeyCode::KeyQ)
+            && *p
+        {
+            roll_ccw += 0.0025;
         }
-        if let Some(p) = keys_pressed.get(&KeyCode::Space) {
-            if *p {
-                fly += 1.0;
-            }
+        if let Some(p) = keys_pressed.get(&KeyCode::KeyE)
+            && *p
+        {
+            roll_ccw -= 0.0025;
         }
-        if let Some(p) = keys_pressed.get(&KeyCode::ShiftLeft) {
-            if *p {
-                fly -= 1.0;
-            }
+        if let Some(p) = keys_pressed.get(&KeyCode::Space)
+            && *p
+        {
+            fly += 1.0;
         }
-        if let Some(p) = keys_pressed.get(&KeyCode::ControlLeft) {
-            if *p {
-                fly_speed *= 20.0;
-            }
+        if let Some(p) = keys_pressed.get(&KeyCode::ShiftLeft)
+            && *p
+        {
+            fly -= 1.0;
         }
 
         let mag = (camera_forward * camera_forward + camera_right * camera_right).sqrt();
@@ -244,14 +470,41 @@ impl Camera for NoClipCamera {
             roll_ccw = 0.0;
         }
 
+        let start_y = self.position.y;
         self.forward(camera_forward);
         self.right(camera_right);
         self.look_ccw(yaw_ccw);
         self.roll_ccw(roll_ccw);
         self.translate(&[0.0, fly, 0.0].into());
+        self.position.y = self.controls.clamp_descent(start_y, self.position.y);
+        self.shake.update(dt);
 
         self.view_proj = (self.projection.projection() * self.create_view()).into();
     }
+    /// Levels the camera with the horizon, keeping its up vector on the same side of it.
+    fn look_at(&mut self, position: &Vector3<Float>, target: &Vector3<Float>) {
+        self.position = *position;
+        if let Some(center) = UnitVector3::try_new(target - position, Float::EPSILON) {
+            let world_up = Vector3::new(0.0, self.up.y.signum(), 0.0);
+            // looking straight up or down keeps the current right vector
+            if let Some(right) = UnitVector3::try_new(center.cross(&world_up), Float::EPSILON) {
+                self.right = right;
+            }
+            self.up = UnitVector3::new_normalize(self.right.cross(&center));
+            self.right = UnitVector3::new_normalize(center.cross(&self.up));
+            self.center = center;
+        }
+        self.view_proj = self.projection.projection() * self.create_view();
+    }
+    fn frame(&mut self, center: &Vector3<Float>, radius: Float) {
+        let (tan_x, tan_y) = self.projection.half_fov_tangents();
+        let distance = framing_distance(radius, tan_x, tan_y);
+        self.look_at(&(center - *self.center * distance), center);
+    }
+    fn resize(&mut self, width: f32, height: f32) {
+        self.projection.resize(width, height);
+        self.view_proj = self.projection.projection() * self.create_view();
+    }
     fn update_gpu(&mut self, queue: &mut Queue) {
         queue.write_buffer(
             &self.buffer,
@@ -261,6 +514,258 @@ impl Camera for NoClipCamera {
     }
 }
 
+/// Stand-in current camera of headless apps, which have no GPU to give a camera a bind group. Stays at
+/// the origin facing +x. Headless apps never draw, so the renderer never asks for its bind group.
+/// Camera::bind_group panics, as there's none to return.
+pub(crate) struct HeadlessCamera {
+    position: Vector3<Float>,
+    up: UnitVector3<Float>,
+    right: UnitVector3<Float>,
+    center: UnitVector3<Float>,
+    view_proj: Matrix4<Float>,
+}
+
+impl HeadlessCamera {
+    pub(crate) fn new() -> Self {
+        Self {
+            position: Vector3::zeros(),
+            up: Vector3::y_axis(),
+            right: Vector3::z_axis(),
+            center: Vector3::x_axis(),
+            view_proj: Matrix4::identity(),
+        }
+    }
+}
+
+impl Camera for HeadlessCamera {
+    fn position(&self) -> &Vector3<Float> {
+        &self.position
+    }
+    fn get_up(&self) -> &UnitVector3<Float> {
+        &self.up
+    }
+    fn get_right(&self) -> &UnitVector3<Float> {
+        &self.right
+    }
+    fn get_center(&self) -> &UnitVector3<Float> {
+        &self.center
+    }
+    fn view_proj(&self) -> &Matrix4<Float> {
+        &self.view_proj
+    }
+    fn look_up(&mut self, _amount: f32) {}
+    fn look_ccw(&mut self, _amount: f32) {}
+    fn update(&mut self, _keys_pressed: &HashMap<KeyCode, bool>, _dt: f32) {}
+    fn update_gpu(&mut self, _queue: &mut Queue) {}
+    fn bind_group(&self) -> &BindGroup {
+        panic!("headless cameras have no bind group")
+    }
+}
+
+/// Orthographic camera for level editing. Looks along a fixed direction, pans with the middle mouse
+/// button and zooms with the scroll wheel. Ignores the FPS look and movement controls.
+#[derive(Debug, Clone)]
+pub struct EditorCamera {
+    buffer: Buffer,
+    bind_group: BindGroup,
+
+    position: Vector3<Float>,
+
+    up: UnitVector3<Float>,
+    right: UnitVector3<Float>,
+    center: UnitVector3<Float>,
+
+    /// Half the view's height, in world units.
+    extent: f32,
+    /// Viewport size in physical pixels, to convert drags to world units.
+    width: f32,
+    height: f32,
+    near: f32,
+    far: f32,
+
+    // generated
+    view_proj: nalgebra::Matrix4<f32>,
+}
+
+impl EditorCamera {
+    /// Smallest extent the camera zooms in to.
+    const MIN_EXTENT: f32 = 1.0e-3;
+
+    /// Near and far planes until EditorCamera::set_depth_range is called.
+    const DEFAULT_DEPTH_RANGE: (f32, f32) = (0.0, 1000.0);
+
+    pub fn new(
+        device: &Device,
+        bind_group_layout: &BindGroupLayout,
+        position: Vector3<f32>,
+        direction: UnitVector3<f32>,
+        extent: f32,
+        width: f32,
+        height: f32,
+    ) -> Self {
+        // looking straight down has north (-z) at the top
+        let world_up = if direction.y.abs() > 0.99 {
+            -Vector3::z()
+        } else {
+            Vector3::y()
+        };
+        let right = UnitVector3::new_normalize(direction.cross(&world_up));
+        let up = UnitVector3::new_normalize(right.cross(&direction));
+
+        let buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Camera Buffer"),
+            contents: bytemuck::cast_slice(&[Into::<[[f32; 4]; 4]>::into(
+                Matrix4::<f32>::identity(),
+            )]),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            layout: bind_group_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+            label: Some("camera_bind_group"),
+        });
+
+        let mut camera = Self {
+            buffer,
+            bind_group,
+            position,
+            up,
+            right,
+            center: direction,
+            extent,
+            width,
+            height,
+            near: Self::DEFAULT_DEPTH_RANGE.0,
+            far: Self::DEFAULT_DEPTH_RANGE.1,
+            view_proj: Matrix4::identity(),
+        };
+        camera.update_view_proj();
+        camera
+    }
+
+    pub fn extent(&self) -> f32 {
+        self.extent
+    }
+
+    /// Sets half the view's height, in world units.
+    pub fn set_extent(&mut self, extent: f32) {
+        self.extent = extent.max(Self::MIN_EXTENT);
+        self.update_view_proj();
+    }
+
+    pub fn set_position(&mut self, position: &Vector3<f32>) {
+        self.position = *position;
+        self.update_view_proj();
+    }
+
+    /// Distances along the view direction of the near and far planes.
+    pub fn set_depth_range(&mut self, near: f32, far: f32) {
+        self.near = near;
+        self.far = far;
+        self.update_view_proj();
+    }
+
+    fn update_view_proj(&mut self) {
+        let half_width = self.extent * self.width / self.height;
+        let projection = Matrix4::new_orthographic(
+            -half_width,
+            half_width,
+            -self.extent,
+            self.extent,
+            self.near,
+            self.far,
+        );
+        let view = Matrix4::look_at_rh(
+            &self.position.into(),
+            &(self.position + *self.center).into(),
+            &self.up,
+        );
+        self.view_proj = projection * view;
+    }
+}
+
+impl Camera for EditorCamera {
+    fn position(&self) -> &Vector3<Float> {
+        &self.position
+    }
+
+    fn get_up(&self) -> &UnitVector3<Float> {
+        &self.up
+    }
+    fn get_right(&self) -> &UnitVector3<Float> {
+        &self.right
+    }
+    fn get_center(&self) -> &UnitVector3<Float> {
+        &self.center
+    }
+    fn view_proj(&self) -> &Matrix4<Float> {
+        &self.view_proj
+    }
+    fn bind_group(&self) -> &BindGroup {
+        &self.bind_group
+    }
+    fn look_up(&mut self, _amount: f32) {}
+    fn look_ccw(&mut self, _amount: f32) {}
+    fn update(&mut self, _keys_pressed: &HashMap<KeyCode, bool>, _dt: f32) {
+        self.update_view_proj();
+    }
+    fn update_gpu(&mut self, queue: &mut Queue) {
+        queue.write_buffer(
+            &self.buffer,
+            0,
+            bytemuck::cast_slice(&[Into::<[[f32; 4]; 4]>::into(self.view_proj)]),
+        );
+    }
+
+    /// Moves the camera so the point under the cursor follows it.
+    fn pan(&mut self, dx: f32, dy: f32) {
+        let units_per_pixel = 2.0 * self.extent / self.height;
+        self.position += (-dx * *self.right + dy * *self.up) * units_per_pixel;
+        self.update_view_proj();
+    }
+
+    fn zoom(&mut self, lines: f32) {
+        self.set_extent(self.extent / EDITOR_ZOOM_STEP.powf(lines));
+    }
+
+    /// Also pushes the far plane back if the sphere would reach past it.
+    fn frame(&mut self, center: &Vector3<Float>, radius: Float) {
+        self.position = center - *self.center * (radius + self.near);
+        self.far = self.far.max(2.0 * radius + self.near);
+        // the extent is half the height, narrower windows fit the sphere's width instead
+        self.set_extent(radius * (self.height / self.width).max(1.0));
+    }
+
+    fn resize(&mut self, width: f32, height: f32) {
+        self.width = width;
+        self.height = height;
+        self.update_view_proj();
+    }
+}
+
+/// Perspective used for cameras the app creates itself.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProjectionConfig {
+    /// Full vertical field of view in degrees.
+    pub fovy: f32,
+    pub near: f32,
+    pub far: f32,
+}
+
+impl Default for ProjectionConfig {
+    fn default() -> Self {
+        Self {
+            fovy: DEFAULT_FOVY,
+            near: DEFAULT_NEAR,
+            far: DEFAULT_FAR,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Projection {
     aspect: f32,
@@ -273,17 +778,22 @@ pub struct Projection {
 }
 
 impl Projection {
+    /// `fovy` is the full vertical field of view in degrees.
     pub fn new(width: f32, height: f32, fovy: f32, near: f32, far: f32) -> Self {
         Self {
             aspect: width / height,
-            fovy: fovy,
+            fovy,
             near,
             far,
-            transform: *nalgebra::Perspective3::new(width / height, fovy * 180.0 / PI, near, far)
+            transform: *nalgebra::Perspective3::new(width / height, fovy.to_radians(), near, far)
                 .as_matrix(),
         }
     }
 
+    pub fn from_config(width: f32, height: f32, config: &ProjectionConfig) -> Self {
+        Self::new(width, height, config.fovy, config.near, config.far)
+    }
+
     pub fn resize(&mut self, width: f32, height: f32) {
         self.aspect = width / height;
         self.update();
@@ -293,12 +803,385 @@ impl Projection {
         &self.transform
     }
 
+    /// Tangents of half the horizontal and vertical field of view, read from the projection matrix.
+    pub fn half_fov_tangents(&self) -> (f32, f32) {
+        (
+            1.0 / self.transform[(0, 0)].abs(),
+            1.0 / self.transform[(1, 1)].abs(),
+        )
+    }
+
     fn update(&mut self) {
         self.transform = nalgebra::Matrix4::new_perspective(
             self.aspect,
-            self.fovy * 180.0 / PI,
+            self.fovy.to_radians(),
             self.near,
             self.far,
         );
     }
 }
+
+#[allow(unused_imports)]
+mod tests {
+    use std::{collections::HashMap, f32::consts::PI};
+
+    use nalgebra::{Matrix4, UnitVector3, Vector3};
+    use wgpu::{
+        BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingType, BufferBindingType,
+        ShaderStages,
+    };
+    use winit::keyboard::KeyCode;
+
+    use crate::{
+        core::{
+            EDITOR_ZOOM_STEP,
+            camera::{
+                Camera, CameraControls, CameraShake, DistanceScaling, EditorCamera, NoClipCamera,
+                Projection, framing_distance,
+            },
+            geometry::{BoundingSphere, Frustum},
+        },
+        render::test_device,
+    };
+
+    #[test]
+    fn sprint_uses_configured_multiplier() {
+        let controls = CameraControls {
+            speed: 4.0,
+            sprint_multiplier: 2.5,
+            ..Default::default()
+        };
+        let mut keys_pressed = HashMap::new();
+        keys_pressed.insert(KeyCode::KeyW, true);
+
+        assert_eq!(controls.current_speed(&keys_pressed), 4.0);
+
+        keys_pressed.insert(KeyCode::ControlLeft, true);
+        assert_eq!(controls.current_speed(&keys_pressed), 10.0);
+    }
+
+    #[test]
+    fn speed_scales_with_reference_distance() {
+        let mut controls = CameraControls {
+            speed: 20.0,
+            distance_scaling: Some(DistanceScaling {
+                speed_per_unit: 0.5,
+                min_speed: 1.0,
+                max_speed: 500.0,
+            }),
+            ..Default::default()
+        };
+        // no distance yet
+        assert_eq!(controls.base_speed(), 20.0);
+
+        let mut speed_at = |distance| {
+            controls.reference_distance = Some(distance);
+            controls.base_speed()
+        };
+        assert_eq!(speed_at(10.0), 5.0);
+        assert_eq!(speed_at(100.0), 50.0);
+        assert_eq!(speed_at(0.5), 1.0);
+        assert_eq!(speed_at(5000.0), 500.0);
+
+        let mut keys_pressed = HashMap::new();
+        keys_pressed.insert(KeyCode::ControlLeft, true);
+        assert_eq!(
+            controls.current_speed(&keys_pressed),
+            500.0 * controls.sprint_multiplier
+        );
+    }
+
+    #[test]
+    fn projection_matches_known_perspective() {
+        let (near, far) = (0.1, 100.0);
+        let projection = Projection::new(800.0, 600.0, 90.0, near, far);
+
+        // tan(45 degrees) is 1, so y is unscaled and x is divided by the aspect
+        let mut expected = Matrix4::zeros();
+        expected[(0, 0)] = 0.75;
+        expected[(1, 1)] = 1.0;
+        expected[(2, 2)] = -(far + near) / (far - near);
+        expected[(2, 3)] = -2.0 * far * near / (far - near);
+        expected[(3, 2)] = -1.0;
+        assert!(
+            (projection.projection() - expected).amax() < 1.0e-5,
+            "{:?}",
+            projection.projection()
+        );
+
+        let mut resized = projection.clone();
+        resized.resize(800.0, 600.0);
+        assert!((resized.projection() - expected).amax() < 1.0e-5);
+        let (tan_x, tan_y) = projection.half_fov_tangents();
+        assert!((tan_x - 4.0 / 3.0).abs() < 1.0e-5 && (tan_y - 1.0).abs() < 1.0e-5);
+    }
+
+    #[test]
+    fn resize_recomputes_projection() {
+        let mut projection = Projection::new(800.0, 600.0, 90.0, 0.1, 100.0);
+        let before = *projection.projection();
+
+        projection.resize(1600.0, 600.0);
+
+        assert_eq!(projection.aspect, 1600.0 / 600.0);
+        assert_ne!(*projection.projection(), before);
+        // only the horizontal scale depends on the aspect
+        assert!((projection.projection()[(0, 0)] - 600.0 / 1600.0).abs() < 1.0e-6);
+        assert!((projection.projection()[(1, 1)] - before[(1, 1)]).abs() < 1.0e-6);
+        let fresh = Projection::new(1600.0, 600.0, 90.0, 0.1, 100.0);
+        assert!((projection.projection() - fresh.projection()).amax() < 1.0e-6);
+    }
+
+    #[test]
+    fn framed_bounds_within_frustum() {
+        let (min, max) = (Vector3::new(-3.0, 0.0, 10.0), Vector3::new(5.0, 2.0, 30.0));
+        let center = (min + max) / 2.0;
+        let radius = (max - min).magnitude() / 2.0;
+        let projection = Projection::new(1200.0, 600.0, 90.0, 0.1, 1000.0);
+        let (tan_x, tan_y) = projection.half_fov_tangents();
+
+        let distance = framing_distance(radius, tan_x, tan_y);
+        let position = center - Vector3::new(0.0, 0.0, 1.0) * distance;
+        let view = Matrix4::look_at_rh(&position.into(), &center.into(), &Vector3::y());
+        let frustum = Frustum::from_view_proj(&(projection.projection() * view));
+
+        assert!(distance > radius);
+        for corner in 0..8 {
+            let point = Vector3::new(
+                if corner & 1 == 0 { min.x } else { max.x },
+                if corner & 2 == 0 { min.y } else { max.y },
+                if corner & 4 == 0 { min.z } else { max.z },
+            );
+            assert!(frustum.contains_sphere(&BoundingSphere {
+                center: point,
+                radius: 0.0,
+            }));
+        }
+        // the sphere touches the top plane of the narrower, vertical, field of view, so any closer and the
+        // point it touches at is outside
+        let (sin, cos) = tan_y.atan().sin_cos();
+        let tangent_point = center + Vector3::new(0.0, cos, -sin) * radius;
+        for (scale, inside) in [(1.01, true), (0.99, false)] {
+            let position = center - Vector3::new(0.0, 0.0, 1.0) * distance * scale;
+            let view = Matrix4::look_at_rh(&position.into(), &center.into(), &Vector3::y());
+            let frustum = Frustum::from_view_proj(&(projection.projection() * view));
+            let sphere = BoundingSphere {
+                center: tangent_point,
+                radius: 0.0,
+            };
+            assert_eq!(frustum.contains_sphere(&sphere), inside, "{}", scale);
+        }
+    }
+
+    #[test]
+    fn spawns_facing_yaw() {
+        let Some((device, _queue)) = test_device() else {
+            return;
+        };
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::VERTEX,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+
+        // away from the origin, so the position can't leak into the direction
+        let camera = NoClipCamera::new(
+            &device,
+            &layout,
+            Vector3::new(40.0, 10.0, -25.0),
+            PI / 2.0,
+            0.0,
+            0.0,
+            Projection::new(800.0, 600.0, 90.0, 0.1, 100.0),
+        );
+
+        let forward = camera.get_center().into_inner();
+        assert!(
+            (forward - Vector3::new(0.0, 0.0, 1.0)).magnitude() < 1.0e-5,
+            "{:?}",
+            forward
+        );
+    }
+
+    #[test]
+    fn shake_decays_to_zero() {
+        let mut shake = CameraShake::with_seed(7);
+        shake.shake(2.0, 0.5);
+
+        let mut previous = shake.magnitude();
+        assert_eq!(previous, 2.0);
+        for _ in 0..5 {
+            shake.update(0.1);
+            assert!(shake.magnitude() < previous);
+            assert!(shake.offset().amax() <= shake.magnitude());
+            previous = shake.magnitude();
+        }
+        shake.update(0.1);
+
+        assert!(!shake.is_active());
+        assert_eq!(shake.magnitude(), 0.0);
+        assert_eq!(*shake.offset(), Vector3::zeros());
+
+        // same seed, same offsets
+        let mut a = CameraShake::with_seed(3);
+        let mut b = CameraShake::with_seed(3);
+        a.shake(1.0, 1.0);
+        b.shake(1.0, 1.0);
+        a.update(0.1);
+        b.update(0.1);
+        assert_eq!(a.offset(), b.offset());
+    }
+
+    #[test]
+    fn shake_leaves_position_unchanged() {
+        let Some((device, _queue)) = test_device() else {
+            return;
+        };
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::VERTEX,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let mut camera = NoClipCamera::new(
+            &device,
+            &layout,
+            Vector3::new(1.0, 2.0, 3.0),
+            0.0,
+            0.0,
+            0.0,
+            Projection::new(800.0, 600.0, 90.0, 0.1, 100.0),
+        );
+        let keys_pressed = HashMap::new();
+        camera.update(&keys_pressed, 0.1);
+        let still = *camera.view_proj();
+        camera.set_shake(CameraShake::with_seed(1));
+
+        camera.shake_mut().unwrap().shake(1.0, 0.3);
+        camera.update(&keys_pressed, 0.1);
+        assert_ne!(*camera.view_proj(), still);
+        assert_eq!(*camera.position(), Vector3::new(1.0, 2.0, 3.0));
+
+        for _ in 0..3 {
+            camera.update(&keys_pressed, 0.1);
+        }
+        assert_eq!(*camera.position(), Vector3::new(1.0, 2.0, 3.0));
+        assert_eq!(*camera.view_proj(), still);
+    }
+
+    #[test]
+    fn fly_stops_at_floor() {
+        let Some((device, _queue)) = test_device() else {
+            return;
+        };
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::VERTEX,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let mut camera = NoClipCamera::new(
+            &device,
+            &layout,
+            Vector3::new(0.0, 1.0, 0.0),
+            0.0,
+            0.0,
+            0.0,
+            Projection::new(800.0, 600.0, 90.0, 0.1, 100.0),
+        );
+        camera.set_controls(CameraControls {
+            speed: 10.0,
+            min_y: Some(0.0),
+            ..Default::default()
+        });
+
+        let up = HashMap::from([(KeyCode::Space, true)]);
+        camera.update(&up, 0.5);
+        assert_eq!(camera.position().y, 6.0);
+
+        let down = HashMap::from([(KeyCode::ShiftLeft, true)]);
+        camera.update(&down, 0.5);
+        assert_eq!(camera.position().y, 1.0);
+        camera.update(&down, 0.5);
+        assert_eq!(camera.position().y, 0.0);
+        camera.update(&down, 0.5);
+        assert_eq!(camera.position().y, 0.0);
+
+        // already below the floor, it isn't pushed up
+        let controls = camera.controls();
+        assert_eq!(controls.clamp_descent(-3.0, -4.0), -3.0);
+        assert_eq!(controls.clamp_descent(-3.0, -2.0), -2.0);
+    }
+
+    #[test]
+    fn editor_camera_pans_and_zooms() {
+        let Some((device, _queue)) = test_device() else {
+            return;
+        };
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::VERTEX,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        // looking down at the xz plane, 20 units tall on a 100 pixel tall viewport
+        let mut camera = EditorCamera::new(
+            &device,
+            &layout,
+            Vector3::new(0.0, 50.0, 0.0),
+            -Vector3::y_axis(),
+            10.0,
+            200.0,
+            100.0,
+        );
+
+        // dragging right and down moves the view left and up, staying at the same height
+        camera.pan(10.0, 5.0);
+        assert!((camera.position() - Vector3::new(-2.0, 50.0, -1.0)).magnitude() < 1.0e-5);
+        assert!(
+            camera
+                .get_center()
+                .dot(&(camera.position() - Vector3::new(0.0, 50.0, 0.0)))
+                .abs()
+                < 1.0e-5
+        );
+
+        camera.zoom(2.0);
+        assert!((camera.extent() - 10.0 / EDITOR_ZOOM_STEP.powi(2)).abs() < 1.0e-5);
+        camera.zoom(-2.0);
+        assert!((camera.extent() - 10.0).abs() < 1.0e-4);
+
+        // the point straight below the camera is in the middle of the view
+        let clip = camera.view_proj() * nalgebra::Vector4::new(-2.0, 0.0, -1.0, 1.0);
+        assert!(clip.x.abs() < 1.0e-5 && clip.y.abs() < 1.0e-5);
+    }
+}
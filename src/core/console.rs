@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+
+#[derive(Debug, PartialEq)]
+pub enum ConsoleError {
+    /// The line had no command in it.
+    Empty,
+
+    UnknownCommand(String),
+
+    /// The command exists but couldn't use the given arguments. Contains a usage hint.
+    InvalidArguments(String),
+
+    /// The command can't run right now, i.e. before the app has started.
+    Unavailable(String),
+}
+
+pub type ConsoleCommand<T> = Box<dyn FnMut(&mut T, &[&str]) -> Result<(), ConsoleError>>;
+
+/// Maps command names to closures operating on [T], for tweaking tunables at runtime.
+///
+/// A line is a command name followed by whitespace separated arguments. A leading "set" is
+/// optional, so "set camera_speed 50" and "camera_speed 50" run the same command.
+pub struct Console<T> {
+    commands: HashMap<String, ConsoleCommand<T>>,
+}
+
+impl<T> Default for Console<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Console<T> {
+    pub fn new() -> Self {
+        Self {
+            commands: HashMap::new(),
+        }
+    }
+
+    /// Replaces any command with the same name.
+    pub fn register(
+        &mut self,
+        name: &str,
+        command: impl FnMut(&mut T, &[&str]) -> Result<(), ConsoleError> + 'static,
+    ) {
+        self.commands.insert(name.to_owned(), Box::new(command));
+    }
+
+    pub fn command_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.commands.keys().map(|n| n.as_str()).collect();
+        names.sort();
+        names
+    }
+
+    pub fn execute(&mut self, target: &mut T, line: &str) -> Result<(), ConsoleError> {
+        let (name, args) = parse(line)?;
+        let command = self
+            .commands
+            .get_mut(name)
+            .ok_or_else(|| ConsoleError::UnknownCommand(name.to_owned()))?;
+        command(target, &args)
+    }
+}
+
+/// Splits a line into the command name and its arguments.
+fn parse(line: &str) -> Result<(&str, Vec<&str>), ConsoleError> {
+    let mut tokens = line.split_whitespace();
+    let mut name = tokens.next().ok_or(ConsoleError::Empty)?;
+    if name == "set" {
+        name = tokens.next().ok_or(ConsoleError::Empty)?;
+    }
+    Ok((name, tokens.collect()))
+}
+
+/// Parses every argument as an f32, expecting exactly [N] of them.
+pub fn parse_floats<const N: usize>(args: &[&str], usage: &str) -> Result<[f32; N], ConsoleError> {
+    let invalid = || ConsoleError::InvalidArguments(usage.to_owned());
+    if args.len() != N {
+        return Err(invalid());
+    }
+    let mut values = [0.0; N];
+    for (value, arg) in values.iter_mut().zip(args) {
+        *value = arg.parse().map_err(|_| invalid())?;
+    }
+    Ok(values)
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use crate::core::{
+        camera::CameraControls,
+        console::{Console, ConsoleError, parse_floats},
+    };
+
+    #[test]
+    fn set_command_updates_value() {
+        let mut console = Console::<CameraControls>::new();
+        console.register("camera_speed", |controls, args| {
+            let [speed] = parse_floats(args, "camera_speed <units per second>")?;
+            controls.speed = speed;
+            Ok(())
+        });
+        let mut controls = CameraControls::default();
+
+        console
+            .execute(&mut controls, "set camera_speed 50")
+            .unwrap();
+        assert_eq!(controls.speed, 50.0);
+
+        console
+            .execute(&mut controls, "  camera_speed 12.5 ")
+            .unwrap();
+        assert_eq!(controls.speed, 12.5);
+
+        assert_eq!(
+            console.execute(&mut controls, "set camera_speed fast"),
+            Err(ConsoleError::InvalidArguments(
+                "camera_speed <units per second>".into()
+            ))
+        );
+        assert_eq!(
+            console.execute(&mut controls, "set fov 90"),
+            Err(ConsoleError::UnknownCommand("fov".into()))
+        );
+        assert_eq!(console.execute(&mut controls, ""), Err(ConsoleError::Empty));
+        assert_eq!(controls.speed, 12.5);
+    }
+}
@@ -1,8 +1,35 @@
-use crate::{Integrator, render::storage::textures::MipLevel};
+use std::time::Duration;
+
+use wgpu::Color;
+use winit::keyboard::KeyCode;
+
+use crate::{Integrator, core::prefabs::BroadPhase};
 
 pub const G: f64 = 6.6743e-11;
 
-pub const GLOBAL_INTEGRATOR: Integrator = Integrator::RK4;
+/// Integrator the app starts with, see ActiveState::set_integrator.
+pub const DEFAULT_INTEGRATOR: Integrator = Integrator::RK4;
+
+/// Gravity softening length, see GravitySystem::with_softening. 0.0 is plain Newtonian gravity.
+pub const DEFAULT_GRAVITY_SOFTENING: f32 = 0.0;
+
+/// Passes CollisionsSystem makes over every overlapping pair per tick, see CollisionsSystem::with_iterations.
+pub const DEFAULT_COLLISION_ITERATIONS: u32 = 4;
+
+/// How CollisionsSystem finds overlapping pairs, see CollisionsSystem::with_broad_phase.
+pub const DEFAULT_BROAD_PHASE: BroadPhase = BroadPhase::SweepAndPrune;
+
+/// Speed under which an entity counts as still at the start of a tick, see Entity::is_asleep.
+pub const SLEEP_SPEED: f32 = 1.0e-2;
+
+/// Ticks an entity has to stay still for before it falls asleep, see Entity::is_asleep.
+pub const SLEEP_TICKS: u32 = 60;
+
+/// Longest frame time passed to the tick hooks. Longer frames (i.e. after a hitch) are simulated as this long.
+pub const DEFAULT_MAX_DT: Duration = Duration::from_millis(100);
+
+/// Time between frames while the window is unfocused, see BackgroundConfig::unfocused_frame_time.
+pub const DEFAULT_UNFOCUSED_FRAME_TIME: Duration = Duration::from_millis(100);
 
 /// Number of vertices per chunk per side (regardless of chunk size). Higher numbers increase performance demands.
 pub const CHUNK_RESOLUTION: usize = 4;
@@ -10,11 +37,55 @@ pub const CHUNK_RESOLUTION: usize = 4;
 /// Units of distance covered by a chunk. Lower numbers increase performance demands.
 pub const CHUNK_SIZE: f32 = 16.0;
 
+/// Slopes (1.0 - the normal's y, 0.0 for flat ground) over which terrain blends from its ground texture
+/// to rock.
+pub const SPLAT_ROCK_SLOPE: (f32, f32) = (0.15, 0.35);
+/// Terrain below this height is sand.
+pub const SPLAT_SAND_HEIGHT: f32 = 0.5;
+/// Terrain above this height is snow.
+pub const SPLAT_SNOW_HEIGHT: f32 = 40.0;
+/// Distance above and below SPLAT_SAND_HEIGHT and SPLAT_SNOW_HEIGHT over which the textures blend.
+pub const SPLAT_HEIGHT_BLEND: f32 = 1.0;
+
+/// Features per unit of distance of the coarsest octave of terrain noise, see NoiseTerrain.
+pub const TERRAIN_FREQUENCY: f32 = 1.0 / 64.0;
+/// Furthest the terrain rises above or sinks below 0.0, see NoiseTerrain.
+pub const TERRAIN_AMPLITUDE: f32 = 8.0;
+/// Layers of terrain noise, each with twice the frequency and half the amplitude of the last.
+pub const TERRAIN_OCTAVES: u32 = 4;
+
 pub const CAMERA_SPEED: f32 = 20.0;
 pub const CAMERA_USES_PITCH: bool = true;
-pub const RENDER_DISTANCE: f32 = 16.0;
+/// Vertical field of view in degrees of the cameras App creates, unless set otherwise, see App::set_projection_config.
+pub const DEFAULT_FOVY: f32 = 90.0;
+pub const DEFAULT_NEAR: f32 = 0.1;
+pub const DEFAULT_FAR: f32 = 10000.0;
+/// Radians the view is turned by per unit of CameraShake intensity, at the start of a shake.
+pub const CAMERA_SHAKE_ANGLE: f32 = 0.02;
+/// The ortho extent of EditorCamera is divided by this per scroll wheel line.
+pub const EDITOR_ZOOM_STEP: f32 = 1.1;
+/// How tightly FollowCameraSystem follows its target unless set otherwise, see FollowCameraSystem::set_stiffness.
+pub const DEFAULT_FOLLOW_STIFFNESS: f32 = 5.0;
+/// Units of distance around the camera in which terrain chunks are loaded, see World::load.
+pub const RENDER_DISTANCE: f32 = 256.0;
 
 pub const MUTE: bool = false;
 
-// must be in decreasing quality
-pub const MIPMAP_LEVELS: [MipLevel; 1] = [MipLevel::Square(2048)];
+/// Most lights the renderer holds unless RendererConfig::max_lights says otherwise.
+pub const DEFAULT_MAX_LIGHTS: usize = 16;
+/// Background of the 3D scene unless set otherwise, see Renderer::set_clear_color.
+pub const DEFAULT_CLEAR_COLOR: Color = Color {
+    r: 0.1,
+    g: 0.2,
+    b: 0.3,
+    a: 1.0,
+};
+
+/// Switches between lit and normal-debug shading.
+pub const SHADING_MODE_TOGGLE_KEY: KeyCode = KeyCode::F3;
+
+/// Switches between filled and wireframe triangles, if the adapter supports Features::POLYGON_MODE_LINE.
+pub const WIREFRAME_TOGGLE_KEY: KeyCode = KeyCode::F4;
+
+/// Opens and closes the command console.
+pub const CONSOLE_KEY: KeyCode = KeyCode::Backquote;
@@ -1,3 +1,7 @@
 mod completer;
+mod id_bank;
+mod sim_clock;
 
 pub use completer::{Completer, CompleterError};
+pub use id_bank::IdBank;
+pub use sim_clock::SimClock;
@@ -0,0 +1,58 @@
+/// Issues entity ids in increasing order. An id is never issued twice, so ids of removed entities are
+/// never reused within a session and ids don't depend on an entity's position in any array.
+#[derive(Debug, Clone)]
+pub struct IdBank {
+    next: u64,
+}
+
+impl Default for IdBank {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IdBank {
+    pub fn new() -> Self {
+        Self { next: 0 }
+    }
+
+    /// Issues a new id.
+    pub fn issue(&mut self) -> u64 {
+        let id = self.next;
+        self.next = self.next.checked_add(1).expect("entity ids exhausted");
+        id
+    }
+
+    /// Number of ids issued so far.
+    pub fn issued(&self) -> u64 {
+        self.next
+    }
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::core::IdBank;
+
+    #[test]
+    fn ids_never_reused_after_removal() {
+        let mut ids = IdBank::new();
+        let mut alive: Vec<u64> = (0..5).map(|_| ids.issue()).collect();
+        let mut issued: HashSet<u64> = alive.iter().copied().collect();
+
+        for round in 0..10 {
+            // remove from the front and the middle, then add back
+            alive.remove(0);
+            alive.remove(alive.len() / 2);
+            for _ in 0..round % 3 + 2 {
+                let id = ids.issue();
+                assert!(issued.insert(id), "{} issued twice", id);
+                assert!(!alive.contains(&id));
+                alive.push(id);
+            }
+        }
+
+        assert_eq!(ids.issued() as usize, issued.len());
+    }
+}
@@ -0,0 +1,60 @@
+/// Simulation time accumulated from ticks. Stops while paused, unlike wall-clock time.
+#[derive(Debug, Clone, Default)]
+pub struct SimClock {
+    // f64 so long sessions don't lose small steps
+    time: f64,
+    paused: bool,
+}
+
+impl SimClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Seconds simulated so far.
+    pub fn time(&self) -> f32 {
+        self.time as f32
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    /// Adds a tick of [dt] seconds. Ignored while paused.
+    pub fn advance(&mut self, dt: f32) {
+        if !self.paused {
+            self.time += dt as f64;
+        }
+    }
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use crate::core::SimClock;
+
+    #[test]
+    fn sums_ticks_except_while_paused() {
+        let mut clock = SimClock::new();
+        let dts = [0.016, 0.017, 0.1, 0.0005];
+
+        for dt in dts {
+            clock.advance(dt);
+        }
+        let expected: f32 = dts.iter().sum();
+        assert!((clock.time() - expected).abs() < 1.0e-6);
+
+        clock.set_paused(true);
+        for dt in dts {
+            clock.advance(dt);
+        }
+        assert!((clock.time() - expected).abs() < 1.0e-6);
+
+        clock.set_paused(false);
+        clock.advance(1.0);
+        assert!((clock.time() - expected - 1.0).abs() < 1.0e-6);
+    }
+}
@@ -1,9 +1,16 @@
-use std::fmt::Debug;
+use std::{collections::HashMap, fmt::Debug};
 
-use nalgebra::{Matrix4, UnitQuaternion, Vector3, Vector4};
+use nalgebra::{Matrix4, UnitQuaternion, Vector3};
 use wgpu::{BufferAddress, VertexAttribute, VertexBufferLayout, VertexFormat, VertexStepMode};
 
-use crate::core::{Instanced, Meshed, Unique, camera::NoClipCamera};
+use crate::{
+    Integrator,
+    core::{
+        DEFAULT_INTEGRATOR, Instanced, Meshed, SLEEP_SPEED, SLEEP_TICKS, Textured, Unique,
+        camera::NoClipCamera,
+    },
+    render::vertex::DefaultInstanceType,
+};
 
 /// Elastic collisions have CollisionResponse::Inelastic(1.0).
 /// Inelastic takes any value. Values exceeding 1.0 will result in
@@ -40,26 +47,107 @@ impl BoundingBox {
 
     pub const ZERO: BoundingBox = Self::new((0.0, 0.0, 0.0), (0.0, 0.0, 0.0));
 
-    /// Returns None if they don't intersect.
-    ///
-    /// Result vector is a signed distance of how far they intersect in each axis.
-    pub fn intersects(&self, other: &BoundingBox) -> Option<[f32; 3]> {
-        let x = (other.x + other.x_size) / 2.0 - (self.x + self.x_size) / 2.0;
-        let y = (other.y + other.y_size) / 2.0 - (self.y + self.y_size) / 2.0;
-        let z = (other.z + other.z_size) / 2.0 - (self.z + self.z_size) / 2.0;
+    /// Corner with the smallest coordinates.
+    pub fn min(&self) -> Vector3<f32> {
+        Vector3::new(
+            self.x.min(self.x + self.x_size),
+            self.y.min(self.y + self.y_size),
+            self.z.min(self.z + self.z_size),
+        )
+    }
 
-        let min_x_size = self.x_size.min(other.x_size);
-        let min_y_size = self.y_size.min(other.y_size);
-        let min_z_size = self.z_size.min(other.z_size);
+    /// Corner with the largest coordinates.
+    pub fn max(&self) -> Vector3<f32> {
+        Vector3::new(
+            self.x.max(self.x + self.x_size),
+            self.y.max(self.y + self.y_size),
+            self.z.max(self.z + self.z_size),
+        )
+    }
 
-        if x.abs() > min_x_size || y.abs() > min_y_size || z.abs() > min_z_size {
+    /// Smallest axis-aligned box enclosing this box after it's scaled, rotated, then translated.
+    pub fn transformed(
+        &self,
+        scale: &Vector3<f32>,
+        rotation: &UnitQuaternion<f32>,
+        translation: &Vector3<f32>,
+    ) -> BoundingBox {
+        let (min, max) = (self.min(), self.max());
+        let mut new_min = Vector3::repeat(f32::INFINITY);
+        let mut new_max = Vector3::repeat(f32::NEG_INFINITY);
+        for corner in 0..8 {
+            let local = Vector3::new(
+                if corner & 1 == 0 { min.x } else { max.x },
+                if corner & 2 == 0 { min.y } else { max.y },
+                if corner & 4 == 0 { min.z } else { max.z },
+            );
+            let transformed = rotation * local.component_mul(scale) + translation;
+            new_min = new_min.inf(&transformed);
+            new_max = new_max.sup(&transformed);
+        }
+        let size = new_max - new_min;
+        BoundingBox::new((new_min.x, new_min.y, new_min.z), (size.x, size.y, size.z))
+    }
+
+    /// Returns None if they don't intersect. Boxes which only touch intersect, by 0.0 on that axis.
+    ///
+    /// Result vector is the depth they overlap by on each axis, positive where the center of [other] lies
+    /// on the positive side of this box's center. Before entity pairs were resolved, it was the offset
+    /// between the centers instead.
+    pub fn intersects(&self, other: &BoundingBox) -> Option<[f32; 3]> {
+        let (self_min, self_max) = (self.min(), self.max());
+        let (other_min, other_max) = (other.min(), other.max());
+        let overlap = self_max.inf(&other_max) - self_min.sup(&other_min);
+        if overlap.iter().any(|o| *o < 0.0) {
             return None;
         }
 
-        Some([x, y, z])
+        let offset = (other_min + other_max) - (self_min + self_max);
+        let mut result = [0.0; 3];
+        for axis in 0..3 {
+            result[axis] = if offset[axis] >= 0.0 {
+                overlap[axis]
+            } else {
+                -overlap[axis]
+            };
+        }
+        Some(result)
     }
 }
 
+impl CollisionResponse {
+    /// Coefficient of restitution, clamped to 0.0 or above. None if immovable.
+    pub fn restitution(&self) -> Option<f32> {
+        match self {
+            CollisionResponse::Immovable => None,
+            CollisionResponse::Inelastic(restitution) => Some(restitution.max(0.0)),
+        }
+    }
+}
+
+/// Decides which render pass and pipeline an entity is drawn with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum MaterialKind {
+    /// Lit and depth tested.
+    #[default]
+    Opaque,
+    /// Drawn with the texture's colour as-is, ignoring lighting.
+    Unlit,
+    /// Alpha blended over opaque geometry. Doesn't write depth.
+    Transparent,
+    /// Drawn by a render module added with Renderer::add_render_module, which returns it.
+    Custom(u32),
+}
+
+impl MaterialKind {
+    /// The built-in materials, in the order they're drawn.
+    pub const ALL: [MaterialKind; 3] = [
+        MaterialKind::Opaque,
+        MaterialKind::Unlit,
+        MaterialKind::Transparent,
+    ];
+}
+
 pub enum EntityType {
     Player {
         // TODO: camera and transform both store a position
@@ -87,6 +175,28 @@ pub struct Entity {
     pub entity_type: EntityType,
     pub response: CollisionResponse,
     pub mass: f32,
+
+    /// Fraction of horizontal velocity lost per second while in contact with the ground.
+    /// 0.0 is frictionless.
+    pub friction: f32,
+
+    /// Seconds left until the entity is despawned by LifetimeSystem. Lives forever if None.
+    pub lifetime: Option<f32>,
+
+    pub material: MaterialKind,
+
+    /// How much the entity glows, from 0.0 (only lit by light sources) to 1.0 (always shown at its
+    /// texture's color, i.e. for a sun).
+    pub emissive: f32,
+
+    /// Multiplies the texture's color, white by default. See Palette for varied tints.
+    pub tint: Vector3<f32>,
+
+    // Consecutive ticks the entity has been still for, see Entity::is_asleep
+    still_ticks: u32,
+
+    // dt of the last Integrator::RK4 step, whose velocity is still missing half of its change
+    pending_kick: Option<f32>,
 }
 
 impl Entity {
@@ -118,12 +228,208 @@ impl Entity {
             entity_type,
             response,
             mass,
+            friction: 0.0,
+            lifetime: None,
+            material: MaterialKind::Opaque,
+            emissive: 0.0,
+            tint: Vector3::new(1.0, 1.0, 1.0),
+            still_ticks: 0,
+            pending_kick: None,
         }
     }
 
     pub fn texture_id(&self) -> &u64 {
         &self.texture_id
     }
+
+    pub fn translation(&self) -> &Vector3<f32> {
+        &self.translation
+    }
+
+    pub fn set_translation(&mut self, translation: Vector3<f32>) {
+        self.translation = translation;
+    }
+
+    pub fn translation_mut(&mut self) -> &mut Vector3<f32> {
+        &mut self.translation
+    }
+
+    /// Moves the entity by [by], in world space.
+    pub fn translate(&mut self, by: &Vector3<f32>) {
+        self.translation += by;
+    }
+
+    pub fn rotation(&self) -> &UnitQuaternion<f32> {
+        &self.rotation
+    }
+
+    pub fn rotation_mut(&mut self) -> &mut UnitQuaternion<f32> {
+        &mut self.rotation
+    }
+
+    /// Applies [by] after the current rotation, turning the entity about its own position by world axes.
+    pub fn rotate(&mut self, by: &UnitQuaternion<f32>) {
+        self.rotation = by * self.rotation;
+    }
+
+    pub fn scale(&self) -> &Vector3<f32> {
+        &self.scale
+    }
+
+    pub fn scale_mut(&mut self) -> &mut Vector3<f32> {
+        &mut self.scale
+    }
+
+    pub fn velocity(&self) -> &Vector3<f32> {
+        &self.velocity
+    }
+
+    pub fn set_velocity(&mut self, velocity: Vector3<f32>) {
+        self.velocity = velocity;
+        self.pending_kick = None;
+    }
+
+    pub fn acceleration(&self) -> &Vector3<f32> {
+        &self.acceleration
+    }
+
+    /// Wakes the entity if [acceleration] differs from its current acceleration, see Entity::is_asleep.
+    pub fn set_acceleration(&mut self, acceleration: Vector3<f32>) {
+        if acceleration != self.acceleration {
+            self.wake();
+        }
+        self.acceleration = acceleration;
+    }
+
+    /// Min and max corners of the axis-aligned box enclosing the bounding box after scale, rotation and
+    /// translation are applied.
+    pub fn world_bounds(&self) -> (Vector3<f32>, Vector3<f32>) {
+        let world = self
+            .bounding_box
+            .transformed(&self.scale, &self.rotation, &self.translation);
+        (world.min(), world.max())
+    }
+
+    /// Advances velocity and translation by [dt] seconds using DEFAULT_INTEGRATOR. Systems use
+    /// [Entity::integrate] with ActiveState::integrator instead, so it can be switched at runtime.
+    pub fn tick(&mut self, dt: f32) {
+        self.integrate(dt, &DEFAULT_INTEGRATOR);
+    }
+
+    /// Advances velocity and translation by [dt] seconds using [integrator], with the acceleration set
+    /// for this tick.
+    ///
+    /// Systems only work out the acceleration once per tick rather than at each RK4 stage, so
+    /// Integrator::RK4 steps with velocity Verlet, which keeps orbits from drifting outwards. The second
+    /// half of its velocity change needs the acceleration at the new translation, so it's applied at the
+    /// start of the next step.
+    pub fn integrate(&mut self, dt: f32, integrator: &Integrator) {
+        let acceleration = self.acceleration;
+        if let Some(last_dt) = self.pending_kick.take() {
+            self.velocity += acceleration * last_dt / 2.0;
+        }
+        match integrator {
+            Integrator::RK4 => {
+                self.velocity += acceleration * dt / 2.0;
+                self.translation += self.velocity * dt;
+                self.pending_kick = Some(dt);
+            }
+            Integrator::Euler => {
+                self.velocity += acceleration * dt;
+                self.translation += self.velocity * dt;
+            }
+        }
+    }
+
+    /// Velocity changes of this entity and [other] if their world bounds overlap and they're moving
+    /// towards each other, conserving momentum along the axis they overlap least on. Immovable entities
+    /// have infinite mass and use the other entity's restitution, otherwise the lower of the two is used.
+    ///
+    /// Returns None if they don't intersect, and zero deltas if they're separating or both immovable.
+    pub fn perform_single_collision(&self, other: &Entity) -> Option<(Vector3<f32>, Vector3<f32>)> {
+        let world = |entity: &Entity| {
+            entity
+                .bounding_box
+                .transformed(&entity.scale, &entity.rotation, &entity.translation)
+        };
+        let penetration = world(self).intersects(&world(other))?;
+
+        let axis = (0..3)
+            .min_by(|a, b| penetration[*a].abs().total_cmp(&penetration[*b].abs()))
+            .unwrap();
+        let mut normal = Vector3::zeros();
+        normal[axis] = if penetration[axis] >= 0.0 { 1.0 } else { -1.0 };
+
+        let (self_inverse, other_inverse) = (self.inverse_mass(), other.inverse_mass());
+        // closing speed along the normal, negative while approaching
+        let relative = (other.velocity - self.velocity).dot(&normal);
+        if self_inverse + other_inverse == 0.0 || relative >= 0.0 {
+            return Some((Vector3::zeros(), Vector3::zeros()));
+        }
+
+        let restitution = match (self.response.restitution(), other.response.restitution()) {
+            (Some(a), Some(b)) => a.min(b),
+            (Some(e), None) | (None, Some(e)) => e,
+            (None, None) => 0.0,
+        };
+        let impulse = -(1.0 + restitution) * relative / (self_inverse + other_inverse);
+        Some((
+            -normal * impulse * self_inverse,
+            normal * impulse * other_inverse,
+        ))
+    }
+
+    /// 0.0 for immovable entities, which act as if infinitely heavy. Entities whose mass isn't positive and
+    /// finite, e.g. 0.0, are treated as immovable too, rather than taking infinite or NaN velocities.
+    pub fn inverse_mass(&self) -> f32 {
+        match self.response {
+            CollisionResponse::Inelastic(_) if self.mass > 0.0 && self.mass.is_finite() => {
+                1.0 / self.mass
+            }
+            _ => 0.0,
+        }
+    }
+
+    /// Scale, then rotation, then translation, built on the CPU. The renderer assembles the same matrix
+    /// from the entity's instance in the vertex shader.
+    pub fn model_matrix(&self) -> Matrix4<f32> {
+        Matrix4::new_translation(&self.translation)
+            * self.rotation.to_homogeneous()
+            * Matrix4::new_nonuniform_scaling(&self.scale)
+    }
+
+    /// Slows down the horizontal (x/z) velocity according to [friction]. Vertical velocity is untouched.
+    ///
+    /// Should only be called while the entity is in contact with the ground.
+    pub fn apply_ground_friction(&mut self, dt: f32) {
+        let factor = (1.0 - self.friction * dt).max(0.0);
+        self.velocity.x *= factor;
+        self.velocity.z *= factor;
+    }
+
+    /// True once the entity has started SLEEP_TICKS ticks in a row slower than SLEEP_SPEED. Its speed is
+    /// checked before integrating, after the last tick's contacts were resolved, so an entity resting on
+    /// the ground under gravity counts as still while one hanging in the air doesn't. DynamicsSystem
+    /// doesn't integrate sleeping entities until a collision or anything else speeds them up again, their
+    /// acceleration is set to something else, or Entity::wake is called.
+    pub fn is_asleep(&self) -> bool {
+        self.still_ticks >= SLEEP_TICKS
+    }
+
+    pub fn wake(&mut self) {
+        self.still_ticks = 0;
+    }
+
+    /// Counts one more tick towards falling asleep if the entity is still, or wakes it. Returns
+    /// Entity::is_asleep.
+    pub fn update_sleep(&mut self) -> bool {
+        if self.velocity.magnitude() < SLEEP_SPEED {
+            self.still_ticks = self.still_ticks.saturating_add(1);
+        } else {
+            self.wake();
+        }
+        self.is_asleep()
+    }
 }
 
 impl Meshed<u64> for Entity {
@@ -132,23 +438,74 @@ impl Meshed<u64> for Entity {
     }
 }
 
+impl Textured<u64> for Entity {
+    fn texture_id(&self) -> &u64 {
+        &self.texture_id
+    }
+}
+
+/// Min and max corners of the smallest axis-aligned box enclosing the world bounds of every entity.
+/// None if there are no entities.
+pub fn combined_bounds<'a>(
+    entities: impl IntoIterator<Item = &'a Entity>,
+) -> Option<(Vector3<f32>, Vector3<f32>)> {
+    entities
+        .into_iter()
+        .map(|e| e.world_bounds())
+        .reduce(|(a_min, a_max), (b_min, b_max)| (a_min.inf(&b_min), a_max.sup(&b_max)))
+}
+
+pub type EntityScript = Box<dyn FnMut(&mut Entity, f32)>;
+
+/// Update closures attached to specific entities, run with the tick's dt before integration.
+/// Stored in ActiveState, see ActiveState::add_script.
+///
+/// A script only gets its own entity, other entities can't be borrowed while it runs.
+#[derive(Default)]
+pub struct EntityScripts {
+    scripts: HashMap<u64, Vec<EntityScript>>,
+}
+
+impl EntityScripts {
+    pub fn register(&mut self, id: u64, script: EntityScript) {
+        self.scripts.entry(id).or_default().push(script);
+    }
+
+    /// Drops every script of [id].
+    pub fn remove(&mut self, id: u64) {
+        self.scripts.remove(&id);
+    }
+
+    /// Runs the scripts of every entity in [entities], in registration order.
+    pub fn run(&mut self, entities: &mut [Entity], dt: f32) {
+        if self.scripts.is_empty() {
+            return;
+        }
+        for entity in entities {
+            if let Some(scripts) = self.scripts.get_mut(&entity.id) {
+                for script in scripts.iter_mut() {
+                    script(entity, dt);
+                }
+            }
+        }
+    }
+}
+
 impl Unique<u64> for Entity {
     fn id(&self) -> &u64 {
         &self.id
     }
 }
 
-impl Instanced<[[f32; 4]; 4]> for Entity {
-    fn instance(&self) -> [[f32; 4]; 4] {
-        let mut mat =
-            Matrix4::from_diagonal(&Vector4::new(self.scale.x, self.scale.y, self.scale.z, 1.0))
-                * self.rotation.to_rotation_matrix().to_homogeneous();
-        let mut column = mat.column_mut(3);
-        column.x = self.translation.x;
-        column.y = self.translation.y;
-        column.z = self.translation.z;
-
-        Into::<[[f32; 4]; 4]>::into(mat)
+impl Instanced<DefaultInstanceType> for Entity {
+    fn instance(&self) -> DefaultInstanceType {
+        DefaultInstanceType {
+            rotation: self.rotation.coords.into(),
+            translation: self.translation.into(),
+            scale: self.scale.into(),
+            emissive: self.emissive,
+            tint: self.tint.into(),
+        }
     }
 }
 
@@ -157,11 +514,23 @@ mod tests {
     use std::f32::consts::PI;
 
     use assertables::{assert_abs_diff_eq_x, assert_abs_diff_lt_x};
-    use nalgebra::{UnitQuaternion, UnitVector3, Vector3};
+    use nalgebra::{Matrix3, Matrix4, UnitQuaternion, UnitVector3, Vector3, Vector4};
+    use rand::{Rng, SeedableRng, rngs::StdRng};
 
-    use crate::core::{
-        Instanced,
-        entity::{BoundingBox, CollisionResponse, Entity, EntityType},
+    use crate::{
+        Integrator,
+        core::{
+            Instanced,
+            entity::{
+                BoundingBox, CollisionResponse, Entity, EntityScripts, EntityType, combined_bounds,
+            },
+        },
+        render::vertex::DefaultInstanceType,
+    };
+    use wgpu::{
+        BindGroupDescriptor, BindGroupEntry, BufferDescriptor, BufferUsages,
+        ComputePipelineDescriptor, MapMode, PollType, ShaderModuleDescriptor, ShaderSource,
+        util::{BufferInitDescriptor, DeviceExt},
     };
 
     #[test]
@@ -189,31 +558,731 @@ mod tests {
             CollisionResponse::Immovable,
             1.0,
         );
-        let rotation: [[f32; 3]; 3] = (*entity.rotation.to_rotation_matrix().matrix()).into();
-        let expected_rotation = [
-            [0.5721, 0.5721, -0.5878],
-            [-0.4046, 0.8202, 0.4045],
-            [0.7135, 0.0064, 0.7006],
+        // the same rotations from their textbook matrices, independently of the quaternions
+        let (sz, cz) = (PI / 4.0).sin_cos();
+        let (sy, cy) = (PI / 5.0).sin_cos();
+        let (sx, cx) = (PI / 6.0).sin_cos();
+        let expected_rotation = Matrix3::new(cz, -sz, 0.0, sz, cz, 0.0, 0.0, 0.0, 1.0)
+            * Matrix3::new(cy, 0.0, sy, 0.0, 1.0, 0.0, -sy, 0.0, cy)
+            * Matrix3::new(1.0, 0.0, 0.0, 0.0, cx, -sx, 0.0, sx, cx);
+        let rotation = entity.rotation.to_rotation_matrix();
+        assert_abs_diff_lt_x!(
+            (rotation.matrix() - expected_rotation).abs().max(),
+            0.0,
+            1.0e-6
+        );
+
+        // scaled along the entity's own axes, then rotated, then translated, the order of its fields
+        let mut expected_transform = (expected_rotation
+            * Matrix3::from_diagonal(&Vector3::new(5.0, 6.0, 7.0)))
+        .to_homogeneous();
+        expected_transform
+            .fixed_view_mut::<3, 1>(0, 3)
+            .copy_from(&Vector3::new(10.0, 11.0, 12.0));
+        let matrix = entity.instance().model_matrix();
+        assert_abs_diff_lt_x!((matrix - expected_transform).abs().max(), 0.0, 1.0e-5);
+        // a point on the local x axis ends up along the rotated x axis, 5 units from the translation
+        let point = matrix * Vector4::new(1.0, 0.0, 0.0, 1.0);
+        let offset = point.xyz() - Vector3::new(10.0, 11.0, 12.0);
+        assert_abs_diff_lt_x!(
+            (offset - expected_rotation.column(0) * 5.0).magnitude(),
+            0.0,
+            1.0e-5
+        );
+    }
+
+    #[test]
+    fn scale_only_changes_scale() {
+        let mut entity = Entity::new(
+            0,
+            0,
+            0,
+            Vector3::new(1.0, 1.0, 1.0),
+            UnitQuaternion::from_axis_angle(
+                &UnitVector3::new_normalize(Vector3::new(1.0, 1.0, 0.0)),
+                PI / 3.0,
+            ),
+            Vector3::new(-3.0, 2.0, 8.0),
+            Vector3::zeros(),
+            Vector3::zeros(),
+            BoundingBox::ZERO,
+            EntityType::Object,
+            CollisionResponse::Immovable,
+            1.0,
+        );
+        let before = entity.instance();
+
+        entity.scale = Vector3::new(2.0, 0.5, 3.0);
+        let after = entity.instance();
+
+        assert_eq!(before.rotation, after.rotation);
+        assert_eq!(before.translation, after.translation);
+        assert_eq!(before.scale, [1.0, 1.0, 1.0]);
+        assert_eq!(after.scale, [2.0, 0.5, 3.0]);
+    }
+
+    #[test]
+    fn combined_bounds_of_three() {
+        let entity = |scale: Vector3<f32>, rotation, translation, size: (f32, f32, f32)| {
+            Entity::new(
+                0,
+                0,
+                0,
+                scale,
+                rotation,
+                translation,
+                Vector3::zeros(),
+                Vector3::zeros(),
+                BoundingBox::new((-size.0 / 2.0, -size.1 / 2.0, -size.2 / 2.0), size),
+                EntityType::Object,
+                CollisionResponse::Immovable,
+                1.0,
+            )
+        };
+        let entities = [
+            entity(
+                Vector3::new(1.0, 1.0, 1.0),
+                UnitQuaternion::identity(),
+                Vector3::new(0.0, 0.0, 0.0),
+                (2.0, 2.0, 2.0),
+            ),
+            // 2x6x2 after scaling
+            entity(
+                Vector3::new(1.0, 3.0, 1.0),
+                UnitQuaternion::identity(),
+                Vector3::new(10.0, 5.0, 0.0),
+                (2.0, 2.0, 2.0),
+            ),
+            // 4x1x1 rotated to lie along z
+            entity(
+                Vector3::new(1.0, 1.0, 1.0),
+                UnitQuaternion::from_axis_angle(&Vector3::y_axis(), PI / 2.0),
+                Vector3::new(-5.0, 0.0, -3.0),
+                (4.0, 1.0, 1.0),
+            ),
         ];
 
+        let (min, max) = combined_bounds(&entities).unwrap();
+
+        let expected_min = [-5.5, -1.0, -5.0];
+        let expected_max = [11.0, 8.0, 1.0];
         for i in 0..3 {
-            for j in 0..3 {
-                assert_abs_diff_lt_x!(expected_rotation[i][j], rotation[i][j], 1.0e-2);
+            assert_abs_diff_lt_x!(expected_min[i], min[i], 1.0e-4);
+            assert_abs_diff_lt_x!(expected_max[i], max[i], 1.0e-4);
+        }
+        assert!(combined_bounds(&[]).is_none());
+    }
+
+    #[test]
+    fn instance_matrix_matches_cpu_matrix() {
+        // seeded, so a failure reproduces
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut random = |range: f32| rng.random_range(-range..range);
+        for _ in 0..100 {
+            let entity = Entity::new(
+                0,
+                0,
+                0,
+                Vector3::new(random(5.0), random(5.0), random(5.0)),
+                UnitQuaternion::from_euler_angles(random(PI), random(PI), random(PI)),
+                Vector3::new(random(100.0), random(100.0), random(100.0)),
+                Vector3::zeros(),
+                Vector3::zeros(),
+                BoundingBox::ZERO,
+                EntityType::Object,
+                CollisionResponse::Immovable,
+                1.0,
+            );
+
+            let expected = entity.model_matrix();
+            let assembled = entity.instance().model_matrix();
+            for i in 0..16 {
+                assert_abs_diff_lt_x!(expected[i], assembled[i], 1.0e-4);
             }
         }
+    }
+
+    #[test]
+    fn model_matrix_composes_known_srt() {
+        let entity = Entity::new(
+            0,
+            0,
+            0,
+            Vector3::new(2.0, 3.0, 4.0),
+            UnitQuaternion::from_axis_angle(&Vector3::y_axis(), PI / 2.0),
+            Vector3::new(1.0, 2.0, 3.0),
+            Vector3::zeros(),
+            Vector3::zeros(),
+            BoundingBox::ZERO,
+            EntityType::Object,
+            CollisionResponse::Immovable,
+            1.0,
+        );
+        // a quarter turn about y takes x to -z and z to x, columns are the scaled axes
+        #[rustfmt::skip]
+        let expected = Matrix4::new(
+            0.0, 0.0, 4.0, 1.0,
+            0.0, 3.0, 0.0, 2.0,
+            -2.0, 0.0, 0.0, 3.0,
+            0.0, 0.0, 0.0, 1.0,
+        );
+
+        let model = entity.model_matrix();
+        let assembled = entity.instance().model_matrix();
+        for i in 0..16 {
+            assert_abs_diff_lt_x!(expected[i], model[i], 1.0e-5);
+            assert_abs_diff_lt_x!(expected[i], assembled[i], 1.0e-5);
+        }
+        let corner = model * Vector4::new(1.0, 1.0, 1.0, 1.0);
+        assert_abs_diff_lt_x!(
+            (corner - Vector4::new(5.0, 5.0, 1.0, 1.0)).amax(),
+            0.0,
+            1.0e-5
+        );
+    }
 
+    #[test]
+    fn normals_perpendicular_under_non_uniform_scale() {
+        let entity = Entity::new(
+            0,
+            0,
+            0,
+            Vector3::new(5.0, 1.0, 0.5),
+            UnitQuaternion::from_euler_angles(0.3, -1.1, 0.7),
+            Vector3::new(4.0, 0.0, -2.0),
+            Vector3::zeros(),
+            Vector3::zeros(),
+            BoundingBox::ZERO,
+            EntityType::Object,
+            CollisionResponse::Immovable,
+            1.0,
+        );
         let instance = entity.instance();
-        // used matlab for values
-        let expected_instance = [
-            [2.8603, 3.4324, -4.1145, 0.0],
-            [-2.0228, 4.9211, 2.8316, 0.0],
-            [3.5675, 0.0383, 4.9044, 0.0],
-            [10.0, 11.0, 12.0, 1.0],
+        let model = instance
+            .model_matrix()
+            .fixed_view::<3, 3>(0, 0)
+            .into_owned();
+        // a sloped surface, spanned by two tangents
+        let (u, v) = (Vector3::new(1.0, 1.0, 0.0), Vector3::new(0.0, 0.0, 1.0));
+        let normal = u.cross(&v).normalize();
+
+        let transformed = (instance.normal_matrix() * normal).normalize();
+        let naive = (model * normal).normalize();
+
+        for tangent in [u, v] {
+            let tangent = (model * tangent).normalize();
+            assert_abs_diff_lt_x!(transformed.dot(&tangent).abs(), 0.0, 1.0e-5);
+        }
+        assert!((naive.dot(&(model * u).normalize())).abs() > 0.1);
+    }
+
+    #[test]
+    fn kinematic_accessors_round_trip() {
+        let mut entity = Entity::new(
+            0,
+            0,
+            0,
+            Vector3::new(1.0, 1.0, 1.0),
+            UnitQuaternion::identity(),
+            Vector3::zeros(),
+            Vector3::zeros(),
+            Vector3::zeros(),
+            BoundingBox::ZERO,
+            EntityType::Object,
+            CollisionResponse::Inelastic(1.0),
+            1.0,
+        );
+
+        entity.set_translation(Vector3::new(1.0, 2.0, 3.0));
+        entity.set_velocity(Vector3::new(-4.0, 0.5, 0.0));
+        entity.set_acceleration(Vector3::new(0.0, -9.8, 0.0));
+
+        assert_eq!(entity.translation(), &Vector3::new(1.0, 2.0, 3.0));
+        assert_eq!(entity.velocity(), &Vector3::new(-4.0, 0.5, 0.0));
+        assert_eq!(entity.acceleration(), &Vector3::new(0.0, -9.8, 0.0));
+    }
+
+    #[test]
+    fn rotated_entity_instance_updated() {
+        let mut entity = Entity::new(
+            0,
+            0,
+            0,
+            Vector3::new(1.0, 1.0, 1.0),
+            UnitQuaternion::identity(),
+            Vector3::new(0.0, 0.0, 5.0),
+            Vector3::zeros(),
+            Vector3::zeros(),
+            BoundingBox::ZERO,
+            EntityType::Object,
+            CollisionResponse::Immovable,
+            1.0,
+        );
+        // an eighth of a turn per tick, as a spinning cube's system would
+        let spin = UnitQuaternion::from_axis_angle(&Vector3::y_axis(), PI / 4.0);
+
+        entity.rotate(&spin);
+        entity.rotate(&spin);
+        entity.translate(&Vector3::new(1.0, 0.0, 0.0));
+        *entity.scale_mut() *= 2.0;
+
+        let assembled = entity.instance().model_matrix();
+        // +x turned to -z, scaled, then moved
+        let x = assembled.transform_point(&Vector3::x().into());
+        assert_abs_diff_lt_x!(
+            (x.coords - Vector3::new(1.0, 0.0, 3.0)).magnitude(),
+            0.0,
+            1.0e-5
+        );
+        let rotation: [f32; 4] = (spin * spin).coords.into();
+        assert_eq!(entity.instance().rotation, rotation);
+    }
+
+    #[test]
+    fn rotated_box_grows() {
+        let unit = BoundingBox::new((-0.5, -0.5, -0.5), (1.0, 1.0, 1.0));
+        let rotation = UnitQuaternion::from_axis_angle(&Vector3::y_axis(), PI / 4.0);
+        let translation = Vector3::new(10.0, 0.0, 0.0);
+
+        let transformed = unit.transformed(&Vector3::new(1.0, 1.0, 1.0), &rotation, &translation);
+
+        // the diagonal of the unit square lies along x and z, height is unchanged
+        let half = 2.0_f32.sqrt() / 2.0;
+        let expected_min = Vector3::new(10.0 - half, -0.5, -half);
+        let expected_max = Vector3::new(10.0 + half, 0.5, half);
+        assert!((transformed.min() - expected_min).magnitude() < 1.0e-5);
+        assert!((transformed.max() - expected_max).magnitude() < 1.0e-5);
+    }
+
+    #[test]
+    fn intersects_checks_every_axis() {
+        let unit = BoundingBox::new((0.0, 0.0, 0.0), (1.0, 1.0, 1.0));
+        // overlapping on the other two axes, apart on one
+        for apart in [
+            BoundingBox::new((2.0, 0.5, 0.5), (1.0, 1.0, 1.0)),
+            BoundingBox::new((0.5, 2.0, 0.5), (1.0, 1.0, 1.0)),
+            BoundingBox::new((0.5, 0.5, 2.0), (1.0, 1.0, 1.0)),
+        ] {
+            assert_eq!(unit.intersects(&apart), None);
+            assert_eq!(apart.intersects(&unit), None);
+        }
+
+        let overlapping = BoundingBox::new((0.75, -0.5, 0.5), (1.0, 1.0, 2.0));
+        assert_eq!(unit.intersects(&overlapping), Some([0.25, -0.5, 0.5]));
+        assert_eq!(overlapping.intersects(&unit), Some([-0.25, 0.5, -0.5]));
+    }
+
+    #[test]
+    fn emissive_term_added_to_shading() {
+        let mut entity = Entity::new(
+            0,
+            0,
+            0,
+            Vector3::new(1.0, 1.0, 1.0),
+            UnitQuaternion::identity(),
+            Vector3::zeros(),
+            Vector3::zeros(),
+            Vector3::zeros(),
+            BoundingBox::ZERO,
+            EntityType::Object,
+            CollisionResponse::Immovable,
+            1.0,
+        );
+        assert_eq!(entity.instance().emissive, 0.0);
+        entity.emissive = 1.0;
+        let emissive = entity.instance().emissive;
+        assert_eq!(emissive, 1.0);
+
+        let base = Vector3::new(0.8, 0.6, 0.2);
+        // lit, and facing away from the light
+        for lighting in [Vector3::new(1.0, 1.0, 1.0) * 0.7, Vector3::zeros()] {
+            assert_eq!(DefaultInstanceType::shade(base, lighting, emissive), base);
+        }
+
+        let lighting = Vector3::new(0.5, 0.5, 0.5);
+        let half = DefaultInstanceType::shade(base, lighting, 0.5);
+        assert_abs_diff_lt_x!((half - (base * 0.25 + base * 0.5)).magnitude(), 0.0, 1.0e-6);
+        assert_eq!(DefaultInstanceType::shade(base, lighting, 0.0), base * 0.5);
+    }
+
+    #[test]
+    fn wgsl_shade_matches_host_side() {
+        let Some((device, queue)) = crate::render::test_device() else {
+            return;
+        };
+        // the shade function of default.wgsl, run on each case by a compute shader
+        let source = include_str!("../render/shaders/default.wgsl");
+        let start = source.find("fn shade(").unwrap();
+        let end = start + source[start..].find("\n}").unwrap() + 2;
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Shade Test Shader"),
+            source: ShaderSource::Wgsl(
+                format!(
+                    "{}
+struct Case {{ base: vec4<f32>, lighting: vec4<f32>, emissive: vec4<f32> }}
+@group(0) @binding(0) var<storage, read> cases: array<Case>;
+@group(0) @binding(1) var<storage, read_write> shaded: array<vec4<f32>>;
+@compute @workgroup_size(1)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {{
+    let c = cases[id.x];
+    shaded[id.x] = vec4<f32>(shade(c.base.xyz, c.lighting.xyz, c.emissive.x), 0.0);
+}}",
+                    &source[start..end]
+                )
+                .into(),
+            ),
+        });
+
+        let base = Vector3::new(0.8, 0.6, 0.2);
+        let cases = [
+            (base, Vector3::new(0.7, 0.7, 0.7), 1.0),
+            (base, Vector3::zeros(), 1.0),
+            (base, Vector3::new(0.5, 0.5, 0.5), 0.5),
+            (base, Vector3::new(0.5, 0.25, 1.0), 0.0),
+            (
+                Vector3::new(1.0, 0.0, 0.5),
+                Vector3::new(2.0, 1.0, 0.0),
+                0.25,
+            ),
         ];
-        for i in 0..4 {
-            for j in 0..4 {
-                assert_abs_diff_lt_x!(expected_instance[i][j], instance[i][j], 1.0e-2);
-            }
+        let case_data: Vec<[f32; 12]> = cases
+            .iter()
+            .map(|(base, lighting, emissive)| {
+                [
+                    base.x, base.y, base.z, 0.0, lighting.x, lighting.y, lighting.z, 0.0,
+                    *emissive, 0.0, 0.0, 0.0,
+                ]
+            })
+            .collect();
+        let size = (cases.len() * 16) as u64;
+        let case_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Shade Test Cases"),
+            contents: bytemuck::cast_slice(&case_data),
+            usage: BufferUsages::STORAGE,
+        });
+        let shaded_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("Shade Test Results"),
+            size,
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let read_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("Shade Test Readback"),
+            size,
+            usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let pipeline = device.create_compute_pipeline(&ComputePipelineDescriptor {
+            label: Some("Shade Test Pipeline"),
+            layout: None,
+            module: &shader,
+            entry_point: Some("main"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Shade Test Bind Group"),
+            layout: &pipeline.get_bind_group_layout(0),
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: case_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: shaded_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let mut encoder = device.create_command_encoder(&Default::default());
+        {
+            let mut pass = encoder.begin_compute_pass(&Default::default());
+            pass.set_pipeline(&pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.dispatch_workgroups(cases.len() as u32, 1, 1);
+        }
+        encoder.copy_buffer_to_buffer(&shaded_buffer, 0, &read_buffer, 0, size);
+        queue.submit(std::iter::once(encoder.finish()));
+        let slice = read_buffer.slice(..);
+        slice.map_async(MapMode::Read, |result| result.unwrap());
+        device.poll(PollType::wait_indefinitely()).unwrap();
+
+        let shaded: Vec<[f32; 4]> = bytemuck::cast_slice(&slice.get_mapped_range()).to_vec();
+        for ((base, lighting, emissive), gpu) in cases.iter().zip(shaded) {
+            let host = DefaultInstanceType::shade(*base, *lighting, *emissive);
+            let gpu = Vector3::new(gpu[0], gpu[1], gpu[2]);
+            assert!((gpu - host).magnitude() < 1.0e-6, "{} != {}", gpu, host);
+        }
+    }
+
+    #[test]
+    fn script_runs_each_tick() {
+        let entity = |id| {
+            Entity::new(
+                id,
+                0,
+                0,
+                Vector3::new(1.0, 1.0, 1.0),
+                UnitQuaternion::identity(),
+                Vector3::zeros(),
+                Vector3::zeros(),
+                Vector3::zeros(),
+                BoundingBox::ZERO,
+                EntityType::Object,
+                CollisionResponse::Immovable,
+                1.0,
+            )
+        };
+        let mut entities = vec![entity(0), entity(1)];
+        let mut scripts = EntityScripts::default();
+        let mut ticks = 0;
+        scripts.register(
+            1,
+            Box::new(move |entity, dt| {
+                ticks += 1;
+                entity.velocity.x += 2.0 * dt;
+                entity.velocity.y = ticks as f32;
+            }),
+        );
+
+        for _ in 0..4 {
+            scripts.run(&mut entities, 0.5);
         }
+
+        assert_eq!(entities[1].velocity, Vector3::new(4.0, 4.0, 0.0));
+        assert_eq!(entities[0].velocity, Vector3::zeros());
+
+        scripts.remove(1);
+        scripts.run(&mut entities, 0.5);
+        assert_eq!(entities[1].velocity, Vector3::new(4.0, 4.0, 0.0));
+    }
+
+    #[cfg(test)]
+    fn cube(x: f32, velocity: Vector3<f32>, response: CollisionResponse, mass: f32) -> Entity {
+        Entity::new(
+            0,
+            0,
+            0,
+            Vector3::new(1.0, 1.0, 1.0),
+            UnitQuaternion::identity(),
+            Vector3::new(x, 0.0, 0.0),
+            velocity,
+            Vector3::zeros(),
+            BoundingBox::new((-0.5, -0.5, -0.5), (1.0, 1.0, 1.0)),
+            EntityType::Object,
+            response,
+            mass,
+        )
+    }
+
+    #[test]
+    fn head_on_collision_conserves_momentum() {
+        let a = cube(
+            0.0,
+            Vector3::new(2.0, 0.0, 0.0),
+            CollisionResponse::Inelastic(1.0),
+            1.0,
+        );
+        let b = cube(
+            0.9,
+            Vector3::new(-1.0, 0.0, 0.0),
+            CollisionResponse::Inelastic(1.0),
+            1.0,
+        );
+
+        let (da, db) = a.perform_single_collision(&b).unwrap();
+
+        // equal masses swap velocities in an elastic collision
+        assert_abs_diff_lt_x!(
+            (a.velocity + da - Vector3::new(-1.0, 0.0, 0.0)).magnitude(),
+            0.0,
+            1.0e-5
+        );
+        assert_abs_diff_lt_x!(
+            (b.velocity + db - Vector3::new(2.0, 0.0, 0.0)).magnitude(),
+            0.0,
+            1.0e-5
+        );
+
+        // negative restitution is clamped, leaving both at the shared velocity
+        let heavy = cube(
+            0.0,
+            Vector3::new(3.0, 0.0, 0.0),
+            CollisionResponse::Inelastic(-2.0),
+            2.0,
+        );
+        let light = cube(
+            0.9,
+            Vector3::zeros(),
+            CollisionResponse::Inelastic(1.0),
+            1.0,
+        );
+        let (dh, dl) = heavy.perform_single_collision(&light).unwrap();
+        assert_abs_diff_lt_x!(
+            (heavy.velocity + dh - Vector3::new(2.0, 0.0, 0.0)).magnitude(),
+            0.0,
+            1.0e-5
+        );
+        assert_abs_diff_lt_x!(
+            (light.velocity + dl - Vector3::new(2.0, 0.0, 0.0)).magnitude(),
+            0.0,
+            1.0e-5
+        );
+
+        let apart = cube(
+            5.0,
+            Vector3::zeros(),
+            CollisionResponse::Inelastic(1.0),
+            1.0,
+        );
+        assert!(a.perform_single_collision(&apart).is_none());
+    }
+
+    #[test]
+    fn glancing_collision_keeps_tangential_velocity() {
+        let a = cube(
+            0.0,
+            Vector3::new(1.0, 0.0, 3.0),
+            CollisionResponse::Inelastic(0.5),
+            1.0,
+        );
+        let mut b = cube(
+            0.8,
+            Vector3::new(0.0, 0.0, -1.0),
+            CollisionResponse::Inelastic(0.5),
+            1.0,
+        );
+        // overlaps 0.2 along x, 0.7 along y and fully along z
+        b.translation.y = 0.3;
+
+        let (da, db) = a.perform_single_collision(&b).unwrap();
+
+        assert_eq!((da.y, da.z, db.y, db.z), (0.0, 0.0, 0.0, 0.0));
+        // closing speed of 1.0 is reversed and halved
+        assert_abs_diff_lt_x!((a.velocity.x + da.x) - (b.velocity.x + db.x), -0.5, 1.0e-5);
+        assert_abs_diff_lt_x!((da.x + db.x).abs(), 0.0, 1.0e-5);
+
+        // already separating
+        let receding = cube(
+            0.8,
+            Vector3::new(2.0, 0.0, 0.0),
+            CollisionResponse::Inelastic(0.5),
+            1.0,
+        );
+        assert_eq!(
+            a.perform_single_collision(&receding),
+            Some((Vector3::zeros(), Vector3::zeros()))
+        );
+    }
+
+    #[test]
+    fn intersects_returns_overlap_depth() {
+        let unit = BoundingBox::new((0.0, 0.0, 0.0), (1.0, 1.0, 1.0));
+
+        // the depth, not the 3.0 between the centers. Centers level on an axis count as positive.
+        let wide = BoundingBox::new((0.5, 0.0, 0.0), (6.0, 1.0, 1.0));
+        assert_eq!(unit.intersects(&wide), Some([0.5, 1.0, 1.0]));
+        assert_eq!(wide.intersects(&unit), Some([-0.5, 1.0, 1.0]));
+
+        // touching faces intersect
+        let touching = BoundingBox::new((1.0, 0.0, 0.0), (1.0, 1.0, 1.0));
+        assert_eq!(unit.intersects(&touching), Some([0.0, 1.0, 1.0]));
+    }
+
+    #[test]
+    fn massless_collides_as_immovable() {
+        let ball = |mass: f32| {
+            cube(
+                0.9,
+                Vector3::new(-4.0, 0.0, 0.0),
+                CollisionResponse::Inelastic(0.5),
+                mass,
+            )
+        };
+        let wall = cube(0.0, Vector3::zeros(), CollisionResponse::Immovable, 1.0);
+
+        for mass in [0.0, f32::INFINITY, -1.0, f32::NAN] {
+            assert_eq!(ball(mass).inverse_mass(), 0.0);
+            // both immovable
+            assert_eq!(
+                wall.perform_single_collision(&ball(mass)),
+                Some((Vector3::zeros(), Vector3::zeros()))
+            );
+            // a massless entity takes the whole impulse
+            let (d_ball, d_other) = ball(mass)
+                .perform_single_collision(&cube(
+                    0.0,
+                    Vector3::new(2.0, 0.0, 0.0),
+                    CollisionResponse::Inelastic(1.0),
+                    1.0,
+                ))
+                .unwrap();
+            assert_eq!(d_ball, Vector3::zeros());
+            assert!(d_other.iter().all(|d| d.is_finite()));
+        }
+    }
+
+    #[test]
+    fn immovable_has_infinite_mass() {
+        let wall = cube(0.0, Vector3::zeros(), CollisionResponse::Immovable, 1.0);
+        let ball = cube(
+            0.9,
+            Vector3::new(-4.0, 0.0, 1.0),
+            CollisionResponse::Inelastic(0.5),
+            1.0,
+        );
+
+        let (d_wall, d_ball) = wall.perform_single_collision(&ball).unwrap();
+
+        assert_eq!(d_wall, Vector3::zeros());
+        assert_abs_diff_lt_x!(
+            (ball.velocity + d_ball - Vector3::new(2.0, 0.0, 1.0)).magnitude(),
+            0.0,
+            1.0e-5
+        );
+
+        let other_wall = cube(
+            0.9,
+            Vector3::new(-4.0, 0.0, 0.0),
+            CollisionResponse::Immovable,
+            1.0,
+        );
+        assert_eq!(
+            wall.perform_single_collision(&other_wall),
+            Some((Vector3::zeros(), Vector3::zeros()))
+        );
+    }
+
+    #[test]
+    fn euler_and_rk4_agree_for_small_dt() {
+        let falling = || {
+            let mut entity = cube(
+                0.0,
+                Vector3::new(3.0, 0.0, 0.0),
+                CollisionResponse::Inelastic(1.0),
+                1.0,
+            );
+            entity.set_acceleration(Vector3::new(0.0, -9.8, 0.0));
+            entity
+        };
+        let (mut euler, mut rk4) = (falling(), falling());
+
+        for _ in 0..1000 {
+            euler.integrate(0.001, &Integrator::Euler);
+            rk4.integrate(0.001, &Integrator::RK4);
+        }
+
+        assert_abs_diff_lt_x!((euler.velocity - rk4.velocity).magnitude(), 0.0, 0.01);
+        assert_abs_diff_lt_x!((euler.translation - rk4.translation).magnitude(), 0.0, 0.01);
+        // RK4 is exact under constant acceleration, 3.0, -4.9 after 1 second, and Euler is close
+        assert_abs_diff_lt_x!(rk4.translation.x, 3.0, 1.0e-4);
+        assert_abs_diff_lt_x!(rk4.translation.y, -4.9, 1.0e-4);
+        // until the next step, which finishes the last one's velocity change
+        assert_abs_diff_lt_x!(rk4.velocity.y, -9.8 + 9.8 * 0.001 / 2.0, 1.0e-4);
+        rk4.integrate(0.0, &Integrator::RK4);
+        assert_abs_diff_lt_x!(rk4.velocity.y, -9.8, 1.0e-4);
+        assert_abs_diff_lt_x!(euler.translation.x, 3.0, 1.0e-4);
+        assert_abs_diff_lt_x!(euler.translation.y, -4.9, 5.0e-3);
     }
 }
@@ -1,9 +1,56 @@
 use std::f32::consts::PI;
 
-use nalgebra::{Matrix3, Rotation3, UnitVector3, Vector3};
+use nalgebra::{Matrix3, Matrix4, Rotation3, UnitQuaternion, UnitVector3, Vector3, Vector4};
 
 use crate::render::{GlobalIndexType, vertex::DefaultVertexType};
 
+#[derive(Debug, PartialEq)]
+pub enum GeometryError {
+    /// The domain and resolution given produce no vertices along the x or z axis.
+    NotEnoughVertices { n_x: u32, n_z: u32 },
+
+    /// Both edges of an EdgeJoin belong to the same face.
+    SameFace,
+
+    /// An EdgeJoin contains an empty edge.
+    EmptyEdge,
+
+    /// An EdgeJoin refers to a face index which wasn't provided.
+    FaceOutOfBounds(usize),
+
+    /// An edge index is not within the vertices of the face it belongs to.
+    EdgeIndexOutOfBounds { face: usize, index: GlobalIndexType },
+
+    /// The shape would have this many vertices, more than MeshStorage::add_mesh accepts.
+    TooManyVertices(usize),
+
+    /// A Face edge doesn't have one index per vertex of the grid along it.
+    EdgeLength {
+        edge: FaceEdge,
+        expected: usize,
+        actual: usize,
+    },
+
+    /// A Face edge index is not within the face's vertices.
+    FaceEdgeOutOfBounds {
+        edge: FaceEdge,
+        index: GlobalIndexType,
+    },
+
+    /// Going counter-clockwise, edge [to] doesn't start at the corner edge [from] ends at.
+    DisconnectedEdges { from: FaceEdge, to: FaceEdge },
+}
+
+/// One of the borders of a Face, named after the side of the flat mesh it's on before any
+/// transformations, see Face::from_function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FaceEdge {
+    PX,
+    NX,
+    PZ,
+    NZ,
+}
+
 pub trait Mesh {
     fn vertices(&self) -> &[DefaultVertexType];
     fn indices(&self) -> &[GlobalIndexType];
@@ -59,6 +106,50 @@ impl Face {
         &self.edge_nz
     }
 
+    pub fn edge(&self, edge: FaceEdge) -> &Vec<GlobalIndexType> {
+        match edge {
+            FaceEdge::PX => &self.edge_px,
+            FaceEdge::NX => &self.edge_nx,
+            FaceEdge::PZ => &self.edge_pz,
+            FaceEdge::NZ => &self.edge_nz,
+        }
+    }
+
+    /// Checks the edges are the borders of a grid of [n_x] by [n_z] vertices, as built by
+    /// Face::from_function: each has one index per vertex along its side, every index is a vertex of the
+    /// face, and each edge starts where the previous one ends, going counter-clockwise.
+    pub fn validate(&self, n_x: usize, n_z: usize) -> Result<(), GeometryError> {
+        // counter-clockwise from the -x side, looking down with -z on the top
+        let order = [FaceEdge::NX, FaceEdge::PZ, FaceEdge::PX, FaceEdge::NZ];
+        for edge in order {
+            let indices = self.edge(edge);
+            let expected = match edge {
+                FaceEdge::PX | FaceEdge::NX => n_z,
+                FaceEdge::PZ | FaceEdge::NZ => n_x,
+            };
+            if indices.len() != expected {
+                return Err(GeometryError::EdgeLength {
+                    edge,
+                    expected,
+                    actual: indices.len(),
+                });
+            }
+            if let Some(index) = indices.iter().find(|i| **i as usize >= self.vertices.len()) {
+                return Err(GeometryError::FaceEdgeOutOfBounds {
+                    edge,
+                    index: *index,
+                });
+            }
+        }
+        for (i, from) in order.into_iter().enumerate() {
+            let to = order[(i + 1) % order.len()];
+            if self.edge(from).last() != self.edge(to).first() {
+                return Err(GeometryError::DisconnectedEdges { from, to });
+            }
+        }
+        Ok(())
+    }
+
     /// Transformed flat mesh. [height] should accept x, z values within the domain described.
     /// Domain should be in a space where (0,1,0) is up. After the mesh is created, it will be rotated
     /// according to the provided up direction about the point (0,0,0).
@@ -79,7 +170,7 @@ impl Face {
         domain_z: (f32, f32),
         resolution: (f32, f32),
         height: fn(f32, f32) -> f32,
-    ) -> Result<Self, String> {
+    ) -> Result<Self, GeometryError> {
         let up = up.normalize();
 
         let length_x = domain_x.1 - domain_x.0;
@@ -93,7 +184,7 @@ impl Face {
         let n_x = e_x.floor() as u32;
         let n_z = e_z.floor() as u32;
         if n_x == 0 || n_z == 0 {
-            return Err(format!("Cannot create mesh (not enough vertices): domains:\n\tx: {:?}\n\tz: {:?}\nresolution: {:?}\nvertex count:\n\tx: {:?}\n\tz:{:?}", domain_x, domain_z, resolution, n_x, n_z).into());
+            return Err(GeometryError::NotEnoughVertices { n_x, n_z });
         }
         let extra_x = (e_x - n_x as f32) * resolution.0;
         let extra_z = (e_z - n_z as f32) * resolution.1;
@@ -173,7 +264,7 @@ impl Face {
             v_up = true;
         }
 
-        Ok(Self {
+        let face = Self {
             vertices,
             indices,
 
@@ -181,7 +272,9 @@ impl Face {
             edge_nx,
             edge_pz,
             edge_nz,
-        })
+        };
+        face.validate(n_x as usize, n_z as usize)?;
+        Ok(face)
     }
 }
 
@@ -213,9 +306,9 @@ impl EdgeJoin {
 
         edge_higher: Vec<GlobalIndexType>,
         higher_face_index: usize,
-    ) -> Result<Self, String> {
+    ) -> Result<Self, GeometryError> {
         if lower_face_index == higher_face_index {
-            return Err("Edges belong to the same face".into());
+            return Err(GeometryError::SameFace);
         }
         Ok(Self {
             edge_lower,
@@ -234,7 +327,7 @@ pub struct Shape3 {
 }
 
 impl Shape3 {
-    pub fn new(mut faces: Vec<Face>, face_joins: Vec<EdgeJoin>) -> Result<Self, String> {
+    pub fn new(mut faces: Vec<Face>, face_joins: Vec<EdgeJoin>) -> Result<Self, GeometryError> {
         // Convert vertices and indices to model coordinates
         let mut face_index_start = vec![];
         let mut vertices: Vec<DefaultVertexType> = vec![];
@@ -255,7 +348,21 @@ impl Shape3 {
         // Join faces
         for join in face_joins {
             if join.edge_higher.is_empty() || join.edge_lower.is_empty() {
-                return Err("Received empty edge in EdgeJoin".into());
+                return Err(GeometryError::EmptyEdge);
+            }
+            for (edge, face) in [
+                (&join.edge_lower, join.lower_face_index),
+                (&join.edge_higher, join.higher_face_index),
+            ] {
+                let Some(face_vertices) = faces.get(face).map(|f| f.vertices.len()) else {
+                    return Err(GeometryError::FaceOutOfBounds(face));
+                };
+                if let Some(index) = edge.iter().find(|i| **i as usize >= face_vertices) {
+                    return Err(GeometryError::EdgeIndexOutOfBounds {
+                        face,
+                        index: *index,
+                    });
+                }
             }
             let mut i = 0;
             let mut j = 0;
@@ -331,6 +438,140 @@ impl Shape3 {
         }
         Ok(Self { vertices, indices })
     }
+
+    /// Sphere centered on the origin, made of [stacks] rings from pole to pole split into [sectors]
+    /// segments each. Texture coordinates wrap once around the equator (u) and run from the top pole to the
+    /// bottom one (v). At least 3 sectors and 2 stacks are used.
+    pub fn uv_sphere(radius: f32, sectors: u32, stacks: u32) -> Shape3 {
+        let sectors = sectors.max(3);
+        let stacks = stacks.max(2);
+
+        // the seam column is repeated so u can reach 1.0
+        let mut vertices = Vec::with_capacity(((stacks + 1) * (sectors + 1)) as usize);
+        for i in 0..=stacks {
+            let v = i as f32 / stacks as f32;
+            let (sin_phi, cos_phi) = (v * PI).sin_cos();
+            for j in 0..=sectors {
+                let u = j as f32 / sectors as f32;
+                let (sin_theta, cos_theta) = (u * 2.0 * PI).sin_cos();
+                let normal = Vector3::new(sin_phi * cos_theta, cos_phi, sin_phi * sin_theta);
+                vertices.push(DefaultVertexType {
+                    position: (normal * radius).into(),
+                    normal: normal.into(),
+                    tex_coords: [u, v],
+                });
+            }
+        }
+
+        let mut indices = vec![];
+        let row = (sectors + 1) as GlobalIndexType;
+        for i in 0..stacks {
+            for j in 0..sectors {
+                let top = i as GlobalIndexType * row + j as GlobalIndexType;
+                let bottom = top + row;
+                // rows touching a pole have one triangle per sector
+                if i != 0 {
+                    indices.extend([top, top + 1, bottom]);
+                }
+                if i != stacks - 1 {
+                    indices.extend([top + 1, bottom + 1, bottom]);
+                }
+            }
+        }
+
+        Self { vertices, indices }
+    }
+
+    /// Cylinder along y, centered on the origin. Radii may differ, a [radius_top] of 0.0 makes a cone.
+    /// The side wall has smooth normals and texture coordinates wrapping once around it, [caps] adds flat
+    /// discs over the open ends. At least 3 segments are used.
+    pub fn cylinder(
+        radius_top: f32,
+        radius_bottom: f32,
+        height: f32,
+        segments: u32,
+        caps: bool,
+    ) -> Result<Shape3, GeometryError> {
+        let segments = segments.max(3) as usize;
+        let ring = segments + 1;
+        let cap_count = if caps {
+            (radius_top > 0.0) as usize + (radius_bottom > 0.0) as usize
+        } else {
+            0
+        };
+        let vertex_count = 2 * ring + cap_count * (ring + 1);
+        if vertex_count > GlobalIndexType::MAX as usize {
+            return Err(GeometryError::TooManyVertices(vertex_count));
+        }
+
+        let half = height / 2.0;
+        let mut vertices = Vec::with_capacity(vertex_count);
+        let mut indices = vec![];
+        let around = |j: usize| {
+            let u = j as f32 / segments as f32;
+            let (sin_theta, cos_theta) = (u * 2.0 * PI).sin_cos();
+            (u, Vector3::new(cos_theta, 0.0, sin_theta))
+        };
+
+        // side wall, top ring then bottom ring. Normals lean towards the narrower end.
+        for (radius, y, v) in [(radius_top, half, 0.0), (radius_bottom, -half, 1.0)] {
+            for j in 0..ring {
+                let (u, radial) = around(j);
+                let normal =
+                    (radial * height + Vector3::y() * (radius_bottom - radius_top)).normalize();
+                vertices.push(DefaultVertexType {
+                    position: (radial * radius + Vector3::y() * y).into(),
+                    normal: normal.into(),
+                    tex_coords: [u, v],
+                });
+            }
+        }
+        for j in 0..segments as GlobalIndexType {
+            let top = j;
+            let bottom = j + ring as GlobalIndexType;
+            // a cone's tip only needs one triangle per segment
+            if radius_top > 0.0 {
+                indices.extend([top, top + 1, bottom]);
+            }
+            if radius_bottom > 0.0 {
+                indices.extend([top + 1, bottom + 1, bottom]);
+            }
+        }
+
+        if caps {
+            for (radius, y) in [(radius_top, half), (radius_bottom, -half)] {
+                if radius <= 0.0 {
+                    continue;
+                }
+                let normal = Vector3::y() * y.signum();
+                let center = vertices.len() as GlobalIndexType;
+                vertices.push(DefaultVertexType {
+                    position: [0.0, y, 0.0],
+                    normal: normal.into(),
+                    tex_coords: [0.5, 0.5],
+                });
+                for j in 0..ring {
+                    let (_, radial) = around(j);
+                    vertices.push(DefaultVertexType {
+                        position: (radial * radius + Vector3::y() * y).into(),
+                        normal: normal.into(),
+                        tex_coords: [0.5 + 0.5 * radial.x, 0.5 + 0.5 * radial.z],
+                    });
+                }
+                for j in 0..segments as GlobalIndexType {
+                    let (a, b) = (center + 1 + j, center + 2 + j);
+                    // counter-clockwise seen from above for the top, from below for the bottom
+                    if y > 0.0 {
+                        indices.extend([center, b, a]);
+                    } else {
+                        indices.extend([center, a, b]);
+                    }
+                }
+            }
+        }
+
+        Ok(Self { vertices, indices })
+    }
 }
 
 impl Mesh for Shape3 {
@@ -392,6 +633,118 @@ pub fn get_orthogonal(original: Vector3<f32>) -> Vector3<f32> {
     )
 }
 
+/// Recomputes vertex normals from the triangles in [indices], which are expected to be wound counter-clockwise
+/// when viewed from the front.
+///
+/// If [smooth] is false, each vertex takes the normal of the last triangle it belongs to, so faces should not share
+/// vertices. Otherwise the normals of every triangle sharing a vertex are averaged, weighted by triangle area.
+///
+/// Vertices not referenced by any (non-degenerate) triangle keep their normal.
+pub fn recompute_normals(
+    vertices: &mut [DefaultVertexType],
+    indices: &[GlobalIndexType],
+    smooth: bool,
+) {
+    let mut sums = vec![Vector3::<f32>::zeros(); vertices.len()];
+    for triangle in indices.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|i| triangle[i] as usize);
+        let [p_a, p_b, p_c]: [Vector3<f32>; 3] = [a, b, c].map(|i| vertices[i].position.into());
+        // magnitude is twice the triangle's area
+        let normal = (p_b - p_a).cross(&(p_c - p_a));
+        for i in [a, b, c] {
+            if smooth {
+                sums[i] += normal;
+            } else {
+                sums[i] = normal;
+            }
+        }
+    }
+    for (vertex, sum) in vertices.iter_mut().zip(sums) {
+        if let Some(normal) = sum.try_normalize(f32::EPSILON) {
+            vertex.normal = normal.into();
+        }
+    }
+}
+
+/// Sphere enclosing a mesh, cheap to transform and test against a Frustum.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingSphere {
+    pub center: Vector3<f32>,
+    pub radius: f32,
+}
+
+impl BoundingSphere {
+    /// Sphere around the center of the points' bounding box, or None if there are no points.
+    pub fn from_points(points: impl IntoIterator<Item = Vector3<f32>>) -> Option<Self> {
+        let points: Vec<Vector3<f32>> = points.into_iter().collect();
+        let first = *points.first()?;
+        let (min, max) = points
+            .iter()
+            .fold((first, first), |(min, max), p| (min.inf(p), max.sup(p)));
+        let center = (min + max) / 2.0;
+        let radius = points
+            .iter()
+            .map(|p| (p - center).magnitude())
+            .fold(0.0, f32::max);
+        Some(Self { center, radius })
+    }
+
+    /// The sphere after scaling, rotating then translating, as in Entity::model_matrix. Non-uniform
+    /// scales grow the radius by the largest factor, so the result still encloses the mesh.
+    pub fn transformed(
+        &self,
+        scale: &Vector3<f32>,
+        rotation: &UnitQuaternion<f32>,
+        translation: &Vector3<f32>,
+    ) -> Self {
+        Self {
+            center: rotation * self.center.component_mul(scale) + translation,
+            radius: self.radius * scale.abs().max(),
+        }
+    }
+}
+
+/// The six planes bounding what a camera draws, pointing inwards.
+#[derive(Debug, Clone)]
+pub struct Frustum {
+    // (normal, distance), normalized so plane.dot(point, 1) is the signed distance to the plane
+    planes: [Vector4<f32>; 6],
+}
+
+impl Frustum {
+    /// Planes of the clip volume of [view_proj], taking 0 <= z <= w like wgpu does.
+    pub fn from_view_proj(view_proj: &Matrix4<f32>) -> Self {
+        let row = |i: usize| view_proj.row(i).transpose();
+        let planes = [
+            row(3) + row(0),
+            row(3) - row(0),
+            row(3) + row(1),
+            row(3) - row(1),
+            row(2),
+            row(3) - row(2),
+        ]
+        .map(|plane| plane / plane.xyz().magnitude());
+        Self { planes }
+    }
+
+    /// False only if [sphere] lies entirely outside one of the planes.
+    pub fn contains_sphere(&self, sphere: &BoundingSphere) -> bool {
+        let center = sphere.center.push(1.0);
+        self.planes
+            .iter()
+            .all(|plane| plane.dot(&center) >= -sphere.radius)
+    }
+
+    /// False only if the axis-aligned box from [min] to [max] lies entirely outside one of the planes.
+    pub fn contains_aabb(&self, min: &Vector3<f32>, max: &Vector3<f32>) -> bool {
+        self.planes.iter().all(|plane| {
+            // the corner furthest along the plane's normal
+            let corner = Vector3::from_fn(|i, _| if plane[i] >= 0.0 { max[i] } else { min[i] });
+            plane.dot(&corner.push(1.0)) >= 0.0
+        })
+    }
+}
+
 mod test {
     #![allow(unused_imports, dead_code)]
 
@@ -481,6 +834,291 @@ mod test {
         }
     }
 
+    #[test]
+    fn edge_index_outside_face() {
+        let triangle = || {
+            Face::new(
+                [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]]
+                    .into_iter()
+                    .map(|position| DefaultVertexType {
+                        position,
+                        normal: [0.0, 1.0, 0.0],
+                        tex_coords: [0.0, 0.0],
+                    })
+                    .collect(),
+                vec![0, 2, 1],
+                vec![],
+                vec![],
+                vec![],
+                vec![],
+            )
+        };
+        let join = EdgeJoin::new(vec![0, 1], 0, vec![1, 5], 1).unwrap();
+
+        let result = Shape3::new(vec![triangle(), triangle()], vec![join]);
+
+        assert_eq!(
+            result.unwrap_err(),
+            GeometryError::EdgeIndexOutOfBounds { face: 1, index: 5 }
+        );
+    }
+
+    #[test]
+    fn generated_face_edges_consistent() {
+        // 4 by 3 vertices
+        let face =
+            Face::from_function(Y_AXIS, (0.0, 3.0), (0.0, 2.0), (1.4, 1.6), |x, z| x * z).unwrap();
+        let (n_x, n_z) = (4, 3);
+        assert_eq!(face.vertices().len(), n_x * n_z);
+
+        for (edge, expected) in [
+            (FaceEdge::PX, n_z),
+            (FaceEdge::NX, n_z),
+            (FaceEdge::PZ, n_x),
+            (FaceEdge::NZ, n_x),
+        ] {
+            assert_eq!(face.edge(edge).len(), expected, "{:?}", edge);
+            assert!(
+                face.edge(edge)
+                    .iter()
+                    .all(|i| (*i as usize) < face.vertices().len())
+            );
+        }
+        assert_eq!(face.validate(n_x, n_z), Ok(()));
+        assert_eq!(
+            face.validate(n_x + 1, n_z),
+            Err(GeometryError::EdgeLength {
+                edge: FaceEdge::PZ,
+                expected: n_x + 1,
+                actual: n_x,
+            })
+        );
+
+        // off by one along the +x side, running past the last vertex
+        let shifted = |edge: &Vec<GlobalIndexType>| edge.iter().map(|i| i + 1).collect();
+        let broken = Face::new(
+            face.vertices().to_vec(),
+            face.indices().to_vec(),
+            shifted(face.edge_px()),
+            face.edge_nx().clone(),
+            face.edge_pz().clone(),
+            face.edge_nz().clone(),
+        );
+        assert_eq!(
+            broken.validate(n_x, n_z),
+            Err(GeometryError::FaceEdgeOutOfBounds {
+                edge: FaceEdge::PX,
+                index: (n_x * n_z) as GlobalIndexType,
+            })
+        );
+    }
+
+    #[test]
+    fn flat_normals_on_cube() {
+        let directions: Vec<Vector3<f32>> = AXES.iter().flat_map(|a| [*a, -a]).collect();
+        let mut vertices = vec![];
+        let mut indices = vec![];
+        for axis in &directions {
+            let u = get_orthogonal(*axis).normalize();
+            let v = axis.cross(&u);
+            let start = vertices.len() as GlobalIndexType;
+            for corner in [-u - v, u - v, u + v, -u + v] {
+                vertices.push(DefaultVertexType {
+                    position: (axis + corner).into(),
+                    normal: [0.0, 0.0, 0.0],
+                    tex_coords: [0.0, 0.0],
+                });
+            }
+            indices.extend([0, 1, 2, 0, 2, 3].map(|i| start + i));
+        }
+
+        recompute_normals(&mut vertices, &indices, false);
+
+        for (i, vertex) in vertices.iter().enumerate() {
+            assert_relative_eq_mat(Vector3::from(vertex.normal), directions[i / 4]);
+        }
+    }
+
+    #[test]
+    fn smooth_normals_on_octahedron() {
+        let mut vertices: Vec<DefaultVertexType> = AXES
+            .iter()
+            .chain(AXES.map(|a| -a).iter())
+            .map(|axis| DefaultVertexType {
+                position: (*axis * 2.0).into(),
+                normal: [0.0, 0.0, 0.0],
+                tex_coords: [0.0, 0.0],
+            })
+            .collect();
+        let mut indices = vec![];
+        for (sx, sy, sz) in [
+            (0, 1, 2),
+            (3, 1, 2),
+            (0, 4, 2),
+            (0, 1, 5),
+            (3, 4, 2),
+            (3, 1, 5),
+            (0, 4, 5),
+            (3, 4, 5),
+        ] {
+            // an odd number of negative axes flips the winding
+            let negatives = [sx, sy, sz].iter().filter(|i| **i >= 3).count();
+            if negatives % 2 == 0 {
+                indices.extend([sx, sy, sz]);
+            } else {
+                indices.extend([sx, sz, sy]);
+            }
+        }
+
+        recompute_normals(&mut vertices, &indices, true);
+
+        for vertex in &vertices {
+            let radial = Vector3::from(vertex.position).normalize();
+            assert_relative_eq_mat(Vector3::from(vertex.normal), radial);
+        }
+    }
+
+    #[test]
+    fn uv_sphere_vertices_on_surface() {
+        let radius = 2.5;
+        let sphere = Shape3::uv_sphere(radius, 12, 8);
+
+        assert_eq!(sphere.vertices().len(), 13 * 9);
+        for vertex in sphere.vertices() {
+            let position = Vector3::from(vertex.position);
+            assert_relative_eq!(position.magnitude(), radius, epsilon = 1.0e-5);
+            assert!((Vector3::from(vertex.normal) - position.normalize()).magnitude() < 1.0e-5);
+        }
+
+        // counter-clockwise seen from outside, with no degenerate triangles at the poles
+        assert_eq!(sphere.indices().len(), 3 * 12 * (2 * 8 - 2));
+        for triangle in sphere.indices().chunks(3) {
+            let [a, b, c] =
+                [0, 1, 2].map(|k| Vector3::from(sphere.vertices()[triangle[k] as usize].position));
+            let face_normal = (b - a).cross(&(c - a));
+            assert!(face_normal.magnitude() > 0.0);
+            assert!(face_normal.dot(&(a + b + c)) > 0.0);
+        }
+    }
+
+    #[test]
+    fn cylinder_and_cone_face_outwards() {
+        let cylinder = Shape3::cylinder(1.0, 1.0, 2.0, 16, true).unwrap();
+        let cone = Shape3::cylinder(0.0, 1.5, 3.0, 16, true).unwrap();
+
+        assert_eq!(cylinder.vertices().len(), 2 * 17 + 2 * 18);
+        // no top cap and half the wall triangles on a cone
+        assert_eq!(cone.vertices().len(), 2 * 17 + 18);
+        assert_eq!(cone.indices().len(), 3 * (16 + 16));
+
+        for shape in [&cylinder, &cone] {
+            for triangle in shape.indices().chunks(3) {
+                let [a, b, c] = [0, 1, 2]
+                    .map(|k| Vector3::from(shape.vertices()[triangle[k] as usize].position));
+                let face_normal = (b - a).cross(&(c - a));
+                assert!(face_normal.magnitude() > 0.0);
+                // every face of a convex shape around the origin faces away from it
+                assert!(face_normal.dot(&(a + b + c)) > 0.0);
+            }
+        }
+
+        // side normals are radial on a cylinder and lean up on a cone
+        let side = &cylinder.vertices()[4];
+        let radial = Vector3::new(side.position[0], 0.0, side.position[2]);
+        assert!((Vector3::from(side.normal) - radial.normalize()).magnitude() < 1.0e-5);
+        let slope = Vector3::from(cone.vertices()[20].normal);
+        assert!((slope.y - 1.5 / 11.25_f32.sqrt()).abs() < 1.0e-5);
+
+        assert_eq!(cylinder.vertices()[0].tex_coords, [0.0, 0.0]);
+        assert_eq!(cylinder.vertices()[33].tex_coords, [1.0, 1.0]);
+    }
+
+    #[test]
+    fn frustum_culls_instances_behind_camera() {
+        // unit cube mesh, instanced around a point 20 units down -z
+        let corners = (0..8).map(|i| {
+            Vector3::new(
+                (i & 1) as f32 - 0.5,
+                (i >> 1 & 1) as f32 - 0.5,
+                (i >> 2 & 1) as f32 - 0.5,
+            )
+        });
+        let mesh = BoundingSphere::from_points(corners).unwrap();
+        assert_eq!(mesh.center, ZERO);
+        assert!((mesh.radius - 0.75_f32.sqrt()).abs() < 1.0e-6);
+
+        let rotation = UnitQuaternion::from_axis_angle(&UnitVector3::new_normalize(X_AXIS), 0.7);
+        let instances: Vec<BoundingSphere> = (-2..=2)
+            .map(|i| {
+                let translation = Vector3::new(i as f32 * 3.0, i as f32, -20.0);
+                mesh.transformed(&Vector3::new(1.0, 4.0, 1.0), &rotation, &translation)
+            })
+            .collect();
+        assert!((instances[0].radius - 4.0 * mesh.radius).abs() < 1.0e-5);
+
+        let projection = Matrix4::new_perspective(16.0 / 9.0, PI / 2.0, 0.1, 100.0);
+        let frustum = |target: Vector3<f32>| {
+            let view = Matrix4::look_at_rh(&ZERO.into(), &target.into(), &Y_AXIS);
+            Frustum::from_view_proj(&(projection * view))
+        };
+
+        let towards = frustum(-Z_AXIS);
+        assert!(instances.iter().all(|s| towards.contains_sphere(s)));
+        let away = frustum(Z_AXIS);
+        assert!(instances.iter().all(|s| !away.contains_sphere(s)));
+        // past the far plane
+        let far = BoundingSphere {
+            center: Vector3::new(0.0, 0.0, -120.0),
+            radius: 1.0,
+        };
+        assert!(!towards.contains_sphere(&far));
+    }
+
+    #[test]
+    fn frustum_planes_of_known_projection() {
+        // orthographic over x in [-2, 2], y in [-1, 1], looking down -z from 1 to 10 units away,
+        // mapped to the 0 <= z <= 1 depth range wgpu clips to
+        #[rustfmt::skip]
+        let projection = Matrix4::new(
+            0.5, 0.0, 0.0, 0.0,
+            0.0, 1.0, 0.0, 0.0,
+            0.0, 0.0, -1.0 / 9.0, -1.0 / 9.0,
+            0.0, 0.0, 0.0, 1.0,
+        );
+        let frustum = Frustum::from_view_proj(&projection);
+
+        // (normal, distance), inwards
+        let expected = [
+            Vector4::new(1.0, 0.0, 0.0, 2.0),
+            Vector4::new(-1.0, 0.0, 0.0, 2.0),
+            Vector4::new(0.0, 1.0, 0.0, 1.0),
+            Vector4::new(0.0, -1.0, 0.0, 1.0),
+            Vector4::new(0.0, 0.0, -1.0, -1.0),
+            Vector4::new(0.0, 0.0, 1.0, 10.0),
+        ];
+        for (plane, expected) in frustum.planes.iter().zip(expected) {
+            assert!((plane - expected).magnitude() < 1.0e-5, "{:?}", plane);
+        }
+
+        // straddling the right plane
+        assert!(frustum.contains_aabb(
+            &Vector3::new(1.5, -0.5, -6.0),
+            &Vector3::new(3.0, 0.5, -5.0)
+        ));
+        // just past it
+        assert!(!frustum.contains_aabb(
+            &Vector3::new(2.1, -0.5, -6.0),
+            &Vector3::new(3.0, 0.5, -5.0)
+        ));
+        // behind the near plane
+        assert!(!frustum.contains_aabb(
+            &Vector3::new(-1.0, -1.0, -0.5),
+            &Vector3::new(1.0, 1.0, 0.0)
+        ));
+        // enclosing the whole frustum
+        assert!(frustum.contains_aabb(&Vector3::repeat(-100.0), &Vector3::repeat(100.0)));
+    }
+
     fn assert_relative_eq_mat<const R: usize, const C: usize>(
         a: Matrix<Float, Const<R>, Const<C>, ArrayStorage<Float, R, C>>,
         b: Matrix<Float, Const<R>, Const<C>, ArrayStorage<Float, R, C>>,
@@ -1,24 +1,58 @@
-use std::{collections::HashMap, f32::consts::PI};
+use std::{
+    collections::{HashMap, HashSet},
+    f32::consts::PI,
+};
 
 use winit::{
-    dpi::PhysicalPosition,
-    event::WindowEvent,
-    keyboard::{KeyCode, PhysicalKey},
+    dpi::{PhysicalPosition, PhysicalSize},
+    event::{KeyEvent, MouseButton, MouseScrollDelta, WindowEvent},
+    keyboard::{Key, KeyCode, NamedKey, PhysicalKey},
     window::Window,
 };
 
 use crate::core::camera::Camera;
 
+/// Pixels of a touchpad scroll counted as one scroll wheel line.
+const PIXELS_PER_SCROLL_LINE: f32 = 20.0;
+
+/// Edits [text] with [typed], as taken from InputController::take_text_input: leftover backspaces delete
+/// from the end of [text], then the rest is appended.
+pub fn apply_text_input(text: &mut String, typed: &str) {
+    for c in typed.chars() {
+        if c == '\u{8}' {
+            text.pop();
+        } else {
+            text.push(c);
+        }
+    }
+}
+
 pub struct InputController {
     keys_pressed: HashMap<KeyCode, bool>,
+    // Edges are cleared in end_frame, once every system has had a chance to see them.
+    just_pressed: HashSet<KeyCode>,
+    just_released: HashSet<KeyCode>,
     esc_toggle: bool,
+    /// Cursor movements shorter than this (in physical pixels) don't rotate the camera.
+    look_dead_zone: f32,
+    // Text typed this frame, also cleared in end_frame
+    text_input: String,
+    mouse_pressed: HashSet<MouseButton>,
+    // Last known cursor position, to measure drags
+    cursor_position: Option<PhysicalPosition<f64>>,
 }
 
 impl InputController {
     pub fn new() -> Self {
         Self {
             keys_pressed: HashMap::with_capacity(100),
+            just_pressed: HashSet::new(),
+            just_released: HashSet::new(),
             esc_toggle: false,
+            look_dead_zone: 0.0,
+            text_input: String::new(),
+            mouse_pressed: HashSet::new(),
+            cursor_position: None,
         }
     }
 
@@ -26,8 +60,142 @@ impl InputController {
         self.keys_pressed.get(key).unwrap_or(&false)
     }
 
+    /// True only during the frame in which [key] went down.
+    pub fn is_just_pressed(&self, key: &KeyCode) -> bool {
+        self.just_pressed.contains(key)
+    }
+
+    /// True only during the frame in which [key] went up.
+    pub fn is_just_released(&self, key: &KeyCode) -> bool {
+        self.just_released.contains(key)
+    }
+
+    /// Clears the just pressed/released edges. Held keys stay pressed.
+    ///
+    /// Called by App once per frame, after every system has run.
+    pub fn end_frame(&mut self) {
+        self.just_pressed.clear();
+        self.just_released.clear();
+        self.text_input.clear();
+    }
+
+    /// Text typed during this frame, with backspaces already applied to it. Backspaces with nothing typed
+    /// this frame to delete are kept as '\u{8}', so they can be applied to text typed earlier.
+    ///
+    /// Systems can read it with InputController::text_input, since they only get a shared reference.
+    pub fn take_text_input(&mut self) -> String {
+        std::mem::take(&mut self.text_input)
+    }
+
+    /// Same as InputController::take_text_input, without taking it.
+    pub fn text_input(&self) -> &str {
+        &self.text_input
+    }
+
+    /// Collects the text typed by [event] without tracking the key, i.e. for key presses the app routes to
+    /// a text field (like the console) instead of the rest of the app.
+    pub(crate) fn push_key_text(&mut self, event: &KeyEvent) {
+        if !event.state.is_pressed() {
+            return;
+        }
+        if event.logical_key == Key::Named(NamedKey::Backspace) {
+            self.push_text("\u{8}");
+        } else if let Some(text) = &event.text {
+            self.push_text(text);
+        }
+    }
+
+    fn push_text(&mut self, text: &str) {
+        for c in text.chars() {
+            match c {
+                '\u{8}' => {
+                    if self.text_input.ends_with(|c| c != '\u{8}') {
+                        self.text_input.pop();
+                    } else {
+                        self.text_input.push(c);
+                    }
+                }
+                c if c.is_control() => {}
+                c => self.text_input.push(c),
+            }
+        }
+    }
+
+    /// Releases every held key and mouse button, i.e. when the window loses focus and won't see them go
+    /// up. Keys released this way are just released until the end of the frame.
+    pub fn release_all(&mut self) {
+        let held: Vec<KeyCode> = self
+            .keys_pressed
+            .iter()
+            .filter(|(_, pressed)| **pressed)
+            .map(|(key, _)| *key)
+            .collect();
+        for key in held {
+            self.set_key(key, false);
+        }
+        self.mouse_pressed.clear();
+        self.cursor_position = None;
+    }
+
+    pub fn is_mouse_pressed(&self, button: &MouseButton) -> bool {
+        self.mouse_pressed.contains(button)
+    }
+
+    fn set_mouse_button(&mut self, button: MouseButton, pressed: bool) {
+        if pressed {
+            self.mouse_pressed.insert(button);
+        } else {
+            self.mouse_pressed.remove(&button);
+        }
+    }
+
+    /// Movement since the last cursor position while the middle mouse button is held, in physical pixels.
+    fn drag_delta(&mut self, position: &PhysicalPosition<f64>) -> Option<(f32, f32)> {
+        let last = self.cursor_position.replace(*position)?;
+        if !self.is_mouse_pressed(&MouseButton::Middle) {
+            return None;
+        }
+        Some(((position.x - last.x) as f32, (position.y - last.y) as f32))
+    }
+
+    pub fn look_dead_zone(&self) -> f32 {
+        self.look_dead_zone
+    }
+
+    /// Ignores cursor movements shorter than [dead_zone] physical pixels, which removes micro-jitter
+    /// while the mouse is held still. Defaults to 0.0 (no dead zone).
+    pub fn set_look_dead_zone(&mut self, dead_zone: f32) {
+        self.look_dead_zone = dead_zone;
+    }
+
+    /// Look up and ccw amounts for a cursor at [position] in a window of [size], or None if the
+    /// movement from the center is within the dead zone.
+    fn look_amounts(
+        &self,
+        position: &PhysicalPosition<f64>,
+        size: &PhysicalSize<u32>,
+    ) -> Option<(f32, f32)> {
+        let dx = size.width as f32 / 2.0 - position.x as f32;
+        let dy = size.height as f32 / 2.0 - position.y as f32;
+        if (dx * dx + dy * dy).sqrt() < self.look_dead_zone {
+            return None;
+        }
+        Some((dy / size.height as f32 * PI, -dx / size.width as f32 * PI))
+    }
+
+    fn set_key(&mut self, key: KeyCode, pressed: bool) {
+        // key repeats are not new presses
+        let was_pressed = *self.is_pressed(&key);
+        if pressed && !was_pressed {
+            self.just_pressed.insert(key);
+        } else if !pressed && was_pressed {
+            self.just_released.insert(key);
+        }
+        self.keys_pressed.insert(key, pressed);
+    }
+
     /// This will only handle events relevant to input. Other events should be handled in App.window_event().
-    pub fn window_event(&mut self, event: &WindowEvent, window: &Window, camera: &mut impl Camera) {
+    pub fn window_event(&mut self, event: &WindowEvent, window: &Window, camera: &mut dyn Camera) {
         match event {
             WindowEvent::KeyboardInput {
                 device_id: _,
@@ -35,34 +203,183 @@ impl InputController {
                 is_synthetic: _,
             } => {
                 if let PhysicalKey::Code(k) = event.physical_key {
-                    self.keys_pressed.insert(k, event.state.is_pressed());
+                    self.set_key(k, event.state.is_pressed());
                     if k == KeyCode::Escape && event.state.is_pressed() {
                         self.esc_toggle = !self.esc_toggle;
                     }
                 }
+                self.push_key_text(event);
             }
             WindowEvent::CursorMoved {
                 device_id: _,
                 position,
             } => {
+                let dragged = self.drag_delta(position);
+                if let Some((dx, dy)) = dragged {
+                    camera.pan(dx, dy);
+                }
                 if !self.esc_toggle {
                     let size = window.inner_size();
-                    window
-                        .set_cursor_position(PhysicalPosition::new(size.width / 2, size.height / 2))
-                        .unwrap();
-                    camera.look_up(
-                        (size.height as f32 / 2.0 - position.y as f32) / size.height as f32 * PI,
-                    );
-                    camera.look_ccw(
-                        -(size.width as f32 / 2.0 - position.x as f32) / size.width as f32 * PI,
-                    );
+                    let center = PhysicalPosition::new(size.width / 2, size.height / 2);
+                    window.set_cursor_position(center).unwrap();
+                    self.cursor_position = Some(center.cast());
+                    // the view only turns while not panning
+                    if let (None, Some((up, ccw))) = (dragged, self.look_amounts(position, &size)) {
+                        camera.look_up(up);
+                        camera.look_ccw(ccw);
+                    }
                 }
             }
+            WindowEvent::MouseInput {
+                device_id: _,
+                state,
+                button,
+            } => {
+                self.set_mouse_button(*button, state.is_pressed());
+            }
+            WindowEvent::MouseWheel {
+                device_id: _,
+                delta,
+                phase: _,
+            } => {
+                camera.zoom(scroll_lines(delta));
+            }
             _ => {}
         }
     }
 
-    pub fn update(&mut self, dt: f32, camera: &mut impl Camera) {
+    pub fn update(&mut self, dt: f32, camera: &mut dyn Camera) {
         camera.update(&self.keys_pressed, dt);
     }
 }
+
+/// Scroll wheel lines of [delta], positive away from the user.
+fn scroll_lines(delta: &MouseScrollDelta) -> f32 {
+    match delta {
+        MouseScrollDelta::LineDelta(_, y) => *y,
+        MouseScrollDelta::PixelDelta(position) => position.y as f32 / PIXELS_PER_SCROLL_LINE,
+    }
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use winit::{
+        dpi::{PhysicalPosition, PhysicalSize},
+        event::{MouseButton, MouseScrollDelta},
+        keyboard::KeyCode,
+    };
+
+    use crate::core::input::{InputController, apply_text_input, scroll_lines};
+
+    #[test]
+    fn edges_last_one_frame() {
+        let mut input = InputController::new();
+
+        input.set_key(KeyCode::KeyW, true);
+        // every system in the frame sees the edge
+        for _ in 0..3 {
+            assert!(input.is_just_pressed(&KeyCode::KeyW));
+        }
+        input.end_frame();
+
+        assert!(!input.is_just_pressed(&KeyCode::KeyW));
+        assert!(input.is_pressed(&KeyCode::KeyW));
+
+        // repeat events while held aren't presses
+        input.set_key(KeyCode::KeyW, true);
+        assert!(!input.is_just_pressed(&KeyCode::KeyW));
+
+        input.set_key(KeyCode::KeyW, false);
+        assert!(input.is_just_released(&KeyCode::KeyW));
+        input.end_frame();
+
+        assert!(!input.is_just_released(&KeyCode::KeyW));
+        assert!(!input.is_pressed(&KeyCode::KeyW));
+    }
+
+    #[test]
+    fn dead_zone_ignores_small_movements() {
+        let mut input = InputController::new();
+        let size = PhysicalSize::new(800, 600);
+        let jitter = PhysicalPosition::new(401.0, 301.0);
+        let movement = PhysicalPosition::new(420.0, 300.0);
+
+        // no dead zone by default
+        assert!(input.look_amounts(&jitter, &size).is_some());
+
+        input.set_look_dead_zone(3.0);
+
+        assert_eq!(input.look_amounts(&jitter, &size), None);
+        let (up, ccw) = input.look_amounts(&movement, &size).unwrap();
+        assert_eq!(up, 0.0);
+        assert!(ccw > 0.0);
+    }
+
+    #[test]
+    fn text_input_applies_backspace() {
+        let mut input = InputController::new();
+
+        for text in ["h", "e", "y", "\u{8}", "l", "\u{8}", "\u{8}", "y!", "\r"] {
+            input.push_text(text);
+        }
+        assert_eq!(input.text_input(), "hy!");
+        assert_eq!(input.take_text_input(), "hy!");
+        assert_eq!(input.take_text_input(), "");
+
+        // nothing left to delete this frame
+        input.push_text("a");
+        input.push_text("\u{8}");
+        input.push_text("\u{8}");
+        assert_eq!(input.take_text_input(), "\u{8}");
+
+        input.push_text("lost");
+        input.end_frame();
+        assert_eq!(input.take_text_input(), "");
+    }
+
+    #[test]
+    fn text_input_applied_to_earlier_text() {
+        let mut input = InputController::new();
+        let mut line = String::from("spawn");
+
+        for text in ["\u{8}", "\u{8}", "\u{8}", "e", "e", "d"] {
+            input.push_text(text);
+        }
+        apply_text_input(&mut line, &input.take_text_input());
+        assert_eq!(line, "speed");
+
+        // more backspaces than there is text
+        apply_text_input(&mut line, &"\u{8}".repeat(8));
+        assert_eq!(line, "");
+    }
+
+    #[test]
+    fn middle_drag_measured_from_last_position() {
+        let mut input = InputController::new();
+
+        assert_eq!(input.drag_delta(&PhysicalPosition::new(100.0, 100.0)), None);
+        // moving without the button only tracks the cursor
+        assert_eq!(input.drag_delta(&PhysicalPosition::new(110.0, 100.0)), None);
+
+        input.set_mouse_button(MouseButton::Middle, true);
+        assert_eq!(
+            input.drag_delta(&PhysicalPosition::new(120.0, 95.0)),
+            Some((10.0, -5.0))
+        );
+        assert_eq!(
+            input.drag_delta(&PhysicalPosition::new(121.0, 95.0)),
+            Some((1.0, 0.0))
+        );
+
+        input.set_mouse_button(MouseButton::Middle, false);
+        assert_eq!(input.drag_delta(&PhysicalPosition::new(150.0, 95.0)), None);
+
+        assert_eq!(scroll_lines(&MouseScrollDelta::LineDelta(0.0, -2.0)), -2.0);
+        assert_eq!(
+            scroll_lines(&MouseScrollDelta::PixelDelta(PhysicalPosition::new(
+                0.0, 40.0
+            ))),
+            2.0
+        );
+    }
+}
@@ -81,6 +81,11 @@ pub struct DisposeArgs {}
 /// ```
 #[allow(unused_variables)]
 pub trait System {
+    /// Used to identify the system (i.e. in logs). Defaults to the type's name.
+    fn name(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
+
     /// This lifecycle hook is most appropriate for updates and initialization which run right before the app starts.
     /// It may be necessary to access the world state and renderer for initialization. It is only run once.
     fn before_start(&mut self, args: &mut BeforeStartArgs) {}
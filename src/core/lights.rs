@@ -1,13 +1,12 @@
 use bytemuck::{Pod, Zeroable};
 use wgpu::{
     BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
-    BindGroupLayoutEntry, BindingType, Buffer, BufferBindingType, BufferUsages, Device,
-    ShaderStages,
-    util::{BufferInitDescriptor, DeviceExt},
+    BindGroupLayoutEntry, BindingType, Buffer, BufferBindingType, BufferDescriptor, BufferUsages,
+    Device, Queue, ShaderStages,
 };
 
 #[repr(C)]
-#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+#[derive(Debug, Clone, Copy, PartialEq, Pod, Zeroable)]
 pub struct LightSource {
     position: [f32; 4],
     colour: [f32; 4],
@@ -15,31 +14,61 @@ pub struct LightSource {
     _padding: [f32; 3],
 }
 
+impl LightSource {
+    pub fn new(position: [f32; 4], colour: [f32; 4], luminance: f32) -> Self {
+        Self {
+            position,
+            colour,
+            luminance,
+            _padding: [0 as f32; 3],
+        }
+    }
+}
+
+/// Precedes the lights in the buffer. Padded to the alignment of LightSource, as in the shaders.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct LightsHeader {
+    count: u32,
+    _padding: [u32; 3],
+}
+
+#[derive(Debug, PartialEq)]
+pub enum LightStorageError {
+    /// Adding the light would exceed the maximum the storage was created with.
+    MaxLightsExceeded(usize),
+}
+
+/// Lights packed into a storage buffer as a count followed by up to [max_lights] LightSources. The
+/// buffer is sized for the maximum up front and re-uploaded whenever a light changes.
 #[derive(Debug)]
 pub struct LightSourceStorage {
-    // TODO: Allow multiple lights
-    light: LightSource,
+    lights: Vec<LightSource>,
+    // Id of each light in [lights]
+    ids: Vec<u64>,
+    next_id: u64,
+    max_lights: usize,
+
     buffer: Buffer,
     layout: BindGroupLayout,
     bind_group: BindGroup,
 }
 
 impl LightSourceStorage {
-    pub fn new(device: &mut Device, light_p: [f32; 4], light_c: [f32; 4], luminance: f32) -> Self {
-        let light = LightSource {
-            position: light_p,
-            colour: light_c,
-            luminance,
-            _padding: [0 as f32; 3],
-        };
-
+    /// Lights past [max_lights] are dropped. Ids are issued from 0 in the order of [lights].
+    pub fn new(
+        device: &mut Device,
+        queue: &Queue,
+        lights: Vec<LightSource>,
+        max_lights: usize,
+    ) -> Self {
         let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
             label: Some("Light Bind Group Layout"),
             entries: &[BindGroupLayoutEntry {
                 binding: 0,
                 visibility: ShaderStages::FRAGMENT,
                 ty: BindingType::Buffer {
-                    ty: BufferBindingType::Uniform,
+                    ty: BufferBindingType::Storage { read_only: true },
                     has_dynamic_offset: false,
                     min_binding_size: None,
                 },
@@ -47,10 +76,11 @@ impl LightSourceStorage {
             }],
         });
 
-        let buffer = device.create_buffer_init(&BufferInitDescriptor {
+        let buffer = device.create_buffer(&BufferDescriptor {
             label: Some("Light Buffer"),
-            contents: bytemuck::cast_slice(&[light]),
-            usage: BufferUsages::COPY_DST | BufferUsages::UNIFORM,
+            size: buffer_size(max_lights),
+            usage: BufferUsages::COPY_DST | BufferUsages::STORAGE,
+            mapped_at_creation: false,
         });
         let bind_group = device.create_bind_group(&BindGroupDescriptor {
             label: Some("Light Bind Group"),
@@ -60,12 +90,20 @@ impl LightSourceStorage {
                 resource: buffer.as_entire_binding(),
             }],
         });
-        Self {
-            light,
+
+        let mut lights = lights;
+        lights.truncate(max_lights);
+        let storage = Self {
+            ids: (0..lights.len() as u64).collect(),
+            next_id: lights.len() as u64,
+            lights,
+            max_lights,
             buffer,
             layout,
             bind_group,
-        }
+        };
+        storage.update_gpu(queue);
+        storage
     }
 
     pub fn layout(&self) -> &BindGroupLayout {
@@ -75,4 +113,135 @@ impl LightSourceStorage {
     pub fn bind_group(&self) -> &BindGroup {
         &self.bind_group
     }
+
+    pub fn len(&self) -> usize {
+        self.lights.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lights.is_empty()
+    }
+
+    pub fn max_lights(&self) -> usize {
+        self.max_lights
+    }
+
+    pub fn get(&self, id: u64) -> Option<&LightSource> {
+        self.slot(id).map(|slot| &self.lights[slot])
+    }
+
+    /// Returns the new light's id.
+    pub fn add_light(
+        &mut self,
+        queue: &Queue,
+        light: LightSource,
+    ) -> Result<u64, LightStorageError> {
+        if self.lights.len() >= self.max_lights {
+            return Err(LightStorageError::MaxLightsExceeded(self.max_lights));
+        }
+        let id = self.next_id;
+        self.next_id += 1;
+        self.ids.push(id);
+        self.lights.push(light);
+        self.update_gpu(queue);
+        Ok(id)
+    }
+
+    /// Removes a light, returning it if it existed.
+    pub fn remove_light(&mut self, queue: &Queue, id: u64) -> Option<LightSource> {
+        let slot = self.slot(id)?;
+        self.ids.remove(slot);
+        let light = self.lights.remove(slot);
+        self.update_gpu(queue);
+        Some(light)
+    }
+
+    /// Replaces a light, returning the previous one if it existed.
+    pub fn update_light(
+        &mut self,
+        queue: &Queue,
+        id: u64,
+        light: LightSource,
+    ) -> Option<LightSource> {
+        let slot = self.slot(id)?;
+        let previous = std::mem::replace(&mut self.lights[slot], light);
+        self.update_gpu(queue);
+        Some(previous)
+    }
+
+    fn slot(&self, id: u64) -> Option<usize> {
+        self.ids.iter().position(|i| *i == id)
+    }
+
+    fn update_gpu(&self, queue: &Queue) {
+        queue.write_buffer(&self.buffer, 0, &pack(&self.lights));
+    }
+}
+
+/// Bytes taken up by the header and [max_lights] lights.
+fn buffer_size(max_lights: usize) -> u64 {
+    // the shader's runtime-sized array needs at least one element
+    (size_of::<LightsHeader>() + size_of::<LightSource>() * max_lights.max(1)) as u64
+}
+
+/// The header followed by [lights], as laid out in the shaders.
+fn pack(lights: &[LightSource]) -> Vec<u8> {
+    let header = LightsHeader {
+        count: lights.len() as u32,
+        _padding: [0; 3],
+    };
+    let mut bytes = bytemuck::bytes_of(&header).to_vec();
+    bytes.extend_from_slice(bytemuck::cast_slice(lights));
+    bytes
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use crate::{
+        core::lights::{LightSource, LightSourceStorage, LightStorageError, buffer_size, pack},
+        render::test_device,
+    };
+
+    #[test]
+    fn packed_with_count_header() {
+        let lights = [
+            LightSource::new([1.0, 2.0, 3.0, 1.0], [1.0, 0.0, 0.0, 1.0], 10.0),
+            LightSource::new([-1.0, 0.0, 0.0, 1.0], [0.0, 0.0, 1.0, 1.0], 5.0),
+        ];
+
+        let bytes = pack(&lights);
+
+        assert_eq!(bytes.len(), 16 + 2 * 48);
+        assert_eq!(u32::from_ne_bytes(bytes[0..4].try_into().unwrap()), 2);
+        let packed: Vec<LightSource> = bytemuck::pod_collect_to_vec(&bytes[16..]);
+        assert_eq!(packed, lights);
+        assert!(bytes.len() as u64 <= buffer_size(2));
+        assert_eq!(buffer_size(0), buffer_size(1));
+    }
+
+    #[test]
+    fn lights_capped_at_max() {
+        let Some((mut device, queue)) = test_device() else {
+            return;
+        };
+        let light = |luminance| LightSource::new([0.0; 4], [1.0; 4], luminance);
+        let mut storage =
+            LightSourceStorage::new(&mut device, &queue, vec![light(1.0), light(2.0)], 3);
+
+        let third = storage.add_light(&queue, light(3.0)).unwrap();
+        assert_eq!(
+            storage.add_light(&queue, light(4.0)),
+            Err(LightStorageError::MaxLightsExceeded(3))
+        );
+
+        assert_eq!(storage.remove_light(&queue, 0), Some(light(1.0)));
+        assert_eq!(
+            storage.update_light(&queue, third, light(5.0)),
+            Some(light(3.0))
+        );
+        assert_eq!(storage.get(1), Some(&light(2.0)));
+        assert_eq!(storage.get(third), Some(&light(5.0)));
+        assert_eq!(storage.len(), 2);
+        assert!(storage.add_light(&queue, light(6.0)).is_ok());
+    }
 }
@@ -0,0 +1,207 @@
+use std::sync::{OnceLock, RwLock};
+
+use log::{LevelFilter, Log, Metadata, Record};
+
+static LOG_FILTER: OnceLock<LogFilter> = OnceLock::new();
+
+/// Levels of log records let through, changeable while the app runs.
+///
+/// Records use the level of the longest target prefix set with [LogFilter::set_target_level], or the
+/// default level if none match. "agate_engine::render" matches "agate_engine::render::renderer" but
+/// not "agate_engine::renderer".
+pub struct LogFilter {
+    levels: RwLock<Levels>,
+}
+
+struct Levels {
+    default: LevelFilter,
+    targets: Vec<(String, LevelFilter)>,
+}
+
+impl LogFilter {
+    pub fn new(default: LevelFilter) -> Self {
+        Self {
+            levels: RwLock::new(Levels {
+                default,
+                targets: vec![],
+            }),
+        }
+    }
+
+    /// Level of targets without their own level.
+    pub fn set_level(&self, level: LevelFilter) {
+        self.levels.write().unwrap().default = level;
+    }
+
+    /// Replaces the level of [target] and the modules under it.
+    pub fn set_target_level(&self, target: &str, level: LevelFilter) {
+        let mut levels = self.levels.write().unwrap();
+        match levels.targets.iter_mut().find(|(t, _)| t == target) {
+            Some((_, l)) => *l = level,
+            None => levels.targets.push((target.to_owned(), level)),
+        }
+    }
+
+    /// [target] goes back to using the level of its closest parent, or the default level.
+    pub fn clear_target_level(&self, target: &str) {
+        self.levels
+            .write()
+            .unwrap()
+            .targets
+            .retain(|(t, _)| t != target);
+    }
+
+    pub fn level_for(&self, target: &str) -> LevelFilter {
+        let levels = self.levels.read().unwrap();
+        levels
+            .targets
+            .iter()
+            .filter(|(prefix, _)| {
+                target
+                    .strip_prefix(prefix.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+            })
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map_or(levels.default, |(_, level)| *level)
+    }
+
+    /// Most verbose level any target uses, for log::set_max_level.
+    pub fn max_level(&self) -> LevelFilter {
+        let levels = self.levels.read().unwrap();
+        levels
+            .targets
+            .iter()
+            .map(|(_, level)| *level)
+            .fold(levels.default, Ord::max)
+    }
+
+    pub fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level_for(metadata.target())
+    }
+}
+
+/// Passes the records [filter] lets through on to [inner].
+pub struct FilteredLogger<L: Log> {
+    filter: &'static LogFilter,
+    inner: L,
+}
+
+impl<L: Log> FilteredLogger<L> {
+    pub fn new(filter: &'static LogFilter, inner: L) -> Self {
+        Self { filter, inner }
+    }
+}
+
+impl<L: Log> Log for FilteredLogger<L> {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.filter.enabled(metadata) && self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if self.filter.enabled(record.metadata()) {
+            self.inner.log(record);
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+/// Installs a logger writing to stdout, filtered by a LogFilter starting at [level] for every target.
+/// Panics if a logger is already installed.
+pub fn init(level: LevelFilter) {
+    let filter = LOG_FILTER.get_or_init(|| LogFilter::new(level));
+    filter.set_level(level);
+    let inner = env_logger::builder()
+        .filter_level(LevelFilter::Trace)
+        .target(env_logger::Target::Stdout)
+        .build();
+    log::set_boxed_logger(Box::new(FilteredLogger::new(filter, inner)))
+        .expect("a logger is already installed");
+    log::set_max_level(filter.max_level());
+}
+
+/// The filter of the logger installed by init_logging, None before then.
+pub fn log_filter() -> Option<&'static LogFilter> {
+    LOG_FILTER.get()
+}
+
+/// Sets the level of [target], or the default level if None. Returns false if init_logging hasn't been
+/// called.
+pub fn set_log_level(target: Option<&str>, level: LevelFilter) -> bool {
+    let Some(filter) = log_filter() else {
+        return false;
+    };
+    match target {
+        Some(target) => filter.set_target_level(target, level),
+        None => filter.set_level(level),
+    }
+    log::set_max_level(filter.max_level());
+    true
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use std::sync::{
+        OnceLock,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    use log::{Level, LevelFilter, Log, Metadata, Record};
+
+    use crate::core::logging::{FilteredLogger, LogFilter};
+
+    #[cfg(test)]
+    struct CountingLogger(AtomicUsize);
+
+    #[cfg(test)]
+    impl Log for CountingLogger {
+        fn enabled(&self, _metadata: &Metadata) -> bool {
+            true
+        }
+        fn log(&self, _record: &Record) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+        fn flush(&self) {}
+    }
+
+    #[test]
+    fn filter_changes_apply_at_runtime() {
+        static FILTER: OnceLock<LogFilter> = OnceLock::new();
+        let filter = FILTER.get_or_init(|| LogFilter::new(LevelFilter::Info));
+        let logger = FilteredLogger::new(filter, CountingLogger(AtomicUsize::new(0)));
+        let log = |level: Level, target: &str| {
+            logger.log(
+                &Record::builder()
+                    .level(level)
+                    .target(target)
+                    .args(format_args!("message"))
+                    .build(),
+            );
+            logger.inner.0.swap(0, Ordering::Relaxed) == 1
+        };
+
+        assert!(log(Level::Info, "agate_engine::render::renderer"));
+        assert!(!log(Level::Debug, "agate_engine::render::renderer"));
+
+        filter.set_target_level("agate_engine::render", LevelFilter::Debug);
+        assert!(log(Level::Debug, "agate_engine::render::renderer"));
+        assert!(!log(Level::Trace, "agate_engine::render::renderer"));
+        // other modules keep the default, including ones which only share a name prefix
+        assert!(!log(Level::Debug, "agate_engine::core::entity"));
+        assert!(!log(Level::Debug, "agate_engine::renderer"));
+        assert_eq!(filter.max_level(), LevelFilter::Debug);
+
+        // the longest matching target wins
+        filter.set_target_level("agate_engine::render::storage", LevelFilter::Error);
+        assert!(!log(Level::Warn, "agate_engine::render::storage::mesh"));
+        assert!(log(Level::Debug, "agate_engine::render::renderer"));
+
+        filter.clear_target_level("agate_engine::render");
+        filter.set_level(LevelFilter::Warn);
+        assert!(!log(Level::Debug, "agate_engine::render::renderer"));
+        assert!(!log(Level::Info, "agate_engine::core::entity"));
+        assert!(log(Level::Warn, "agate_engine::core::entity"));
+    }
+}
@@ -0,0 +1,96 @@
+use nalgebra::Vector3;
+use rand::{Rng, SeedableRng, rngs::StdRng};
+
+use crate::core::{Unique, entity::Entity};
+
+/// A set of tint colors assigned to entities by id and seed, so scattered objects get varied but
+/// reproducible colors.
+#[derive(Debug, Clone)]
+pub struct Palette {
+    colors: Vec<Vector3<f32>>,
+    seed: u64,
+}
+
+impl Palette {
+    /// Panics if [colors] is empty.
+    pub fn new(colors: Vec<Vector3<f32>>, seed: u64) -> Self {
+        assert!(!colors.is_empty(), "a palette needs at least one color");
+        Self { colors, seed }
+    }
+
+    pub fn colors(&self) -> &[Vector3<f32>] {
+        &self.colors
+    }
+
+    /// The color of entity [id]. Only depends on the seed and the id, not on the order entities are tinted in.
+    pub fn color(&self, id: u64) -> Vector3<f32> {
+        // spreads neighboring ids before seeding
+        let mut rng = StdRng::seed_from_u64(self.seed ^ id.wrapping_mul(0x9E37_79B9_7F4A_7C15));
+        self.colors[rng.random_range(0..self.colors.len())]
+    }
+
+    /// Sets the tint of every entity in [entities].
+    pub fn tint(&self, entities: &mut [Entity]) {
+        for entity in entities {
+            entity.tint = self.color(*entity.id());
+        }
+    }
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use nalgebra::{UnitQuaternion, Vector3};
+
+    use crate::core::{
+        Unique,
+        entity::{BoundingBox, CollisionResponse, Entity, EntityType},
+        palette::Palette,
+    };
+
+    #[test]
+    fn same_seed_same_colors() {
+        let colors = vec![
+            Vector3::new(0.9, 0.2, 0.2),
+            Vector3::new(0.2, 0.9, 0.2),
+            Vector3::new(0.2, 0.2, 0.9),
+            Vector3::new(0.9, 0.9, 0.2),
+        ];
+        let entities = || -> Vec<Entity> {
+            (0..50)
+                .map(|id| {
+                    Entity::new(
+                        id,
+                        0,
+                        0,
+                        Vector3::new(1.0, 1.0, 1.0),
+                        UnitQuaternion::identity(),
+                        Vector3::zeros(),
+                        Vector3::zeros(),
+                        Vector3::zeros(),
+                        BoundingBox::ZERO,
+                        EntityType::Object,
+                        CollisionResponse::Immovable,
+                        1.0,
+                    )
+                })
+                .collect()
+        };
+        let tints = |seed: u64, reversed: bool| -> Vec<Vector3<f32>> {
+            let mut entities = entities();
+            if reversed {
+                entities.reverse();
+            }
+            Palette::new(colors.clone(), seed).tint(&mut entities);
+            entities.sort_by_key(|e| *e.id());
+            entities.iter().map(|e| e.tint).collect()
+        };
+
+        let first = tints(42, false);
+        assert_eq!(first, tints(42, false));
+        assert_eq!(first, tints(42, true));
+        assert_ne!(first, tints(43, false));
+        assert!(first.iter().all(|tint| colors.contains(tint)));
+        // varied, not a single color
+        assert!(colors.iter().all(|color| first.contains(color)));
+    }
+}
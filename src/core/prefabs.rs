@@ -2,28 +2,60 @@ mod systems;
 
 use std::time::Duration;
 
-pub use systems::CollisionsSystem;
+pub(crate) use systems::gravity_accelerations;
+pub use systems::{
+    BroadPhase, CollisionCallback, CollisionCallbacks, CollisionsSystem, Contact,
+    FollowCameraSystem, GravitySystem, HudSystem, LifetimeSystem, LockstepInput,
+    LockstepSimulation, MinimapSystem, MinimapView, SystemTimings, TopDownCamera, orbital_velocity,
+};
 
 use crate::core::{
     System,
     prefabs::systems::{
-        AudioSystem, BoundarySystem, DynamicsSystem, EntitySpawnerSystem, GravitySystem,
-        MetricsSystem,
+        AudioSystem, BoundarySystem, DynamicsSystem, EntitySpawnerSystem, MetricsSystem,
     },
 };
 
-pub const DEFAULT_SYSTEMS: fn() -> Vec<Box<dyn System>> = || {
-    vec![
-        Box::new(CollisionsSystem),
-        Box::new(MetricsSystem::new(Duration::new(5, 0))),
-        Box::new(AudioSystem::new()),
-        Box::new(DynamicsSystem),
-        Box::new(GravitySystem),
-        //Box::new(EntitySpawnerSystem::new(0, 0)),
-        Box::new(BoundarySystem::new(
-            [-50.0, 50.0],
-            [-50.0, 50.0],
-            [-50.0, 50.0],
-        )),
-    ]
-};
+/// Systems included in DEFAULT_SYSTEMS, which can be enabled individually with App::with_default_systems.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DefaultSystem {
+    Collisions,
+    Metrics,
+    /// Requires an audio output device.
+    Audio,
+    Dynamics,
+    Gravity,
+    Lifetime,
+    Boundary,
+}
+
+impl DefaultSystem {
+    pub const ALL: [DefaultSystem; 7] = [
+        DefaultSystem::Collisions,
+        DefaultSystem::Metrics,
+        DefaultSystem::Audio,
+        DefaultSystem::Dynamics,
+        DefaultSystem::Gravity,
+        DefaultSystem::Lifetime,
+        DefaultSystem::Boundary,
+    ];
+
+    pub fn build(&self) -> Box<dyn System> {
+        match self {
+            DefaultSystem::Collisions => Box::new(CollisionsSystem::new()),
+            DefaultSystem::Metrics => Box::new(MetricsSystem::new(Duration::new(5, 0))),
+            DefaultSystem::Audio => Box::new(AudioSystem::new()),
+            DefaultSystem::Dynamics => Box::new(DynamicsSystem),
+            DefaultSystem::Gravity => Box::new(GravitySystem::new()),
+            DefaultSystem::Lifetime => Box::new(LifetimeSystem),
+            DefaultSystem::Boundary => Box::new(BoundarySystem::new(
+                [-50.0, 50.0],
+                [-50.0, 50.0],
+                [-50.0, 50.0],
+            )),
+        }
+    }
+}
+
+pub const DEFAULT_SYSTEMS: fn() -> Vec<Box<dyn System>> =
+    || DefaultSystem::ALL.iter().map(|s| s.build()).collect();
@@ -2,14 +2,27 @@ mod audio;
 mod boundary;
 mod collisions;
 mod dynamics;
+mod follow;
 mod gravity;
+mod hud;
+mod lifetime;
+mod lockstep;
 mod metrics;
+mod minimap;
 mod spawner;
 
 pub use audio::AudioSystem;
 pub use boundary::BoundarySystem;
-pub use collisions::CollisionsSystem;
+pub use collisions::{
+    BroadPhase, CollisionCallback, CollisionCallbacks, CollisionsSystem, Contact,
+};
 pub use dynamics::DynamicsSystem;
-pub use gravity::GravitySystem;
-pub use metrics::MetricsSystem;
+pub use follow::FollowCameraSystem;
+pub(crate) use gravity::gravity_accelerations;
+pub use gravity::{GravitySystem, orbital_velocity};
+pub use hud::HudSystem;
+pub use lifetime::LifetimeSystem;
+pub use lockstep::{LockstepInput, LockstepSimulation};
+pub use metrics::{MetricsSystem, SystemTimings};
+pub use minimap::{MinimapSystem, MinimapView, TopDownCamera};
 pub use spawner::EntitySpawnerSystem;
@@ -1,5 +1,6 @@
-use rodio::{Decoder, OutputStream, Sink};
-use std::{fs::File, time::Duration};
+use log::warn;
+use rodio::{Decoder, OutputStream, Sink, Source, decoder::DecoderError};
+use std::{fs::File, io::BufReader, time::Duration};
 use winit::keyboard::KeyCode;
 
 use crate::core;
@@ -7,6 +8,8 @@ use crate::core;
 pub struct AudioSystem {
     sink: Sink,
     _stream_handle: OutputStream,
+    // The clip restarts once played past this, None if its length isn't known
+    loop_length: Option<Duration>,
 }
 
 impl AudioSystem {
@@ -19,18 +22,58 @@ impl AudioSystem {
         } else {
             sink.set_volume(0.2);
         }
-        sink.append(
-            // TODO: Currently hardcoded to example audio.
-            Decoder::try_from(File::open("examples/rover/assets/engine.wav").unwrap()).unwrap(),
-        );
+        // TODO: Currently hardcoded to example audio.
+        let loop_length = match open_clip("examples/rover/assets/engine.wav") {
+            Ok(clip) => {
+                let loop_length = clip.total_duration();
+                if loop_length.is_none() {
+                    warn!("Length of the engine clip is unknown, it won't loop.");
+                }
+                sink.append(clip);
+                loop_length
+            }
+            Err(e) => {
+                warn!("Engine clip not played: {}", e);
+                None
+            }
+        };
 
         Self {
             sink,
             _stream_handle: stream_handle,
+            loop_length,
         }
     }
 }
 
+#[derive(Debug)]
+pub enum ClipError {
+    /// The file couldn't be opened, i.e. it doesn't exist.
+    Io(std::io::Error),
+
+    /// The file isn't audio rodio can decode.
+    Decode(DecoderError),
+}
+
+impl std::fmt::Display for ClipError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ClipError::Io(e) => write!(f, "the file couldn't be opened: {}", e),
+            ClipError::Decode(e) => write!(f, "the file couldn't be decoded: {}", e),
+        }
+    }
+}
+
+fn open_clip(path: &str) -> Result<Decoder<BufReader<File>>, ClipError> {
+    let file = File::open(path).map_err(ClipError::Io)?;
+    Decoder::try_from(file).map_err(ClipError::Decode)
+}
+
+/// True once [position] reaches [loop_length], so the clip should go back to the start.
+fn past_loop_point(position: Duration, loop_length: Option<Duration>) -> bool {
+    loop_length.is_some_and(|length| position >= length)
+}
+
 impl core::System for AudioSystem {
     fn before_tick(&mut self, args: &mut core::BeforeTickArgs) {
         if *args.input.is_pressed(&KeyCode::KeyW)
@@ -44,7 +87,7 @@ impl core::System for AudioSystem {
                 self.sink.set_speed(1.0);
             }
             self.sink.play();
-            if self.sink.get_pos() > Duration::new(5, 0) {
+            if past_loop_point(self.sink.get_pos(), self.loop_length) {
                 self.sink.try_seek(Duration::ZERO).unwrap();
             }
         } else {
@@ -53,3 +96,42 @@ impl core::System for AudioSystem {
         }
     }
 }
+
+#[allow(unused_imports)]
+mod tests {
+    use rodio::Source;
+    use std::time::Duration;
+
+    use crate::core::prefabs::systems::audio::{ClipError, open_clip, past_loop_point};
+
+    #[test]
+    fn missing_clip_is_an_error() {
+        assert!(matches!(
+            open_clip("examples/rover/assets/missing.wav"),
+            Err(ClipError::Io(_))
+        ));
+        // not audio
+        assert!(matches!(open_clip("Cargo.toml"), Err(ClipError::Decode(_))));
+    }
+
+    #[test]
+    fn loops_at_clip_length() {
+        let loop_length = open_clip("examples/rover/assets/engine.wav")
+            .unwrap()
+            .total_duration();
+        // the engine clip is 10.5s long, not the 5s this used to assume
+        let length = loop_length.unwrap();
+        assert!(length.abs_diff(Duration::from_millis(10_500)) < Duration::from_millis(1));
+        assert!(!past_loop_point(Duration::from_secs(5), loop_length));
+        assert!(!past_loop_point(Duration::from_secs(10), loop_length));
+        assert!(past_loop_point(Duration::from_millis(10_500), loop_length));
+
+        // any other length
+        let configured = Some(Duration::from_secs(2));
+        assert!(!past_loop_point(Duration::from_millis(1_999), configured));
+        assert!(past_loop_point(Duration::from_secs(2), configured));
+
+        // unknown length never loops
+        assert!(!past_loop_point(Duration::from_secs(60), None));
+    }
+}
@@ -1,7 +1,748 @@
-use crate::core;
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
 
-pub struct CollisionsSystem;
+use nalgebra::Vector3;
+use serde_json::{Value, json};
+
+use crate::core::{
+    self, DEFAULT_BROAD_PHASE, DEFAULT_COLLISION_ITERATIONS, Unique, entity::Entity,
+};
+
+/// Entities within this distance above the ground are considered to be touching it.
+const GROUND_CONTACT_TOLERANCE: f32 = 1.0e-3;
+
+/// A point where two entities (or an entity and the ground) overlap.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Contact {
+    pub a: u64,
+    /// None if [a] is touching the ground.
+    pub b: Option<u64>,
+    pub point: Vector3<f32>,
+    /// Unit vector pointing from [a] towards [b].
+    pub normal: Vector3<f32>,
+    /// How far the two overlap along [normal].
+    pub penetration: f32,
+}
+
+/// How CollisionsSystem finds the pairs of entities which overlap, instead of testing every pair.
+/// Both find the same pairs and only differ in speed, depending on how entities are spread out.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BroadPhase {
+    /// Buckets bounds into cubes [cell_size] wide and only tests entities sharing a cube. Fastest when
+    /// entities are spread evenly and around a cell in size; entities spanning many cells are slow.
+    Grid { cell_size: f32 },
+    /// Sorts bounds along the axis entities are spread furthest on and only tests entities whose
+    /// intervals along it overlap. Needs no tuning and suits clustered or elongated scenes.
+    SweepAndPrune,
+}
+
+impl BroadPhase {
+    /// Pairs (i, j) of indices into [bounds], given as (min, max) corners, whose boxes overlap or touch.
+    /// i < j, in ascending order.
+    pub fn overlapping_pairs(
+        &self,
+        bounds: &[(Vector3<f32>, Vector3<f32>)],
+    ) -> Vec<(usize, usize)> {
+        let mut pairs = match self {
+            BroadPhase::Grid { cell_size } => grid_candidates(bounds, *cell_size),
+            BroadPhase::SweepAndPrune => sweep_and_prune_candidates(bounds),
+        };
+        pairs.sort_unstable();
+        pairs.dedup();
+        pairs.retain(|(i, j)| aabb_contact(bounds[*i], bounds[*j]).is_some());
+        pairs
+    }
+}
+
+pub type CollisionCallback = Box<dyn FnMut(u64, &Contact)>;
+
+/// Callbacks run by CollisionsSystem when a specific entity collides with another entity.
+/// Stored in ActiveState, see ActiveState::on_collision.
+#[derive(Default)]
+pub struct CollisionCallbacks {
+    callbacks: HashMap<u64, Vec<CollisionCallback>>,
+}
+
+impl CollisionCallbacks {
+    pub fn register(&mut self, id: u64, callback: CollisionCallback) {
+        self.callbacks.entry(id).or_default().push(callback);
+    }
+
+    /// Drops every callback of [id].
+    pub fn remove(&mut self, id: u64) {
+        self.callbacks.remove(&id);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.callbacks.is_empty()
+    }
+
+    /// Calls the callbacks of both entities of each contact with the other entity's id. Each entity sees
+    /// the contact from its own side, with the normal pointing towards the other. Ground contacts are skipped.
+    pub fn dispatch(&mut self, contacts: &[Contact]) {
+        for contact in contacts {
+            let Some(b) = contact.b else {
+                continue;
+            };
+            if let Some(callbacks) = self.callbacks.get_mut(&contact.a) {
+                for callback in callbacks.iter_mut() {
+                    callback(b, contact);
+                }
+            }
+            if let Some(callbacks) = self.callbacks.get_mut(&b) {
+                let flipped = Contact {
+                    a: b,
+                    b: Some(contact.a),
+                    normal: -contact.normal,
+                    ..contact.clone()
+                };
+                for callback in callbacks.iter_mut() {
+                    callback(contact.a, &flipped);
+                }
+            }
+        }
+    }
+}
+
+pub struct CollisionsSystem {
+    /// Height of a flat ground plane. Entities are clipped to it and experience friction while touching it.
+    /// No ground is simulated if None.
+    ground_height: Option<f32>,
+
+    iterations: u32,
+    broad_phase: BroadPhase,
+
+    /// Contacts found during the last tick, only kept if recording.
+    contacts: Vec<Contact>,
+    record_contacts: bool,
+
+    gui_data: Option<Arc<RwLock<HashMap<String, Value>>>>,
+}
+
+impl Default for CollisionsSystem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CollisionsSystem {
+    pub fn new() -> Self {
+        Self {
+            ground_height: None,
+            iterations: DEFAULT_COLLISION_ITERATIONS,
+            broad_phase: DEFAULT_BROAD_PHASE,
+            contacts: vec![],
+            record_contacts: false,
+            gui_data: None,
+        }
+    }
+
+    pub fn with_ground(ground_height: f32) -> Self {
+        Self {
+            ground_height: Some(ground_height),
+            ..Self::new()
+        }
+    }
+
+    /// Resolves every overlapping pair [iterations] times per tick, at least once. Each pass pushes apart what
+    /// the last pass pushed together, so stacked entities need a few to settle instead of sinking into each
+    /// other.
+    pub fn with_iterations(mut self, iterations: u32) -> Self {
+        self.iterations = iterations.max(1);
+        self
+    }
+
+    /// Finds overlapping pairs with [broad_phase]. Panics if a grid's cell size isn't positive.
+    pub fn with_broad_phase(mut self, broad_phase: BroadPhase) -> Self {
+        if let BroadPhase::Grid { cell_size } = broad_phase {
+            assert!(cell_size > 0.0, "grid cell size must be positive");
+        }
+        self.broad_phase = broad_phase;
+        self
+    }
+
+    pub fn broad_phase(&self) -> BroadPhase {
+        self.broad_phase
+    }
+
+    /// Records every contact found each tick, readable through CollisionsSystem::contacts and
+    /// the "contacts" entry of the renderer's gui data.
+    pub fn with_contact_recording(mut self) -> Self {
+        self.record_contacts = true;
+        self
+    }
+
+    /// Contacts found during the last tick. Always empty unless recording.
+    pub fn contacts(&self) -> &[Contact] {
+        &self.contacts
+    }
+
+    fn find_contacts(&self, entities: &[Entity]) -> Vec<Contact> {
+        let bounds: Vec<_> = entities.iter().map(Entity::world_bounds).collect();
+        let mut contacts = vec![];
+        for (i, j) in self.broad_phase.overlapping_pairs(&bounds) {
+            if let Some((point, normal, penetration)) = aabb_contact(bounds[i], bounds[j]) {
+                contacts.push(Contact {
+                    a: *entities[i].id(),
+                    b: Some(*entities[j].id()),
+                    point,
+                    normal,
+                    penetration,
+                });
+            }
+        }
+        for a in entities {
+            if let Some(ground) = self.ground_height
+                && a.translation.y <= ground + GROUND_CONTACT_TOLERANCE
+            {
+                contacts.push(Contact {
+                    a: *a.id(),
+                    b: None,
+                    point: Vector3::new(a.translation.x, ground, a.translation.z),
+                    normal: -Vector3::y(),
+                    penetration: (ground - a.translation.y).max(0.0),
+                });
+            }
+        }
+        contacts
+    }
+
+    /// Clips entities which fell through the ground back onto it and applies ground friction to
+    /// every entity touching it.
+    fn resolve_ground(&self, entities: &mut [Entity], dt: f32) {
+        let Some(ground) = self.ground_height else {
+            return;
+        };
+        for entity in entities {
+            if entity.translation.y < ground {
+                entity.translation.y = ground;
+                if entity.velocity.y < 0.0 {
+                    entity.velocity.y = 0.0;
+                }
+            }
+            if entity.translation.y <= ground + GROUND_CONTACT_TOLERANCE {
+                entity.apply_ground_friction(dt);
+            }
+        }
+    }
+}
 
 impl core::System for CollisionsSystem {
-    // TODO
+    fn before_start(&mut self, args: &mut core::BeforeStartArgs) {
+        self.gui_data = Some(args.renderer.gui_data());
+    }
+
+    fn handle_tick(&mut self, args: &mut core::HandleTickArgs) {
+        let dt = args.elapsed.as_secs_f32();
+        let entities = args.state.entities_mut();
+        resolve_collisions(entities, self.iterations, &self.broad_phase);
+        apply_resting_friction(entities, &self.broad_phase, dt);
+    }
+
+    fn after_tick(&mut self, args: &mut core::AfterTickArgs) {
+        let dt = args.elapsed.as_secs_f32();
+        if self.record_contacts || !args.state.collision_callbacks_mut().is_empty() {
+            // before resolving, so penetrations are visible
+            let contacts = self.find_contacts(args.state.entities());
+            args.state.collision_callbacks_mut().dispatch(&contacts);
+            if self.record_contacts {
+                self.contacts = contacts;
+            }
+        }
+        if self.record_contacts
+            && let Some(gui_data) = &self.gui_data
+            && let Ok(mut gui_data) = gui_data.write()
+        {
+            gui_data.insert(
+                "contacts".into(),
+                Value::Array(
+                    self.contacts
+                        .iter()
+                        .map(|c| {
+                            json!({
+                                "a": c.a,
+                                "b": c.b,
+                                "point": [c.point.x, c.point.y, c.point.z],
+                                "normal": [c.normal.x, c.normal.y, c.normal.z],
+                                "penetration": c.penetration,
+                            })
+                        })
+                        .collect(),
+                ),
+            );
+        }
+        self.resolve_ground(args.state.entities_mut(), dt);
+    }
+}
+
+/// Applies Entity::perform_single_collision to every overlapping pair, in order, and pushes them apart.
+/// Repeated [iterations] times, finding the pairs with [broad_phase] at the start of each pass. Pairs
+/// pushed into each other during a pass are resolved in the next.
+fn resolve_collisions(entities: &mut [Entity], iterations: u32, broad_phase: &BroadPhase) {
+    for _ in 0..iterations {
+        let bounds: Vec<_> = entities.iter().map(Entity::world_bounds).collect();
+        let mut pairs = broad_phase.overlapping_pairs(&bounds);
+        // each entity against every one before it, in turn
+        pairs.sort_unstable_by_key(|(i, j)| (*j, *i));
+        for (i, j) in pairs {
+            let (before, rest) = entities.split_at_mut(j);
+            let (a, b) = (&mut before[i], &mut rest[0]);
+            if let Some((a_delta, b_delta)) = a.perform_single_collision(b) {
+                a.velocity += a_delta;
+                b.velocity += b_delta;
+                separate(a, b);
+            }
+        }
+    }
+}
+
+/// Applies ground friction to entities resting on top of another entity, i.e. on an immovable floor, so
+/// Entity::friction takes effect without a ground plane too.
+fn apply_resting_friction(entities: &mut [Entity], broad_phase: &BroadPhase, dt: f32) {
+    // reaching a little below each entity, so ones pushed out to just above another still touch it
+    let bounds: Vec<_> = entities
+        .iter()
+        .map(|entity| {
+            let (min, max) = entity.world_bounds();
+            (min - Vector3::y() * GROUND_CONTACT_TOLERANCE, max)
+        })
+        .collect();
+    let mut resting = vec![false; entities.len()];
+    for (i, j) in broad_phase.overlapping_pairs(&bounds) {
+        if let Some((_point, normal, _penetration)) = aabb_contact(bounds[i], bounds[j]) {
+            // the normal points from i to j, so down if i is on top
+            if normal.y < 0.0 {
+                resting[i] = true;
+            } else if normal.y > 0.0 {
+                resting[j] = true;
+            }
+        }
+    }
+    for (entity, resting) in entities.iter_mut().zip(resting) {
+        if resting {
+            entity.apply_ground_friction(dt);
+        }
+    }
+}
+
+/// Pairs of indices into [bounds] sharing at least one grid cell, i < j. May contain duplicates.
+fn grid_candidates(bounds: &[(Vector3<f32>, Vector3<f32>)], cell_size: f32) -> Vec<(usize, usize)> {
+    let cell = |corner: &Vector3<f32>| corner.map(|c| (c / cell_size).floor() as i64);
+    let mut cells: HashMap<(i64, i64, i64), Vec<usize>> = HashMap::new();
+    for (i, (min, max)) in bounds.iter().enumerate() {
+        let (low, high) = (cell(min), cell(max));
+        for x in low.x..=high.x {
+            for y in low.y..=high.y {
+                for z in low.z..=high.z {
+                    cells.entry((x, y, z)).or_default().push(i);
+                }
+            }
+        }
+    }
+    let mut pairs = vec![];
+    for members in cells.values() {
+        // pushed in ascending order
+        for (k, i) in members.iter().enumerate() {
+            pairs.extend(members[k + 1..].iter().map(|j| (*i, *j)));
+        }
+    }
+    pairs
+}
+
+/// Pairs of indices into [bounds] whose intervals overlap along the axis they're spread furthest on, i < j.
+fn sweep_and_prune_candidates(bounds: &[(Vector3<f32>, Vector3<f32>)]) -> Vec<(usize, usize)> {
+    let axis = spread_axis(bounds);
+    let mut order: Vec<usize> = (0..bounds.len()).collect();
+    order.sort_unstable_by(|a, b| bounds[*a].0[axis].total_cmp(&bounds[*b].0[axis]));
+
+    let mut pairs = vec![];
+    // entities whose interval hasn't ended before the current one starts
+    let mut active: Vec<usize> = vec![];
+    for i in order {
+        let start = bounds[i].0[axis];
+        active.retain(|a| bounds[*a].1[axis] >= start);
+        pairs.extend(active.iter().map(|a| ((*a).min(i), (*a).max(i))));
+        active.push(i);
+    }
+    pairs
+}
+
+/// Axis along which the centers of [bounds] are spread furthest, x if empty.
+fn spread_axis(bounds: &[(Vector3<f32>, Vector3<f32>)]) -> usize {
+    let mut centers = bounds.iter().map(|(min, max)| (min + max) / 2.0);
+    let Some(first) = centers.next() else {
+        return 0;
+    };
+    let (low, high) = centers.fold((first, first), |(low, high), center| {
+        (low.inf(&center), high.sup(&center))
+    });
+    (high - low).imax()
+}
+
+/// Moves two overlapping entities apart along the axis they overlap least on, each by a share of the
+/// penetration inversely proportional to its mass. Immovable entities stay put.
+fn separate(a: &mut Entity, b: &mut Entity) {
+    let (a_inverse, b_inverse) = (a.inverse_mass(), b.inverse_mass());
+    if a_inverse + b_inverse == 0.0 {
+        return;
+    }
+    if let Some((_point, normal, penetration)) = aabb_contact(a.world_bounds(), b.world_bounds()) {
+        let correction = normal * penetration / (a_inverse + b_inverse);
+        a.translation -= correction * a_inverse;
+        b.translation += correction * b_inverse;
+    }
+}
+
+/// Contact point, normal (from a to b) and penetration depth of two overlapping axis-aligned boxes,
+/// given as (min, max) corners. The normal is along the axis with the least overlap.
+fn aabb_contact(
+    (a_min, a_max): (Vector3<f32>, Vector3<f32>),
+    (b_min, b_max): (Vector3<f32>, Vector3<f32>),
+) -> Option<(Vector3<f32>, Vector3<f32>, f32)> {
+    let overlap_min = a_min.sup(&b_min);
+    let overlap_max = a_max.inf(&b_max);
+    let overlap = overlap_max - overlap_min;
+    if overlap.iter().any(|o| *o < 0.0) {
+        return None;
+    }
+
+    let axis = overlap.imin();
+    let a_center = (a_min + a_max) / 2.0;
+    let b_center = (b_min + b_max) / 2.0;
+    let mut normal = Vector3::zeros();
+    normal[axis] = if b_center[axis] >= a_center[axis] {
+        1.0
+    } else {
+        -1.0
+    };
+
+    Some(((overlap_min + overlap_max) / 2.0, normal, overlap[axis]))
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use nalgebra::{UnitQuaternion, Vector3};
+
+    use crate::core::{
+        entity::{BoundingBox, CollisionResponse, Entity, EntityType},
+        prefabs::systems::{
+            BroadPhase, CollisionCallbacks, CollisionsSystem, Contact,
+            collisions::{aabb_contact, apply_resting_friction, resolve_collisions},
+        },
+    };
+
+    #[test]
+    fn friction_only_slows_grounded_entities() {
+        let system = CollisionsSystem::with_ground(0.0);
+        let mut entities: Vec<Entity> = [0.0, 10.0]
+            .iter()
+            .enumerate()
+            .map(|(i, height)| {
+                let mut entity = Entity::new(
+                    i as u64,
+                    0,
+                    0,
+                    Vector3::new(1.0, 1.0, 1.0),
+                    UnitQuaternion::identity(),
+                    Vector3::new(0.0, *height, 0.0),
+                    Vector3::new(4.0, 0.0, 3.0),
+                    Vector3::zeros(),
+                    BoundingBox::ZERO,
+                    EntityType::Object,
+                    CollisionResponse::Inelastic(1.0),
+                    1.0,
+                );
+                entity.friction = 0.5;
+                entity
+            })
+            .collect();
+
+        for _ in 0..10 {
+            system.resolve_ground(&mut entities, 0.1);
+        }
+
+        let grounded = &entities[0];
+        let airborne = &entities[1];
+        assert!(grounded.velocity.xz().magnitude() < 5.0);
+        assert_eq!(grounded.velocity.y, 0.0);
+        assert_eq!(airborne.velocity, Vector3::new(4.0, 0.0, 3.0));
+    }
+
+    #[test]
+    fn friction_slows_entities_resting_on_others() {
+        let entity = |id, translation, size: f32, response| {
+            let mut entity = Entity::new(
+                id,
+                0,
+                0,
+                Vector3::new(1.0, 1.0, 1.0),
+                UnitQuaternion::identity(),
+                translation,
+                Vector3::new(4.0, 0.0, 3.0),
+                Vector3::zeros(),
+                BoundingBox::new((-size / 2.0, 0.0, -size / 2.0), (size, 1.0, size)),
+                EntityType::Object,
+                response,
+                1.0,
+            );
+            entity.friction = 0.5;
+            entity
+        };
+        let mut entities = vec![
+            // floor, from y = -1 to 0
+            entity(
+                0,
+                Vector3::new(0.0, -1.0, 0.0),
+                100.0,
+                CollisionResponse::Immovable,
+            ),
+            // on the floor, and just above it
+            entity(1, Vector3::zeros(), 1.0, CollisionResponse::Inelastic(1.0)),
+            entity(
+                2,
+                Vector3::new(5.0, 1.0e-4, 0.0),
+                1.0,
+                CollisionResponse::Inelastic(1.0),
+            ),
+            // in the air
+            entity(
+                3,
+                Vector3::new(0.0, 10.0, 0.0),
+                1.0,
+                CollisionResponse::Inelastic(1.0),
+            ),
+        ];
+        entities[0].velocity = Vector3::zeros();
+
+        for _ in 0..10 {
+            apply_resting_friction(&mut entities, &BroadPhase::SweepAndPrune, 0.1);
+        }
+
+        for resting in &entities[1..3] {
+            assert!(resting.velocity.xz().magnitude() < 5.0);
+            assert_eq!(resting.velocity.y, 0.0);
+        }
+        assert_eq!(entities[3].velocity, Vector3::new(4.0, 0.0, 3.0));
+        // whatever's underneath doesn't rest on the entities above
+        assert_eq!(entities[0].velocity, Vector3::zeros());
+    }
+
+    #[test]
+    fn overlapping_pair_contact() {
+        let cube = |id, translation| {
+            Entity::new(
+                id,
+                0,
+                0,
+                Vector3::new(1.0, 1.0, 1.0),
+                UnitQuaternion::identity(),
+                translation,
+                Vector3::zeros(),
+                Vector3::zeros(),
+                BoundingBox::new((-1.0, -1.0, -1.0), (2.0, 2.0, 2.0)),
+                EntityType::Object,
+                CollisionResponse::Inelastic(1.0),
+                1.0,
+            )
+        };
+        // overlapping by 0.5 along x, fully along y and z
+        let entities = vec![
+            cube(0, Vector3::new(0.0, 0.0, 0.0)),
+            cube(1, Vector3::new(1.5, 0.2, 0.0)),
+            cube(2, Vector3::new(10.0, 0.0, 0.0)),
+        ];
+        let system = CollisionsSystem::new();
+
+        let contacts = system.find_contacts(&entities);
+
+        assert_eq!(contacts.len(), 1);
+        let contact = &contacts[0];
+        assert_eq!((contact.a, contact.b), (0, Some(1)));
+        assert_eq!(contact.normal, Vector3::new(1.0, 0.0, 0.0));
+        assert!((contact.penetration - 0.5).abs() < 1.0e-5);
+        assert!((contact.point.x - 0.75).abs() < 1.0e-5);
+    }
+
+    #[test]
+    fn callback_fires_with_other_id() {
+        let cube = |id, x| {
+            Entity::new(
+                id,
+                0,
+                0,
+                Vector3::new(1.0, 1.0, 1.0),
+                UnitQuaternion::identity(),
+                Vector3::new(x, 0.0, 0.0),
+                Vector3::zeros(),
+                Vector3::zeros(),
+                BoundingBox::new((-1.0, -1.0, -1.0), (2.0, 2.0, 2.0)),
+                EntityType::Object,
+                CollisionResponse::Inelastic(1.0),
+                1.0,
+            )
+        };
+        let entities = vec![cube(4, 0.0), cube(7, 1.5), cube(9, 20.0)];
+        let hits = Rc::new(RefCell::new(Vec::<(u64, u64, Vector3<f32>)>::new()));
+        let mut callbacks = CollisionCallbacks::default();
+        for id in [7, 9] {
+            let hits = hits.clone();
+            callbacks.register(
+                id,
+                Box::new(move |other, contact| hits.borrow_mut().push((id, other, contact.normal))),
+            );
+        }
+
+        let contacts = CollisionsSystem::new().find_contacts(&entities);
+        callbacks.dispatch(&contacts);
+
+        // 9 collides with nothing, 7 is hit by 4 from -x
+        assert_eq!(*hits.borrow(), vec![(7, 4, Vector3::new(-1.0, 0.0, 0.0))]);
+    }
+
+    #[test]
+    fn colliding_pair_bounces_apart() {
+        let cube = |id, x, velocity_x| {
+            Entity::new(
+                id,
+                0,
+                0,
+                Vector3::new(1.0, 1.0, 1.0),
+                UnitQuaternion::identity(),
+                Vector3::new(x, 0.0, 0.0),
+                Vector3::new(velocity_x, 0.0, 0.0),
+                Vector3::zeros(),
+                BoundingBox::new((-1.0, -1.0, -1.0), (2.0, 2.0, 2.0)),
+                EntityType::Object,
+                CollisionResponse::Inelastic(1.0),
+                1.0,
+            )
+        };
+        let mut entities = vec![cube(0, 0.0, 1.0), cube(1, 1.5, -1.0), cube(2, 10.0, -1.0)];
+
+        resolve_collisions(&mut entities, 1, &BroadPhase::SweepAndPrune);
+
+        assert_eq!(entities[0].velocity, Vector3::new(-1.0, 0.0, 0.0));
+        assert_eq!(entities[1].velocity, Vector3::new(1.0, 0.0, 0.0));
+        assert_eq!(entities[2].velocity, Vector3::new(-1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn stack_settles_with_enough_iterations() {
+        let dt = 1.0 / 60.0;
+        let stack = || -> Vec<Entity> {
+            let floor = Entity::new(
+                0,
+                0,
+                0,
+                Vector3::new(1.0, 1.0, 1.0),
+                UnitQuaternion::identity(),
+                Vector3::zeros(),
+                Vector3::zeros(),
+                Vector3::zeros(),
+                BoundingBox::new((-5.0, -1.0, -5.0), (10.0, 1.0, 10.0)),
+                EntityType::Object,
+                CollisionResponse::Immovable,
+                1.0,
+            );
+            // unit boxes resting on each other
+            let boxes = (1..4).map(|i| {
+                Entity::new(
+                    i,
+                    0,
+                    0,
+                    Vector3::new(1.0, 1.0, 1.0),
+                    UnitQuaternion::identity(),
+                    Vector3::new(0.0, i as f32 - 0.5, 0.0),
+                    Vector3::zeros(),
+                    Vector3::new(0.0, -9.8, 0.0),
+                    BoundingBox::new((-0.5, -0.5, -0.5), (1.0, 1.0, 1.0)),
+                    EntityType::Object,
+                    CollisionResponse::Inelastic(0.0),
+                    1.0,
+                )
+            });
+            std::iter::once(floor).chain(boxes).collect()
+        };
+        // how far each box sank into the one below it, or the floor
+        let overlaps = |entities: &[Entity]| -> Vec<f32> {
+            entities
+                .windows(2)
+                .map(|pair| pair[0].world_bounds().1.y - pair[1].world_bounds().0.y)
+                .collect()
+        };
+        let simulate = |iterations: u32| {
+            let mut entities = stack();
+            for _ in 0..120 {
+                // in the order of the default systems, collisions then dynamics
+                resolve_collisions(&mut entities, iterations, &BroadPhase::SweepAndPrune);
+                for entity in entities.iter_mut() {
+                    entity.tick(dt);
+                }
+            }
+            resolve_collisions(&mut entities, iterations, &BroadPhase::SweepAndPrune);
+            entities
+        };
+
+        let settled = simulate(10);
+        assert!(
+            overlaps(&settled).iter().all(|o| o.abs() < 1.0e-3),
+            "{:?}",
+            overlaps(&settled)
+        );
+        assert!((settled[3].translation.y - 2.5).abs() < 1.0e-3);
+
+        let sunk = simulate(1);
+        assert!(overlaps(&sunk)[0] > 1.0e-2, "{:?}", overlaps(&sunk));
+    }
+
+    #[test]
+    fn broad_phases_find_same_pairs() {
+        // a cluster, a long row touching end to end, a box spanning many cells and a far away loner
+        let mut state: u32 = 7;
+        let mut random = move || {
+            state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+            (state >> 8) as f32 / (1 << 24) as f32
+        };
+        let mut bounds = vec![];
+        for _ in 0..40 {
+            let min = Vector3::new(random(), random(), random()) * 6.0;
+            bounds.push((min, min + Vector3::new(random(), random(), random()) * 2.0));
+        }
+        for i in 0..30 {
+            let min = Vector3::new(i as f32 * 2.0, 20.0, 0.0);
+            bounds.push((min, min + Vector3::new(2.0, 1.0, 1.0)));
+        }
+        bounds.push((
+            Vector3::new(-5.0, -5.0, -5.0),
+            Vector3::new(15.0, 25.0, 3.0),
+        ));
+        bounds.push((Vector3::repeat(500.0), Vector3::repeat(501.0)));
+
+        let mut every_pair = vec![];
+        for i in 0..bounds.len() {
+            for j in i + 1..bounds.len() {
+                if aabb_contact(bounds[i], bounds[j]).is_some() {
+                    every_pair.push((i, j));
+                }
+            }
+        }
+        assert!(every_pair.len() > bounds.len());
+
+        assert_eq!(
+            BroadPhase::SweepAndPrune.overlapping_pairs(&bounds),
+            every_pair
+        );
+        for cell_size in [0.5, 2.0, 100.0] {
+            assert_eq!(
+                BroadPhase::Grid { cell_size }.overlapping_pairs(&bounds),
+                every_pair,
+                "cell size {cell_size}"
+            );
+        }
+        assert!(BroadPhase::SweepAndPrune.overlapping_pairs(&[]).is_empty());
+    }
 }
@@ -1,38 +1,152 @@
-use nalgebra::Vector3;
-
-use crate::{Integrator, core};
+use crate::{
+    Integrator,
+    core::{self, entity::Entity},
+};
 
 pub struct DynamicsSystem;
 
 impl core::System for DynamicsSystem {
     fn handle_tick(&mut self, args: &mut core::HandleTickArgs) {
         let dt = args.elapsed.as_secs_f32();
-        for entity in args.state.entities_mut() {
-            match crate::core::GLOBAL_INTEGRATOR {
-                Integrator::RK4 => {
-                    let acceleration = Vector3::from(entity.acceleration);
-                    let a_k1 = acceleration;
-                    let a_k2 = acceleration + a_k1 * dt / 2.0;
-                    let a_k3 = acceleration + a_k2 * dt / 2.0;
-                    let a_k4 = acceleration + a_k3 * dt;
-                    entity.velocity += (a_k1 + 2.0 * a_k2 + 2.0 * a_k3 + a_k4) / 6.0 * dt;
-
-                    let velocity = Vector3::from(entity.velocity);
-                    let v_k1 = velocity;
-                    let v_k2 = velocity + v_k1 * dt / 2.0;
-                    let v_k3 = velocity + v_k2 * dt / 2.0;
-                    let v_k4 = velocity + v_k3 * dt;
-
-                    entity.translation += (v_k1 + 2.0 * v_k2 + 2.0 * v_k3 + v_k4) / 6.0 * dt;
-                }
-                Integrator::Euler => {
-                    let acceleration = Vector3::from(entity.acceleration);
-                    entity.velocity += acceleration * dt;
-
-                    let velocity = Vector3::from(entity.velocity);
-                    entity.translation += velocity * dt;
-                }
-            }
+        let integrator = args.state.integrator();
+        integrate_awake(args.state.entities_mut(), dt, &integrator);
+    }
+}
+
+/// Integrates every entity that isn't asleep, see Entity::is_asleep.
+fn integrate_awake(entities: &mut [Entity], dt: f32, integrator: &Integrator) {
+    for entity in entities {
+        if !entity.update_sleep() {
+            entity.integrate(dt, integrator);
+        }
+    }
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use std::time::Duration;
+
+    use nalgebra::{UnitQuaternion, Vector3};
+
+    use crate::{
+        core::{
+            Completer, DEFAULT_INTEGRATOR, SLEEP_TICKS,
+            entity::{BoundingBox, CollisionResponse, Entity, EntityType, MaterialKind},
+            prefabs::{
+                DefaultSystem,
+                systems::{CollisionsSystem, dynamics::integrate_awake},
+            },
+        },
+        render::app::{App, ObjectInitData},
+    };
+
+    #[test]
+    fn constant_velocity_moves_linearly() {
+        let mut entity = Entity::new(
+            0,
+            0,
+            0,
+            Vector3::new(1.0, 1.0, 1.0),
+            UnitQuaternion::identity(),
+            Vector3::zeros(),
+            Vector3::zeros(),
+            Vector3::zeros(),
+            BoundingBox::ZERO,
+            EntityType::Object,
+            CollisionResponse::Inelastic(1.0),
+            1.0,
+        );
+        entity.set_velocity(Vector3::new(2.0, 0.0, -1.0));
+
+        for _ in 0..10 {
+            entity.tick(0.1);
         }
+
+        assert!((entity.translation() - Vector3::new(2.0, 0.0, -1.0)).magnitude() < 1.0e-5);
+        assert_eq!(entity.velocity(), &Vector3::new(2.0, 0.0, -1.0));
+
+        // accelerating from rest
+        entity.set_velocity(Vector3::zeros());
+        entity.set_acceleration(Vector3::new(0.0, 1.0, 0.0));
+        entity.tick(1.0);
+
+        assert!(entity.velocity().y > 0.0);
+        assert!(entity.translation().y > 0.0);
+    }
+
+    #[test]
+    fn resting_entity_sleeps_until_pushed() {
+        let mut entities = vec![Entity::new(
+            0,
+            0,
+            0,
+            Vector3::new(1.0, 1.0, 1.0),
+            UnitQuaternion::identity(),
+            Vector3::zeros(),
+            Vector3::zeros(),
+            Vector3::zeros(),
+            BoundingBox::ZERO,
+            EntityType::Object,
+            CollisionResponse::Inelastic(1.0),
+            1.0,
+        )];
+        // slow enough to count as still, so it only drifts while awake
+        entities[0].set_velocity(Vector3::new(1.0e-3, 0.0, 0.0));
+
+        for _ in 0..SLEEP_TICKS - 1 {
+            integrate_awake(&mut entities, 0.1, &DEFAULT_INTEGRATOR);
+        }
+        assert!(!entities[0].is_asleep());
+        integrate_awake(&mut entities, 0.1, &DEFAULT_INTEGRATOR);
+        assert!(entities[0].is_asleep());
+
+        let resting = *entities[0].translation();
+        for _ in 0..10 {
+            integrate_awake(&mut entities, 0.1, &DEFAULT_INTEGRATOR);
+        }
+        assert_eq!(entities[0].translation(), &resting);
+
+        // an impulse wakes it on the next tick
+        entities[0].set_velocity(Vector3::new(1.0, 0.0, 0.0));
+        integrate_awake(&mut entities, 0.1, &DEFAULT_INTEGRATOR);
+        assert!(!entities[0].is_asleep());
+        assert!(entities[0].translation().x > resting.x + 0.05);
+    }
+
+    #[test]
+    fn entity_resting_on_ground_sleeps_under_gravity() {
+        let mut app = App::new_headless(0, &[DefaultSystem::Dynamics]);
+        app.add_system(Box::new(CollisionsSystem::with_ground(0.0)));
+        let mut add_falling = |height: f32| {
+            app.add_object(ObjectInitData {
+                mesh_id: Completer::from_value(0),
+                texture_id: Completer::from_value(0),
+                velocity: Vector3::zeros(),
+                acceleration: Vector3::new(0.0, -9.8, 0.0),
+                bounding_box: BoundingBox::ZERO,
+                scale: Vector3::new(1.0, 1.0, 1.0),
+                rotation: UnitQuaternion::identity(),
+                translation: Vector3::new(0.0, height, 0.0),
+                response: CollisionResponse::Inelastic(0.0),
+                mass: 1.0,
+                friction: 0.0,
+                lifetime: None,
+                material: MaterialKind::Opaque,
+                emissive: 0.0,
+            });
+        };
+        add_falling(0.0);
+        add_falling(100.0);
+
+        for _ in 0..SLEEP_TICKS + 10 {
+            app.step(Duration::from_secs_f32(1.0 / 60.0));
+        }
+
+        let entities = app.state().unwrap().entities();
+        // held up by the ground every tick, so it never gets going
+        assert!(entities[0].is_asleep());
+        assert_eq!(entities[0].translation().y, 0.0);
+        assert!(!entities[1].is_asleep());
+        assert!(entities[1].translation().y < 100.0);
     }
 }
@@ -0,0 +1,156 @@
+use nalgebra::{UnitQuaternion, Vector3};
+
+use crate::core::{self, DEFAULT_FOLLOW_STIFFNESS, Unique, camera::Camera};
+
+/// Keeps the active camera at an offset from a target entity, facing it. The camera eases towards the
+/// offset instead of snapping to it, and does nothing while the target doesn't exist.
+///
+/// Cameras which ignore Camera::look_at, like EditorCamera, aren't moved.
+pub struct FollowCameraSystem {
+    target: u64,
+    offset: Vector3<f32>,
+    stiffness: f32,
+}
+
+impl FollowCameraSystem {
+    /// [offset] is in the target's local space, so a positive z keeps the camera behind an entity
+    /// facing -z as it turns.
+    pub fn new(target: u64, offset: Vector3<f32>) -> Self {
+        Self {
+            target,
+            offset,
+            stiffness: DEFAULT_FOLLOW_STIFFNESS,
+        }
+    }
+
+    pub fn target(&self) -> u64 {
+        self.target
+    }
+
+    pub fn set_target(&mut self, target: u64) {
+        self.target = target;
+    }
+
+    pub fn set_offset(&mut self, offset: Vector3<f32>) {
+        self.offset = offset;
+    }
+
+    /// The camera covers 1 - e^(-stiffness * dt) of the remaining distance each frame, so higher values
+    /// follow more tightly. Independent of the frame rate.
+    pub fn set_stiffness(&mut self, stiffness: f32) {
+        self.stiffness = stiffness;
+    }
+
+    /// Moves [camera] [dt] seconds closer to the offset from a target at [translation] and [rotation].
+    pub fn follow(
+        &self,
+        camera: &mut dyn Camera,
+        translation: &Vector3<f32>,
+        rotation: &UnitQuaternion<f32>,
+        dt: f32,
+    ) {
+        let goal = translation + rotation * self.offset;
+        let position = ease_towards(camera.position(), &goal, self.stiffness, dt);
+        camera.look_at(&position, translation);
+    }
+}
+
+impl core::System for FollowCameraSystem {
+    fn before_render(&mut self, args: &mut core::BeforeRenderArgs) {
+        let Some((translation, rotation)) = args
+            .state
+            .entities()
+            .iter()
+            .find(|e| *e.id() == self.target)
+            .map(|e| (e.translation, e.rotation))
+        else {
+            return;
+        };
+        self.follow(
+            args.state.current_camera_mut(),
+            &translation,
+            &rotation,
+            args.elapsed.as_secs_f32(),
+        );
+    }
+}
+
+/// [current] moved towards [goal] by the fraction of the way covered in [dt] seconds.
+fn ease_towards(
+    current: &Vector3<f32>,
+    goal: &Vector3<f32>,
+    stiffness: f32,
+    dt: f32,
+) -> Vector3<f32> {
+    current + (goal - current) * (1.0 - (-stiffness * dt).exp())
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use nalgebra::{UnitQuaternion, UnitVector3, Vector3};
+    use wgpu::{
+        BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingType, BufferBindingType,
+        ShaderStages,
+    };
+
+    use crate::{
+        core::{
+            camera::{Camera, NoClipCamera, Projection},
+            prefabs::systems::follow::{FollowCameraSystem, ease_towards},
+        },
+        render::test_device,
+    };
+
+    #[test]
+    fn camera_converges_behind_target() {
+        // halfway each frame, however it's split up
+        let stiffness = 2.0_f32.ln();
+        let halfway = ease_towards(&Vector3::zeros(), &Vector3::x(), stiffness, 1.0);
+        assert!((halfway.x - 0.5).abs() < 1.0e-6);
+        let quarters = ease_towards(&Vector3::zeros(), &Vector3::x(), stiffness, 0.5);
+        let quarters = ease_towards(&quarters, &Vector3::x(), stiffness, 0.5);
+        assert!((quarters - halfway).magnitude() < 1.0e-6);
+
+        let Some((device, _queue)) = test_device() else {
+            return;
+        };
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::VERTEX,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let mut camera = NoClipCamera::new(
+            &device,
+            &layout,
+            Vector3::zeros(),
+            0.0,
+            0.0,
+            0.0,
+            Projection::new(800.0, 600.0, 90.0, 0.1, 100.0),
+        );
+        // turned a quarter ccw, so its back is towards +x
+        let translation = Vector3::new(10.0, 0.0, -5.0);
+        let rotation =
+            UnitQuaternion::from_axis_angle(&Vector3::y_axis(), std::f32::consts::FRAC_PI_2);
+        let system = FollowCameraSystem::new(0, Vector3::new(0.0, 2.0, 6.0));
+
+        for _ in 0..120 {
+            system.follow(&mut camera, &translation, &rotation, 1.0 / 60.0);
+        }
+
+        let expected = Vector3::new(16.0, 2.0, -5.0);
+        assert!((camera.position() - expected).magnitude() < 0.01);
+        let towards_target = (translation - camera.position()).normalize();
+        assert!(camera.get_center().dot(&towards_target) > 0.9999);
+        // level with the horizon
+        assert!(camera.get_right().y.abs() < 1.0e-5);
+    }
+}
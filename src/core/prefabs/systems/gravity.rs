@@ -1,38 +1,166 @@
 use nalgebra::Vector3;
 
-use crate::core::{G, System, Unique, entity::Entity};
+use crate::core::{DEFAULT_GRAVITY_SOFTENING, G, System, entity::Entity};
 
-pub struct GravitySystem;
+pub struct GravitySystem {
+    softening: f32,
+}
+
+impl Default for GravitySystem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GravitySystem {
+    pub fn new() -> Self {
+        Self {
+            softening: DEFAULT_GRAVITY_SOFTENING,
+        }
+    }
+
+    /// Uses dist^2 + softening^2 as the denominator of the gravitational pull, which keeps the
+    /// acceleration bounded when two entities get very close.
+    pub fn with_softening(softening: f32) -> Self {
+        Self { softening }
+    }
+
+    pub fn softening(&self) -> f32 {
+        self.softening
+    }
+}
 
 impl System for GravitySystem {
     fn before_tick(&mut self, args: &mut crate::core::BeforeTickArgs) {
-        // SAFETY: As long as only 1 thread has access to entities, and this function does not
-        // insert, remove, or re-allocate the backing entities Vec this operation is safe.
-        // This is to avoid performing an initial iteration to calculate new accelerations,
-        // Then doing another iteration with iter_mut to update them.
-        // If multithreading is introduced, this may have to be updated.
-        unsafe {
-            for a in args.state.entities() {
-                let a = a as *const Entity;
-                let mut accel = Vector3::zeros();
-                for b in args.state.entities() {
-                    let a = a.as_ref().unwrap();
-                    if a.id() != b.id() {
-                        let vec = b.translation - a.translation;
-                        accel += (G as f32 * b.mass / vec.magnitude().powi(2)) * vec.normalize();
-                    }
-                }
-                if accel.x.is_nan() {
-                    accel.x = 0.0;
-                }
-                if accel.y.is_nan() {
-                    accel.y = 0.0;
-                }
-                if accel.z.is_nan() {
-                    accel.z = 0.0;
+        apply_gravity(args.state.entities_mut(), self.softening);
+    }
+}
+
+/// Sets the acceleration of every entity to the sum of the gravitational pulls of all other entities,
+/// softened by [softening] (see GravitySystem::with_softening).
+pub(super) fn apply_gravity(entities: &mut [Entity], softening: f32) {
+    let bodies: Vec<(Vector3<f32>, f32)> = entities
+        .iter()
+        .map(|entity| (entity.translation, entity.mass))
+        .collect();
+    for (entity, accel) in entities
+        .iter_mut()
+        .zip(gravity_accelerations(&bodies, softening))
+    {
+        entity.acceleration = accel;
+    }
+}
+
+/// Acceleration of each of [bodies], given as (position, mass), from the gravitational pull of all the
+/// others, softened by [softening]. Components which aren't a number, i.e. of two bodies in the same
+/// place, are 0.0.
+pub(crate) fn gravity_accelerations(
+    bodies: &[(Vector3<f32>, f32)],
+    softening: f32,
+) -> Vec<Vector3<f32>> {
+    bodies
+        .iter()
+        .enumerate()
+        .map(|(i, (position, _))| {
+            let mut accel = Vector3::zeros();
+            for (j, (other, mass)) in bodies.iter().enumerate() {
+                if i != j {
+                    let vec = other - position;
+                    let denominator = vec.magnitude_squared() + softening * softening;
+                    accel += (G as f32 * mass / denominator) * vec.normalize();
                 }
-                (*a.cast_mut()).acceleration = accel;
             }
+            if accel.x.is_nan() {
+                accel.x = 0.0;
+            }
+            if accel.y.is_nan() {
+                accel.y = 0.0;
+            }
+            if accel.z.is_nan() {
+                accel.z = 0.0;
+            }
+            accel
+        })
+        .collect()
+}
+
+/// Speed needed for a circular orbit of [radius] around a body of [central_mass], with gravitational constant [g].
+pub fn orbital_velocity(central_mass: f32, radius: f32, g: f32) -> f32 {
+    (g * central_mass / radius).sqrt()
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use nalgebra::{UnitQuaternion, Vector3};
+
+    use crate::{
+        core::{
+            Completer, G,
+            entity::{BoundingBox, CollisionResponse, Entity, MaterialKind},
+            prefabs::systems::gravity::apply_gravity,
+        },
+        render::app::ObjectInitData,
+    };
+
+    #[cfg(test)]
+    fn object(translation: Vector3<f32>, mass: f32) -> ObjectInitData {
+        ObjectInitData {
+            mesh_id: Completer::from_value(0),
+            texture_id: Completer::from_value(0),
+            velocity: Vector3::zeros(),
+            acceleration: Vector3::zeros(),
+            bounding_box: BoundingBox::ZERO,
+            scale: Vector3::new(1.0, 1.0, 1.0),
+            rotation: UnitQuaternion::identity(),
+            translation,
+            response: CollisionResponse::Immovable,
+            mass,
+            friction: 0.0,
+            lifetime: None,
+            material: MaterialKind::Opaque,
+            emissive: 0.0,
         }
     }
+
+    #[test]
+    fn orbit_stays_near_radius() {
+        let center = Vector3::new(5.0, 0.0, -5.0);
+        let central_mass = 1.0e13;
+        let radius = 10.0;
+        let mut entities = vec![
+            object(center, central_mass).into_entity(0),
+            object(Vector3::zeros(), 1.0)
+                .in_orbit_around(center, central_mass, radius)
+                .into_entity(1),
+        ];
+
+        // a little over 3 orbits
+        for _ in 0..3000 {
+            apply_gravity(&mut entities, 0.0);
+            for entity in entities.iter_mut() {
+                entity.tick(0.01);
+            }
+            let distance = (entities[1].translation - entities[0].translation).magnitude();
+            assert!((distance - radius).abs() < radius * 0.05, "{}", distance);
+        }
+    }
+
+    #[test]
+    fn softening_bounds_close_encounters() {
+        let mut entities: Vec<Entity> = [0.0, 1.0e-4]
+            .into_iter()
+            .enumerate()
+            .map(|(i, x)| object(Vector3::new(x, 0.0, 0.0), 1.0e10).into_entity(i as u64))
+            .collect();
+
+        apply_gravity(&mut entities, 0.0);
+        let unsoftened = entities[0].acceleration.magnitude();
+        apply_gravity(&mut entities, 0.5);
+        let softened = entities[0].acceleration.magnitude();
+
+        assert!(unsoftened > 1.0e6, "{}", unsoftened);
+        assert!(softened.is_finite());
+        // at most G * m / softening^2
+        assert!(softened <= G as f32 * 1.0e10 / 0.25, "{}", softened);
+    }
 }
@@ -0,0 +1,89 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+use nalgebra::Vector3;
+use serde_json::Value;
+
+use crate::core;
+
+/// Shows the camera's position and speed along with the FPS reported by MetricsSystem.
+///
+/// The readout is written to the "hud" entry of the renderer's gui data every frame.
+pub struct HudSystem {
+    last_position: Option<Vector3<f32>>,
+
+    gui_data: Option<Arc<RwLock<HashMap<String, Value>>>>,
+}
+
+impl Default for HudSystem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HudSystem {
+    pub fn new() -> Self {
+        Self {
+            last_position: None,
+            gui_data: None,
+        }
+    }
+}
+
+impl core::System for HudSystem {
+    fn before_start(&mut self, args: &mut core::BeforeStartArgs) {
+        self.gui_data = Some(args.renderer.gui_data());
+    }
+
+    fn before_render(&mut self, args: &mut core::BeforeRenderArgs) {
+        let position = *args.state.current_camera().position();
+        let dt = args.elapsed.as_secs_f32();
+        let speed = match self.last_position {
+            Some(last) if dt > 0.0 => (position - last).magnitude() / dt,
+            _ => 0.0,
+        };
+        self.last_position = Some(position);
+
+        if let Some(gui_data) = &self.gui_data
+            && let Ok(mut gui_data) = gui_data.write()
+        {
+            let fps = gui_data.get("fps").and_then(|fps| fps.as_f64());
+            gui_data.insert(
+                "hud".into(),
+                Value::String(format_hud(&position, speed, fps)),
+            );
+        }
+    }
+}
+
+fn format_hud(position: &Vector3<f32>, speed: f32, fps: Option<f64>) -> String {
+    let fps = match fps {
+        Some(fps) => format!("{:.1}", fps),
+        None => "-".into(),
+    };
+    format!(
+        "Position: ({:.2}, {:.2}, {:.2})\nSpeed: {:.2}\nFPS: {}",
+        position.x, position.y, position.z, speed, fps
+    )
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use nalgebra::Vector3;
+
+    use crate::core::prefabs::systems::hud::format_hud;
+
+    #[test]
+    fn hud_readout() {
+        assert_eq!(
+            format_hud(&Vector3::new(1.0, -2.5, 3.333), 20.0, Some(59.94)),
+            "Position: (1.00, -2.50, 3.33)\nSpeed: 20.00\nFPS: 59.9"
+        );
+        assert_eq!(
+            format_hud(&Vector3::zeros(), 0.0, None),
+            "Position: (0.00, 0.00, 0.00)\nSpeed: 0.00\nFPS: -"
+        );
+    }
+}
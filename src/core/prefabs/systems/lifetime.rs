@@ -0,0 +1,84 @@
+use crate::core::{self, Unique, entity::Entity};
+
+/// Counts down entity lifetimes and despawns entities whose lifetime ran out.
+pub struct LifetimeSystem;
+
+impl core::System for LifetimeSystem {
+    fn handle_tick(&mut self, args: &mut core::HandleTickArgs) {
+        let dt = args.elapsed.as_secs_f32();
+        for id in count_down(args.state.entities_mut(), dt) {
+            args.state.remove_entity(id);
+        }
+    }
+}
+
+/// Decrements every lifetime by [dt] and returns the ids of entities which expired.
+pub(super) fn count_down(entities: &mut [Entity], dt: f32) -> Vec<u64> {
+    let mut expired = vec![];
+    for entity in entities {
+        if let Some(lifetime) = &mut entity.lifetime {
+            *lifetime -= dt;
+            if *lifetime <= 0.0 {
+                expired.push(*entity.id());
+            }
+        }
+    }
+    expired
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use std::time::Duration;
+
+    use nalgebra::{UnitQuaternion, Vector3};
+
+    use crate::{
+        core::{
+            Completer, Unique,
+            entity::{BoundingBox, CollisionResponse, MaterialKind},
+            prefabs::DefaultSystem,
+        },
+        render::app::{App, ObjectInitData},
+    };
+
+    #[test]
+    fn expired_entities_are_removed() {
+        let mut app = App::new_headless(0, &[DefaultSystem::Lifetime]);
+        let ids: Vec<u64> = [Some(1.0), None]
+            .into_iter()
+            .map(|lifetime| {
+                app.add_object(ObjectInitData {
+                    mesh_id: Completer::from_value(0),
+                    texture_id: Completer::from_value(0),
+                    velocity: Vector3::zeros(),
+                    acceleration: Vector3::zeros(),
+                    bounding_box: BoundingBox::ZERO,
+                    scale: Vector3::new(1.0, 1.0, 1.0),
+                    rotation: UnitQuaternion::identity(),
+                    translation: Vector3::zeros(),
+                    response: CollisionResponse::Immovable,
+                    mass: 1.0,
+                    friction: 0.0,
+                    lifetime,
+                    material: MaterialKind::Opaque,
+                    emissive: 0.0,
+                })
+                .consume()
+                .unwrap()
+            })
+            .collect();
+
+        for tick in 0..20 {
+            app.step(Duration::from_secs_f32(0.1));
+            if tick < 9 {
+                assert_eq!(app.state().unwrap().entities().len(), 2);
+            }
+        }
+
+        let state = app.state_mut().unwrap();
+        assert_eq!(state.entities().len(), 1);
+        assert_eq!(*state.entities()[0].id(), ids[1]);
+        // and the renderer is told to drop its instance
+        assert_eq!(state.take_removed(), vec![(0, ids[0])]);
+    }
+}
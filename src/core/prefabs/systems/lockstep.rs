@@ -0,0 +1,157 @@
+use nalgebra::Vector3;
+
+use crate::core::{
+    DEFAULT_GRAVITY_SOFTENING, Unique,
+    entity::Entity,
+    prefabs::systems::{gravity::apply_gravity, lifetime::count_down},
+};
+
+/// An input applied to a single entity for one lockstep step.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LockstepInput {
+    pub entity_id: u64,
+    /// Added to the entity's acceleration after gravity is applied.
+    pub thrust: Vector3<f32>,
+}
+
+/// A simulation which only advances on explicit LockstepSimulation::step calls, using a fixed dt.
+///
+/// Entities and inputs are always processed in id order and nothing in a step depends on randomness
+/// or hash map iteration order, so two simulations started from the same entities and fed the same
+/// inputs end up in bit-identical states.
+pub struct LockstepSimulation {
+    entities: Vec<Entity>,
+    dt: f32,
+    steps: u64,
+}
+
+impl LockstepSimulation {
+    pub fn new(mut entities: Vec<Entity>, dt: f32) -> Self {
+        entities.sort_by_key(|e| *e.id());
+        Self {
+            entities,
+            dt,
+            steps: 0,
+        }
+    }
+
+    /// Gravity, then inputs, then integration, then lifetimes.
+    pub fn step(&mut self, inputs: &[LockstepInput]) {
+        let mut inputs: Vec<&LockstepInput> = inputs.iter().collect();
+        // stable, so inputs for the same entity keep their order
+        inputs.sort_by_key(|i| i.entity_id);
+
+        apply_gravity(&mut self.entities, DEFAULT_GRAVITY_SOFTENING);
+        for input in inputs {
+            if let Ok(i) = self
+                .entities
+                .binary_search_by_key(&input.entity_id, |e| *e.id())
+            {
+                self.entities[i].acceleration += input.thrust;
+            }
+        }
+        for entity in self.entities.iter_mut() {
+            entity.tick(self.dt);
+        }
+        for id in count_down(&mut self.entities, self.dt) {
+            self.entities.retain(|e| *e.id() != id);
+        }
+
+        self.steps += 1;
+    }
+
+    pub fn add_entity(&mut self, entity: Entity) {
+        let i = self.entities.partition_point(|e| e.id() < entity.id());
+        self.entities.insert(i, entity);
+    }
+
+    /// Entities, sorted by id.
+    pub fn entities(&self) -> &[Entity] {
+        &self.entities
+    }
+
+    pub fn dt(&self) -> f32 {
+        self.dt
+    }
+
+    /// Number of steps taken so far.
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use nalgebra::{UnitQuaternion, Vector3};
+
+    use crate::core::{
+        Unique,
+        entity::{BoundingBox, CollisionResponse, Entity, EntityType},
+        prefabs::systems::lockstep::{LockstepInput, LockstepSimulation},
+    };
+
+    #[test]
+    fn identical_inputs_give_identical_states() {
+        let simulation = || {
+            // deliberately out of id order
+            let entities = [(2, 5.0e9), (0, 1.0e10), (1, 2.0e8)]
+                .into_iter()
+                .map(|(id, mass)| {
+                    let mut entity = Entity::new(
+                        id,
+                        0,
+                        0,
+                        Vector3::new(1.0, 1.0, 1.0),
+                        UnitQuaternion::identity(),
+                        Vector3::new(id as f32 * 10.0, 0.0, id as f32 * -3.0),
+                        Vector3::new(0.0, 0.1 * id as f32, 0.0),
+                        Vector3::zeros(),
+                        BoundingBox::ZERO,
+                        EntityType::Object,
+                        CollisionResponse::Inelastic(1.0),
+                        mass,
+                    );
+                    entity.lifetime = if id == 1 { Some(2.5) } else { None };
+                    entity
+                })
+                .collect();
+            LockstepSimulation::new(entities, 1.0 / 60.0)
+        };
+        let inputs = |step: u64| {
+            vec![
+                LockstepInput {
+                    entity_id: 2,
+                    thrust: Vector3::new(0.0, (step % 7) as f32, 0.0),
+                },
+                LockstepInput {
+                    entity_id: 0,
+                    thrust: Vector3::new(-0.5, 0.0, (step % 3) as f32),
+                },
+            ]
+        };
+
+        let mut a = simulation();
+        let mut b = simulation();
+        for step in 0..300 {
+            a.step(&inputs(step));
+            b.step(&inputs(step));
+        }
+
+        assert_eq!(a.steps(), 300);
+        // the entity with a lifetime has expired in both
+        assert_eq!(a.entities().len(), 2);
+        assert_eq!(a.entities().len(), b.entities().len());
+        for (a, b) in a.entities().iter().zip(b.entities()) {
+            assert_eq!(a.id(), b.id());
+            assert_eq!(
+                a.translation.map(f32::to_bits),
+                b.translation.map(f32::to_bits)
+            );
+            assert_eq!(a.velocity.map(f32::to_bits), b.velocity.map(f32::to_bits));
+            assert_eq!(
+                a.acceleration.map(f32::to_bits),
+                b.acceleration.map(f32::to_bits)
+            );
+        }
+    }
+}
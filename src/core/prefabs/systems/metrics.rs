@@ -1,4 +1,5 @@
 use std::{
+    cmp::Reverse,
     collections::HashMap,
     sync::{Arc, RwLock},
     time::{Duration, Instant},
@@ -127,6 +128,78 @@ impl core::System for MetricsSystem {
     }
 }
 
+/// Time spent in each system's per-frame hooks. Recorded by App once enabled with App::enable_system_timings.
+#[derive(Debug)]
+pub struct SystemTimings {
+    totals: HashMap<String, Duration>,
+    frames: u32,
+    // Instant::now unless replaced, see SystemTimings::with_clock
+    clock: fn() -> Instant,
+}
+
+impl Default for SystemTimings {
+    fn default() -> Self {
+        Self::with_clock(Instant::now)
+    }
+}
+
+impl SystemTimings {
+    /// Times hooks with [clock] instead of Instant::now, i.e. a fake clock in tests.
+    pub fn with_clock(clock: fn() -> Instant) -> Self {
+        Self {
+            totals: HashMap::new(),
+            frames: 0,
+            clock,
+        }
+    }
+
+    pub fn record(&mut self, system_name: &str, elapsed: Duration) {
+        match self.totals.get_mut(system_name) {
+            Some(total) => *total += elapsed,
+            None => {
+                self.totals.insert(system_name.to_owned(), elapsed);
+            }
+        }
+    }
+
+    /// Runs [hook] on [system] and records how long it took.
+    pub fn time(
+        &mut self,
+        system: &mut dyn core::System,
+        hook: impl FnOnce(&mut dyn core::System),
+    ) {
+        let start = (self.clock)();
+        hook(&mut *system);
+        self.record(system.name(), (self.clock)() - start);
+    }
+
+    pub fn end_frame(&mut self) {
+        self.frames += 1;
+    }
+
+    /// Time spent in the hooks of [system_name] since the timings were enabled or reset.
+    pub fn total(&self, system_name: &str) -> Option<Duration> {
+        self.totals.get(system_name).copied()
+    }
+
+    /// Average time per frame of each system, slowest first.
+    pub fn breakdown(&self) -> Vec<(&str, Duration)> {
+        let frames = self.frames.max(1);
+        let mut breakdown: Vec<(&str, Duration)> = self
+            .totals
+            .iter()
+            .map(|(name, total)| (name.as_str(), *total / frames))
+            .collect();
+        breakdown.sort_by_key(|(_, average)| Reverse(*average));
+        breakdown
+    }
+
+    pub fn reset(&mut self) {
+        self.totals.clear();
+        self.frames = 0;
+    }
+}
+
 fn has_nan<const R: usize, const C: usize>(
     data: &nalgebra::Matrix<f32, Const<R>, Const<C>, ArrayStorage<f32, R, C>>,
 ) -> bool {
@@ -138,3 +211,53 @@ fn has_nan<const R: usize, const C: usize>(
 
     return false;
 }
+
+#[allow(unused_imports)]
+mod tests {
+    use std::{
+        cell::Cell,
+        time::{Duration, Instant},
+    };
+
+    use crate::core::{
+        DisposeArgs, System,
+        prefabs::systems::{MetricsSystem, SystemTimings},
+    };
+
+    thread_local! {
+        static START: Instant = Instant::now();
+        static ELAPSED: Cell<Duration> = const { Cell::new(Duration::ZERO) };
+    }
+
+    /// Only moves when a test advances it.
+    #[cfg(test)]
+    fn fake_now() -> Instant {
+        START.with(|start| *start + ELAPSED.get())
+    }
+
+    #[test]
+    fn slow_system_timed() {
+        struct SlowSystem;
+        impl System for SlowSystem {
+            fn name(&self) -> &str {
+                "slow"
+            }
+            fn dispose(&mut self, _args: &mut DisposeArgs) {
+                ELAPSED.set(ELAPSED.get() + Duration::from_millis(20));
+            }
+        }
+        let mut system = SlowSystem;
+        let mut timings = SystemTimings::with_clock(fake_now);
+
+        for _ in 0..2 {
+            timings.time(&mut system, |s| s.dispose(&mut DisposeArgs {}));
+            timings.record("fast", Duration::ZERO);
+            timings.end_frame();
+        }
+
+        assert_eq!(timings.total("slow"), Some(Duration::from_millis(40)));
+        let breakdown = timings.breakdown();
+        assert_eq!(breakdown[0], ("slow", Duration::from_millis(20)));
+        assert_eq!(breakdown[1], ("fast", Duration::ZERO));
+    }
+}
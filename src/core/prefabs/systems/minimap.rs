@@ -0,0 +1,198 @@
+use std::collections::HashSet;
+
+use nalgebra::{Matrix4, Point3, Vector2, Vector3, Vector4};
+
+use crate::{
+    core::{self, Completer, Unique},
+    render::vertex::ui::UiElement,
+};
+
+/// Orthographic camera looking straight down at the XZ plane, with -z at the top of the image.
+#[derive(Debug, Clone)]
+pub struct TopDownCamera {
+    /// World x and z at the middle of the image.
+    pub center: Vector2<f32>,
+    /// World units from the middle to each edge of the image.
+    pub extent: f32,
+    /// Height the camera looks down from. Anything above it or below -height isn't visible.
+    pub height: f32,
+}
+
+impl TopDownCamera {
+    pub fn view_proj(&self) -> Matrix4<f32> {
+        let eye = Point3::new(self.center.x, self.height, self.center.y);
+        let view = Matrix4::look_at_rh(
+            &eye,
+            &Point3::new(self.center.x, 0.0, self.center.y),
+            &-Vector3::z(),
+        );
+        // wgpu clips depths outside of 0 to 1, which this maps from the eye down to 2 * height below it
+        let projection = Matrix4::new_orthographic(
+            -self.extent,
+            self.extent,
+            -self.extent,
+            self.extent,
+            -2.0 * self.height,
+            2.0 * self.height,
+        );
+        projection * view
+    }
+
+    /// Position of [world] on the image, with (0, 0) at the top left and (1, 1) at the bottom right.
+    /// None if it's outside of the image.
+    pub fn uv(&self, world: &Vector3<f32>) -> Option<Vector2<f32>> {
+        let clip = self.view_proj() * Vector4::new(world.x, world.y, world.z, 1.0);
+        let uv = Vector2::new(clip.x * 0.5 + 0.5, 0.5 - clip.y * 0.5);
+        if uv.iter().all(|c| (0.0..=1.0).contains(c)) {
+            Some(uv)
+        } else {
+            None
+        }
+    }
+}
+
+/// Scene rendered from a TopDownCamera into a square texture, which the renderer shows in the UI pass
+/// under the UI elements. See ActiveState::set_minimap.
+#[derive(Debug, Clone)]
+pub struct MinimapView {
+    pub camera: TopDownCamera,
+    /// Top left corner of the minimap on the window, in pixels.
+    pub offset: Vector2<f32>,
+    /// Side length of the minimap on the window, in pixels. Also the resolution of its texture.
+    pub size: f32,
+}
+
+/// Shows a minimap of the scene centered on the current camera, with a dot drawn over it for every entity.
+///
+/// Dots are UiElements of [dot_mesh] with the id of their entity, so other UiElements of that mesh shouldn't
+/// use entity ids.
+pub struct MinimapSystem {
+    view: MinimapView,
+    dot_mesh: Completer<u64>,
+
+    // Entities with a visible dot
+    shown: HashSet<u64>,
+}
+
+impl MinimapSystem {
+    /// Shows [extent] world units around the camera in each direction.
+    pub fn new(dot_mesh: Completer<u64>, offset: Vector2<f32>, size: f32, extent: f32) -> Self {
+        Self {
+            view: MinimapView {
+                camera: TopDownCamera {
+                    center: Vector2::zeros(),
+                    extent,
+                    height: 1.0e4,
+                },
+                offset,
+                size,
+            },
+            dot_mesh,
+            shown: HashSet::new(),
+        }
+    }
+
+    pub fn camera(&self) -> &TopDownCamera {
+        &self.view.camera
+    }
+}
+
+impl core::System for MinimapSystem {
+    fn before_render(&mut self, args: &mut core::BeforeRenderArgs) {
+        let Ok(dot_mesh) = self.dot_mesh.clone().consume() else {
+            return;
+        };
+        let position = args.state.current_camera().position();
+        self.view.camera.center = Vector2::new(position.x, position.z);
+        args.state.set_minimap(Some(self.view.clone()));
+
+        let dots: Vec<(u64, Option<Vector2<f32>>)> = args
+            .state
+            .entities()
+            .iter()
+            .map(|e| (*e.id(), self.view.camera.uv(&e.translation)))
+            .collect();
+        let visible: HashSet<u64> = dots
+            .iter()
+            .filter(|(_, uv)| uv.is_some())
+            .map(|(id, _)| *id)
+            .collect();
+
+        let elements = args.state.ui_elements_mut();
+        elements.retain(|e| e.mesh_id != dot_mesh);
+        for (id, uv) in dots {
+            if let Some(uv) = uv {
+                elements.push(UiElement {
+                    id,
+                    mesh_id: dot_mesh,
+                    offset: self.view.offset + uv * self.view.size,
+                    scale: Vector2::new(1.0, 1.0),
+                });
+            }
+        }
+        // UI instances stay in the renderer, so dots which left the map are hidden instead
+        for id in self.shown.difference(&visible) {
+            elements.push(UiElement {
+                id: *id,
+                mesh_id: dot_mesh,
+                offset: Vector2::zeros(),
+                scale: Vector2::zeros(),
+            });
+        }
+        self.shown = visible;
+    }
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use nalgebra::{Vector2, Vector3};
+
+    use crate::core::{
+        geometry::{BoundingSphere, Frustum},
+        prefabs::systems::minimap::TopDownCamera,
+    };
+
+    #[test]
+    fn world_xz_maps_to_minimap_uv() {
+        let camera = TopDownCamera {
+            center: Vector2::new(10.0, -20.0),
+            extent: 50.0,
+            height: 1000.0,
+        };
+        let uv = |x, y, z| camera.uv(&Vector3::new(x, y, z));
+
+        let center = uv(10.0, 0.0, -20.0).unwrap();
+        assert!((center - Vector2::new(0.5, 0.5)).magnitude() < 1.0e-5);
+
+        // +x to the right, -z towards the top, height doesn't matter
+        let corner = uv(35.0, 300.0, -45.0).unwrap();
+        assert!((corner - Vector2::new(0.75, 0.25)).magnitude() < 1.0e-5);
+        let corner = uv(-15.0, -5.0, 5.0).unwrap();
+        assert!((corner - Vector2::new(0.25, 0.75)).magnitude() < 1.0e-5);
+
+        assert_eq!(uv(61.0, 0.0, -20.0), None);
+    }
+
+    #[test]
+    fn top_down_depth_range_around_ground() {
+        let camera = TopDownCamera {
+            center: Vector2::zeros(),
+            extent: 50.0,
+            height: 100.0,
+        };
+        let frustum = Frustum::from_view_proj(&camera.view_proj());
+        let visible = |y| {
+            frustum.contains_sphere(&BoundingSphere {
+                center: Vector3::new(10.0, y, 10.0),
+                radius: 0.5,
+            })
+        };
+
+        // above and below the ground, as wgpu clips depth
+        assert!(visible(0.0));
+        assert!(visible(90.0));
+        assert!(visible(-90.0));
+        assert!(!visible(110.0));
+        assert!(!visible(-110.0));
+    }
+}
@@ -3,7 +3,10 @@ use std::{f32::consts::PI, time::Instant};
 use nalgebra::{UnitQuaternion, Vector3};
 
 use crate::{
-    core::{Completer, System, entity::BoundingBox},
+    core::{
+        Completer, System,
+        entity::{BoundingBox, MaterialKind},
+    },
     render::app::ObjectInitData,
 };
 
@@ -49,6 +52,10 @@ impl System for EntitySpawnerSystem {
                 ),
                 response: crate::core::entity::CollisionResponse::Inelastic(1.0),
                 mass: 5.0e8,
+                friction: 0.0,
+                lifetime: None,
+                material: MaterialKind::Opaque,
+                emissive: 0.0,
             });
             self.last = Instant::now()
         }
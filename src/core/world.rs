@@ -1,2 +1 @@
-pub mod planets;
 pub mod terrain;
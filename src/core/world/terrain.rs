@@ -1,44 +1,433 @@
-use std::collections::{HashMap, hash_map::Entry};
+use std::collections::{HashMap, HashSet};
 
 use bytemuck::{Pod, Zeroable};
+use nalgebra::Vector3;
+use wgpu::FrontFace;
 
-use crate::core::CHUNK_RESOLUTION;
+use crate::{
+    core::{
+        CHUNK_RESOLUTION, CHUNK_SIZE, DEFAULT_GRAVITY_SOFTENING, SPLAT_HEIGHT_BLEND,
+        SPLAT_ROCK_SLOPE, SPLAT_SAND_HEIGHT, SPLAT_SNOW_HEIGHT, TERRAIN_AMPLITUDE,
+        TERRAIN_FREQUENCY, TERRAIN_OCTAVES, prefabs::gravity_accelerations,
+    },
+    render::{GlobalIndexType, app::MeshInitData, vertex::TerrainVertexType},
+};
 
-#[repr(C)]
-#[derive(Debug, Pod, Zeroable, Clone, Copy)]
+/// Height samples per chunk side, for rendering and for collisions. Edge samples are shared with the
+/// neighboring chunk, so there are at least 2, see TerrainConfig::clamped.
+#[derive(Debug, Clone, Copy)]
+pub struct TerrainConfig {
+    /// Samples of the chunk meshes.
+    pub render_resolution: usize,
+    /// Samples used for height queries (i.e. collisions), usually coarser than the meshes.
+    pub collision_resolution: usize,
+}
+
+impl Default for TerrainConfig {
+    fn default() -> Self {
+        Self {
+            render_resolution: CHUNK_RESOLUTION,
+            collision_resolution: CHUNK_RESOLUTION,
+        }
+    }
+}
+
+impl TerrainConfig {
+    /// This config with each resolution raised to at least 2, one sample on either edge of a chunk.
+    pub fn clamped(self) -> Self {
+        Self {
+            render_resolution: self.render_resolution.max(2),
+            collision_resolution: self.collision_resolution.max(2),
+        }
+    }
+}
+
+/// Textures blended over the terrain, in the order of the layers given to Renderer::set_terrain_textures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SplatLayer {
+    Grass,
+    /// Steep slopes at any height.
+    Rock,
+    /// Low ground, see SPLAT_SAND_HEIGHT.
+    Sand,
+    /// High ground, see SPLAT_SNOW_HEIGHT.
+    Snow,
+}
+
+impl SplatLayer {
+    pub const COUNT: usize = 4;
+}
+
+/// Height of the ground at a world x and z.
+trait HeightSource: std::fmt::Debug {
+    fn height(&self, x: f32, z: f32) -> f32;
+}
+
+impl HeightSource for fn(f32, f32) -> f32 {
+    fn height(&self, x: f32, z: f32) -> f32 {
+        self(x, z)
+    }
+}
+
+/// Rolling hills from octaves of value noise. Heights only depend on the seed and the world position, so
+/// chunks can be loaded in any order and still meet seamlessly.
+#[derive(Debug, Clone, Copy)]
+pub struct NoiseTerrain {
+    pub seed: u64,
+    /// Features per unit of distance of the first octave.
+    pub frequency: f32,
+    /// Furthest the terrain gets from 0.0.
+    pub amplitude: f32,
+    /// Each octave adds detail at twice the frequency and half the amplitude of the last.
+    pub octaves: u32,
+}
+
+impl NoiseTerrain {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            frequency: TERRAIN_FREQUENCY,
+            amplitude: TERRAIN_AMPLITUDE,
+            octaves: TERRAIN_OCTAVES,
+        }
+    }
+}
+
+impl HeightSource for NoiseTerrain {
+    fn height(&self, x: f32, z: f32) -> f32 {
+        let (mut total, mut weight, mut weights) = (0.0, 1.0, 0.0);
+        let mut frequency = self.frequency;
+        for octave in 0..self.octaves {
+            let seed = self.seed.wrapping_add(octave as u64);
+            total += weight * value_noise(seed, x * frequency, z * frequency);
+            weights += weight;
+            weight *= 0.5;
+            frequency *= 2.0;
+        }
+        if weights == 0.0 {
+            return 0.0;
+        }
+        self.amplitude * total / weights
+    }
+}
+
+/// Smooth noise between -1.0 and 1.0, interpolating random values at integer coordinates.
+fn value_noise(seed: u64, x: f32, z: f32) -> f32 {
+    let (x0, z0) = (x.floor(), z.floor());
+    let (ix, iz) = (x0 as i64, z0 as i64);
+    let corners = [
+        [lattice_value(seed, ix, iz), lattice_value(seed, ix, iz + 1)],
+        [
+            lattice_value(seed, ix + 1, iz),
+            lattice_value(seed, ix + 1, iz + 1),
+        ],
+    ];
+    bilinear(corners, fade(x - x0), fade(z - z0))
+}
+
+/// Random value between -1.0 and 1.0 for a lattice point, always the same for the same seed.
+fn lattice_value(seed: u64, x: i64, z: i64) -> f32 {
+    // splitmix64 finalizer over the combined inputs
+    let mut h = seed
+        ^ (x as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+        ^ (z as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F);
+    h = (h ^ (h >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    h = (h ^ (h >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    h ^= h >> 31;
+    (h >> 40) as f32 / (1u64 << 23) as f32 - 1.0
+}
+
+/// Eases [t] from 0.0 to 1.0 with zero first and second derivatives at both ends, so octaves don't show
+/// creases along the lattice.
+fn fade(t: f32) -> f32 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+/// Square grid of heights covering a chunk.
+#[derive(Debug)]
+struct HeightGrid {
+    resolution: usize,
+    heights: Vec<f32>,
+}
+
+impl HeightGrid {
+    /// Samples [source] over chunk [key].
+    fn sample(resolution: usize, key: (i64, i64), source: &dyn HeightSource) -> Self {
+        let mut heights = Vec::with_capacity(resolution * resolution);
+        for i in 0..resolution {
+            for j in 0..resolution {
+                heights.push(source.height(
+                    sample_position(key.0, i, resolution),
+                    sample_position(key.1, j, resolution),
+                ));
+            }
+        }
+        Self {
+            resolution,
+            heights,
+        }
+    }
+
+    fn get(&self, i: usize, j: usize) -> f32 {
+        self.heights[i * self.resolution + j]
+    }
+}
+
+/// Interpolates the heights at the four corners of a grid cell, indexed [x][z], at fractions [fu] along x
+/// and [fv] along z of the cell.
+fn bilinear(corners: [[f32; 2]; 2], fu: f32, fv: f32) -> f32 {
+    let near = corners[0][0] * (1.0 - fv) + corners[0][1] * fv;
+    let far = corners[1][0] * (1.0 - fv) + corners[1][1] * fv;
+    near * (1.0 - fu) + far * fu
+}
+
+/// Distance between neighboring samples of a grid with [resolution] samples per side.
+fn sample_spacing(resolution: usize) -> f32 {
+    CHUNK_SIZE / (resolution - 1) as f32
+}
+
+/// Key of the chunk covering world [x] and [z].
+fn chunk_key(x: f32, z: f32) -> (i64, i64) {
+    (
+        (x / CHUNK_SIZE).floor() as i64,
+        (z / CHUNK_SIZE).floor() as i64,
+    )
+}
+
+/// World coordinate of sample [i] along one axis of chunk [chunk]. Counted from the world's origin so edge
+/// samples shared by two chunks land on exactly the same position.
+fn sample_position(chunk: i64, i: usize, resolution: usize) -> f32 {
+    (chunk * (resolution as i64 - 1) + i as i64) as f32 * sample_spacing(resolution)
+}
+
+#[derive(Debug)]
 struct Chunk {
-    latitude: f32,
-    longitude: f32,
-    heights: [[f32; CHUNK_RESOLUTION]; CHUNK_RESOLUTION],
+    /// Sampled at TerrainConfig::render_resolution
+    heights: HeightGrid,
+    /// Sampled at TerrainConfig::collision_resolution
+    collision_heights: HeightGrid,
 }
 
 /// Belongs to a LargeBody.
 #[derive(Debug)]
 struct Terrain {
     chunks_loaded: HashMap<(i64, i64), Chunk>, // TODO: Implement as quadtree
-    chunk_loader: fn(i64, i64) -> Chunk,
+    heights: Box<dyn HeightSource>,
+    config: TerrainConfig,
+}
+
+impl Terrain {
+    fn load_chunk(&self, key: (i64, i64)) -> Chunk {
+        Chunk {
+            heights: HeightGrid::sample(self.config.render_resolution, key, self.heights.as_ref()),
+            collision_heights: HeightGrid::sample(
+                self.config.collision_resolution,
+                key,
+                self.heights.as_ref(),
+            ),
+        }
+    }
+
+    /// Height of render sample (i, j) of chunk [key]. Samples outside of the grid are read from the
+    /// neighboring chunk. None if that chunk isn't loaded.
+    fn height(&self, key: (i64, i64), i: i64, j: i64) -> Option<f32> {
+        // (chunk offset, index in that chunk) along one axis
+        let res = self.config.render_resolution as i64;
+        let last = res - 1;
+        let wrap = |n: i64| {
+            if (0..res).contains(&n) {
+                (0, n)
+            } else {
+                (n.div_euclid(last), n.rem_euclid(last))
+            }
+        };
+        let ((di, i), (dj, j)) = (wrap(i), wrap(j));
+        let chunk = self.chunks_loaded.get(&(key.0 + di, key.1 + dj))?;
+        Some(chunk.heights.get(i as usize, j as usize))
+    }
+
+    /// Height of the ground at world [x] and [z], interpolated from the collision grid. None if the
+    /// chunk isn't loaded.
+    fn height_at(&self, x: f32, z: f32) -> Option<f32> {
+        let key = chunk_key(x, z);
+        let grid = &self.chunks_loaded.get(&key)?.collision_heights;
+        let spacing = sample_spacing(grid.resolution);
+        let u = (x - key.0 as f32 * CHUNK_SIZE) / spacing;
+        let v = (z - key.1 as f32 * CHUNK_SIZE) / spacing;
+        let last = grid.resolution - 1;
+        let (i, j) = ((u as usize).min(last - 1), (v as usize).min(last - 1));
+        let corners = [
+            [grid.get(i, j), grid.get(i, j + 1)],
+            [grid.get(i + 1, j), grid.get(i + 1, j + 1)],
+        ];
+        Some(bilinear(corners, u - i as f32, v - j as f32))
+    }
+
+    /// Grid mesh of a loaded chunk at the render resolution, with normals from central differences of
+    /// neighboring heights and texture weights from each vertex's height and slope. Samples on the chunk's edges use the neighboring chunks if they're loaded.
+    fn chunk_mesh(&self, key: (i64, i64)) -> Option<MeshInitData<TerrainVertexType>> {
+        let chunk = self.chunks_loaded.get(&key)?;
+        let resolution = self.config.render_resolution;
+        let res = resolution as i64;
+        let spacing = sample_spacing(resolution);
+
+        let mut vertices = Vec::with_capacity(resolution * resolution);
+        for i in 0..res {
+            for j in 0..res {
+                let height = chunk.heights.get(i as usize, j as usize);
+                let dh_dx = central_difference(
+                    self.height(key, i - 1, j),
+                    height,
+                    self.height(key, i + 1, j),
+                    spacing,
+                );
+                let dh_dz = central_difference(
+                    self.height(key, i, j - 1),
+                    height,
+                    self.height(key, i, j + 1),
+                    spacing,
+                );
+                let normal = surface_normal(dh_dx, dh_dz);
+                vertices.push(TerrainVertexType {
+                    position: [
+                        sample_position(key.0, i as usize, resolution),
+                        height,
+                        sample_position(key.1, j as usize, resolution),
+                    ],
+                    normal: normal.into(),
+                    tex_coords: [i as f32 / (res - 1) as f32, j as f32 / (res - 1) as f32],
+                    splat: splat_weights(height, &normal),
+                });
+            }
+        }
+
+        let mut indices = Vec::with_capacity((resolution - 1).pow(2) * 6);
+        let index = |i: usize, j: usize| (i * resolution + j) as GlobalIndexType;
+        for i in 0..resolution - 1 {
+            for j in 0..resolution - 1 {
+                // counter-clockwise seen from above
+                indices.extend([index(i, j), index(i, j + 1), index(i + 1, j)]);
+                indices.extend([index(i + 1, j), index(i, j + 1), index(i + 1, j + 1)]);
+            }
+        }
+
+        Some(MeshInitData {
+            vertices,
+            indices,
+            // counter-clockwise seen from above
+            front_face: FrontFace::Ccw,
+        })
+    }
+}
+
+/// Slope at a sample given its neighbors [spacing] away on either side, one-sided if a neighbor is missing.
+fn central_difference(before: Option<f32>, at: f32, after: Option<f32>, spacing: f32) -> f32 {
+    match (before, after) {
+        (Some(before), Some(after)) => (after - before) / (2.0 * spacing),
+        (Some(before), None) => (at - before) / spacing,
+        (None, Some(after)) => (after - at) / spacing,
+        (None, None) => 0.0,
+    }
+}
+
+/// Unit normal of a height field with the given slopes along x and z.
+fn surface_normal(dh_dx: f32, dh_dz: f32) -> Vector3<f32> {
+    Vector3::new(-dh_dx, 1.0, -dh_dz).normalize()
+}
+
+/// Weight of each SplatLayer at a point of the terrain with [height] and unit [normal]. Steep slopes are
+/// rock, the rest is sand, grass or snow by height, blending between them near the thresholds.
+fn splat_weights(height: f32, normal: &Vector3<f32>) -> [f32; SplatLayer::COUNT] {
+    let rock = smoothstep(SPLAT_ROCK_SLOPE.0, SPLAT_ROCK_SLOPE.1, 1.0 - normal.y);
+    let sand = 1.0
+        - smoothstep(
+            SPLAT_SAND_HEIGHT - SPLAT_HEIGHT_BLEND,
+            SPLAT_SAND_HEIGHT + SPLAT_HEIGHT_BLEND,
+            height,
+        );
+    let snow = smoothstep(
+        SPLAT_SNOW_HEIGHT - SPLAT_HEIGHT_BLEND,
+        SPLAT_SNOW_HEIGHT + SPLAT_HEIGHT_BLEND,
+        height,
+    );
+    let grass = (1.0 - sand - snow).max(0.0);
+    let ground = 1.0 - rock;
+    let mut weights = [0.0; SplatLayer::COUNT];
+    weights[SplatLayer::Grass as usize] = grass * ground;
+    weights[SplatLayer::Rock as usize] = rock;
+    weights[SplatLayer::Sand as usize] = sand * ground;
+    weights[SplatLayer::Snow as usize] = snow * ground;
+    weights
+}
+
+/// 0.0 below [edge0], 1.0 above [edge1], easing between them.
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// A large body of the world, like a planet or a star, pulled by the gravity of every other body in it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Body {
+    pub radius: f32,
+    pub mass: f32,
+    pub position: Vector3<f32>,
+    pub velocity: Vector3<f32>,
+    /// Set from the other bodies' pull on every World::update.
+    pub acceleration: Vector3<f32>,
+}
+
+impl Body {
+    /// A body at rest at [position].
+    pub fn new(radius: f32, mass: f32, position: Vector3<f32>) -> Self {
+        Self {
+            radius,
+            mass,
+            position,
+            velocity: Vector3::zeros(),
+            acceleration: Vector3::zeros(),
+        }
+    }
+
+    pub fn with_velocity(mut self, velocity: Vector3<f32>) -> Self {
+        self.velocity = velocity;
+        self
+    }
 }
 
 /// In this world, the sun and moon orbit this infinite world
 pub struct World {
     terrain: Terrain,
+    // Chunks whose meshes are out of date, see World::take_changed_chunks
+    changed_chunks: HashSet<(i64, i64)>,
+    /// Seconds simulated by World::update.
     time: f32,
+    bodies: Vec<Body>,
     sun: Sun,
     moon: Moon,
 }
 
 impl World {
+    /// A world with NoiseTerrain generated from [seed].
     pub fn new(seed: u64) -> Self {
+        Self::with_terrain_config(seed, TerrainConfig::default())
+    }
+
+    pub fn with_terrain_config(seed: u64, terrain_config: TerrainConfig) -> Self {
+        Self::with_terrain(NoiseTerrain::new(seed), terrain_config)
+    }
+
+    /// Resolutions below 2 in [terrain_config] are raised to 2.
+    pub fn with_terrain(noise: NoiseTerrain, terrain_config: TerrainConfig) -> Self {
         Self {
             terrain: Terrain {
                 chunks_loaded: HashMap::new(),
-                chunk_loader: |x, z| Chunk {
-                    latitude: x as f32,
-                    longitude: z as f32,
-                    heights: [[0.0; CHUNK_RESOLUTION]; CHUNK_RESOLUTION],
-                },
+                heights: Box::new(noise),
+                config: terrain_config.clamped(),
             },
+            changed_chunks: HashSet::new(),
             time: 0.0,
+            bodies: Vec::new(),
             sun: Sun {
                 radius: 6.963e8,
                 distance: 150.0e9,
@@ -53,16 +442,78 @@ impl World {
         }
     }
 
-    /// Blocks until all chunks load
-    pub fn load(&mut self, at: (f32, f32), radius: f32) {
-        for x in ((at.0 - radius).floor() as i64)..((at.0 + radius).ceil() as i64) {
-            for z in ((at.1 - radius).floor() as i64)..((at.1 + radius).ceil() as i64) {
-                if let Entry::Vacant(not_loaded) = self.terrain.chunks_loaded.entry((x, z)) {
-                    not_loaded.insert((self.terrain.chunk_loader)(x, z));
+    /// Loads every chunk within [distance] along x and z of the world position [center], blocking until
+    /// they're all loaded. Chunks which are already loaded are kept as they are.
+    pub fn load(&mut self, center: (f32, f32), distance: f32) {
+        let min = chunk_key(center.0 - distance, center.1 - distance);
+        let max = chunk_key(center.0 + distance, center.1 + distance);
+        for x in min.0..=max.0 {
+            for z in min.1..=max.1 {
+                if !self.terrain.chunks_loaded.contains_key(&(x, z)) {
+                    let chunk = self.terrain.load_chunk((x, z));
+                    self.terrain.chunks_loaded.insert((x, z), chunk);
+                    // the neighbors' edge normals change now that they can read this chunk
+                    for key in [(x, z), (x - 1, z), (x + 1, z), (x, z - 1), (x, z + 1)] {
+                        if self.terrain.chunks_loaded.contains_key(&key) {
+                            self.changed_chunks.insert(key);
+                        }
+                    }
                 }
             }
         }
     }
+
+    /// Chunks loaded, or whose neighbors were loaded, since the last call, sorted. Their meshes need to be
+    /// (re)built with World::chunk_mesh.
+    pub fn take_changed_chunks(&mut self) -> Vec<(i64, i64)> {
+        let mut keys: Vec<_> = self.changed_chunks.drain().collect();
+        keys.sort();
+        keys
+    }
+
+    /// Mesh of the chunk at [key], or None if it isn't loaded. See World::load.
+    pub fn chunk_mesh(&self, key: (i64, i64)) -> Option<MeshInitData<TerrainVertexType>> {
+        self.terrain.chunk_mesh(key)
+    }
+
+    /// Ground height at world [xz] from the coarse collision grid, or None if that chunk isn't loaded.
+    pub fn height(&self, xz: (f32, f32)) -> Option<f32> {
+        self.terrain.height_at(xz.0, xz.1)
+    }
+
+    /// Adds [body] to the world, returning its index in World::bodies.
+    pub fn add_body(&mut self, body: Body) -> usize {
+        self.bodies.push(body);
+        self.bodies.len() - 1
+    }
+
+    pub fn bodies(&self) -> &[Body] {
+        &self.bodies
+    }
+
+    pub fn body_mut(&mut self, index: usize) -> Option<&mut Body> {
+        self.bodies.get_mut(index)
+    }
+
+    /// Seconds simulated so far.
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    /// Advances the world by [elapsed] seconds: every body is accelerated by the gravity of the others,
+    /// then moved.
+    pub fn update(&mut self, elapsed: f32) {
+        self.time += elapsed;
+        let bodies: Vec<(Vector3<f32>, f32)> =
+            self.bodies.iter().map(|b| (b.position, b.mass)).collect();
+        let accelerations = gravity_accelerations(&bodies, DEFAULT_GRAVITY_SOFTENING);
+        for (body, accel) in self.bodies.iter_mut().zip(accelerations) {
+            body.acceleration = accel;
+            // semi-implicit Euler, which keeps orbits from spiraling out
+            body.velocity += accel * elapsed;
+            body.position += body.velocity * elapsed;
+        }
+    }
 }
 
 #[repr(C)]
@@ -81,3 +532,324 @@ pub struct Moon {
     distance: f32,
     _padding: [f32; 2],
 }
+
+#[allow(unused_imports)]
+mod tests {
+    use std::collections::HashMap;
+
+    use nalgebra::Vector3;
+
+    use crate::core::{
+        CHUNK_RESOLUTION, CHUNK_SIZE, G,
+        prefabs::orbital_velocity,
+        world::terrain::{
+            Body, HeightSource, NoiseTerrain, SplatLayer, Terrain, TerrainConfig, World, bilinear,
+        },
+    };
+
+    #[cfg(test)]
+    fn terrain(
+        height_fn: fn(f32, f32) -> f32,
+        config: TerrainConfig,
+        keys: &[(i64, i64)],
+    ) -> Terrain {
+        let mut terrain = Terrain {
+            chunks_loaded: HashMap::new(),
+            heights: Box::new(height_fn),
+            config,
+        };
+        for key in keys {
+            let chunk = terrain.load_chunk(*key);
+            terrain.chunks_loaded.insert(*key, chunk);
+        }
+        terrain
+    }
+
+    #[test]
+    fn noise_terrain_seamless_across_chunks() {
+        let noise = NoiseTerrain {
+            seed: 7,
+            frequency: 0.1,
+            amplitude: 8.0,
+            octaves: 3,
+        };
+        let config = TerrainConfig {
+            render_resolution: 9,
+            collision_resolution: 5,
+        };
+        let mut terrain = Terrain {
+            chunks_loaded: HashMap::new(),
+            heights: Box::new(noise),
+            config,
+        };
+        for key in [(0, 0), (1, 0), (0, -1)] {
+            let chunk = terrain.load_chunk(key);
+            terrain.chunks_loaded.insert(key, chunk);
+        }
+        let chunk = |key: (i64, i64)| &terrain.chunks_loaded[&key];
+
+        for (collision, res) in [(false, 9), (true, 5)] {
+            let grid = |key| {
+                let chunk = chunk(key);
+                if !collision {
+                    &chunk.heights
+                } else {
+                    &chunk.collision_heights
+                }
+            };
+            for n in 0..res {
+                // shared edge along x, then along z
+                assert_eq!(grid((0, 0)).get(res - 1, n), grid((1, 0)).get(0, n));
+                assert_eq!(grid((0, 0)).get(n, 0), grid((0, -1)).get(n, res - 1));
+            }
+        }
+
+        // not flat, within the amplitude, and the same for the same seed only
+        let heights = &chunk((0, 0)).heights.heights;
+        assert!(heights.iter().any(|h| *h != heights[0]));
+        assert!(heights.iter().all(|h| h.abs() <= 8.0));
+        assert_eq!(noise.height(3.5, -2.25), noise.height(3.5, -2.25));
+        let other = NoiseTerrain { seed: 8, ..noise };
+        assert_ne!(noise.height(3.5, -2.25), other.height(3.5, -2.25));
+    }
+
+    #[test]
+    fn flat_terrain_normals_point_up() {
+        let terrain = terrain(|_, _| 3.0, TerrainConfig::default(), &[(0, 0), (1, 0)]);
+
+        let mesh = terrain.chunk_mesh((0, 0)).unwrap();
+
+        assert_eq!(mesh.vertices.len(), CHUNK_RESOLUTION * CHUNK_RESOLUTION);
+        for vertex in mesh.vertices {
+            assert_eq!(vertex.normal, [0.0, 1.0, 0.0]);
+        }
+    }
+
+    #[test]
+    fn flat_chunk_mesh_counts() {
+        let flat = NoiseTerrain {
+            amplitude: 0.0,
+            ..NoiseTerrain::new(0)
+        };
+        let config = TerrainConfig {
+            render_resolution: 5,
+            collision_resolution: 3,
+        };
+        let mut world = World::with_terrain(flat, config);
+
+        world.load((0.0, 0.0), 1.0);
+
+        let changed = world.take_changed_chunks();
+        assert_eq!(changed, vec![(-1, -1), (-1, 0), (0, -1), (0, 0)]);
+        for key in changed {
+            let mesh = world.chunk_mesh(key).unwrap();
+            assert_eq!(mesh.vertices.len(), 5 * 5);
+            // two triangles per cell
+            assert_eq!(mesh.indices.len(), 4 * 4 * 6);
+            assert!(mesh.vertices.iter().all(|v| v.position[1] == 0.0));
+        }
+        assert!(world.take_changed_chunks().is_empty());
+
+        // new chunks, and the loaded neighbors whose edges they change
+        world.load((CHUNK_SIZE, 0.0), 1.0);
+        assert_eq!(
+            world.take_changed_chunks(),
+            vec![(0, -1), (0, 0), (1, -1), (1, 0)]
+        );
+    }
+
+    #[test]
+    fn world_loads_chunks_within_distance() {
+        let mut world = World::new(0);
+
+        // straddles the chunks on either side of x = 0, and reaches one chunk along z
+        world.load((4.0, 8.0), CHUNK_SIZE / 2.0);
+
+        assert_eq!(
+            world.take_changed_chunks(),
+            vec![(-1, 0), (-1, 1), (0, 0), (0, 1)]
+        );
+        assert!(world.chunk_mesh((1, 0)).is_none());
+        assert!(world.height((0.0, 0.0)).is_some());
+        assert_eq!(world.height((2.0 * CHUNK_SIZE, 0.0)), None);
+
+        // already loaded, nothing changes
+        world.load((4.0, 8.0), 1.0);
+        assert!(world.take_changed_chunks().is_empty());
+    }
+
+    #[test]
+    fn world_height_matches_terrain() {
+        let noise = NoiseTerrain::new(3);
+        let config = TerrainConfig {
+            render_resolution: 9,
+            collision_resolution: 5,
+        };
+        let mut world = World::with_terrain(noise, config);
+        world.load((0.0, 0.0), 0.0);
+
+        // on collision samples, 4.0 apart
+        for (x, z) in [(0.0, 0.0), (8.0, 4.0), (12.0, 12.0)] {
+            assert_eq!(world.height((x, z)), Some(noise.height(x, z)));
+        }
+        // between samples, interpolated from them
+        let height = world.height((2.0, 0.0)).unwrap();
+        let expected = (noise.height(0.0, 0.0) + noise.height(4.0, 0.0)) / 2.0;
+        assert!((height - expected).abs() < 1.0e-5, "{}", height);
+        assert_eq!(world.height((-0.5, 0.0)), None);
+    }
+
+    #[test]
+    fn bodies_orbit_under_gravity() {
+        let mut world = World::new(0);
+        let (sun_mass, radius) = (1.0e13, 10.0);
+        let speed = orbital_velocity(sun_mass, radius, G as f32);
+        let sun = world.add_body(Body::new(1.0, sun_mass, Vector3::zeros()));
+        let planet = world.add_body(
+            Body::new(0.1, 1.0, Vector3::new(radius, 0.0, 0.0))
+                .with_velocity(Vector3::new(0.0, 0.0, speed)),
+        );
+
+        // a little over 3 orbits
+        for _ in 0..3000 {
+            world.update(0.01);
+            let offset = world.bodies()[planet].position - world.bodies()[sun].position;
+            assert!(
+                (offset.magnitude() - radius).abs() < radius * 0.05,
+                "{}",
+                offset
+            );
+        }
+        assert!((world.time() - 30.0).abs() < 1.0e-2, "{}", world.time());
+        // pulled towards the sun, which barely moves
+        let planet = world.bodies()[planet];
+        assert!(planet.acceleration.dot(&-planet.position) > 0.0);
+        assert!(world.bodies()[sun].position.magnitude() < 1.0e-3);
+
+        // terrain is sampled as usual alongside the bodies
+        world.load((0.0, 0.0), 0.0);
+        assert!(world.height((1.0, 1.0)).is_some());
+    }
+
+    #[test]
+    fn sloped_terrain_normals_tilt_downhill() {
+        // rises by 1 per unit along x, continuing across chunks
+        let terrain = terrain(
+            |x, _| x,
+            TerrainConfig::default(),
+            &[(0, 0), (1, 0), (-1, 0)],
+        );
+        let expected = Vector3::new(-1.0, 1.0, 0.0).normalize();
+
+        let mesh = terrain.chunk_mesh((0, 0)).unwrap();
+
+        // including the edges, which read the neighboring chunks
+        for vertex in mesh.vertices {
+            let normal = Vector3::from(vertex.normal);
+            assert!((normal - expected).magnitude() < 1.0e-5, "{:?}", normal);
+        }
+    }
+
+    #[test]
+    fn render_and_collision_grids_differ() {
+        let config = TerrainConfig {
+            render_resolution: 9,
+            collision_resolution: 3,
+        };
+        // curved, so interpolating the coarse grid differs from the real height
+        let terrain = terrain(|x, _| x * x, config, &[(0, 0)]);
+        let x = CHUNK_SIZE / 4.0;
+
+        let mesh = terrain.chunk_mesh((0, 0)).unwrap();
+        assert_eq!(mesh.vertices.len(), 81);
+        let vertex = mesh
+            .vertices
+            .iter()
+            .find(|v| v.position[0] == x && v.position[2] == 0.0)
+            .unwrap();
+        assert_eq!(vertex.position[1], x * x);
+
+        // halfway between the collision samples at 0 and CHUNK_SIZE / 2
+        let half = CHUNK_SIZE / 2.0;
+        let height = terrain.height_at(x, 0.0).unwrap();
+        assert!((height - half * half / 2.0).abs() < 1.0e-3, "{}", height);
+        assert_eq!(terrain.height_at(-1.0, 0.0), None);
+    }
+
+    #[test]
+    fn terrain_resolutions_clamped() {
+        for resolution in [0, 1] {
+            let config = TerrainConfig {
+                render_resolution: resolution,
+                collision_resolution: resolution,
+            };
+            let mut world = World::with_terrain_config(0, config);
+
+            world.load((0.0, 0.0), 1.0);
+
+            // one sample on each corner
+            let mesh = world.chunk_mesh((0, 0)).unwrap();
+            assert_eq!(mesh.vertices.len(), 4);
+            assert_eq!(mesh.indices.len(), 6);
+            assert!(world.height((CHUNK_SIZE / 2.0, 1.0)).unwrap().is_finite());
+        }
+    }
+
+    #[test]
+    fn height_is_bilinear_within_a_cell() {
+        let config = TerrainConfig {
+            render_resolution: 3,
+            collision_resolution: 5,
+        };
+        let height_fn = |x: f32, z: f32| x * x - 2.0 * z + 0.1 * x * z;
+        let terrain = terrain(height_fn, config, &[(0, 0)]);
+        let spacing = CHUNK_SIZE / 4.0;
+        // 30% into the second cell along x, 70% into the third along z
+        let (x, z) = (1.3 * spacing, 2.7 * spacing);
+
+        let [x0, x1, z0, z1] = [1.0, 2.0, 2.0, 3.0].map(|n| n * spacing);
+        let expected = (1.0 - 0.3) * ((1.0 - 0.7) * height_fn(x0, z0) + 0.7 * height_fn(x0, z1))
+            + 0.3 * ((1.0 - 0.7) * height_fn(x1, z0) + 0.7 * height_fn(x1, z1));
+        let height = terrain.height_at(x, z).unwrap();
+        assert!(
+            (height - expected).abs() < 1.0e-3,
+            "{} != {}",
+            height,
+            expected
+        );
+
+        // corners are returned as-is
+        assert_eq!(bilinear([[1.0, 2.0], [3.0, 4.0]], 0.0, 1.0), 2.0);
+        assert_eq!(bilinear([[1.0, 2.0], [3.0, 4.0]], 1.0, 0.0), 3.0);
+    }
+
+    #[test]
+    fn steep_slopes_splatted_with_rock() {
+        // flat for x < 8, then rising steeply, all well between the sand and snow heights
+        let terrain = terrain(
+            |x, _| 10.0 + (x - 8.0).max(0.0) * 2.0,
+            TerrainConfig {
+                render_resolution: 17,
+                collision_resolution: 3,
+            },
+            &[(0, 0)],
+        );
+
+        let mesh = terrain.chunk_mesh((0, 0)).unwrap();
+
+        for vertex in mesh.vertices {
+            let total: f32 = vertex.splat.iter().sum();
+            assert!((total - 1.0).abs() < 1.0e-5, "{:?}", vertex.splat);
+            let x = vertex.position[0];
+            let (grass, rock) = (
+                vertex.splat[SplatLayer::Grass as usize],
+                vertex.splat[SplatLayer::Rock as usize],
+            );
+            if x < 7.0 {
+                assert_eq!((grass, rock), (1.0, 0.0), "{}", x);
+            } else if x > 9.0 {
+                assert_eq!((grass, rock), (0.0, 1.0), "{}", x);
+            }
+        }
+    }
+}
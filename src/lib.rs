@@ -12,14 +12,12 @@
 pub mod core;
 pub mod render;
 
+/// Logs to stdout at [level]. Levels can be changed while the app runs, see core::logging::set_log_level.
 pub fn init_logging(level: log::LevelFilter) {
-    env_logger::builder()
-        .filter_level(level)
-        .target(env_logger::Target::Stdout)
-        .init();
+    core::logging::init(level);
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Integrator {
     Euler,
     RK4,
@@ -14,10 +14,21 @@ pub mod vertex;
        2. For each frame:
            a. Scale, Rotation, Transformation turned into 4x4 transform matrix (object.instance())
            b. Copied to instance storage in the CPU at the correct location (update_instances())
-           c. Entire instance buffer is copied to VRAM (update_gpu())
+           c. Instances that changed are copied to VRAM (update_gpu())
            d. Each mesh gets own draw_instanced call
 */
 
 // These types should match
 pub type GlobalIndexType = u32;
 pub const GLOBAL_INDEX_FORMAT: IndexFormat = IndexFormat::Uint32;
+
+/// Requests a device from any available adapter. Returns None if there is no adapter (i.e. on headless CI),
+/// in which case tests needing a GPU should return early.
+#[allow(dead_code)]
+pub(crate) fn test_device() -> Option<(wgpu::Device, wgpu::Queue)> {
+    let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
+    let adapter =
+        pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default()))
+            .ok()?;
+    pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor::default())).ok()
+}
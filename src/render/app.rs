@@ -1,36 +1,57 @@
-use std::{sync::Arc, time::Instant};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use bytemuck::{Pod, Zeroable};
 use image::DynamicImage;
-use log::{error, info};
+use log::{LevelFilter, error, info, warn};
 use nalgebra::{UnitQuaternion, Vector3};
-use serde_json::{Number, Value};
+use serde_json::{Number, Value, json};
+use wgpu::{Color, FrontFace};
 use winit::{
     application::ApplicationHandler,
-    dpi::{PhysicalSize, Size},
-    event::WindowEvent,
+    dpi::{LogicalSize, PhysicalSize, Size},
+    event::{KeyEvent, StartCause, WindowEvent},
     event_loop::{ActiveEventLoop, ControlFlow, EventLoop},
+    keyboard::{Key, NamedKey, PhysicalKey},
     window::{Icon, Window, WindowId},
 };
 
 use crate::{
+    Integrator,
     core::{
         AfterRenderArgs, AfterTickArgs, BeforeInputArgs, BeforeRenderArgs, BeforeStartArgs,
-        BeforeTickArgs, Completer, DisposeArgs, HandleInputArgs, HandleTickArgs, RENDER_DISTANCE,
-        System,
-        assets::ICON,
-        camera::{NoClipCamera, Projection},
-        entity::{BoundingBox, CollisionResponse, Entity, EntityType},
-        input::InputController,
-        prefabs::DEFAULT_SYSTEMS,
+        BeforeTickArgs, CONSOLE_KEY, Completer, DEFAULT_CLEAR_COLOR, DEFAULT_INTEGRATOR,
+        DEFAULT_MAX_DT, DEFAULT_UNFOCUSED_FRAME_TIME, DisposeArgs, G, HandleInputArgs,
+        HandleTickArgs, IdBank, RENDER_DISTANCE, SHADING_MODE_TOGGLE_KEY, SimClock, System, Unique,
+        WIREFRAME_TOGGLE_KEY,
+        assets::{icon_from_rgba, load_icon},
+        camera::{
+            Camera, CameraControls, HeadlessCamera, NoClipCamera, Projection, ProjectionConfig,
+        },
+        console::{Console, ConsoleError, parse_floats},
+        entity::{
+            BoundingBox, CollisionResponse, Entity, EntityScripts, EntityType, MaterialKind,
+            combined_bounds,
+        },
+        input::{InputController, apply_text_input},
+        logging::set_log_level,
+        prefabs::{
+            CollisionCallbacks, Contact, DefaultSystem, MinimapView, SystemTimings,
+            orbital_velocity,
+        },
         world::terrain::World,
     },
     render::{
         GlobalIndexType,
         model::{TobjModel, TobjModelError},
-        renderer::Renderer,
-        storage::{mesh::MeshStorageError, textures::ResizeStrategy},
-        vertex::DefaultVertexType,
+        renderer::{Renderer, RendererConfig, ShadingMode},
+        storage::{
+            mesh::MeshStorageError,
+            textures::{ResizeStrategy, TextureKind},
+        },
+        vertex::{DefaultVertexType, UiVertexType, ui::UiElement},
     },
 };
 
@@ -40,9 +61,15 @@ pub struct AppInitData {
     pub width: u32,
     pub height: u32,
     pub transform_meshes: Vec<(Completer<u64>, MeshInitData<DefaultVertexType>)>,
+    pub ui_meshes: Vec<(Completer<u64>, MeshInitData<UiVertexType>)>,
     pub textures: Vec<(Completer<u64>, TextureInitData)>,
     pub players: Vec<(Completer<u64>, PlayerInitData)>,
     pub objects: Vec<(Completer<u64>, ObjectInitData)>,
+    pub renderer_config: RendererConfig,
+    /// Replaces the default window icon if set.
+    pub window_icon: Option<DynamicImage>,
+    pub integrator: Integrator,
+    pub clear_color: Color,
 }
 
 impl AppInitData {
@@ -51,26 +78,40 @@ impl AppInitData {
     ) -> (
         (u32, u32),
         Vec<(Completer<u64>, MeshInitData<DefaultVertexType>)>,
+        Vec<(Completer<u64>, MeshInitData<UiVertexType>)>,
         Vec<(Completer<u64>, PlayerInitData)>,
         Vec<(Completer<u64>, TextureInitData)>,
         Vec<(Completer<u64>, ObjectInitData)>,
+        RendererConfig,
+        Option<DynamicImage>,
+        Integrator,
+        Color,
     ) {
         (
             (self.width, self.height),
             self.transform_meshes,
+            self.ui_meshes,
             self.players,
             self.textures,
             self.objects,
+            self.renderer_config,
+            self.window_icon,
+            self.integrator,
+            self.clear_color,
         )
     }
 }
 
+#[derive(Clone)]
 pub struct MeshInitData<V>
 where
     V: Pod + Zeroable + Clone + Copy + std::fmt::Debug,
 {
     pub vertices: Vec<V>,
     pub indices: Vec<GlobalIndexType>,
+    /// Winding of the front faces. Meshes imported from tools exporting clockwise winding need
+    /// FrontFace::Cw, or they're culled as back faces.
+    pub front_face: FrontFace,
 }
 
 pub struct ObjectInitData {
@@ -84,6 +125,43 @@ pub struct ObjectInitData {
     pub translation: Vector3<f32>,
     pub response: CollisionResponse,
     pub mass: f32,
+    pub friction: f32,
+    pub lifetime: Option<f32>,
+    pub material: MaterialKind,
+    /// See Entity::emissive.
+    pub emissive: f32,
+}
+
+impl ObjectInitData {
+    /// Places this object [radius] units along +x from [center] with the velocity (along +z) needed for a
+    /// circular orbit around a stationary body of [central_mass] at [center].
+    pub fn in_orbit_around(mut self, center: Vector3<f32>, central_mass: f32, radius: f32) -> Self {
+        self.translation = center + Vector3::new(radius, 0.0, 0.0);
+        self.velocity = Vector3::new(0.0, 0.0, orbital_velocity(central_mass, radius, G as f32));
+        self
+    }
+
+    pub(crate) fn into_entity(self, id: u64) -> Entity {
+        let mut entity = Entity::new(
+            id,
+            self.mesh_id.consume().unwrap(),
+            self.texture_id.consume().unwrap(),
+            self.scale,
+            self.rotation,
+            self.translation,
+            self.velocity,
+            self.acceleration,
+            self.bounding_box,
+            EntityType::Object,
+            self.response,
+            self.mass,
+        );
+        entity.friction = self.friction;
+        entity.lifetime = self.lifetime;
+        entity.material = self.material;
+        entity.emissive = self.emissive;
+        entity
+    }
 }
 
 pub struct PlayerInitData {
@@ -97,54 +175,212 @@ pub struct PlayerInitData {
     pub translation: Vector3<f32>,
     pub response: CollisionResponse,
     pub mass: f32,
+    pub friction: f32,
+    /// Initial direction of the player's camera, in radians. Yaw 0.0 faces +x, increasing towards +z.
+    pub yaw: f32,
+    pub pitch: f32,
+}
+
+impl PlayerInitData {
+    fn into_entity(self, id: u64, renderer: &Renderer, projection: &ProjectionConfig) -> Entity {
+        let camera = NoClipCamera::new(
+            renderer.device(),
+            renderer.camera_bind_group_layout(),
+            self.translation,
+            self.yaw,
+            self.pitch,
+            0.0,
+            Projection::from_config(
+                renderer.config().width as f32,
+                renderer.config().height as f32,
+                projection,
+            ),
+        );
+        self.into_entity_of(id, EntityType::Player { camera })
+    }
+
+    /// The player's body as a plain object, for headless apps which have nothing to create its camera with.
+    fn into_object(self, id: u64) -> Entity {
+        self.into_entity_of(id, EntityType::Object)
+    }
+
+    fn into_entity_of(self, id: u64, entity_type: EntityType) -> Entity {
+        let mut entity = Entity::new(
+            id,
+            self.mesh_id.consume().unwrap(),
+            self.texture_id.consume().unwrap(),
+            self.scale,
+            self.rotation,
+            self.translation,
+            self.velocity,
+            self.acceleration,
+            self.bounding_box,
+            entity_type,
+            self.response,
+            self.mass,
+        );
+        entity.friction = self.friction;
+        entity
+    }
 }
 
 pub struct TextureInitData {
     pub image: DynamicImage,
     pub resize: ResizeStrategy,
+    pub kind: TextureKind,
+    /// Width and height of the texture in pixels. The image's own size if None.
+    pub size: Option<(u32, u32)>,
 }
 
 // Data only available once the window and renderer are created.
 pub struct ActiveState {
-    current_camera: NoClipCamera,
+    current_camera: Box<dyn Camera>,
     entities: Vec<Entity>,
+    ui_elements: Vec<UiElement>,
+    minimap: Option<MinimapView>,
+    ids: IdBank,
+    // (mesh id, entity id) of entities removed since the renderer last updated
+    removed: Vec<(u64, u64)>,
+    collision_callbacks: CollisionCallbacks,
+    scripts: EntityScripts,
+    clock: SimClock,
+    integrator: Integrator,
 
-    last_update: Instant,
+    // None until the first frame is drawn
+    last_update: Option<Instant>,
 }
 
 impl ActiveState {
-    pub fn add_object(&mut self, object: ObjectInitData) -> u64 {
-        let id = self.entities.len() as u64;
-        let object = Entity::new(
-            id,
-            object.mesh_id.consume().unwrap(),
-            object.texture_id.consume().unwrap(),
-            object.scale,
-            object.rotation,
-            object.translation,
-            object.velocity,
-            object.acceleration,
-            object.bounding_box,
-            EntityType::Object,
-            object.response,
-            object.mass,
-        );
+    fn new(
+        current_camera: Box<dyn Camera>,
+        entities: Vec<Entity>,
+        ids: IdBank,
+        integrator: Integrator,
+    ) -> Self {
+        Self {
+            current_camera,
+            entities,
+            ui_elements: vec![],
+            minimap: None,
+            ids,
+            removed: vec![],
+            collision_callbacks: CollisionCallbacks::default(),
+            scripts: EntityScripts::default(),
+            clock: SimClock::new(),
+            integrator,
+            last_update: None,
+        }
+    }
 
-        self.entities.push(object);
+    pub fn add_object(&mut self, object: ObjectInitData) -> u64 {
+        let id = self.next_id();
+        self.entities.push(object.into_entity(id));
         id
     }
 
-    pub fn update(&mut self, _elapsed: f32, world: &mut World) {
+    /// Returns the ids of the objects, in order.
+    pub fn add_objects(&mut self, objects: impl IntoIterator<Item = ObjectInitData>) -> Vec<u64> {
+        let objects = objects.into_iter();
+        self.entities.reserve(objects.size_hint().0);
+        objects.map(|object| self.add_object(object)).collect()
+    }
+
+    /// Removes an entity from the simulation and the renderer.
+    pub fn remove_entity(&mut self, id: u64) -> Option<Entity> {
+        let index = self.entities.iter().position(|e| *e.id() == id)?;
+        let entity = self.entities.remove(index);
+        self.removed.push((entity.mesh_id, id));
+        self.collision_callbacks.remove(id);
+        self.scripts.remove(id);
+        Some(entity)
+    }
+
+    /// Calls [callback] with the other entity's id and the contact whenever entity [id] collides with another
+    /// entity. Run by CollisionsSystem after each tick. Callbacks are dropped when the entity is removed.
+    pub fn on_collision(&mut self, id: u64, callback: impl FnMut(u64, &Contact) + 'static) {
+        self.collision_callbacks.register(id, Box::new(callback));
+    }
+
+    /// Runs [script] on entity [id] every tick, after the before_tick hooks and before systems integrate
+    /// (in handle_tick). Scripts are dropped when the entity is removed.
+    pub fn add_script(&mut self, id: u64, script: impl FnMut(&mut Entity, f32) + 'static) {
+        self.scripts.register(id, Box::new(script));
+    }
+
+    fn run_scripts(&mut self, dt: f32) {
+        self.scripts.run(&mut self.entities, dt);
+    }
+
+    /// Seconds simulated since the app started, the sum of every tick's dt. Doesn't advance while paused.
+    /// During the tick hooks, this is the time at the start of the tick.
+    pub fn sim_time(&self) -> f32 {
+        self.clock.time()
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.clock.is_paused()
+    }
+
+    /// Skips the tick hooks and entity scripts while paused. Input and render hooks still run.
+    pub fn set_paused(&mut self, paused: bool) {
+        self.clock.set_paused(paused);
+    }
+
+    /// Used by DynamicsSystem to advance entities each tick.
+    pub fn integrator(&self) -> Integrator {
+        self.integrator
+    }
+
+    pub fn set_integrator(&mut self, integrator: Integrator) {
+        self.integrator = integrator;
+    }
+
+    pub fn collision_callbacks_mut(&mut self) -> &mut CollisionCallbacks {
+        &mut self.collision_callbacks
+    }
+
+    pub(crate) fn take_removed(&mut self) -> Vec<(u64, u64)> {
+        std::mem::take(&mut self.removed)
+    }
+
+    fn next_id(&mut self) -> u64 {
+        self.ids.issue()
+    }
+
+    pub fn update(&mut self, elapsed: f32, world: &mut World) {
         let pos = self.current_camera.position();
         world.load((pos[0], pos[2]), RENDER_DISTANCE);
+        world.update(elapsed);
     }
 
-    pub fn current_camera(&self) -> &NoClipCamera {
-        &self.current_camera
+    pub fn current_camera(&self) -> &dyn Camera {
+        self.current_camera.as_ref()
     }
 
-    pub fn current_camera_mut(&mut self) -> &mut NoClipCamera {
-        &mut self.current_camera
+    pub fn current_camera_mut(&mut self) -> &mut dyn Camera {
+        self.current_camera.as_mut()
+    }
+
+    /// Fits the current camera and every player's camera to a viewport of [width] by [height] physical
+    /// pixels. Sizes with a zero side, like a minimized window's, are ignored.
+    pub fn resize_cameras(&mut self, width: u32, height: u32) {
+        if width == 0 || height == 0 {
+            return;
+        }
+        let (width, height) = (width as f32, height as f32);
+        self.current_camera.resize(width, height);
+        for entity in self.entities.iter_mut() {
+            if let EntityType::Player { camera } = &mut entity.entity_type {
+                camera.resize(width, height);
+            }
+        }
+    }
+
+    /// Replaces the camera used for rendering and input, returning the previous one.
+    ///
+    /// The camera's bind group must be created with Renderer::camera_bind_group_layout.
+    pub fn set_camera(&mut self, camera: Box<dyn Camera>) -> Box<dyn Camera> {
+        std::mem::replace(&mut self.current_camera, camera)
     }
 
     pub fn entities(&self) -> &Vec<Entity> {
@@ -154,6 +390,54 @@ impl ActiveState {
     pub fn entities_mut(&mut self) -> &mut Vec<Entity> {
         &mut self.entities
     }
+
+    /// Min and max corners of the world-space box enclosing every entity. None if there are no entities.
+    pub fn scene_bounds(&self) -> Option<(Vector3<f32>, Vector3<f32>)> {
+        combined_bounds(&self.entities)
+    }
+
+    /// Moves the current camera back until every entity is in view, facing the middle of the scene. Returns
+    /// false, leaving the camera, if there are no entities.
+    pub fn frame_all(&mut self) -> bool {
+        let Some(bounds) = self.scene_bounds() else {
+            return false;
+        };
+        self.frame_bounds(bounds);
+        true
+    }
+
+    /// Frames entity [id] with the current camera, like frame_all. Returns false if there's no such entity.
+    pub fn focus_on(&mut self, id: u64) -> bool {
+        let Some(entity) = self.entities.iter().find(|e| *e.id() == id) else {
+            return false;
+        };
+        self.frame_bounds(entity.world_bounds());
+        true
+    }
+
+    fn frame_bounds(&mut self, (min, max): (Vector3<f32>, Vector3<f32>)) {
+        let center = (min + max) / 2.0;
+        // entities with a zero-sized box still get some room around them
+        let radius = ((max - min).magnitude() / 2.0).max(1.0);
+        self.current_camera.frame(&center, radius);
+    }
+
+    pub fn ui_elements(&self) -> &Vec<UiElement> {
+        &self.ui_elements
+    }
+
+    pub fn ui_elements_mut(&mut self) -> &mut Vec<UiElement> {
+        &mut self.ui_elements
+    }
+
+    pub fn minimap(&self) -> Option<&MinimapView> {
+        self.minimap.as_ref()
+    }
+
+    /// Has the renderer draw the scene into a minimap as well, or stop if None.
+    pub fn set_minimap(&mut self, minimap: Option<MinimapView>) {
+        self.minimap = minimap;
+    }
 }
 
 enum AppState {
@@ -166,12 +450,51 @@ enum AppState {
         renderer: Renderer,
         state: ActiveState,
     },
+    /// Simulation without a window or renderer, see App::new_headless.
+    Headless {
+        state: ActiveState,
+        // Issued for meshes and textures, which aren't kept since nothing is drawn
+        resource_ids: IdBank,
+    },
 }
 
 pub enum Event {
     WindowEvent(WindowId, WindowEvent),
 }
 
+/// What the app does while its window is unfocused or hidden, see App::set_background_config.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BackgroundConfig {
+    /// Time between frames while the window is unfocused. None keeps rendering as fast as possible.
+    /// Hidden (occluded) windows aren't rendered until they're shown again either way.
+    pub unfocused_frame_time: Option<Duration>,
+    /// Skips the tick hooks and entity scripts while unfocused or hidden, like ActiveState::set_paused.
+    pub pause_simulation: bool,
+    /// Releases held keys and mouse buttons when focus is lost, since the window won't see them go up.
+    pub release_input: bool,
+}
+
+impl Default for BackgroundConfig {
+    fn default() -> Self {
+        Self {
+            unfocused_frame_time: Some(DEFAULT_UNFOCUSED_FRAME_TIME),
+            pause_simulation: false,
+            release_input: true,
+        }
+    }
+}
+
+/// How often the app draws frames, depending on whether its window is focused and visible.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameCadence {
+    /// As fast as possible.
+    Continuous,
+    /// One frame every given duration.
+    Throttled(Duration),
+    /// No frames until the window is shown again.
+    Suspended,
+}
+
 /// Main struct for the entire app.
 ///
 /// App needs to be started with:
@@ -192,13 +515,34 @@ pub struct App {
     state: AppState,
     world: World,
     input: InputController,
+    max_dt: Duration,
+    background: BackgroundConfig,
+    focused: bool,
+    occluded: bool,
+    /// Used for the player cameras and the default camera.
+    projection: ProjectionConfig,
+
+    /// Size of the window's surface in physical pixels.
+    physical_size: PhysicalSize<u32>,
+    /// Physical pixels per logical pixel, 1.0 until the window is created.
+    scale_factor: f64,
 
     systems: Vec<Box<dyn System>>,
+    /// Per-system hook durations, None unless enabled.
+    system_timings: Option<SystemTimings>,
+
+    console: Console<App>,
+    /// Text typed into the console, None while it's closed.
+    console_line: Option<String>,
 }
 
 impl App {
     // static method
     pub fn start(app: &mut Self) {
+        if let AppState::Headless { .. } = app.state {
+            warn!("Headless apps are advanced with App::step, not started.");
+            return;
+        }
         let event_loop: EventLoop<Event> = EventLoop::with_user_event().build().unwrap();
         event_loop.set_control_flow(ControlFlow::Poll);
         event_loop.run_app(app).unwrap();
@@ -207,23 +551,131 @@ impl App {
 
 impl App {
     pub fn new(width: u32, height: u32, seed: u64) -> Self {
+        Self::with_default_systems(width, height, seed, &DefaultSystem::ALL)
+    }
+
+    /// Same as App::new, but only the given default systems are added.
+    pub fn with_default_systems(
+        width: u32,
+        height: u32,
+        seed: u64,
+        default_systems: &[DefaultSystem],
+    ) -> Self {
         Self {
             state: AppState::NeedsInit(AppInitData {
                 width,
                 height,
                 transform_meshes: vec![],
+                ui_meshes: vec![],
                 players: vec![],
                 objects: vec![],
                 textures: vec![],
+                renderer_config: RendererConfig::default(),
+                window_icon: None,
+                integrator: DEFAULT_INTEGRATOR,
+                clear_color: DEFAULT_CLEAR_COLOR,
             }),
             world: World::new(seed),
             input: InputController::new(),
-            systems: DEFAULT_SYSTEMS(),
+            max_dt: DEFAULT_MAX_DT,
+            background: BackgroundConfig::default(),
+            focused: true,
+            occluded: false,
+            projection: ProjectionConfig::default(),
+            physical_size: PhysicalSize::new(width, height),
+            scale_factor: 1.0,
+            systems: default_systems.iter().map(|s| s.build()).collect(),
+            system_timings: None,
+            console: default_console(),
+            console_line: None,
+        }
+    }
+
+    /// An app without a window or renderer, for tests and benchmarks. It's started right away and advanced
+    /// with App::step instead of App::start.
+    ///
+    /// Systems' before_start isn't run, since it's given the renderer, and neither are the input and
+    /// render hooks. Meshes and textures get ids but aren't kept, players are added as plain objects since
+    /// there's nothing to create their cameras with, and window and renderer settings are ignored.
+    ///
+    /// The current camera has no bind group. Calling bind_group on it (i.e. from a system through
+    /// ActiveState::current_camera) panics unless it's replaced with ActiveState::set_camera first.
+    pub fn new_headless(seed: u64, default_systems: &[DefaultSystem]) -> Self {
+        let mut app = Self::with_default_systems(0, 0, seed, default_systems);
+        app.state = AppState::Headless {
+            state: ActiveState::new(
+                Box::new(HeadlessCamera::new()),
+                vec![],
+                IdBank::new(),
+                DEFAULT_INTEGRATOR,
+            ),
+            resource_ids: IdBank::new(),
+        };
+        app
+    }
+
+    /// Runs the tick hooks and entity scripts for [dt], clamped to the max dt, and advances the world, as a
+    /// frame would. Only headless apps are stepped; windowed apps tick on their own.
+    pub fn step(&mut self, dt: Duration) {
+        let AppState::Headless { state, .. } = &mut self.state else {
+            warn!("Only headless apps can be stepped, ignoring.");
+            return;
+        };
+        let tick_dur = clamp_dt(dt, self.max_dt);
+        if !state.is_paused() {
+            run_tick_hooks(
+                &mut self.systems,
+                &mut self.system_timings,
+                state,
+                &self.input,
+                &tick_dur,
+            );
+        }
+        state.update(tick_dur.as_secs_f32(), &mut self.world);
+    }
+
+    /// The simulation, once the app has started.
+    pub fn state(&self) -> Option<&ActiveState> {
+        match &self.state {
+            AppState::NeedsInit(_) => None,
+            AppState::Started { state, .. } | AppState::Headless { state, .. } => Some(state),
         }
     }
 
-    /// Returns a completer for the mesh id and texture id (in that order).
-    pub fn add_obj_model(&mut self, path: &str) -> Result<Completer<u64>, MeshStorageError> {
+    pub fn state_mut(&mut self) -> Option<&mut ActiveState> {
+        match &mut self.state {
+            AppState::NeedsInit(_) => None,
+            AppState::Started { state, .. } | AppState::Headless { state, .. } => Some(state),
+        }
+    }
+
+    /// Systems run in the order they're added, after the default systems.
+    pub fn add_system(&mut self, system: Box<dyn System>) {
+        self.systems.push(system);
+    }
+
+    pub fn system_names(&self) -> Vec<&str> {
+        self.systems.iter().map(|s| s.name()).collect()
+    }
+
+    /// Times every system's per-frame hooks from now on. The average time per frame of each system is
+    /// written to the "system_timings" entry of the renderer's gui data, in milliseconds.
+    pub fn enable_system_timings(&mut self) {
+        self.system_timings
+            .get_or_insert_with(SystemTimings::default);
+    }
+
+    pub fn system_timings(&self) -> Option<&SystemTimings> {
+        self.system_timings.as_ref()
+    }
+
+    /// Returns a completer for the mesh id and texture id (in that order). [front_face] is the winding the
+    /// file was exported with.
+    pub fn add_obj_model(
+        &mut self,
+        path: &str,
+        front_face: FrontFace,
+    ) -> Result<Completer<u64>, MeshStorageError> {
         let model =
             TobjModel::load_from_obj(path).map_err(|e| MeshStorageError::TobjModelError(e))?;
         let indices: Vec<GlobalIndexType> = model
@@ -252,7 +704,11 @@ impl App {
                 ],
             })
         }
-        let mesh = MeshInitData { vertices, indices };
+        let mesh = MeshInitData {
+            vertices,
+            indices,
+            front_face,
+        };
         let completer = Completer::new(APP_START_PRECOND);
         match &mut self.state {
             AppState::NeedsInit(init_data) => {
@@ -266,6 +722,9 @@ impl App {
                 let mesh_id = renderer.add_mesh_instanced(mesh)?;
                 Ok(Completer::from_value(mesh_id))
             }
+            AppState::Headless { resource_ids, .. } => {
+                Ok(Completer::from_value(resource_ids.issue()))
+            }
         }
     }
 
@@ -290,6 +749,29 @@ impl App {
                 let mesh_id = renderer.add_mesh_instanced(mesh)?;
                 Ok(Completer::from_value(mesh_id))
             }
+            AppState::Headless { resource_ids, .. } => {
+                Ok(Completer::from_value(resource_ids.issue()))
+            }
+        }
+    }
+
+    /// Adds a mesh for UiElements, drawn in screen space over the 3D scene.
+    pub fn add_ui_mesh(
+        &mut self,
+        mesh: MeshInitData<UiVertexType>,
+    ) -> Result<Completer<u64>, MeshStorageError> {
+        match &mut self.state {
+            AppState::NeedsInit(init_data) => {
+                let completer = Completer::new(APP_START_PRECOND);
+                init_data.ui_meshes.push((completer.clone(), mesh));
+                Ok(completer)
+            }
+            AppState::Started {
+                renderer, state: _, ..
+            } => Ok(Completer::from_value(renderer.add_ui_mesh(mesh)?)),
+            AppState::Headless { resource_ids, .. } => {
+                Ok(Completer::from_value(resource_ids.issue()))
+            }
         }
     }
 
@@ -303,38 +785,15 @@ impl App {
             AppState::Started {
                 renderer, state, ..
             } => {
-                let id = state.entities.len() as u64;
-                let player = Entity::new(
-                    id,
-                    player.mesh_id.consume().unwrap(),
-                    player.texture_id.consume().unwrap(),
-                    player.scale,
-                    player.rotation,
-                    player.translation,
-                    player.velocity,
-                    player.acceleration,
-                    player.bounding_box,
-                    EntityType::Player {
-                        camera: NoClipCamera::new(
-                            renderer.device(),
-                            renderer.camera_bind_group_layout(),
-                            player.translation,
-                            0.0,
-                            0.0,
-                            0.0,
-                            Projection::new(
-                                renderer.config().width as f32,
-                                renderer.config().height as f32,
-                                90.0,
-                                0.1,
-                                10000.0,
-                            ),
-                        ),
-                    },
-                    player.response,
-                    player.mass,
-                );
-                state.entities.push(player);
+                let id = state.next_id();
+                state
+                    .entities
+                    .push(player.into_entity(id, renderer, &self.projection));
+                Completer::from_value(id)
+            }
+            AppState::Headless { state, .. } => {
+                let id = state.next_id();
+                state.entities.push(player.into_object(id));
                 Completer::from_value(id)
             }
         }
@@ -347,28 +806,82 @@ impl App {
                 init_data.objects.push((completer.clone(), object));
                 completer
             }
+            AppState::Started { state, .. } | AppState::Headless { state, .. } => {
+                Completer::from_value(state.add_object(object))
+            }
+        }
+    }
+
+    /// Removes an entity, see ActiveState::remove_entity. Its instance stops being drawn on the next frame.
+    /// Entities only exist once the app has started, so this returns None before then.
+    pub fn remove_entity(&mut self, id: u64) -> Option<Entity> {
+        match &mut self.state {
+            AppState::NeedsInit(_) => None,
+            AppState::Started { state, .. } | AppState::Headless { state, .. } => {
+                state.remove_entity(id)
+            }
+        }
+    }
+
+    /// Adds every player at once, see App::add_player. Instances are uploaded together on the next frame.
+    pub fn add_players(
+        &mut self,
+        players: impl IntoIterator<Item = PlayerInitData>,
+    ) -> Vec<Completer<u64>> {
+        match &mut self.state {
+            AppState::NeedsInit(init_data) => players
+                .into_iter()
+                .map(|player| {
+                    let completer = Completer::new(APP_START_PRECOND);
+                    init_data.players.push((completer.clone(), player));
+                    completer
+                })
+                .collect(),
             AppState::Started {
                 renderer, state, ..
             } => {
-                let id = state.entities.len() as u64;
-                let object = Entity::new(
-                    id,
-                    object.mesh_id.consume().unwrap(),
-                    object.texture_id.consume().unwrap(),
-                    object.scale,
-                    object.rotation,
-                    object.translation,
-                    object.velocity,
-                    object.acceleration,
-                    object.bounding_box,
-                    EntityType::Object,
-                    object.response,
-                    object.mass,
-                );
-
-                state.entities.push(object);
-                Completer::from_value(id)
+                let players = players.into_iter();
+                state.entities.reserve(players.size_hint().0);
+                players
+                    .map(|player| {
+                        let id = state.next_id();
+                        state
+                            .entities
+                            .push(player.into_entity(id, renderer, &self.projection));
+                        Completer::from_value(id)
+                    })
+                    .collect()
             }
+            AppState::Headless { state, .. } => players
+                .into_iter()
+                .map(|player| {
+                    let id = state.next_id();
+                    state.entities.push(player.into_object(id));
+                    Completer::from_value(id)
+                })
+                .collect(),
+        }
+    }
+
+    /// Adds every object at once, see App::add_object. Instances are uploaded together on the next frame.
+    pub fn add_objects(
+        &mut self,
+        objects: impl IntoIterator<Item = ObjectInitData>,
+    ) -> Vec<Completer<u64>> {
+        match &mut self.state {
+            AppState::NeedsInit(init_data) => objects
+                .into_iter()
+                .map(|object| {
+                    let completer = Completer::new(APP_START_PRECOND);
+                    init_data.objects.push((completer.clone(), object));
+                    completer
+                })
+                .collect(),
+            AppState::Started { state, .. } | AppState::Headless { state, .. } => state
+                .add_objects(objects)
+                .into_iter()
+                .map(Completer::from_value)
+                .collect(),
         }
     }
 
@@ -382,33 +895,266 @@ impl App {
             AppState::Started {
                 renderer, state: _, ..
             } => Completer::from_value(renderer.new_texture(data)),
+            AppState::Headless { resource_ids, .. } => Completer::from_value(resource_ids.issue()),
+        }
+    }
+
+    pub fn background_config(&self) -> BackgroundConfig {
+        self.background
+    }
+
+    /// Sets how the app throttles rendering and input while its window is unfocused or hidden.
+    pub fn set_background_config(&mut self, config: BackgroundConfig) {
+        self.background = config;
+    }
+
+    pub fn frame_cadence(&self) -> FrameCadence {
+        frame_cadence(self.focused, self.occluded, &self.background)
+    }
+
+    fn in_background(&self) -> bool {
+        !self.focused || self.occluded
+    }
+
+    fn set_focused(&mut self, focused: bool) {
+        self.focused = focused;
+        if !focused && self.background.release_input {
+            self.input.release_all();
+        }
+    }
+
+    fn set_occluded(&mut self, occluded: bool) {
+        self.occluded = occluded;
+    }
+
+    /// Asks for the next frame as soon as the frame cadence allows.
+    fn schedule_frame(&self, event_loop: &ActiveEventLoop) {
+        let AppState::Started { renderer, .. } = &self.state else {
+            return;
+        };
+        match self.frame_cadence() {
+            FrameCadence::Continuous => {
+                event_loop.set_control_flow(ControlFlow::Poll);
+                renderer.window().request_redraw();
+            }
+            // the redraw is requested in new_events once the time is reached
+            FrameCadence::Throttled(frame_time) => {
+                event_loop.set_control_flow(ControlFlow::WaitUntil(Instant::now() + frame_time));
+            }
+            FrameCadence::Suspended => event_loop.set_control_flow(ControlFlow::Wait),
+        }
+    }
+
+    /// Sets the longest frame time passed to the tick hooks, see [DEFAULT_MAX_DT].
+    pub fn set_max_dt(&mut self, max_dt: Duration) {
+        self.max_dt = max_dt;
+    }
+
+    /// Sets the field of view and clip distances of cameras created from now on, the default camera and
+    /// player cameras. Cameras that already exist keep theirs.
+    pub fn set_projection_config(&mut self, config: ProjectionConfig) {
+        self.projection = config;
+    }
+
+    pub fn projection_config(&self) -> &ProjectionConfig {
+        &self.projection
+    }
+
+    pub fn scale_factor(&self) -> f64 {
+        self.scale_factor
+    }
+
+    /// Window size in physical pixels. Cursor positions and the render surface use this space.
+    pub fn physical_size(&self) -> PhysicalSize<u32> {
+        self.physical_size
+    }
+
+    /// Window size in logical pixels, i.e. the physical size divided by the scale factor.
+    pub fn logical_size(&self) -> LogicalSize<f64> {
+        self.physical_size.to_logical(self.scale_factor)
+    }
+
+    /// The renderer, once the app has started. Its device and queue can be used to create custom GPU
+    /// resources.
+    pub fn renderer(&self) -> Option<&Renderer> {
+        match &self.state {
+            AppState::NeedsInit(_) | AppState::Headless { .. } => None,
+            AppState::Started { renderer, .. } => Some(renderer),
+        }
+    }
+
+    pub fn renderer_mut(&mut self) -> Option<&mut Renderer> {
+        match &mut self.state {
+            AppState::NeedsInit(_) | AppState::Headless { .. } => None,
+            AppState::Started { renderer, .. } => Some(renderer),
+        }
+    }
+
+    /// Commands registered here can be run from the in-app console, opened with [CONSOLE_KEY].
+    pub fn console_mut(&mut self) -> &mut Console<App> {
+        &mut self.console
+    }
+
+    /// Runs a line as if it were entered in the console.
+    pub fn run_command(&mut self, line: &str) -> Result<(), ConsoleError> {
+        // taken out so commands can borrow the app mutably
+        let mut console = std::mem::take(&mut self.console);
+        let result = console.execute(self, line);
+        self.console = console;
+        result
+    }
+
+    /// Opens, closes or runs the console line, or edits it with the text InputController collects from the
+    /// key. Returns true if the key press was used by the console, which the rest of the app shouldn't see.
+    fn console_key(&mut self, event: &KeyEvent) -> bool {
+        if !event.state.is_pressed() {
+            return false;
+        }
+        let is_console_key = event.physical_key == PhysicalKey::Code(CONSOLE_KEY);
+        match (&mut self.console_line, is_console_key) {
+            (None, false) => return false,
+            (None, true) => self.console_line = Some(String::new()),
+            (Some(_), true) => self.console_line = None,
+            (Some(line), false) => match &event.logical_key {
+                Key::Named(NamedKey::Enter) => {
+                    let line = std::mem::take(line);
+                    self.console_line = None;
+                    match self.run_command(&line) {
+                        Ok(()) => info!("> {}", line),
+                        Err(e) => warn!("> {}: {:?}", line, e),
+                    }
+                }
+                Key::Named(NamedKey::Escape) => self.console_line = None,
+                _ => {
+                    self.input.push_key_text(event);
+                    apply_text_input(line, &self.input.take_text_input());
+                }
+            },
+        }
+        true
+    }
+
+    fn set_scale_factor(&mut self, scale_factor: f64) {
+        self.scale_factor = scale_factor;
+    }
+
+    /// Sets options used when the renderer is created. Has no effect once the app has started.
+    pub fn set_renderer_config(&mut self, config: RendererConfig) {
+        match &mut self.state {
+            AppState::NeedsInit(init_data) => init_data.renderer_config = config,
+            AppState::Started { .. } => {
+                warn!("Renderer config was set after the renderer was created, ignoring.")
+            }
+            AppState::Headless { .. } => {}
+        }
+    }
+
+    /// Switches the integrator DynamicsSystem uses, taking effect from the next tick.
+    pub fn set_integrator(&mut self, integrator: Integrator) {
+        match &mut self.state {
+            AppState::NeedsInit(init_data) => init_data.integrator = integrator,
+            AppState::Started { state, .. } | AppState::Headless { state, .. } => {
+                state.set_integrator(integrator)
+            }
+        }
+    }
+
+    pub fn integrator(&self) -> Integrator {
+        match &self.state {
+            AppState::NeedsInit(init_data) => init_data.integrator,
+            AppState::Started { state, .. } | AppState::Headless { state, .. } => {
+                state.integrator()
+            }
+        }
+    }
+
+    /// Background color of the 3D scene, see Renderer::set_clear_color.
+    pub fn set_clear_color(&mut self, clear_color: Color) {
+        match &mut self.state {
+            AppState::NeedsInit(init_data) => init_data.clear_color = clear_color,
+            AppState::Started { renderer, .. } => renderer.set_clear_color(clear_color),
+            AppState::Headless { .. } => {}
+        }
+    }
+
+    pub fn clear_color(&self) -> Color {
+        match &self.state {
+            AppState::NeedsInit(init_data) => init_data.clear_color,
+            AppState::Started { renderer, .. } => renderer.clear_color(),
+            AppState::Headless { .. } => DEFAULT_CLEAR_COLOR,
+        }
+    }
+
+    /// Replaces the default window icon. Has no effect once the app has started.
+    pub fn set_window_icon(&mut self, icon: DynamicImage) {
+        match &mut self.state {
+            AppState::NeedsInit(init_data) => init_data.window_icon = Some(icon),
+            AppState::Started { .. } => {
+                warn!("Window icon was set after the window was created, ignoring.")
+            }
+            AppState::Headless { .. } => {}
         }
     }
 }
 
 impl ApplicationHandler<Event> for App {
+    fn new_events(&mut self, _event_loop: &ActiveEventLoop, cause: StartCause) {
+        // a throttled frame is due
+        if let StartCause::ResumeTimeReached { .. } = cause
+            && let AppState::Started { renderer, .. } = &self.state
+        {
+            renderer.window().request_redraw();
+        }
+    }
+
     fn resumed(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
         if let AppState::NeedsInit(data) = &mut self.state {
             let mut old_data = AppInitData {
                 width: 0,
                 height: 0,
                 transform_meshes: vec![],
+                ui_meshes: vec![],
                 players: vec![],
                 objects: vec![],
                 textures: vec![],
+                renderer_config: RendererConfig::default(),
+                window_icon: None,
+                integrator: DEFAULT_INTEGRATOR,
+                clear_color: DEFAULT_CLEAR_COLOR,
             };
             std::mem::swap(&mut old_data, data);
-            let (size, mut meshes, mut players_init, mut textures, mut objects_init) =
-                old_data.inner();
+            let (
+                size,
+                mut meshes,
+                mut ui_meshes,
+                mut players_init,
+                mut textures,
+                mut objects_init,
+                renderer_config,
+                icon_image,
+                integrator,
+                clear_color,
+            ) = old_data.inner();
             let mut win_attr = Window::default_attributes();
             win_attr.inner_size = Some(Size::Physical(PhysicalSize::new(size.0, size.1)));
             win_attr.title = "Rover".into();
-            win_attr.window_icon = Some(Icon::from_rgba(ICON.to_vec(), 8, 8).unwrap());
+            win_attr.window_icon = window_icon(icon_image.as_ref());
             win_attr.visible = false;
 
             let window = Arc::new(event_loop.create_window(win_attr).unwrap());
+            self.physical_size = window.inner_size();
+            self.scale_factor = window.scale_factor();
 
-            let mut renderer = pollster::block_on(Renderer::new(window.clone()));
+            let mut renderer =
+                match pollster::block_on(Renderer::new(window.clone(), renderer_config)) {
+                    Ok(renderer) => renderer,
+                    Err(e) => {
+                        error!("Couldn't create the renderer, {}", e);
+                        event_loop.exit();
+                        return;
+                    }
+                };
+            renderer.set_clear_color(clear_color);
 
             info!("Adding meshes");
             while meshes.len() > 0 {
@@ -416,6 +1162,11 @@ impl ApplicationHandler<Event> for App {
                 let mesh_id = renderer.add_mesh_instanced(mesh).unwrap();
                 completer.complete(mesh_id).unwrap();
             }
+            while !ui_meshes.is_empty() {
+                let (mut completer, mesh) = ui_meshes.remove(0);
+                let mesh_id = renderer.add_ui_mesh(mesh).unwrap();
+                completer.complete(mesh_id).unwrap();
+            }
             info!("Adding textures");
             while textures.len() > 0 {
                 let (mut completer, texture_init) = textures.remove(0);
@@ -425,87 +1176,39 @@ impl ApplicationHandler<Event> for App {
 
             info!("Adding entities");
             let mut entities = vec![];
+            let mut ids = IdBank::new();
 
             while players_init.len() > 0 {
-                let (mut completer, entity) = players_init.remove(0);
-                let id = entities.len() as u64;
-                let player = Entity::new(
-                    id,
-                    entity.mesh_id.consume().unwrap(),
-                    entity.texture_id.consume().unwrap(),
-                    entity.scale,
-                    entity.rotation,
-                    entity.translation,
-                    entity.velocity,
-                    entity.acceleration,
-                    entity.bounding_box,
-                    EntityType::Player {
-                        camera: NoClipCamera::new(
-                            renderer.device(),
-                            renderer.camera_bind_group_layout(),
-                            entity.translation,
-                            0.0,
-                            0.0,
-                            0.0,
-                            Projection::new(
-                                renderer.config().width as f32,
-                                renderer.config().height as f32,
-                                90.0,
-                                0.1,
-                                10000.0,
-                            ),
-                        ),
-                    },
-                    entity.response,
-                    entity.mass,
-                );
-                entities.push(player);
+                let (mut completer, player_init) = players_init.remove(0);
+                let id = ids.issue();
+                entities.push(player_init.into_entity(id, &renderer, &self.projection));
                 completer.complete(id).unwrap();
             }
 
             while objects_init.len() > 0 {
                 let (mut completer, object_init) = objects_init.remove(0);
-                let id = entities.len() as u64;
-                let object = Entity::new(
-                    id,
-                    object_init.mesh_id.consume().unwrap(),
-                    object_init.texture_id.consume().unwrap(),
-                    object_init.scale,
-                    object_init.rotation,
-                    object_init.translation,
-                    object_init.velocity,
-                    object_init.acceleration,
-                    object_init.bounding_box,
-                    EntityType::Object,
-                    object_init.response,
-                    object_init.mass,
-                );
-
-                entities.push(object);
+                let id = ids.issue();
+                entities.push(object_init.into_entity(id));
                 completer.complete(id).unwrap();
             }
 
-            let mut active_state = ActiveState {
-                current_camera: NoClipCamera::new(
-                    renderer.device(),
-                    renderer.camera_bind_group_layout(),
-                    Vector3::identity(),
-                    0.0,
-                    0.0,
-                    0.0,
-                    Projection::new(
-                        renderer.config().width as f32,
-                        renderer.config().height as f32,
-                        90.0,
-                        0.1,
-                        10000.0,
-                    ),
+            let camera = NoClipCamera::new(
+                renderer.device(),
+                renderer.camera_bind_group_layout(),
+                Vector3::identity(),
+                0.0,
+                0.0,
+                0.0,
+                Projection::from_config(
+                    renderer.config().width as f32,
+                    renderer.config().height as f32,
+                    &self.projection,
                 ),
-                entities,
-                last_update: Instant::now(),
-            };
+            );
+            let mut active_state = ActiveState::new(Box::new(camera), entities, ids, integrator);
 
             renderer.update_instances(&mut active_state);
+            renderer.update_terrain(&mut self.world);
             renderer.update_gpu();
 
             {
@@ -536,23 +1239,40 @@ impl ApplicationHandler<Event> for App {
         _window_id: WindowId,
         event: WindowEvent,
     ) {
+        if let WindowEvent::KeyboardInput { event, .. } = &event
+            && self.console_key(event)
+        {
+            return;
+        }
         if let AppState::Started {
             renderer, state, ..
         } = &mut self.state
         {
             self.input
-                .window_event(&event, renderer.window(), &mut state.current_camera);
+                .window_event(&event, renderer.window(), state.current_camera.as_mut());
         }
 
         match event {
             WindowEvent::Resized(physical_size) => {
-                if let AppState::Started {
-                    renderer, state: _, ..
-                } = &mut self.state
-                {
+                self.physical_size = physical_size;
+                if let AppState::Started { renderer, state } = &mut self.state {
                     renderer.resize(physical_size.width, physical_size.height);
+                    // the current camera is uploaded with the next frame's instances
+                    state.resize_cameras(physical_size.width, physical_size.height);
                 }
             }
+            WindowEvent::Focused(focused) => {
+                self.set_focused(focused);
+                self.schedule_frame(event_loop);
+            }
+            WindowEvent::Occluded(occluded) => {
+                self.set_occluded(occluded);
+                self.schedule_frame(event_loop);
+            }
+            // A Resized event with the new physical size follows this.
+            WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                self.set_scale_factor(scale_factor);
+            }
             WindowEvent::CloseRequested | WindowEvent::Destroyed => {
                 info!("Started Shutdown");
                 {
@@ -566,10 +1286,21 @@ impl ApplicationHandler<Event> for App {
             }
 
             WindowEvent::RedrawRequested => {
+                let background_paused = self.background.pause_simulation && self.in_background();
                 if let AppState::Started { renderer, state } = &mut self.state {
-                    let elapsed_dur = state.last_update.elapsed();
+                    // nothing runs until the surface exists, so the first tick isn't simulated blind
+                    renderer.apply_pending_resize();
+                    if !surface_ready(renderer.is_ready(), state) {
+                        renderer.window().request_redraw();
+                        return;
+                    }
+
+                    let now = Instant::now();
+                    let elapsed_dur = frame_dt(state.last_update, now);
                     let elapsed = elapsed_dur.as_secs_f32();
-                    state.last_update = Instant::now();
+                    // input and render hooks still see the real frame time
+                    let tick_dur = clamp_dt(elapsed_dur, self.max_dt);
+                    state.last_update = Some(now);
 
                     // start redraw
                     {
@@ -579,10 +1310,12 @@ impl ApplicationHandler<Event> for App {
                             input: &self.input,
                         };
                         for system in self.systems.iter_mut() {
-                            system.before_input(&mut before_input);
+                            run_hook(&mut self.system_timings, system.as_mut(), |s| {
+                                s.before_input(&mut before_input)
+                            });
                         }
                     }
-                    self.input.update(elapsed, &mut state.current_camera);
+                    self.input.update(elapsed, state.current_camera.as_mut());
                     {
                         let mut handle_input = HandleInputArgs {
                             elapsed: &elapsed_dur,
@@ -590,44 +1323,23 @@ impl ApplicationHandler<Event> for App {
                             input: &self.input,
                         };
                         for system in self.systems.iter_mut() {
-                            system.handle_input(&mut handle_input);
+                            run_hook(&mut self.system_timings, system.as_mut(), |s| {
+                                s.handle_input(&mut handle_input)
+                            });
                         }
                     }
 
-                    {
-                        let mut before_tick = BeforeTickArgs {
-                            elapsed: &elapsed_dur,
+                    if !state.is_paused() && !background_paused {
+                        run_tick_hooks(
+                            &mut self.systems,
+                            &mut self.system_timings,
                             state,
-                            input: &self.input,
-                        };
-                        for system in self.systems.iter_mut() {
-                            system.before_tick(&mut before_tick);
-                        }
-                    }
-
-                    {
-                        let mut handle_tick = HandleTickArgs {
-                            elapsed: &elapsed_dur,
-                            state,
-                            input: &self.input,
-                        };
-                        for system in self.systems.iter_mut() {
-                            system.handle_tick(&mut handle_tick);
-                        }
-                    }
-
-                    {
-                        let mut after_tick = AfterTickArgs {
-                            elapsed: &elapsed_dur,
-                            state,
-                            input: &self.input,
-                        };
-                        for system in self.systems.iter_mut() {
-                            system.after_tick(&mut after_tick);
-                        }
+                            &self.input,
+                            &tick_dur,
+                        );
                     }
 
-                    state.update(elapsed, &mut self.world);
+                    state.update(tick_dur.as_secs_f32(), &mut self.world);
 
                     {
                         let mut before_render = BeforeRenderArgs {
@@ -636,11 +1348,20 @@ impl ApplicationHandler<Event> for App {
                             input: &self.input,
                         };
                         for system in self.systems.iter_mut() {
-                            system.before_render(&mut before_render);
+                            run_hook(&mut self.system_timings, system.as_mut(), |s| {
+                                s.before_render(&mut before_render)
+                            });
                         }
                     }
 
+                    if self.input.is_just_pressed(&SHADING_MODE_TOGGLE_KEY) {
+                        renderer.set_shading_mode(renderer.shading_mode().toggled());
+                    }
+                    if self.input.is_just_pressed(&WIREFRAME_TOGGLE_KEY) {
+                        renderer.set_wireframe(!renderer.wireframe());
+                    }
                     renderer.update_instances(state);
+                    renderer.update_terrain(&mut self.world);
                     renderer.update_gpu();
 
                     match renderer.render(state) {
@@ -655,7 +1376,32 @@ impl ApplicationHandler<Event> for App {
                             input: &self.input,
                         };
                         for system in self.systems.iter_mut() {
-                            system.after_render(&mut after_render);
+                            run_hook(&mut self.system_timings, system.as_mut(), |s| {
+                                s.after_render(&mut after_render)
+                            });
+                        }
+                    }
+                    // every system has seen this frame's input edges
+                    self.input.end_frame();
+                    if let Some(timings) = &mut self.system_timings {
+                        timings.end_frame();
+                        let gui_data = renderer.gui_data();
+                        if let Ok(mut gui_data) = gui_data.write() {
+                            gui_data.insert(
+                                "system_timings".into(),
+                                Value::Object(
+                                    timings
+                                        .breakdown()
+                                        .into_iter()
+                                        .map(|(name, per_frame)| {
+                                            (
+                                                name.to_owned(),
+                                                json!(per_frame.as_secs_f64() * 1000.0),
+                                            )
+                                        })
+                                        .collect(),
+                                ),
+                            );
                         }
                     }
                     {
@@ -691,6 +1437,12 @@ impl ApplicationHandler<Event> for App {
                             ]),
                         );
 
+                        match &self.console_line {
+                            Some(line) => {
+                                data.insert("console".into(), format!("> {}", line).into())
+                            }
+                            None => data.remove("console"),
+                        };
                         data.insert(
                             "v_position".into(),
                             Value::Array(vec![
@@ -701,10 +1453,744 @@ impl ApplicationHandler<Event> for App {
                         );
                     }
 
-                    renderer.window().request_redraw();
+                    self.schedule_frame(event_loop);
                 }
             }
             _ => {}
         }
     }
 }
+
+/// Commands available in every app's console.
+fn default_console() -> Console<App> {
+    let mut console = Console::<App>::new();
+    console.register("camera_speed", |app, args| {
+        let [speed] = parse_floats(args, "camera_speed <units per second>")?;
+        camera_controls(app)?.speed = speed;
+        Ok(())
+    });
+    console.register("sprint_multiplier", |app, args| {
+        let [multiplier] = parse_floats(args, "sprint_multiplier <multiplier>")?;
+        camera_controls(app)?.sprint_multiplier = multiplier;
+        Ok(())
+    });
+    console.register("max_dt", |app, args| {
+        let [max_dt] = parse_floats(args, "max_dt <seconds>")?;
+        let max_dt = Duration::try_from_secs_f32(max_dt)
+            .map_err(|_| ConsoleError::InvalidArguments("max_dt <seconds>".into()))?;
+        app.set_max_dt(max_dt);
+        Ok(())
+    });
+    console.register("clear_color", |app, args| {
+        let [r, g, b] = parse_floats(args, "clear_color <r> <g> <b>")?;
+        app.set_clear_color(Color {
+            r: r as f64,
+            g: g as f64,
+            b: b as f64,
+            a: 1.0,
+        });
+        Ok(())
+    });
+    console.register("shading", |app, args| {
+        let shading_mode = match args {
+            ["lit"] => ShadingMode::Lit,
+            ["normals"] => ShadingMode::Normals,
+            _ => {
+                return Err(ConsoleError::InvalidArguments(
+                    "shading <lit|normals>".into(),
+                ));
+            }
+        };
+        let AppState::Started { renderer, .. } = &mut app.state else {
+            return Err(ConsoleError::Unavailable(
+                "the renderer isn't created yet".into(),
+            ));
+        };
+        renderer.set_shading_mode(shading_mode);
+        Ok(())
+    });
+    console.register("frame_all", |app, args| {
+        if !args.is_empty() {
+            return Err(ConsoleError::InvalidArguments("frame_all".into()));
+        }
+        let AppState::Started { state, .. } = &mut app.state else {
+            return Err(ConsoleError::Unavailable("there is no camera yet".into()));
+        };
+        if !state.frame_all() {
+            return Err(ConsoleError::Unavailable("there are no entities".into()));
+        }
+        Ok(())
+    });
+    console.register("focus", |app, args| {
+        let usage = || ConsoleError::InvalidArguments("focus <entity id>".into());
+        let [id] = args else {
+            return Err(usage());
+        };
+        let id: u64 = id.parse().map_err(|_| usage())?;
+        let AppState::Started { state, .. } = &mut app.state else {
+            return Err(ConsoleError::Unavailable("there is no camera yet".into()));
+        };
+        if !state.focus_on(id) {
+            return Err(ConsoleError::Unavailable(format!(
+                "there is no entity {}",
+                id
+            )));
+        }
+        Ok(())
+    });
+    console.register("frustum_culling", |app, args| {
+        let frustum_culling = match args {
+            ["on"] => true,
+            ["off"] => false,
+            _ => {
+                return Err(ConsoleError::InvalidArguments(
+                    "frustum_culling <on|off>".into(),
+                ));
+            }
+        };
+        let AppState::Started { renderer, .. } = &mut app.state else {
+            return Err(ConsoleError::Unavailable(
+                "the renderer isn't created yet".into(),
+            ));
+        };
+        renderer.set_frustum_culling(frustum_culling);
+        Ok(())
+    });
+    console.register("wireframe", |app, args| {
+        let wireframe = match args {
+            ["on"] => true,
+            ["off"] => false,
+            _ => {
+                return Err(ConsoleError::InvalidArguments("wireframe <on|off>".into()));
+            }
+        };
+        let AppState::Started { renderer, .. } = &mut app.state else {
+            return Err(ConsoleError::Unavailable(
+                "the renderer isn't created yet".into(),
+            ));
+        };
+        if !renderer.set_wireframe(wireframe) {
+            return Err(ConsoleError::Unavailable(
+                "Features::POLYGON_MODE_LINE isn't enabled".into(),
+            ));
+        }
+        Ok(())
+    });
+    console.register("log", |_app, args| {
+        let usage = || {
+            ConsoleError::InvalidArguments("log <off|error|warn|info|debug|trace> [target]".into())
+        };
+        let (level, target) = match args {
+            [level] => (level, None),
+            [level, target] => (level, Some(*target)),
+            _ => return Err(usage()),
+        };
+        let level = level.parse::<LevelFilter>().map_err(|_| usage())?;
+        if !set_log_level(target, level) {
+            return Err(ConsoleError::Unavailable(
+                "logging isn't initialized".into(),
+            ));
+        }
+        Ok(())
+    });
+    console.register("integrator", |app, args| {
+        let integrator = match args {
+            ["euler"] => Integrator::Euler,
+            ["rk4"] => Integrator::RK4,
+            _ => {
+                return Err(ConsoleError::InvalidArguments(
+                    "integrator <euler|rk4>".into(),
+                ));
+            }
+        };
+        app.set_integrator(integrator);
+        Ok(())
+    });
+    console
+}
+
+fn camera_controls(app: &mut App) -> Result<&mut CameraControls, ConsoleError> {
+    let AppState::Started { state, .. } = &mut app.state else {
+        return Err(ConsoleError::Unavailable("there is no camera yet".into()));
+    };
+    state
+        .current_camera_mut()
+        .controls_mut()
+        .ok_or_else(|| ConsoleError::Unavailable("the camera has no controls".into()))
+}
+
+/// Runs the before_tick, handle_tick and after_tick hooks and entity scripts for [dt], then advances the
+/// simulation clock.
+fn run_tick_hooks(
+    systems: &mut [Box<dyn System>],
+    timings: &mut Option<SystemTimings>,
+    state: &mut ActiveState,
+    input: &InputController,
+    dt: &Duration,
+) {
+    {
+        let mut before_tick = BeforeTickArgs {
+            elapsed: dt,
+            state,
+            input,
+        };
+        for system in systems.iter_mut() {
+            run_hook(timings, system.as_mut(), |s| {
+                s.before_tick(&mut before_tick)
+            });
+        }
+    }
+    state.run_scripts(dt.as_secs_f32());
+
+    {
+        let mut handle_tick = HandleTickArgs {
+            elapsed: dt,
+            state,
+            input,
+        };
+        for system in systems.iter_mut() {
+            run_hook(timings, system.as_mut(), |s| {
+                s.handle_tick(&mut handle_tick)
+            });
+        }
+    }
+
+    {
+        let mut after_tick = AfterTickArgs {
+            elapsed: dt,
+            state,
+            input,
+        };
+        for system in systems.iter_mut() {
+            run_hook(timings, system.as_mut(), |s| s.after_tick(&mut after_tick));
+        }
+    }
+    state.clock.advance(dt.as_secs_f32());
+}
+
+/// Runs [hook] on [system], timing it if timings are enabled.
+fn run_hook(
+    timings: &mut Option<SystemTimings>,
+    system: &mut dyn System,
+    hook: impl FnOnce(&mut dyn System),
+) {
+    match timings {
+        Some(timings) => timings.time(system, hook),
+        None => hook(system),
+    }
+}
+
+/// RGBA bytes, width and height of [image] for use as a window icon. None if it has no pixels.
+fn icon_rgba(image: &DynamicImage) -> Option<(Vec<u8>, u32, u32)> {
+    if image.width() == 0 || image.height() == 0 {
+        return None;
+    }
+    Some((image.to_rgba8().into_raw(), image.width(), image.height()))
+}
+
+/// Window icon from [image], or the default ICON if there's no image or it can't be used.
+fn window_icon(image: Option<&DynamicImage>) -> Option<Icon> {
+    if let Some(image) = image {
+        match icon_rgba(image).map(|(rgba, width, height)| icon_from_rgba(&rgba, width, height)) {
+            Some(Ok(icon)) => return Some(icon),
+            Some(Err(e)) => warn!("Invalid window icon, using the default: {:?}", e),
+            None => warn!("Window icon has no pixels, using the default"),
+        }
+    }
+    load_icon()
+        .inspect_err(|e| error!("Default window icon is invalid: {:?}", e))
+        .ok()
+}
+
+/// How often to draw frames with the window [focused] or not, and [occluded] (hidden) or not.
+fn frame_cadence(focused: bool, occluded: bool, config: &BackgroundConfig) -> FrameCadence {
+    if occluded {
+        return FrameCadence::Suspended;
+    }
+    match (focused, config.unfocused_frame_time) {
+        (false, Some(frame_time)) => FrameCadence::Throttled(frame_time),
+        _ => FrameCadence::Continuous,
+    }
+}
+
+/// Time since the last frame was drawn at [last_update]. Zero on the first frame, since the time until then
+/// was spent setting up rather than simulating.
+fn frame_dt(last_update: Option<Instant>, now: Instant) -> Duration {
+    last_update.map_or(Duration::ZERO, |last| now.saturating_duration_since(last))
+}
+
+/// Whether a frame can run, given whether the renderer is ready. Until it is, the last update is forgotten so
+/// the first frame drawn doesn't simulate the time spent waiting for the surface.
+fn surface_ready(renderer_ready: bool, state: &mut ActiveState) -> bool {
+    if !renderer_ready {
+        state.last_update = None;
+    }
+    renderer_ready
+}
+
+/// Limits the time simulated in a single tick so that a long pause doesn't destabilize the simulation.
+fn clamp_dt(elapsed: Duration, max_dt: Duration) -> Duration {
+    elapsed.min(max_dt)
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use image::{DynamicImage, Rgba, RgbaImage};
+    use nalgebra::{UnitQuaternion, Vector3};
+    use wgpu::{
+        BindGroup, BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingType, BufferBindingType,
+        Color, ShaderStages,
+    };
+    use winit::dpi::{LogicalSize, PhysicalSize};
+
+    use crate::{
+        Integrator,
+        core::{
+            Completer, DEFAULT_CLEAR_COLOR, DEFAULT_INTEGRATOR, DEFAULT_MAX_DT,
+            DEFAULT_UNFOCUSED_FRAME_TIME, HandleTickArgs, IdBank, Instanced, SimClock, System,
+            Unique,
+            camera::{Camera, NoClipCamera, Projection},
+            console::ConsoleError,
+            entity::{
+                BoundingBox, CollisionResponse, Entity, EntityScripts, EntityType, MaterialKind,
+            },
+            input::InputController,
+            prefabs::{CollisionCallbacks, DefaultSystem},
+        },
+        render::{
+            app::{
+                ActiveState, App, AppState, BackgroundConfig, FrameCadence, ObjectInitData,
+                clamp_dt, frame_dt, icon_rgba, surface_ready, window_icon,
+            },
+            storage::instance::InstanceStorage,
+            test_device,
+            vertex::DefaultInstanceType,
+        },
+    };
+
+    #[test]
+    fn unfocused_window_throttled() {
+        let mut app = App::with_default_systems(800, 600, 0, &[]);
+        assert_eq!(app.frame_cadence(), FrameCadence::Continuous);
+
+        app.set_focused(false);
+        assert_eq!(
+            app.frame_cadence(),
+            FrameCadence::Throttled(DEFAULT_UNFOCUSED_FRAME_TIME)
+        );
+        app.set_occluded(true);
+        assert_eq!(app.frame_cadence(), FrameCadence::Suspended);
+        app.set_occluded(false);
+        app.set_focused(true);
+        assert_eq!(app.frame_cadence(), FrameCadence::Continuous);
+
+        app.set_background_config(BackgroundConfig {
+            unfocused_frame_time: None,
+            ..BackgroundConfig::default()
+        });
+        app.set_focused(false);
+        assert_eq!(app.frame_cadence(), FrameCadence::Continuous);
+        // hidden windows are never drawn
+        app.set_occluded(true);
+        assert_eq!(app.frame_cadence(), FrameCadence::Suspended);
+    }
+
+    #[test]
+    fn long_frames_are_clamped() {
+        let hitch = Duration::from_secs(3);
+        let frame = Duration::from_millis(16);
+
+        assert_eq!(clamp_dt(hitch, DEFAULT_MAX_DT), DEFAULT_MAX_DT);
+        assert_eq!(clamp_dt(frame, DEFAULT_MAX_DT), frame);
+    }
+
+    #[test]
+    fn first_frame_simulates_no_time() {
+        // as if setup took 5s before the first frame
+        let setup_start = Instant::now();
+        let first_frame = setup_start + Duration::from_secs(5);
+
+        assert_eq!(frame_dt(None, first_frame), Duration::ZERO);
+
+        let second_frame = first_frame + Duration::from_millis(16);
+        assert_eq!(
+            frame_dt(Some(first_frame), second_frame),
+            Duration::from_millis(16)
+        );
+    }
+
+    #[test]
+    fn app_without_audio() {
+        let app = App::with_default_systems(
+            800,
+            600,
+            0,
+            &[DefaultSystem::Dynamics, DefaultSystem::Gravity],
+        );
+        let names = app.system_names();
+
+        assert_eq!(names.len(), 2);
+        assert!(names.iter().any(|n| n.ends_with("DynamicsSystem")));
+        assert!(!names.iter().any(|n| n.ends_with("AudioSystem")));
+    }
+
+    #[test]
+    fn logical_size_follows_scale_factor() {
+        let mut app = App::with_default_systems(800, 600, 0, &[]);
+        assert_eq!(app.scale_factor(), 1.0);
+        assert_eq!(app.logical_size(), LogicalSize::new(800.0, 600.0));
+
+        app.set_scale_factor(2.0);
+
+        assert_eq!(app.scale_factor(), 2.0);
+        assert_eq!(app.physical_size(), PhysicalSize::new(800, 600));
+        assert_eq!(app.logical_size(), LogicalSize::new(400.0, 300.0));
+    }
+
+    #[test]
+    fn swapped_camera_is_used_for_rendering() {
+        let Some((device, _queue)) = test_device() else {
+            return;
+        };
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::VERTEX,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let camera = |position| {
+            Box::new(NoClipCamera::new(
+                &device,
+                &layout,
+                position,
+                0.0,
+                0.0,
+                0.0,
+                Projection::new(800.0, 600.0, 90.0, 0.1, 100.0),
+            ))
+        };
+        let mut state = ActiveState::new(
+            camera(Vector3::zeros()),
+            vec![],
+            IdBank::new(),
+            DEFAULT_INTEGRATOR,
+        );
+        let new_camera = camera(Vector3::new(0.0, 10.0, 0.0));
+        let new_bind_group: *const BindGroup = new_camera.bind_group();
+
+        let old_camera = state.set_camera(new_camera);
+
+        assert_eq!(old_camera.position(), &Vector3::zeros());
+        assert_eq!(
+            state.current_camera().position(),
+            &Vector3::new(0.0, 10.0, 0.0)
+        );
+        // the render passes bind the current camera's bind group
+        assert!(std::ptr::eq(
+            state.current_camera().bind_group(),
+            new_bind_group
+        ));
+    }
+
+    #[test]
+    fn integrator_switched_at_runtime() {
+        let mut app = App::with_default_systems(800, 600, 0, &[]);
+        assert_eq!(app.integrator(), DEFAULT_INTEGRATOR);
+        app.run_command("integrator rk4").unwrap();
+        assert_eq!(app.integrator(), Integrator::RK4);
+        app.set_integrator(Integrator::Euler);
+        assert_eq!(app.integrator(), Integrator::Euler);
+
+        let Some((device, _queue)) = test_device() else {
+            return;
+        };
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::VERTEX,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        // accelerating from rest, where the two integrators disagree
+        let falling = || {
+            Entity::new(
+                0,
+                0,
+                0,
+                Vector3::new(1.0, 1.0, 1.0),
+                UnitQuaternion::identity(),
+                Vector3::zeros(),
+                Vector3::zeros(),
+                Vector3::new(0.0, -10.0, 0.0),
+                BoundingBox::ZERO,
+                EntityType::Object,
+                CollisionResponse::Inelastic(1.0),
+                1.0,
+            )
+        };
+        let mut state = ActiveState::new(
+            Box::new(NoClipCamera::new(
+                &device,
+                &layout,
+                Vector3::zeros(),
+                0.0,
+                0.0,
+                0.0,
+                Projection::new(800.0, 600.0, 90.0, 0.1, 100.0),
+            )),
+            vec![],
+            IdBank::new(),
+            app.integrator(),
+        );
+        let input = InputController::new();
+        let elapsed = Duration::from_millis(500);
+        let mut dynamics = DefaultSystem::Dynamics.build();
+        let mut tick = |state: &mut ActiveState| {
+            state.entities = vec![falling()];
+            dynamics.handle_tick(&mut HandleTickArgs {
+                elapsed: &elapsed,
+                state,
+                input: &input,
+            });
+            *state.entities[0].translation()
+        };
+        let expected = |integrator| {
+            let mut entity = falling();
+            entity.integrate(elapsed.as_secs_f32(), &integrator);
+            *entity.translation()
+        };
+
+        let euler = tick(&mut state);
+        state.set_integrator(Integrator::RK4);
+        let rk4 = tick(&mut state);
+
+        assert_eq!(euler, expected(Integrator::Euler));
+        assert_eq!(rk4, expected(Integrator::RK4));
+        assert_ne!(euler, rk4);
+    }
+
+    #[test]
+    fn console_commands_run_against_app() {
+        let mut app = App::with_default_systems(800, 600, 0, &[]);
+
+        app.run_command("set max_dt 0.25").unwrap();
+        assert_eq!(app.max_dt, Duration::from_millis(250));
+        assert!(matches!(
+            app.run_command("max_dt -1"),
+            Err(ConsoleError::InvalidArguments(_))
+        ));
+
+        // needs a camera
+        assert!(matches!(
+            app.run_command("camera_speed 50"),
+            Err(ConsoleError::Unavailable(_))
+        ));
+    }
+
+    #[test]
+    fn clear_color_set_before_start() {
+        let mut app = App::with_default_systems(800, 600, 0, &[]);
+        assert_eq!(app.clear_color(), DEFAULT_CLEAR_COLOR);
+
+        app.run_command("clear_color 0.5 0.25 1").unwrap();
+
+        // kept until the renderer is created with it
+        let AppState::NeedsInit(init_data) = &app.state else {
+            panic!("app started");
+        };
+        let expected = Color {
+            r: 0.5,
+            g: 0.25,
+            b: 1.0,
+            a: 1.0,
+        };
+        assert_eq!(init_data.clear_color, expected);
+        assert_eq!(app.clear_color(), expected);
+    }
+
+    #[test]
+    fn objects_added_in_one_batch() {
+        let mut app = App::with_default_systems(800, 600, 0, &[]);
+        let object = |i: usize| ObjectInitData {
+            mesh_id: Completer::from_value(0),
+            texture_id: Completer::from_value(0),
+            velocity: Vector3::zeros(),
+            acceleration: Vector3::zeros(),
+            bounding_box: BoundingBox::ZERO,
+            scale: Vector3::new(1.0, 1.0, 1.0),
+            rotation: UnitQuaternion::identity(),
+            translation: Vector3::new(i as f32, 0.0, 0.0),
+            response: CollisionResponse::Immovable,
+            mass: 1.0,
+            friction: 0.0,
+            lifetime: None,
+            material: MaterialKind::Opaque,
+            emissive: 0.0,
+        };
+
+        let completers = app.add_objects((0..1000).map(object));
+
+        assert_eq!(completers.len(), 1000);
+        let AppState::NeedsInit(init_data) = &app.state else {
+            panic!("app shouldn't have started");
+        };
+        // queued in order, to be added when the app starts
+        assert_eq!(init_data.objects.len(), 1000);
+        assert_eq!(init_data.objects[999].1.translation.x, 999.0);
+    }
+
+    #[test]
+    fn frames_wait_for_surface() {
+        let mut app = App::new_headless(0, &[]);
+        let AppState::Headless { state, .. } = &mut app.state else {
+            panic!("app isn't headless");
+        };
+        let start = Instant::now();
+        state.last_update = Some(start);
+
+        // no tick runs before the surface is configured
+        let mut ticks = 0;
+        for _ in 0..3 {
+            if surface_ready(false, state) {
+                ticks += 1;
+            }
+        }
+        assert_eq!(ticks, 0);
+        assert_eq!(state.last_update, None);
+
+        // and the first frame after it doesn't simulate the wait
+        assert!(surface_ready(true, state));
+        let now = start + Duration::from_secs(5);
+        assert_eq!(frame_dt(state.last_update, now), Duration::ZERO);
+        state.last_update = Some(now);
+        assert!(surface_ready(true, state));
+        assert_eq!(state.last_update, Some(now));
+    }
+
+    #[test]
+    fn batch_uploaded_in_one_write() {
+        let Some((device, queue)) = test_device() else {
+            return;
+        };
+        let object = |i: usize| ObjectInitData {
+            mesh_id: Completer::from_value(0),
+            texture_id: Completer::from_value(0),
+            velocity: Vector3::zeros(),
+            acceleration: Vector3::zeros(),
+            bounding_box: BoundingBox::ZERO,
+            scale: Vector3::new(1.0, 1.0, 1.0),
+            rotation: UnitQuaternion::identity(),
+            translation: Vector3::new(i as f32, 0.0, 0.0),
+            response: CollisionResponse::Immovable,
+            mass: 1.0,
+            friction: 0.0,
+            lifetime: None,
+            material: MaterialKind::Opaque,
+            emissive: 0.0,
+        };
+        // the instances of the next frame, as Renderer::update_instances stores them
+        let upload = |storage: &mut InstanceStorage<DefaultInstanceType>, app: &App| {
+            let AppState::Headless { state, .. } = &app.state else {
+                panic!("app isn't headless");
+            };
+            for entity in state.entities() {
+                storage.upsert_instance(entity.id(), entity.instance());
+            }
+            storage.update_gpu(&queue, &device)
+        };
+
+        let mut app = App::new_headless(0, &[]);
+        let mut storage = InstanceStorage::new(&device);
+        app.add_objects((0..1000).map(object));
+        assert_eq!(upload(&mut storage, &app), 1);
+        // nothing changed since
+        assert_eq!(upload(&mut storage, &app), 0);
+
+        // one at a time, with a frame after each
+        let mut app = App::new_headless(0, &[]);
+        let mut storage = InstanceStorage::new(&device);
+        let writes: usize = (0..1000)
+            .map(|i| {
+                app.add_object(object(i));
+                upload(&mut storage, &app)
+            })
+            .sum();
+        assert_eq!(writes, 1000);
+    }
+
+    #[test]
+    fn headless_app_steps_systems() {
+        let mut app = App::new_headless(0, &[DefaultSystem::Dynamics]);
+        let id = app
+            .add_object(ObjectInitData {
+                mesh_id: Completer::from_value(0),
+                texture_id: Completer::from_value(0),
+                velocity: Vector3::new(1.0, 0.0, 0.0),
+                acceleration: Vector3::zeros(),
+                bounding_box: BoundingBox::ZERO,
+                scale: Vector3::new(1.0, 1.0, 1.0),
+                rotation: UnitQuaternion::identity(),
+                translation: Vector3::zeros(),
+                response: CollisionResponse::Inelastic(1.0),
+                mass: 1.0,
+                friction: 0.0,
+                lifetime: None,
+                material: MaterialKind::Opaque,
+                emissive: 0.0,
+            })
+            .consume()
+            .unwrap();
+
+        for _ in 0..60 {
+            app.step(Duration::from_secs_f32(1.0 / 60.0));
+        }
+
+        assert!(app.renderer().is_none());
+        let state = app.state().unwrap();
+        let entity = state.entities().iter().find(|e| *e.id() == id).unwrap();
+        assert!((entity.translation().x - 1.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn renderer_unavailable_before_start() {
+        let mut app = App::with_default_systems(800, 600, 0, &[]);
+
+        assert!(app.renderer().is_none());
+        assert!(app.renderer_mut().is_none());
+    }
+
+    #[test]
+    fn window_icon_from_image() {
+        let mut image = RgbaImage::new(2, 1);
+        image.put_pixel(0, 0, Rgba([10, 20, 30, 255]));
+        image.put_pixel(1, 0, Rgba([40, 50, 60, 0]));
+        let image = DynamicImage::ImageRgba8(image);
+
+        assert_eq!(
+            icon_rgba(&image),
+            Some((vec![10, 20, 30, 255, 40, 50, 60, 0], 2, 1))
+        );
+        assert!(window_icon(Some(&image)).is_some());
+
+        // an empty image falls back to the default instead of panicking
+        let empty = DynamicImage::new_rgba8(0, 0);
+        assert_eq!(icon_rgba(&empty), None);
+        assert!(window_icon(Some(&empty)).is_some());
+        assert!(window_icon(None).is_some());
+    }
+}
@@ -10,48 +10,101 @@
 //  - Fragment shader
 //  - Render Pipeline (draw order, face culling options, render configuration)
 
-use std::{collections::HashMap, io::Read, num::NonZero, ops::Deref};
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    num::NonZero,
+    ops::{Deref, Range},
+};
 
 use bytemuck::{Pod, Zeroable};
+use log::warn;
 use wgpu::{
     BindGroup, BindGroupLayout, ColorTargetState, DepthStencilState, Device, FragmentState,
-    IndexFormat, MultisampleState, PipelineCache, PipelineCompilationOptions,
-    PipelineLayoutDescriptor, PrimitiveState, Queue, RenderPass, RenderPipeline,
-    RenderPipelineDescriptor, ShaderModuleDescriptor, ShaderSource, VertexBufferLayout,
+    FrontFace, MultisampleState, PipelineCache, PipelineCompilationOptions, PipelineLayout,
+    PipelineLayoutDescriptor, PolygonMode, PrimitiveState, Queue, RenderPass, RenderPipeline,
+    RenderPipelineDescriptor, ShaderModule, ShaderModuleDescriptor, VertexBufferLayout,
     VertexState,
 };
 
 use crate::{
-    core::{Instanced, Meshed, Unique},
+    core::{Instanced, Meshed, Textured, Unique},
     render::{
         GLOBAL_INDEX_FORMAT,
         app::MeshInitData,
         storage::{
-            instance::InstanceStorage,
+            instance::{InstanceStorage, check_instance_stride},
             mesh::{MeshStorage, MeshStorageError},
+            textures::TextureStorage,
         },
     },
 };
 
 // Utility data types
 
+/// Mesh id and texture id of a set of instances drawn together. Instances without a texture are drawn with
+/// the bind groups passed to InstancedRenderModule::draw_all only.
+type InstanceGroup = (u64, Option<u64>);
+
+/// Texture bind group, instances, index bounds, instance range and winding of a draw.
+type Draw<'t, I> = (
+    Option<&'t BindGroup>,
+    &'t InstanceStorage<I>,
+    (usize, usize),
+    Range<u32>,
+    FrontFace,
+);
+
 pub struct VertexSpec {
     pub vertex_layout: VertexBufferLayout<'static>,
     pub instance_layout: VertexBufferLayout<'static>,
 }
 
+/// Where a shader's WGSL comes from.
+pub enum ShaderSource {
+    /// Read from this path, relative to the working directory, when the module is created.
+    Path(String),
+    /// Compiled into the binary, i.e. with include_str!, so it doesn't depend on the working directory.
+    Inline(&'static str),
+}
+
+impl ShaderSource {
+    pub fn load(&self) -> Result<Cow<'static, str>, ShaderLoadError> {
+        match self {
+            ShaderSource::Path(path) => load_shader(path).map(Cow::Owned),
+            ShaderSource::Inline(source) => Ok(Cow::Borrowed(*source)),
+        }
+    }
+}
+
 pub struct ShaderSpec {
-    pub path: String,
+    pub source: ShaderSource,
     pub vertex_shader_name: String,
     pub fragment_shader_name: String,
 }
 
+/// A shader file which couldn't be read, i.e. because the app isn't run from the crate's root.
+#[derive(Debug)]
+pub struct ShaderLoadError {
+    pub path: String,
+    pub error: std::io::Error,
+}
+
+/// Reads the WGSL source at [path].
+pub fn load_shader(path: &str) -> Result<String, ShaderLoadError> {
+    std::fs::read_to_string(path).map_err(|error| ShaderLoadError {
+        path: path.to_owned(),
+        error,
+    })
+}
+
 pub struct UniformSpec {
     pub bind_group_layout: BindGroupLayout,
 }
 
 /// Render pipeline configuration options that need to be specified manually in
 /// InstancedRenderModule::new.
+#[derive(Clone)]
 pub struct RenderPipelineSpec<'a> {
     pub fragment_color_target_state: Option<ColorTargetState>,
     pub primitive: PrimitiveState,
@@ -61,6 +114,62 @@ pub struct RenderPipelineSpec<'a> {
     pub cache: Option<&'a PipelineCache>,
 }
 
+/// Meshes along with the winding of their front faces. Each InstancedRenderModule has its own, or can draw
+/// others with InstancedRenderModule::draw_all_with so modules with different pipelines share one copy.
+pub struct ModuleMeshes<V>
+where
+    V: Pod + Zeroable + Clone + Copy + std::fmt::Debug,
+{
+    storage: MeshStorage<V>,
+    // Winding of each mesh's front faces
+    front_faces: HashMap<u64, FrontFace>,
+}
+
+impl<V> ModuleMeshes<V>
+where
+    V: Pod + Zeroable + Clone + Copy + std::fmt::Debug,
+{
+    pub fn new(device: &Device) -> Self {
+        Self {
+            storage: MeshStorage::new(device),
+            front_faces: HashMap::new(),
+        }
+    }
+
+    pub fn add_mesh(&mut self, mesh: MeshInitData<V>) -> Result<u64, MeshStorageError> {
+        let id = self.storage.add_mesh(&mesh.vertices, &mesh.indices)?;
+        self.front_faces.insert(id, mesh.front_face);
+        Ok(id)
+    }
+
+    /// Removes every mesh in [mesh_ids], compacting the storage once afterwards. Returns the ids which
+    /// existed.
+    pub fn remove_meshes(&mut self, mesh_ids: impl IntoIterator<Item = u64>) -> HashSet<u64> {
+        let removed: HashSet<u64> = mesh_ids
+            .into_iter()
+            .filter(|id| self.storage.remove_mesh(*id))
+            .collect();
+        if !removed.is_empty() {
+            self.storage.compact();
+            self.front_faces.retain(|id, _| !removed.contains(id));
+        }
+        removed
+    }
+
+    /// Winding of the mesh's front faces, None if there's no such mesh.
+    pub fn front_face(&self, mesh_id: &u64) -> Option<FrontFace> {
+        self.front_faces.get(mesh_id).copied()
+    }
+
+    pub fn storage(&self) -> &MeshStorage<V> {
+        &self.storage
+    }
+
+    pub fn update_gpu(&mut self, device: &Device, queue: &Queue) {
+        self.storage.update_gpu(queue, device);
+    }
+}
+
 /// Expects that the instance data comes after the vertex data in the shader.
 ///
 /// Main data type for managing instanced geometry.
@@ -73,9 +182,24 @@ where
     V: Pod + Zeroable + Clone + Copy + std::fmt::Debug,
     I: Pod + Zeroable + Clone + Copy + std::fmt::Debug,
 {
-    render_pipeline: RenderPipeline,
-    meshes: MeshStorage<V>,
-    instances: HashMap<u64, InstanceStorage<I>>,
+    // One per winding, meshes are drawn with the one matching their front faces
+    render_pipelines: HashMap<FrontFace, RenderPipeline>,
+    // Kept to rebuild the pipelines with another fragment shader
+    shader: ShaderModule,
+    render_pipeline_layout: PipelineLayout,
+    vertex_layouts: [VertexBufferLayout<'static>; 2],
+    vertex_shader_name: String,
+    fragment_shader_name: String,
+    pipeline_spec: RenderPipelineSpec<'static>,
+
+    meshes: ModuleMeshes<V>,
+    // Instances are stored the instance layout's array_stride apart
+    instance_stride: u64,
+    instances: HashMap<InstanceGroup, InstanceStorage<I>>,
+    // Group each entity's instance is stored in
+    entity_groups: HashMap<u64, InstanceGroup>,
+    // Entity ids drawn one at a time in this order instead of by group, if set
+    draw_order: Option<Vec<u64>>,
 }
 
 impl<V, I> InstancedRenderModule<V, I>
@@ -83,6 +207,7 @@ where
     V: Pod + Zeroable + Clone + Copy + std::fmt::Debug,
     I: Pod + Zeroable + Clone + Copy + std::fmt::Debug,
 {
+    /// Panics if the instance layout's array_stride can't hold an [I], see check_instance_stride.
     pub fn new<'a>(
         device: &Device,
         debug_name: Option<&str>,
@@ -90,15 +215,16 @@ where
         shader_spec: &ShaderSpec,
         uniform_specs: impl Iterator<Item = &'a UniformSpec>,
         pipeline_spec: &RenderPipelineSpec,
-    ) -> Result<Self, std::io::Error> {
-        let mut shader = String::new();
-        std::fs::File::open(&shader_spec.path)?
-            .read_to_string(&mut shader)
-            .unwrap();
+    ) -> Result<Self, ShaderLoadError> {
+        let instance_stride = vertex_spec.instance_layout.array_stride;
+        if let Err(e) = check_instance_stride::<I>(instance_stride) {
+            panic!("invalid instance layout: {:?}", e);
+        }
+        let shader = shader_spec.source.load()?;
 
         let shader = device.create_shader_module(ShaderModuleDescriptor {
             label: Some("Shader"),
-            source: ShaderSource::Wgsl(shader.into()),
+            source: wgpu::ShaderSource::Wgsl(shader),
         });
         let render_pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
             label: debug_name
@@ -109,104 +235,981 @@ where
                 .collect::<Vec<&BindGroupLayout>>(),
             push_constant_ranges: &[],
         });
-        let render_pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
-            label: Some("Render Pipeline"),
-            layout: Some(&render_pipeline_layout),
-            vertex: VertexState {
-                module: &shader,
-                entry_point: Some(&shader_spec.vertex_shader_name),
-                buffers: &[
-                    vertex_spec.vertex_layout.clone(),
-                    vertex_spec.instance_layout.clone(),
-                ],
-                compilation_options: PipelineCompilationOptions::default(),
-            },
-            fragment: Some(FragmentState {
-                module: &shader,
-                entry_point: Some(&shader_spec.fragment_shader_name),
-                targets: &[pipeline_spec.fragment_color_target_state.clone()],
-                compilation_options: PipelineCompilationOptions::default(),
-            }),
-            primitive: pipeline_spec.primitive,
-            depth_stencil: pipeline_spec.depth_stencil.clone(),
-            multisample: pipeline_spec.multisample,
-            multiview: pipeline_spec.multiview,
-            cache: pipeline_spec.cache,
-        });
+        let vertex_layouts = [
+            vertex_spec.vertex_layout.clone(),
+            vertex_spec.instance_layout.clone(),
+        ];
 
-        Ok(Self {
-            render_pipeline,
-            meshes: MeshStorage::new(device),
+        let mut module = Self {
+            render_pipelines: HashMap::new(),
+            shader,
+            render_pipeline_layout,
+            vertex_layouts,
+            vertex_shader_name: shader_spec.vertex_shader_name.clone(),
+            fragment_shader_name: shader_spec.fragment_shader_name.clone(),
+            pipeline_spec: RenderPipelineSpec {
+                cache: None,
+                ..pipeline_spec.clone()
+            },
+            meshes: ModuleMeshes::new(device),
+            instance_stride,
             instances: HashMap::new(),
-        })
+            entity_groups: HashMap::new(),
+            draw_order: None,
+        };
+        module.rebuild_pipeline(device);
+        Ok(module)
+    }
+
+    /// Primitive state (topology, culling) the render pipelines were created with. Its front_face is the
+    /// default winding, meshes wound otherwise are drawn with a pipeline of their own.
+    pub fn primitive(&self) -> &PrimitiveState {
+        &self.pipeline_spec.primitive
+    }
+
+    /// Winding of the mesh's front faces, None if the mesh isn't in this module.
+    pub fn front_face(&self, mesh_id: &u64) -> Option<FrontFace> {
+        self.meshes.front_face(mesh_id)
+    }
+
+    pub fn fragment_shader_name(&self) -> &str {
+        &self.fragment_shader_name
+    }
+
+    /// Rebuilds the render pipelines to use another fragment shader entry point from the same shader file.
+    /// Meshes and instances are kept.
+    pub fn set_fragment_shader(&mut self, device: &Device, fragment_shader_name: &str) {
+        if self.fragment_shader_name == fragment_shader_name {
+            return;
+        }
+        self.fragment_shader_name = fragment_shader_name.to_owned();
+        self.rebuild_pipeline(device);
+    }
+
+    /// Rebuilds the render pipelines to fill triangles or only draw their edges or corners. Anything but
+    /// PolygonMode::Fill needs the device to have been created with Features::POLYGON_MODE_LINE or
+    /// Features::POLYGON_MODE_POINT. Meshes and instances are kept.
+    pub fn set_polygon_mode(&mut self, device: &Device, polygon_mode: PolygonMode) {
+        if self.pipeline_spec.primitive.polygon_mode == polygon_mode {
+            return;
+        }
+        self.pipeline_spec.primitive.polygon_mode = polygon_mode;
+        self.rebuild_pipeline(device);
+    }
+
+    fn rebuild_pipeline(&mut self, device: &Device) {
+        for front_face in [FrontFace::Ccw, FrontFace::Cw] {
+            let pipeline = self.create_pipeline(device, front_face);
+            self.render_pipelines.insert(front_face, pipeline);
+        }
+    }
+
+    /// Pipeline options for meshes whose front faces are wound [front_face].
+    fn pipeline_spec_for(&self, front_face: FrontFace) -> RenderPipelineSpec<'static> {
+        RenderPipelineSpec {
+            primitive: PrimitiveState {
+                front_face,
+                ..self.pipeline_spec.primitive
+            },
+            ..self.pipeline_spec.clone()
+        }
+    }
+
+    fn create_pipeline(&self, device: &Device, front_face: FrontFace) -> RenderPipeline {
+        create_render_pipeline(
+            device,
+            &self.render_pipeline_layout,
+            &self.shader,
+            &self.vertex_layouts,
+            &self.vertex_shader_name,
+            &self.fragment_shader_name,
+            &self.pipeline_spec_for(front_face),
+        )
     }
 
-    /// Add mesh to this module. Mesh will only be valid in this render module.
+    /// Add mesh to this module. Mesh will only be valid in this render module. It's drawn with a pipeline
+    /// for its MeshInitData::front_face.
     pub fn add_mesh(
         &mut self,
         device: &Device,
         queue: &Queue,
         mesh: MeshInitData<V>,
     ) -> Result<u64, MeshStorageError> {
-        let id = self.meshes.add_mesh(&mesh.vertices, &mesh.indices)?;
-        self.instances.insert(id, InstanceStorage::new(device));
+        let id = self.meshes.add_mesh(mesh)?;
+        self.instances.insert(
+            (id, None),
+            new_instance_storage(device, self.instance_stride),
+        );
 
         Ok(id)
     }
 
-    pub fn upsert_instances(
+    /// Removes a mesh along with all of its instances, returning whether it existed. The mesh storage is
+    /// compacted right away.
+    pub fn remove_mesh(&mut self, mesh_id: u64) -> bool {
+        self.remove_meshes([mesh_id]) == 1
+    }
+
+    /// Removes every mesh in [mesh_ids] along with their instances, compacting the mesh storage once
+    /// afterwards. Returns how many existed.
+    pub fn remove_meshes(&mut self, mesh_ids: impl IntoIterator<Item = u64>) -> usize {
+        let removed = self.meshes.remove_meshes(mesh_ids);
+        self.remove_instances_of(&removed);
+        removed.len()
+    }
+
+    /// Removes every instance of the meshes in [mesh_ids], i.e. after removing them from the ModuleMeshes
+    /// this module is drawn with.
+    pub fn remove_instances_of(&mut self, mesh_ids: &HashSet<u64>) {
+        self.instances.retain(|(id, _), _| !mesh_ids.contains(id));
+        self.entity_groups
+            .retain(|_, (id, _)| !mesh_ids.contains(id));
+    }
+
+    /// Upserts instances drawn without a texture of their own.
+    pub fn upsert_instances<'e, E>(
         &mut self,
+        device: &Device,
         // TODO: Allow for adding of static instances which dont need an ID and never get referenced.
         // InstanceStorage will need to manage static and dynamic instances separately somehow.
-        entities: &Vec<impl Instanced<I> + Meshed<u64> + Unique<u64>>,
-    ) -> Result<(), String> {
+        entities: impl IntoIterator<Item = &'e E>,
+    ) -> Result<(), String>
+    where
+        E: Instanced<I> + Meshed<u64> + Unique<u64> + 'e,
+    {
         for entity in entities {
-            let mesh_id = entity.mesh_id();
-            let entity_id = entity.id();
+            self.upsert_instance(
+                device,
+                (*entity.mesh_id(), None),
+                entity.id(),
+                entity.instance(),
+            );
+        }
 
-            self.instances
-                .get_mut(mesh_id)
-                .unwrap()
-                .upsert_instance(entity_id, entity.instance());
+        Ok(())
+    }
+
+    /// Upserts instances grouped by their texture, which draw_all binds for each group.
+    pub fn upsert_textured_instances<'e, E>(
+        &mut self,
+        device: &Device,
+        entities: impl IntoIterator<Item = &'e E>,
+    ) -> Result<(), String>
+    where
+        E: Instanced<I> + Meshed<u64> + Textured<u64> + Unique<u64> + 'e,
+    {
+        for entity in entities {
+            self.upsert_instance(
+                device,
+                (*entity.mesh_id(), Some(*entity.texture_id())),
+                entity.id(),
+                entity.instance(),
+            );
         }
 
         Ok(())
     }
 
+    /// Moves the instance out of its previous group if its mesh or texture changed.
+    fn upsert_instance(
+        &mut self,
+        device: &Device,
+        group: InstanceGroup,
+        entity_id: &u64,
+        instance: I,
+    ) {
+        let previous = self.entity_groups.insert(*entity_id, group);
+        if let Some(previous) = previous.filter(|g| *g != group)
+            && let Some(storage) = self.instances.get_mut(&previous)
+        {
+            storage.remove_instance(entity_id);
+        }
+        self.instances
+            .entry(group)
+            .or_insert_with(|| new_instance_storage(device, self.instance_stride))
+            .upsert_instance(entity_id, instance);
+    }
+
+    /// Draws the instances of the given entities one at a time, in order, instead of a draw per group.
+    /// Entities left out aren't drawn. Needed by alpha blended modules, whose instances have to be drawn
+    /// back to front. None goes back to drawing by group.
+    pub fn set_draw_order(&mut self, entity_ids: Option<Vec<u64>>) {
+        self.draw_order = entity_ids;
+    }
+
+    pub fn remove_instance(&mut self, entity_id: &u64) -> Option<I> {
+        let group = self.entity_groups.remove(entity_id)?;
+        self.instances.get_mut(&group)?.remove_instance(entity_id)
+    }
+
     pub fn update_gpu(&mut self, device: &Device, queue: &Queue) {
-        self.meshes.update_gpu(queue, device);
+        self.meshes.update_gpu(device, queue);
         for (_id, instance) in self.instances.iter_mut() {
             instance.update_gpu(queue, device);
         }
     }
 
+    /// Binds [uniforms] in order, then draws every group of instances. If [textures] is given, each textured
+    /// group's texture (or the missing texture) is bound at its bind group index first.
     pub fn draw_all<'a>(
         &self,
         render_pass: &mut RenderPass,
         uniforms: impl Iterator<Item = &'a (impl Deref<Target = &'a BindGroup> + 'a)>, // TODO: May be too convoluted but works for now
+        textures: Option<(u32, &TextureStorage)>,
     ) {
-        render_pass.set_pipeline(&self.render_pipeline);
+        self.draw_all_with(render_pass, &self.meshes, uniforms, textures);
+    }
 
-        render_pass.set_vertex_buffer(0, self.meshes.vertex_slice(..));
-        render_pass.set_index_buffer(self.meshes.index_slice(..), GLOBAL_INDEX_FORMAT);
+    /// Same as draw_all, with the instances' mesh ids referring to [meshes] instead of this module's own.
+    pub fn draw_all_with<'a>(
+        &self,
+        render_pass: &mut RenderPass,
+        meshes: &ModuleMeshes<V>,
+        uniforms: impl Iterator<Item = &'a (impl Deref<Target = &'a BindGroup> + 'a)>,
+        textures: Option<(u32, &TextureStorage)>,
+    ) {
+        render_pass.set_vertex_buffer(0, meshes.storage().vertex_slice(..));
+        render_pass.set_index_buffer(meshes.storage().index_slice(..), GLOBAL_INDEX_FORMAT);
 
         for (i, bg) in uniforms.enumerate() {
             render_pass.set_bind_group(i as u32, Into::<&BindGroup>::into(**bg), &[]);
         }
 
-        for (mesh_id, storage) in self.instances.iter() {
-            if storage.len() > 0 {
+        let mut bound = None;
+        let mut bound_storage = None;
+        let mut bound_front_face = None;
+        for (texture, storage, (start, end), instances, front_face) in
+            self.draw_set(meshes, textures.map(|(_, t)| t))
+        {
+            // groups are sorted by winding too, so the pipeline changes at most once per winding
+            if bound_front_face != Some(front_face) {
+                render_pass.set_pipeline(&self.render_pipelines[&front_face]);
+                bound_front_face = Some(front_face);
+            }
+            if let (Some((index, _)), Some(texture)) = (textures, texture) {
+                // consecutive draws with the same texture only bind it once, groups are sorted by texture
+                if bound.is_none_or(|b| !std::ptr::eq(b, texture)) {
+                    render_pass.set_bind_group(index, texture, &[]);
+                    bound = Some(texture);
+                }
+            }
+            if bound_storage.is_none_or(|b| !std::ptr::eq(b, storage)) {
                 render_pass.set_vertex_buffer(1, storage.slice());
-                let (start, end) = self.meshes.get_mesh_index_bounds(&mesh_id).unwrap();
-                render_pass.draw_indexed(start as u32..end as u32, 0, 0..storage.len() as u32);
+                bound_storage = Some(storage);
             }
+            render_pass.draw_indexed(start as u32..end as u32, 0, instances);
+        }
+    }
+
+    /// Texture bind group, instances, index bounds, instance range and winding of each draw. Without a
+    /// draw order that's one draw per group with instances, sorted by winding and then texture. Textures
+    /// are only looked up in [textures] if given. Instances of meshes which aren't in [meshes] are skipped
+    /// (and logged) instead of drawn.
+    fn draw_set<'t>(
+        &'t self,
+        meshes: &ModuleMeshes<V>,
+        textures: Option<&'t TextureStorage>,
+    ) -> Vec<Draw<'t, I>> {
+        let texture_of = |texture_id: Option<u64>| {
+            textures
+                .zip(texture_id)
+                .map(|(textures, id)| &textures.get_or_missing(&id).3)
+        };
+        let default_front_face = self.pipeline_spec.primitive.front_face;
+        let front_face_of =
+            |mesh_id: &u64| meshes.front_face(mesh_id).unwrap_or(default_front_face);
+        if let Some(order) = &self.draw_order {
+            return order
+                .iter()
+                .filter_map(|entity_id| {
+                    let group = self.entity_groups.get(entity_id)?;
+                    let storage = self.instances.get(group)?;
+                    let slot = storage.slot(entity_id)? as u32;
+                    let bounds = meshes.storage().get_mesh_index_bounds(&group.0)?;
+                    Some((
+                        texture_of(group.1),
+                        storage,
+                        bounds,
+                        slot..slot + 1,
+                        front_face_of(&group.0),
+                    ))
+                })
+                .collect();
         }
+        let mut groups: Vec<(&InstanceGroup, &InstanceStorage<I>)> = self
+            .instances
+            .iter()
+            .filter(|(_, storage)| storage.len() > 0)
+            .collect();
+        groups.sort_by_key(|((mesh_id, texture_id), _)| {
+            (
+                front_face_of(mesh_id) != default_front_face,
+                *texture_id,
+                *mesh_id,
+            )
+        });
+        groups
+            .into_iter()
+            .filter_map(|((mesh_id, texture_id), storage)| {
+                let Some(bounds) = meshes.storage().get_mesh_index_bounds(mesh_id) else {
+                    warn!(
+                        "skipping {} instances of missing mesh {}",
+                        storage.len(),
+                        mesh_id
+                    );
+                    return None;
+                };
+                Some((
+                    texture_of(*texture_id),
+                    storage,
+                    bounds,
+                    0..storage.len() as u32,
+                    front_face_of(mesh_id),
+                ))
+            })
+            .collect()
     }
 }
 
+// [stride] is checked in InstancedRenderModule::new
+fn new_instance_storage<I: Pod + Zeroable + Clone + Copy + std::fmt::Debug>(
+    device: &Device,
+    stride: u64,
+) -> InstanceStorage<I> {
+    InstanceStorage::with_stride(device, stride).expect("instance stride was checked")
+}
+
+fn create_render_pipeline(
+    device: &Device,
+    layout: &PipelineLayout,
+    shader: &ShaderModule,
+    vertex_layouts: &[VertexBufferLayout<'static>],
+    vertex_shader_name: &str,
+    fragment_shader_name: &str,
+    pipeline_spec: &RenderPipelineSpec,
+) -> RenderPipeline {
+    device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: Some("Render Pipeline"),
+        layout: Some(layout),
+        vertex: VertexState {
+            module: shader,
+            entry_point: Some(vertex_shader_name),
+            buffers: vertex_layouts,
+            compilation_options: PipelineCompilationOptions::default(),
+        },
+        fragment: Some(FragmentState {
+            module: shader,
+            entry_point: Some(fragment_shader_name),
+            targets: std::slice::from_ref(&pipeline_spec.fragment_color_target_state),
+            compilation_options: PipelineCompilationOptions::default(),
+        }),
+        primitive: pipeline_spec.primitive,
+        depth_stencil: pipeline_spec.depth_stencil.clone(),
+        multisample: pipeline_spec.multisample,
+        multiview: pipeline_spec.multiview,
+        cache: pipeline_spec.cache,
+    })
+}
+
 #[allow(unused_imports)]
 mod tests {
     use assertables::assert_abs_diff_lt_x;
+    use bytemuck::Zeroable;
+    use image::{DynamicImage, imageops::FilterType};
+    use nalgebra::Matrix4;
+    use wgpu::{
+        BindGroupDescriptor, BindGroupEntry, BindGroupLayoutDescriptor, BindGroupLayoutEntry,
+        BindingType, BufferBindingType, BufferUsages, Color, CommandEncoderDescriptor, Device,
+        Extent3d, Face, FrontFace, LoadOp, MultisampleState, Operations, PolygonMode,
+        PrimitiveState, PrimitiveTopology, Queue, RenderPassColorAttachment, RenderPassDescriptor,
+        SamplerBindingType, ShaderStages, StoreOp, TextureDescriptor, TextureDimension,
+        TextureFormat, TextureSampleType, TextureUsages, TextureViewDescriptor,
+        TextureViewDimension,
+        util::{BufferInitDescriptor, DeviceExt},
+    };
+
+    use crate::{
+        core::{Instanced, Meshed, Textured, Unique},
+        render::{
+            app::{MeshInitData, TextureInitData},
+            module::{
+                InstancedRenderModule, ModuleMeshes, RenderPipelineSpec, ShaderSource, ShaderSpec,
+                UniformSpec, VertexSpec,
+            },
+            renderer::capture_texture,
+            storage::{
+                instance::InstanceStorage,
+                textures::{ResizeStrategy, TextureKind, TextureStorage},
+            },
+            test_device,
+            vertex::{
+                MarkerInstanceType, MarkerVertexType,
+                marker::{MARKER_INDICES, MARKER_VERTICES},
+            },
+        },
+    };
+
+    /// Marker module drawing to an Rgba8UnormSrgb target, without depth.
+    #[cfg(test)]
+    fn marker_module(
+        device: &Device,
+        primitive: PrimitiveState,
+    ) -> InstancedRenderModule<MarkerVertexType, MarkerInstanceType> {
+        let camera_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::VERTEX,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        InstancedRenderModule::<MarkerVertexType, MarkerInstanceType>::new(
+            device,
+            None,
+            &VertexSpec {
+                vertex_layout: MarkerVertexType::vertex_desc(),
+                instance_layout: MarkerVertexType::instance_desc(),
+            },
+            &ShaderSpec {
+                source: ShaderSource::Path("src/render/shaders/marker.wgsl".into()),
+                vertex_shader_name: "vs_main".into(),
+                fragment_shader_name: "fs_main".into(),
+            },
+            [UniformSpec {
+                bind_group_layout: camera_layout,
+            }]
+            .iter(),
+            &RenderPipelineSpec {
+                fragment_color_target_state: Some(TextureFormat::Rgba8UnormSrgb.into()),
+                primitive,
+                depth_stencil: None,
+                multisample: MultisampleState::default(),
+                multiview: None,
+                cache: None,
+            },
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn module_without_culling() {
+        let Some((device, queue)) = test_device() else {
+            return;
+        };
+        let primitive = PrimitiveState {
+            topology: PrimitiveTopology::TriangleList,
+            cull_mode: None,
+            polygon_mode: PolygonMode::Fill,
+            ..Default::default()
+        };
+        let mut module = marker_module(&device, primitive);
+
+        // the pipelines of both windings are built without culling
+        for front_face in [FrontFace::Ccw, FrontFace::Cw] {
+            assert_eq!(
+                module.pipeline_spec_for(front_face).primitive.cull_mode,
+                None
+            );
+        }
+
+        // so a triangle facing away is still drawn
+        let back_facing = [[-1.0, -1.0], [0.0, 1.0], [1.0, -1.0]]
+            .into_iter()
+            .map(|[x, y]| MarkerVertexType {
+                position: [x, y, 0.0],
+                color: [1.0, 1.0, 1.0],
+                _padding: [0.0; 2],
+            })
+            .collect();
+        let mesh_id = module
+            .add_mesh(
+                &device,
+                &queue,
+                MeshInitData {
+                    vertices: back_facing,
+                    indices: vec![0, 1, 2],
+                    front_face: FrontFace::Ccw,
+                },
+            )
+            .unwrap();
+        let identity = MarkerInstanceType {
+            x: [1.0, 0.0, 0.0, 0.0],
+            y: [0.0, 1.0, 0.0, 0.0],
+            z: [0.0, 0.0, 1.0, 0.0],
+            w: [0.0, 0.0, 0.0, 1.0],
+        };
+        module.upsert_instance(&device, (mesh_id, None), &0, identity);
+        module.update_gpu(&device, &queue);
+        assert_eq!(center_pixel(&device, &queue, &module), [255, 255, 255, 255]);
+    }
+
+    /// Draws [module] into a small Rgba8UnormSrgb target cleared to black, with an identity camera, and
+    /// returns the pixel in the middle.
+    #[cfg(test)]
+    fn center_pixel(
+        device: &Device,
+        queue: &Queue,
+        module: &InstancedRenderModule<MarkerVertexType, MarkerInstanceType>,
+    ) -> [u8; 4] {
+        let texture = device.create_texture(&TextureDescriptor {
+            label: None,
+            size: Extent3d {
+                width: 8,
+                height: 8,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Rgba8UnormSrgb,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&TextureViewDescriptor::default());
+        let camera = device.create_buffer_init(&BufferInitDescriptor {
+            label: None,
+            contents: bytemuck::cast_slice(Matrix4::<f32>::identity().as_slice()),
+            usage: BufferUsages::UNIFORM,
+        });
+        let camera_bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout: &module.render_pipelines[&module.primitive().front_face]
+                .get_bind_group_layout(0),
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: camera.as_entire_binding(),
+            }],
+        });
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor { label: None });
+        {
+            let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                label: None,
+                color_attachments: &[Some(RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: Operations {
+                        load: LoadOp::Clear(Color::BLACK),
+                        store: StoreOp::Store,
+                    },
+                    depth_slice: None,
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+            module.draw_all(&mut render_pass, [&&camera_bind_group].iter(), None);
+        }
+        queue.submit(std::iter::once(encoder.finish()));
+
+        capture_texture(device, queue, &texture)
+            .unwrap()
+            .get_pixel(4, 4)
+            .0
+    }
+
+    #[test]
+    fn clockwise_mesh_drawn_with_cw_pipeline() {
+        let Some((device, queue)) = test_device() else {
+            return;
+        };
+        // a triangle over the middle of the target, as an exporter using clockwise winding would write it
+        let clockwise: Vec<MarkerVertexType> = [[-1.0, -1.0], [0.0, 1.0], [1.0, -1.0]]
+            .into_iter()
+            .map(|[x, y]| MarkerVertexType {
+                position: [x, y, 0.0],
+                color: [1.0, 1.0, 1.0],
+                _padding: [0.0; 2],
+            })
+            .collect();
+        let identity = MarkerInstanceType {
+            x: [1.0, 0.0, 0.0, 0.0],
+            y: [0.0, 1.0, 0.0, 0.0],
+            z: [0.0, 0.0, 1.0, 0.0],
+            w: [0.0, 0.0, 0.0, 1.0],
+        };
+        let mut center = vec![];
+        let mut draw_front_faces = vec![];
+        for front_face in [FrontFace::Ccw, FrontFace::Cw] {
+            let mut module = marker_module(
+                &device,
+                PrimitiveState {
+                    cull_mode: Some(Face::Back),
+                    ..Default::default()
+                },
+            );
+            let mesh_id = module
+                .add_mesh(
+                    &device,
+                    &queue,
+                    MeshInitData {
+                        vertices: clockwise.clone(),
+                        indices: vec![0, 1, 2],
+                        front_face,
+                    },
+                )
+                .unwrap();
+            module.upsert_instance(&device, (mesh_id, None), &0, identity);
+            module.update_gpu(&device, &queue);
+
+            assert_eq!(module.front_face(&mesh_id), Some(front_face));
+            draw_front_faces.push(module.draw_set(&module.meshes, None)[0].4);
+            assert_eq!(
+                module.pipeline_spec_for(front_face).primitive.cull_mode,
+                Some(Face::Back)
+            );
+            center.push(center_pixel(&device, &queue, &module));
+        }
+
+        assert_eq!(draw_front_faces, vec![FrontFace::Ccw, FrontFace::Cw]);
+        // culled as a back face under the default winding
+        assert_eq!(center[0], [0, 0, 0, 255]);
+        assert_eq!(center[1], [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn meshes_of_both_windings_in_one_module() {
+        let Some((device, queue)) = test_device() else {
+            return;
+        };
+        let mut module = marker_module(&device, PrimitiveState::default());
+        let mut mesh_ids = vec![];
+        for front_face in [FrontFace::Cw, FrontFace::Ccw] {
+            let mesh_id = module
+                .add_mesh(
+                    &device,
+                    &queue,
+                    MeshInitData {
+                        vertices: MARKER_VERTICES([1.0, 0.0, 0.0].into()),
+                        indices: MARKER_INDICES.to_vec(),
+                        front_face,
+                    },
+                )
+                .unwrap();
+            module.upsert_instance(
+                &device,
+                (mesh_id, None),
+                &mesh_id,
+                MarkerInstanceType::zeroed(),
+            );
+            mesh_ids.push(mesh_id);
+        }
+
+        let draw_set = module.draw_set(&module.meshes, None);
+
+        assert_eq!(module.render_pipelines.len(), 2);
+        // the default winding's pipeline is bound first
+        assert_eq!(draw_set[0].4, FrontFace::Ccw);
+        assert!(draw_set[0].1.get_instance(&mesh_ids[1]).is_some());
+        assert_eq!(draw_set[1].4, FrontFace::Cw);
+
+        module.remove_mesh(mesh_ids[0]);
+
+        assert_eq!(module.front_face(&mesh_ids[0]), None);
+        assert_eq!(module.front_face(&mesh_ids[1]), Some(FrontFace::Ccw));
+    }
+
+    #[test]
+    fn modules_draw_shared_meshes() {
+        let Some((device, _queue)) = test_device() else {
+            return;
+        };
+        let mut meshes = ModuleMeshes::new(&device);
+        let mesh_id = meshes
+            .add_mesh(MeshInitData {
+                vertices: MARKER_VERTICES([1.0, 0.0, 0.0].into()),
+                indices: MARKER_INDICES.to_vec(),
+                front_face: FrontFace::Ccw,
+            })
+            .unwrap();
+        let mut modules = [
+            marker_module(&device, PrimitiveState::default()),
+            marker_module(&device, PrimitiveState::default()),
+        ];
+        for (entity_id, module) in modules.iter_mut().enumerate() {
+            module.upsert_instance(
+                &device,
+                (mesh_id, None),
+                &(entity_id as u64),
+                MarkerInstanceType::zeroed(),
+            );
+        }
+
+        for (entity_id, module) in modules.iter().enumerate() {
+            let draw_set = module.draw_set(&meshes, None);
+
+            // the modules only hold instances
+            assert!(module.meshes.storage().is_empty());
+            assert_eq!(draw_set.len(), 1);
+            assert!(draw_set[0].1.get_instance(&(entity_id as u64)).is_some());
+            assert_eq!(
+                draw_set[0].2,
+                meshes.storage().get_mesh_index_bounds(&mesh_id).unwrap()
+            );
+        }
+
+        let removed = meshes.remove_meshes([mesh_id]);
+        for module in modules.iter_mut() {
+            module.remove_instances_of(&removed);
+
+            assert!(module.draw_set(&meshes, None).is_empty());
+        }
+    }
+
+    #[test]
+    fn instances_of_missing_mesh_skipped() {
+        let Some((device, queue)) = test_device() else {
+            return;
+        };
+        let mut module = marker_module(&device, PrimitiveState::default());
+        let mesh_id = module
+            .add_mesh(
+                &device,
+                &queue,
+                MeshInitData {
+                    vertices: MARKER_VERTICES([1.0, 0.0, 0.0].into()),
+                    indices: MARKER_INDICES.to_vec(),
+                    front_face: FrontFace::Ccw,
+                },
+            )
+            .unwrap();
+        module
+            .instances
+            .get_mut(&(mesh_id, None))
+            .unwrap()
+            .upsert_instance(&0, MarkerInstanceType::zeroed());
+        // desynced, as if the mesh had been removed but not its instances
+        let mut orphans = InstanceStorage::new(&device);
+        orphans.upsert_instance(&1, MarkerInstanceType::zeroed());
+        module.instances.insert((mesh_id + 1, None), orphans);
+
+        let draw_set = module.draw_set(&module.meshes, None);
+
+        assert_eq!(draw_set.len(), 1);
+        assert!(draw_set[0].1.get_instance(&0).is_some());
+        assert_eq!(
+            draw_set[0].2,
+            module
+                .meshes
+                .storage()
+                .get_mesh_index_bounds(&mesh_id)
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn meshes_removed_together() {
+        let Some((device, queue)) = test_device() else {
+            return;
+        };
+        let mut module = marker_module(&device, PrimitiveState::default());
+        let ids: Vec<u64> = (0..3)
+            .map(|_| {
+                module
+                    .add_mesh(
+                        &device,
+                        &queue,
+                        MeshInitData {
+                            vertices: MARKER_VERTICES([1.0, 0.0, 0.0].into()),
+                            indices: MARKER_INDICES.to_vec(),
+                            front_face: FrontFace::Ccw,
+                        },
+                    )
+                    .unwrap()
+            })
+            .collect();
+
+        // the last id was never added
+        assert_eq!(module.remove_meshes([ids[0], ids[2], ids[2] + 1]), 2);
+
+        assert_eq!(module.meshes.storage().dead_len(), (0, 0));
+        assert_eq!(module.meshes.storage().len(), 1);
+        assert!(module.meshes.storage().get_mesh(&ids[1]).is_some());
+        assert!(module.instances.contains_key(&(ids[1], None)));
+        assert!(!module.instances.contains_key(&(ids[0], None)));
+        assert!(!module.remove_mesh(ids[0]));
+    }
+
+    #[cfg(test)]
+    struct Sprite {
+        id: u64,
+        mesh_id: u64,
+        texture_id: u64,
+    }
+
+    #[cfg(test)]
+    impl Instanced<MarkerInstanceType> for Sprite {
+        fn instance(&self) -> MarkerInstanceType {
+            MarkerInstanceType::zeroed()
+        }
+    }
+
+    #[cfg(test)]
+    impl Unique<u64> for Sprite {
+        fn id(&self) -> &u64 {
+            &self.id
+        }
+    }
+
+    #[cfg(test)]
+    impl Meshed<u64> for Sprite {
+        fn mesh_id(&self) -> &u64 {
+            &self.mesh_id
+        }
+    }
+
+    #[cfg(test)]
+    impl Textured<u64> for Sprite {
+        fn texture_id(&self) -> &u64 {
+            &self.texture_id
+        }
+    }
+
+    #[test]
+    fn instances_drawn_with_their_texture() {
+        let Some((mut device, mut queue)) = test_device() else {
+            return;
+        };
+        let texture_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let mut textures = TextureStorage::new();
+        textures.add_missing_texture(&device, &queue, &texture_layout);
+        let mut add_texture = || {
+            textures.new_texture(
+                &mut device,
+                &mut queue,
+                TextureInitData {
+                    image: DynamicImage::new_rgba8(2, 2),
+                    resize: ResizeStrategy::Stretch(FilterType::Nearest),
+                    kind: TextureKind::Color,
+                    size: None,
+                },
+                &texture_layout,
+            )
+        };
+        let (grass, stone) = (add_texture(), add_texture());
+
+        let mut module = marker_module(&device, PrimitiveState::default());
+        let mesh_id = module
+            .add_mesh(
+                &device,
+                &queue,
+                MeshInitData {
+                    vertices: MARKER_VERTICES([1.0, 0.0, 0.0].into()),
+                    indices: MARKER_INDICES.to_vec(),
+                    front_face: FrontFace::Ccw,
+                },
+            )
+            .unwrap();
+        let sprite = |id, texture_id| Sprite {
+            id,
+            mesh_id,
+            texture_id,
+        };
+        module
+            .upsert_textured_instances(
+                &device,
+                &[sprite(0, grass), sprite(1, stone), sprite(2, grass)],
+            )
+            .unwrap();
+
+        let draw_set = module.draw_set(&module.meshes, Some(&textures));
+        let ids = |group: usize| -> Vec<bool> {
+            (0..3)
+                .map(|id| draw_set[group].1.get_instance(&id).is_some())
+                .collect()
+        };
+        assert_eq!(draw_set.len(), 2);
+        // sorted by texture id
+        assert!(std::ptr::eq(
+            draw_set[0].0.unwrap(),
+            &textures.get(&grass).unwrap().3
+        ));
+        assert_eq!(ids(0), vec![true, false, true]);
+        assert!(std::ptr::eq(
+            draw_set[1].0.unwrap(),
+            &textures.get(&stone).unwrap().3
+        ));
+        assert_eq!(ids(1), vec![false, true, false]);
+
+        // changing texture moves the instance between groups
+        module
+            .upsert_textured_instances(&device, &[sprite(1, grass)])
+            .unwrap();
+        assert!(module.remove_instance(&2).is_some());
+        let draw_set = module.draw_set(&module.meshes, Some(&textures));
+        assert_eq!(draw_set.len(), 1);
+        assert_eq!(draw_set[0].1.len(), 2);
+    }
+
+    #[test]
+    fn draw_order_draws_instances_one_at_a_time() {
+        let Some((device, queue)) = test_device() else {
+            return;
+        };
+        let mut module = marker_module(&device, PrimitiveState::default());
+        let mesh_id = module
+            .add_mesh(
+                &device,
+                &queue,
+                MeshInitData {
+                    vertices: MARKER_VERTICES([1.0, 0.0, 0.0].into()),
+                    indices: MARKER_INDICES.to_vec(),
+                    front_face: FrontFace::Ccw,
+                },
+            )
+            .unwrap();
+        let sprites: Vec<Sprite> = (0..3)
+            .map(|id| Sprite {
+                id,
+                mesh_id,
+                texture_id: 0,
+            })
+            .collect();
+        module.upsert_instances(&device, &sprites).unwrap();
+        let bounds = module
+            .meshes
+            .storage()
+            .get_mesh_index_bounds(&mesh_id)
+            .unwrap();
+
+        // unknown ids are skipped
+        module.set_draw_order(Some(vec![2, 5, 0]));
+        let draw_set = module.draw_set(&module.meshes, None);
+
+        assert_eq!(draw_set.len(), 2);
+        assert_eq!((draw_set[0].2, draw_set[0].3.clone()), (bounds, 2..3));
+        assert_eq!((draw_set[1].2, draw_set[1].3.clone()), (bounds, 0..1));
+
+        module.set_draw_order(None);
+        let draw_set = module.draw_set(&module.meshes, None);
+
+        assert_eq!(draw_set.len(), 1);
+        assert_eq!(draw_set[0].3, 0..3);
+    }
 
     #[test]
     fn cast_slice_equivalence() {
@@ -1,6 +1,8 @@
 use egui::{Color32, RichText};
 use egui_wgpu::{RendererOptions, ScreenDescriptor};
-use nalgebra::Vector3;
+use image::{Rgba, RgbaImage};
+use log::{info, warn};
+use nalgebra::{Matrix4, Vector2, Vector3};
 use serde_json::Value;
 use std::{
     collections::HashMap,
@@ -9,34 +11,159 @@ use std::{
 use wgpu::{
     AddressMode, Backends, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
     BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType, BlendState,
-    BufferBindingType, Color, ColorTargetState, ColorWrites, CommandEncoderDescriptor,
-    CompareFunction, DepthBiasState, DepthStencilState, Device, ExperimentalFeatures, Extent3d,
-    Face, Features, FilterMode, FrontFace, Instance, InstanceDescriptor, Limits, LoadOp,
-    MultisampleState, Operations, PolygonMode, PowerPreference, PresentMode, PrimitiveState,
-    PrimitiveTopology, Queue, RenderPassColorAttachment, RenderPassDepthStencilAttachment,
-    RenderPassDescriptor, RequestAdapterOptions, Sampler, SamplerBindingType, SamplerDescriptor,
-    ShaderStages, StencilState, StoreOp, Surface, SurfaceConfiguration, SurfaceError, Texture,
+    Buffer, BufferBindingType, BufferDescriptor, BufferUsages, COPY_BYTES_PER_ROW_ALIGNMENT, Color,
+    ColorTargetState, ColorWrites, CommandEncoder, CommandEncoderDescriptor, CompareFunction,
+    DepthBiasState, DepthStencilState, Device, ExperimentalFeatures, Extent3d, Face, Features,
+    FilterMode, FrontFace, Instance, InstanceDescriptor, Limits, LoadOp, MapMode, MultisampleState,
+    Operations, PollType, PolygonMode, PowerPreference, PresentMode, PrimitiveState,
+    PrimitiveTopology, Queue, RenderPass, RenderPassColorAttachment,
+    RenderPassDepthStencilAttachment, RenderPassDescriptor, RequestAdapterOptions, Sampler,
+    SamplerBindingType, SamplerDescriptor, ShaderStages, StencilState, StoreOp, Surface,
+    SurfaceConfiguration, SurfaceError, TexelCopyBufferInfo, TexelCopyBufferLayout, Texture,
     TextureDescriptor, TextureDimension, TextureFormat, TextureSampleType, TextureUsages,
-    TextureView, TextureViewDescriptor, TextureViewDimension, Trace, wgt::DeviceDescriptor,
+    TextureView, TextureViewDescriptor, TextureViewDimension, Trace,
+    util::{BufferInitDescriptor, DeviceExt},
+    wgt::DeviceDescriptor,
 };
 use winit::window::Window;
 
 use crate::{
     Float,
-    core::{camera::Camera, entity::Entity, lights::LightSourceStorage},
+    core::{
+        DEFAULT_CLEAR_COLOR, DEFAULT_MAX_LIGHTS, Unique,
+        entity::{BoundingBox, Entity, MaterialKind},
+        geometry::{BoundingSphere, Frustum},
+        lights::{LightSource, LightSourceStorage, LightStorageError},
+        prefabs::MinimapView,
+        world::terrain::{SplatLayer, World},
+    },
     render::{
         app::{ActiveState, MeshInitData, TextureInitData},
         gui::EguiRenderer,
-        module::{InstancedRenderModule, RenderPipelineSpec, ShaderSpec, UniformSpec, VertexSpec},
-        storage::{mesh, textures::TextureStorage},
+        module::{
+            InstancedRenderModule, ModuleMeshes, RenderPipelineSpec, ShaderLoadError, ShaderSource,
+            ShaderSpec, UniformSpec, VertexSpec,
+        },
+        storage::{
+            mesh,
+            textures::{MISSING_TEXTURE_ARRAY_ID, MISSING_TEXTURE_ID, TextureStorage},
+        },
         vertex::{
             DefaultInstanceType, DefaultVertexType, MarkerInstanceType, MarkerVertexType,
-            TerrainInstanceType, TerrainVertexType,
+            TerrainInstanceType, TerrainVertexType, UiInstanceType, UiVertexType,
             marker::{MARKER_INDICES, MARKER_VERTICES, MarkerEntity},
+            terrain::TerrainChunk,
+            ui::UiElement,
         },
     },
 };
 
+/// Options for the renderer which are fixed once it's created.
+#[derive(Debug, Clone, Copy)]
+pub struct RendererConfig {
+    /// Faces culled by the entity and terrain pipelines. None renders geometry double-sided.
+    pub cull_mode: Option<Face>,
+    /// Most light sources the renderer holds, which sizes the light buffer.
+    pub max_lights: usize,
+    /// Features enabled if the adapter supports them. Renderer::features tells which were, so anything
+    /// depending on one can check before using it.
+    pub optional_features: Features,
+}
+
+impl Default for RendererConfig {
+    fn default() -> Self {
+        Self {
+            cull_mode: Some(Face::Back),
+            max_lights: DEFAULT_MAX_LIGHTS,
+            // for Renderer::set_wireframe
+            optional_features: Features::POLYGON_MODE_LINE,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum RendererError {
+    /// The window can't be drawn to.
+    Surface(wgpu::CreateSurfaceError),
+
+    /// No adapter compatible with the window was found.
+    Adapter(wgpu::RequestAdapterError),
+
+    /// The adapter couldn't create a device, even with only the features it supports.
+    Device(wgpu::RequestDeviceError),
+
+    /// A shader file couldn't be read. Built-in shaders are compiled in and always load.
+    Shader(ShaderLoadError),
+}
+
+#[derive(Debug)]
+pub enum CaptureError {
+    /// The window has no size yet, or is minimized.
+    NotReady,
+
+    /// Waiting for the GPU to finish the frame failed.
+    Poll(wgpu::PollError),
+
+    /// The copied frame couldn't be read back.
+    Map(wgpu::BufferAsyncError),
+}
+
+impl std::fmt::Display for RendererError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RendererError::Surface(e) => write!(f, "the window can't be drawn to: {}", e),
+            RendererError::Adapter(e) => write!(f, "no compatible graphics adapter: {}", e),
+            RendererError::Device(e) => write!(f, "the graphics device couldn't be created: {}", e),
+            RendererError::Shader(e) => {
+                write!(
+                    f,
+                    "the shader at \"{}\" couldn't be read: {}",
+                    e.path, e.error
+                )
+            }
+        }
+    }
+}
+
+/// Shader and pipeline options of a render module added with Renderer::add_render_module.
+///
+/// The module uses the same vertex and instance layouts and bind groups (camera, texture, lights, depth) as
+/// default.wgsl, so a custom shader can start from a copy of it.
+pub struct RenderModuleSpec {
+    pub shader: ShaderSpec,
+    /// Built-in material whose pipeline options (blending, depth writes) the module is created with.
+    pub pipeline: MaterialKind,
+}
+
+#[derive(Debug)]
+pub enum RenderModuleError {
+    /// A render module with this name already exists. The built-in ones are named after their MaterialKind.
+    NameTaken(String),
+
+    /// The shader file couldn't be read.
+    Shader(ShaderLoadError),
+}
+
+/// How entities are shaded. Can be changed while running with Renderer::set_shading_mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ShadingMode {
+    /// Each material's regular shading.
+    #[default]
+    Lit,
+    /// World-space normals mapped to RGB, for checking generated geometry.
+    Normals,
+}
+
+impl ShadingMode {
+    /// The other mode.
+    pub fn toggled(self) -> Self {
+        match self {
+            ShadingMode::Lit => ShadingMode::Normals,
+            ShadingMode::Normals => ShadingMode::Lit,
+        }
+    }
+}
+
 pub struct Renderer {
     window: Arc<Window>,
 
@@ -44,15 +171,48 @@ pub struct Renderer {
     device: Device,
     queue: Queue,
     config: SurfaceConfiguration,
-    is_surface_configured: bool,
+    surface_size: SurfaceSize,
 
-    render_module_transformed: InstancedRenderModule<DefaultVertexType, DefaultInstanceType>,
+    // Each only holds its pipelines and instances, they all draw entity_meshes
+    render_modules_transformed:
+        RenderModules<InstancedRenderModule<DefaultVertexType, DefaultInstanceType>>,
+    entity_meshes: ModuleMeshes<DefaultVertexType>,
+    renderer_config: RendererConfig,
+    // Material each entity's instance was last stored under
+    entity_materials: HashMap<u64, MaterialKind>,
+    shading_mode: ShadingMode,
+    // Optional features the device was created with
+    features: Features,
+    wireframe: bool,
+    /// In sRGB, converted in clear_load_op
+    clear_color: Color,
+    max_render_distance: Option<f32>,
+    // Bounds of each instanced mesh, to frustum cull its instances
+    mesh_bounds: HashMap<u64, MeshBounds>,
+    frustum_culling: bool,
     render_module_terrain: InstancedRenderModule<TerrainVertexType, TerrainInstanceType>,
+    // Mesh id of each loaded chunk in the terrain module
+    terrain_chunks: HashMap<(i64, i64), u64>,
     render_module_markers: InstancedRenderModule<MarkerVertexType, MarkerInstanceType>,
+    render_module_ui: InstancedRenderModule<UiVertexType, UiInstanceType>,
+    // Draws the minimap's texture as a UI element
+    render_module_minimap: InstancedRenderModule<UiVertexType, UiInstanceType>,
+    // Unit square the minimap is drawn on
+    minimap_mesh: u64,
+    // Set while ActiveState::minimap is
+    minimap: Option<MinimapTarget>,
+
+    // Orthographic projection from window pixels, used by the UI pass
+    screen_buffer: Buffer,
+    screen_bind_group: BindGroup,
 
     textures: TextureStorage,
     texture_bind_group_layout: BindGroupLayout,
+    terrain_texture_bind_group_layout: BindGroupLayout,
+    // Texture array the terrain is splatted with
+    terrain_textures: u64,
     camera_bind_group_layout: BindGroupLayout,
+    depth_texture_bind_group_layout: BindGroupLayout,
 
     lights: LightSourceStorage,
 
@@ -65,7 +225,10 @@ pub struct Renderer {
 }
 
 impl Renderer {
-    pub async fn new(window: Arc<Window>) -> Self {
+    pub async fn new(
+        window: Arc<Window>,
+        renderer_config: RendererConfig,
+    ) -> Result<Self, RendererError> {
         let size = window.inner_size();
 
         let instance = Instance::new(&InstanceDescriptor {
@@ -73,7 +236,9 @@ impl Renderer {
             ..Default::default()
         });
 
-        let surface = instance.create_surface(window.clone()).unwrap();
+        let surface = instance
+            .create_surface(window.clone())
+            .map_err(RendererError::Surface)?;
 
         let adapter = instance
             .request_adapter(&RequestAdapterOptions {
@@ -82,19 +247,28 @@ impl Renderer {
                 force_fallback_adapter: false,
             })
             .await
-            .unwrap();
+            .map_err(RendererError::Adapter)?;
+
+        let features = negotiate_features(renderer_config.optional_features, adapter.features());
+        let missing = renderer_config.optional_features.difference(features);
+        if !missing.is_empty() {
+            info!(
+                "The adapter doesn't support optional features {:?}",
+                missing
+            );
+        }
 
         let (mut device, queue) = adapter
             .request_device(&DeviceDescriptor {
                 label: None,
-                required_features: Features::empty(),
+                required_features: features,
                 experimental_features: ExperimentalFeatures::disabled(),
                 required_limits: Limits::defaults(),
                 memory_hints: Default::default(),
                 trace: Trace::Off,
             })
             .await
-            .unwrap();
+            .map_err(RendererError::Device)?;
 
         let surface_caps = surface.get_capabilities(&adapter);
 
@@ -115,28 +289,16 @@ impl Renderer {
             desired_maximum_frame_latency: 2,
         };
 
-        let texture_bind_group_layout =
-            device.create_bind_group_layout(&BindGroupLayoutDescriptor {
-                entries: &[
-                    BindGroupLayoutEntry {
-                        binding: 0,
-                        visibility: ShaderStages::FRAGMENT,
-                        ty: BindingType::Texture {
-                            sample_type: TextureSampleType::Float { filterable: true },
-                            view_dimension: TextureViewDimension::D2,
-                            multisampled: false,
-                        },
-                        count: None,
-                    },
-                    BindGroupLayoutEntry {
-                        binding: 1,
-                        visibility: ShaderStages::FRAGMENT,
-                        ty: BindingType::Sampler(SamplerBindingType::Filtering),
-                        count: None,
-                    },
-                ],
-                label: Some("Texture Bind Group Layout"),
-            });
+        let texture_layout = texture_bind_group_layout(
+            &device,
+            TextureViewDimension::D2,
+            "Texture Bind Group Layout",
+        );
+        let terrain_texture_bind_group_layout = texture_bind_group_layout(
+            &device,
+            TextureViewDimension::D2Array,
+            "Terrain Texture Bind Group Layout",
+        );
 
         let camera_bind_group_layout =
             device.create_bind_group_layout(&BindGroupLayoutDescriptor {
@@ -201,71 +363,35 @@ impl Renderer {
 
         let lights = LightSourceStorage::new(
             &mut device,
-            [1000.0, 1000.0, 1000.0, 1.0],
-            [255.0 / 255.0, 255.0 / 255.0, 255.0 / 255.0, 1.0],
-            1.0e6,
+            &queue,
+            vec![LightSource::new(
+                [1000.0, 1000.0, 1000.0, 1.0],
+                [1.0, 1.0, 1.0, 1.0],
+                1.0e6,
+            )],
+            renderer_config.max_lights,
         );
 
-        let render_module_transformed =
-            InstancedRenderModule::<DefaultVertexType, DefaultInstanceType>::new(
+        let render_modules_transformed = RenderModules::try_new(|material| {
+            entity_render_module(
                 &device,
-                Some("Main Render Module"),
-                &VertexSpec {
-                    vertex_layout: DefaultVertexType::vertex_desc(),
-                    instance_layout: DefaultVertexType::instance_desc(),
-                },
+                &format!("{:?}", material),
                 &ShaderSpec {
-                    path: "src/render/shaders/default.wgsl".into(),
+                    source: ShaderSource::Inline(include_str!("shaders/default.wgsl")),
                     vertex_shader_name: "vs_main".into(),
-                    fragment_shader_name: "fs_main".into(),
-                },
-                (vec![
-                    UniformSpec {
-                        bind_group_layout: camera_bind_group_layout.clone(),
-                    },
-                    UniformSpec {
-                        bind_group_layout: texture_bind_group_layout.clone(),
-                    },
-                    UniformSpec {
-                        bind_group_layout: lights.layout().clone(),
-                    },
-                    UniformSpec {
-                        bind_group_layout: depth_texture_bind_group_layout.clone(),
-                    },
-                ])
-                .iter(),
-                &RenderPipelineSpec {
-                    primitive: PrimitiveState {
-                        topology: PrimitiveTopology::TriangleList,
-                        strip_index_format: None,
-                        front_face: FrontFace::Ccw,
-                        cull_mode: Some(Face::Back),
-                        polygon_mode: PolygonMode::Fill,
-                        unclipped_depth: false,
-                        conservative: false,
-                    },
-                    depth_stencil: Some(DepthStencilState {
-                        format: TextureFormat::Depth32Float,
-                        depth_write_enabled: true,
-                        depth_compare: CompareFunction::Less,
-                        stencil: StencilState::default(),
-                        bias: DepthBiasState::default(),
-                    }),
-                    multisample: MultisampleState {
-                        count: 1,
-                        mask: !0,
-                        alpha_to_coverage_enabled: false,
-                    },
-                    multiview: None,
-                    cache: None,
-                    fragment_color_target_state: Some(ColorTargetState {
-                        format: config.format,
-                        blend: Some(BlendState::REPLACE),
-                        write_mask: ColorWrites::ALL,
-                    }),
+                    fragment_shader_name: fragment_entry_point(material, ShadingMode::default())
+                        .into(),
                 },
+                [
+                    &camera_bind_group_layout,
+                    &texture_layout,
+                    lights.layout(),
+                    &depth_texture_bind_group_layout,
+                ],
+                &material_pipeline_spec(config.format, &renderer_config, material),
             )
-            .unwrap();
+        })
+        .map_err(RendererError::Shader)?;
 
         let render_module_terrain =
             InstancedRenderModule::<TerrainVertexType, TerrainInstanceType>::new(
@@ -276,17 +402,17 @@ impl Renderer {
                     instance_layout: TerrainVertexType::instance_desc(),
                 },
                 &ShaderSpec {
-                    path: "src/render/shaders/terrain.wgsl".into(),
+                    source: ShaderSource::Inline(include_str!("shaders/terrain.wgsl")),
                     vertex_shader_name: "vs_main".into(),
                     fragment_shader_name: "fs_main".into(),
                 },
-                (vec![
+                [
                     // TODO: Add sun and moon
                     UniformSpec {
                         bind_group_layout: camera_bind_group_layout.clone(),
                     },
                     UniformSpec {
-                        bind_group_layout: texture_bind_group_layout.clone(),
+                        bind_group_layout: terrain_texture_bind_group_layout.clone(),
                     },
                     UniformSpec {
                         bind_group_layout: lights.layout().clone(),
@@ -294,40 +420,11 @@ impl Renderer {
                     UniformSpec {
                         bind_group_layout: depth_texture_bind_group_layout.clone(),
                     },
-                ])
+                ]
                 .iter(),
-                &RenderPipelineSpec {
-                    primitive: PrimitiveState {
-                        topology: PrimitiveTopology::TriangleList,
-                        strip_index_format: None,
-                        front_face: FrontFace::Ccw,
-                        cull_mode: Some(Face::Back),
-                        polygon_mode: PolygonMode::Fill,
-                        unclipped_depth: false,
-                        conservative: false,
-                    },
-                    depth_stencil: Some(DepthStencilState {
-                        format: TextureFormat::Depth32Float,
-                        depth_write_enabled: true,
-                        depth_compare: CompareFunction::Less,
-                        stencil: StencilState::default(),
-                        bias: DepthBiasState::default(),
-                    }),
-                    multisample: MultisampleState {
-                        count: 1,
-                        mask: !0,
-                        alpha_to_coverage_enabled: false,
-                    },
-                    multiview: None,
-                    cache: None,
-                    fragment_color_target_state: Some(ColorTargetState {
-                        format: config.format,
-                        blend: Some(BlendState::REPLACE),
-                        write_mask: ColorWrites::ALL,
-                    }),
-                },
+                &pipeline_spec(config.format, renderer_config.cull_mode),
             )
-            .unwrap();
+            .map_err(RendererError::Shader)?;
 
         let mut render_module_markers =
             InstancedRenderModule::<MarkerVertexType, MarkerInstanceType>::new(
@@ -338,46 +435,17 @@ impl Renderer {
                     instance_layout: MarkerVertexType::instance_desc(),
                 },
                 &ShaderSpec {
-                    path: "src/render/shaders/marker.wgsl".into(),
+                    source: ShaderSource::Inline(include_str!("shaders/marker.wgsl")),
                     vertex_shader_name: "vs_main".into(),
                     fragment_shader_name: "fs_main".into(),
                 },
-                (vec![UniformSpec {
+                [UniformSpec {
                     bind_group_layout: camera_bind_group_layout.clone(),
-                }])
+                }]
                 .iter(),
-                &RenderPipelineSpec {
-                    primitive: PrimitiveState {
-                        topology: PrimitiveTopology::TriangleList,
-                        strip_index_format: None,
-                        front_face: FrontFace::Ccw,
-                        cull_mode: Some(Face::Back),
-                        polygon_mode: PolygonMode::Fill,
-                        unclipped_depth: false,
-                        conservative: false,
-                    },
-                    depth_stencil: Some(DepthStencilState {
-                        format: TextureFormat::Depth32Float,
-                        depth_write_enabled: true,
-                        depth_compare: CompareFunction::Less,
-                        stencil: StencilState::default(),
-                        bias: DepthBiasState::default(),
-                    }),
-                    multisample: MultisampleState {
-                        count: 1,
-                        mask: !0,
-                        alpha_to_coverage_enabled: false,
-                    },
-                    multiview: None,
-                    cache: None,
-                    fragment_color_target_state: Some(ColorTargetState {
-                        format: config.format,
-                        blend: Some(BlendState::REPLACE),
-                        write_mask: ColorWrites::ALL,
-                    }),
-                },
+                &pipeline_spec(config.format, Some(Face::Back)),
             )
-            .unwrap();
+            .map_err(RendererError::Shader)?;
 
         let right_mesh = render_module_markers
             .add_mesh(
@@ -386,6 +454,7 @@ impl Renderer {
                 MeshInitData {
                     vertices: MARKER_VERTICES([1.0, 0.0, 0.0].into()),
                     indices: MARKER_INDICES.to_vec(),
+                    front_face: FrontFace::Ccw,
                 },
             )
             .unwrap();
@@ -396,6 +465,7 @@ impl Renderer {
                 MeshInitData {
                     vertices: MARKER_VERTICES([0.0, 1.0, 0.0].into()),
                     indices: MARKER_INDICES.to_vec(),
+                    front_face: FrontFace::Ccw,
                 },
             )
             .unwrap();
@@ -406,36 +476,113 @@ impl Renderer {
                 MeshInitData {
                     vertices: MARKER_VERTICES([0.0, 0.0, 1.0].into()),
                     indices: MARKER_INDICES.to_vec(),
+                    front_face: FrontFace::Ccw,
                 },
             )
             .unwrap();
 
         render_module_markers
-            .upsert_instances(&vec![
-                MarkerEntity {
-                    position: Vector3::zeros(),
-                    direction: Vector3::new(1.0, 0.0, 0.0),
-                    color: Vector3::new(1.0, 0.0, 0.0),
-                    id: 0,
-                    mesh_id: right_mesh,
+            .upsert_instances(
+                &device,
+                &vec![
+                    MarkerEntity {
+                        position: Vector3::zeros(),
+                        direction: Vector3::new(1.0, 0.0, 0.0),
+                        color: Vector3::new(1.0, 0.0, 0.0),
+                        id: 0,
+                        mesh_id: right_mesh,
+                    },
+                    MarkerEntity {
+                        position: Vector3::zeros(),
+                        direction: Vector3::new(0.0, 1.0, 0.0),
+                        color: Vector3::new(0.0, 1.0, 0.0),
+                        id: 1,
+                        mesh_id: up_mesh,
+                    },
+                    MarkerEntity {
+                        position: Vector3::zeros(),
+                        direction: Vector3::new(0.0, 0.0, 1.0),
+                        color: Vector3::new(0.0, 0.0, 1.0),
+                        id: 2,
+                        mesh_id: forward_mesh,
+                    },
+                ],
+            )
+            .unwrap();
+        render_module_markers.update_gpu(&device, &queue);
+
+        let screen_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Screen Buffer"),
+            contents: bytemuck::cast_slice(&[screen_projection(config.width, config.height)]),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+        let screen_bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Screen Bind Group"),
+            layout: &camera_bind_group_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: screen_buffer.as_entire_binding(),
+            }],
+        });
+        let render_module_ui = InstancedRenderModule::<UiVertexType, UiInstanceType>::new(
+            &device,
+            Some("UI Render Module"),
+            &VertexSpec {
+                vertex_layout: UiVertexType::vertex_desc(),
+                instance_layout: UiVertexType::instance_desc(),
+            },
+            &ShaderSpec {
+                source: ShaderSource::Inline(include_str!("shaders/ui.wgsl")),
+                vertex_shader_name: "vs_main".into(),
+                fragment_shader_name: "fs_main".into(),
+            },
+            [UniformSpec {
+                bind_group_layout: camera_bind_group_layout.clone(),
+            }]
+            .iter(),
+            &ui_pipeline_spec(config.format),
+        )
+        .map_err(RendererError::Shader)?;
+        let mut render_module_minimap = InstancedRenderModule::<UiVertexType, UiInstanceType>::new(
+            &device,
+            Some("Minimap Render Module"),
+            &VertexSpec {
+                vertex_layout: UiVertexType::vertex_desc(),
+                instance_layout: UiVertexType::instance_desc(),
+            },
+            &ShaderSpec {
+                source: ShaderSource::Inline(include_str!("shaders/minimap.wgsl")),
+                vertex_shader_name: "vs_main".into(),
+                fragment_shader_name: "fs_main".into(),
+            },
+            [
+                UniformSpec {
+                    bind_group_layout: camera_bind_group_layout.clone(),
                 },
-                MarkerEntity {
-                    position: Vector3::zeros(),
-                    direction: Vector3::new(0.0, 1.0, 0.0),
-                    color: Vector3::new(0.0, 1.0, 0.0),
-                    id: 1,
-                    mesh_id: up_mesh,
+                UniformSpec {
+                    bind_group_layout: texture_layout.clone(),
                 },
-                MarkerEntity {
-                    position: Vector3::zeros(),
-                    direction: Vector3::new(0.0, 0.0, 1.0),
-                    color: Vector3::new(0.0, 0.0, 1.0),
-                    id: 2,
-                    mesh_id: forward_mesh,
+            ]
+            .iter(),
+            &ui_pipeline_spec(config.format),
+        )
+        .map_err(RendererError::Shader)?;
+        let minimap_mesh = render_module_minimap
+            .add_mesh(
+                &device,
+                &queue,
+                MeshInitData {
+                    vertices: [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]]
+                        .map(|position| UiVertexType {
+                            position,
+                            color: [1.0; 4],
+                        })
+                        .to_vec(),
+                    indices: vec![0, 1, 2, 0, 2, 3],
+                    front_face: FrontFace::Ccw,
                 },
-            ])
+            )
             .unwrap();
-        render_module_markers.update_gpu(&device, &queue);
 
         let depth_bind_group = device.create_bind_group(&BindGroupDescriptor {
             label: Some("Depth Bind Group"),
@@ -525,22 +672,60 @@ impl Renderer {
                 }
                 ui.label(RichText::new(metrics_str).color(Color32::from_rgb(0, 0, 0)));
                 ui.label(RichText::new(debug_str).color(Color32::from_rgb(0, 0, 0)));
+                if let Ok(data) = data.read() {
+                    if let Some(hud) = data.get("hud").and_then(|hud| hud.as_str()) {
+                        ui.label(RichText::new(hud).color(Color32::from_rgb(0, 0, 0)));
+                    }
+                    if let Some(console) = data.get("console").and_then(|c| c.as_str()) {
+                        ui.label(RichText::new(console).color(Color32::from_rgb(0, 0, 0)));
+                    }
+                }
             },
         );
 
+        // Entities are drawn with the missing texture until one is added
+        let mut textures = TextureStorage::new();
+        textures.add_missing_texture(&device, &queue, &texture_layout);
+        textures.add_missing_texture_array(
+            &device,
+            &queue,
+            SplatLayer::COUNT as u32,
+            &terrain_texture_bind_group_layout,
+        );
+
+        let entity_meshes = ModuleMeshes::new(&device);
+
         window.set_visible(true);
 
-        Self {
+        Ok(Self {
             window,
             surface,
             device,
             queue,
             config,
-            is_surface_configured: false,
+            surface_size: SurfaceSize::default(),
 
-            render_module_transformed,
+            render_modules_transformed,
+            entity_meshes,
+            renderer_config,
+            entity_materials: HashMap::new(),
+            shading_mode: ShadingMode::default(),
+            features,
+            wireframe: false,
+            clear_color: DEFAULT_CLEAR_COLOR,
+            max_render_distance: None,
+            mesh_bounds: HashMap::new(),
+            frustum_culling: true,
             render_module_terrain,
+            terrain_chunks: HashMap::new(),
             render_module_markers,
+            render_module_ui,
+            render_module_minimap,
+            minimap_mesh,
+            minimap: None,
+
+            screen_buffer,
+            screen_bind_group,
 
             depth_texture,
             depth_view,
@@ -549,20 +734,152 @@ impl Renderer {
 
             lights,
 
-            textures: TextureStorage::new(),
-            texture_bind_group_layout,
+            textures,
+            texture_bind_group_layout: texture_layout,
+            terrain_texture_bind_group_layout,
+            terrain_textures: MISSING_TEXTURE_ARRAY_ID,
 
             camera_bind_group_layout,
+            depth_texture_bind_group_layout,
 
             egui_renderer,
+        })
+    }
+
+    /// Optional features from RendererConfig::optional_features the adapter supports and the renderer
+    /// enabled.
+    pub fn features(&self) -> Features {
+        self.features
+    }
+
+    /// Adds a render module drawing entities whose material is the returned MaterialKind::Custom, alongside
+    /// the built-in ones. Modules are drawn in the order they're added, after Opaque and Unlit and before
+    /// Transparent.
+    pub fn add_render_module(
+        &mut self,
+        name: &str,
+        spec: RenderModuleSpec,
+    ) -> Result<MaterialKind, RenderModuleError> {
+        let mut module = entity_render_module(
+            &self.device,
+            name,
+            &spec.shader,
+            [
+                &self.camera_bind_group_layout,
+                &self.texture_bind_group_layout,
+                self.lights.layout(),
+                &self.depth_texture_bind_group_layout,
+            ],
+            &material_pipeline_spec(self.config.format, &self.renderer_config, spec.pipeline),
+        )
+        .map_err(RenderModuleError::Shader)?;
+        if self.wireframe {
+            module.set_polygon_mode(&self.device, PolygonMode::Line);
+        }
+        self.render_modules_transformed.add(name, module)
+    }
+
+    /// Material of the render module named [name], including the built-in ones.
+    pub fn render_module(&self, name: &str) -> Option<MaterialKind> {
+        self.render_modules_transformed.material(name)
+    }
+
+    /// Names of the entity render modules, in the order they're drawn.
+    pub fn render_module_names(&self) -> Vec<&str> {
+        self.render_modules_transformed.names()
+    }
+
+    pub fn shading_mode(&self) -> ShadingMode {
+        self.shading_mode
+    }
+
+    /// Switches the fragment shader of every built-in entity pass.
+    pub fn set_shading_mode(&mut self, shading_mode: ShadingMode) {
+        // modules added with add_render_module keep their own shader
+        for material in MaterialKind::ALL {
+            self.render_modules_transformed
+                .get_mut(material)
+                .set_fragment_shader(&self.device, fragment_entry_point(material, shading_mode));
+        }
+        self.shading_mode = shading_mode;
+    }
+
+    pub fn wireframe(&self) -> bool {
+        self.wireframe
+    }
+
+    /// Draws the edges of entity and terrain triangles instead of filling them, for inspecting meshes.
+    ///
+    /// Needs Features::POLYGON_MODE_LINE in RendererConfig::optional_features and supported by the adapter.
+    /// Returns false and leaves the renderer as it was if turning wireframe on without it.
+    pub fn set_wireframe(&mut self, wireframe: bool) -> bool {
+        let Some(polygon_mode) = wireframe_polygon_mode(wireframe, self.features) else {
+            warn!("Wireframe needs Features::POLYGON_MODE_LINE, which wasn't enabled.");
+            return false;
+        };
+        for module in self.render_modules_transformed.iter_mut() {
+            module.set_polygon_mode(&self.device, polygon_mode);
         }
+        self.render_module_terrain
+            .set_polygon_mode(&self.device, polygon_mode);
+        self.wireframe = wireframe;
+        true
+    }
+
+    pub fn clear_color(&self) -> Color {
+        self.clear_color
+    }
+
+    /// Background color of the 3D scene, in sRGB (i.e. as picked in an image editor). Converted to linear
+    /// when the surface format is sRGB, so the background shows the color given.
+    pub fn set_clear_color(&mut self, clear_color: Color) {
+        self.clear_color = clear_color;
+    }
+
+    pub fn max_render_distance(&self) -> Option<f32> {
+        self.max_render_distance
+    }
+
+    /// Entities farther than [max_render_distance] from the camera aren't drawn. None (the default) draws
+    /// every entity.
+    pub fn set_max_render_distance(&mut self, max_render_distance: Option<f32>) {
+        self.max_render_distance = max_render_distance;
+    }
+
+    pub fn frustum_culling(&self) -> bool {
+        self.frustum_culling
+    }
+
+    /// Entities outside the camera's view aren't drawn unless this is turned off. On by default.
+    pub fn set_frustum_culling(&mut self, frustum_culling: bool) {
+        self.frustum_culling = frustum_culling;
     }
 
     pub fn gui_data(&self) -> Arc<RwLock<HashMap<String, Value>>> {
         self.egui_renderer.data()
     }
 
+    /// Resizes the surface and depth texture at the start of the next frame. Only the last size requested
+    /// before a frame is applied, so dragging a window edge doesn't reallocate on every Resized event.
     pub fn resize(&mut self, width: u32, height: u32) {
+        self.surface_size.request(width, height);
+    }
+
+    /// False until the surface has been configured by the first resize. Renderer::render draws nothing
+    /// before then.
+    pub fn is_ready(&self) -> bool {
+        self.surface_size.is_configured()
+    }
+
+    /// Applies the last size passed to Renderer::resize, if any. Called by render, and by App at the start
+    /// of a frame so it can tell whether the renderer is ready.
+    pub fn apply_pending_resize(&mut self) {
+        if let Some((width, height)) = self.surface_size.take() {
+            self.apply_resize(width, height);
+        }
+    }
+
+    fn apply_resize(&mut self, width: u32, height: u32) {
         self.config.width = width;
         self.config.height = height;
         self.surface.configure(&self.device, &self.config);
@@ -584,34 +901,153 @@ impl Renderer {
         self.depth_view = self
             .depth_texture
             .create_view(&TextureViewDescriptor::default());
+        self.queue.write_buffer(
+            &self.screen_buffer,
+            0,
+            bytemuck::cast_slice(&[screen_projection(width, height)]),
+        );
+    }
+
+    pub fn lights(&self) -> &LightSourceStorage {
+        &self.lights
+    }
+
+    /// Returns the new light's id. The renderer starts with a single light, id 0.
+    pub fn add_light(&mut self, light: LightSource) -> Result<u64, LightStorageError> {
+        self.lights.add_light(&self.queue, light)
+    }
+
+    pub fn remove_light(&mut self, id: u64) -> Option<LightSource> {
+        self.lights.remove_light(&self.queue, id)
+    }
 
-        self.is_surface_configured = true;
+    pub fn update_light(&mut self, id: u64, light: LightSource) -> Option<LightSource> {
+        self.lights.update_light(&self.queue, id, light)
     }
 
     pub fn new_texture(&mut self, data: TextureInitData) -> u64 {
         self.textures.new_texture(
             &mut self.device,
             &mut self.queue,
-            data.image,
-            data.resize,
+            data,
             &self.texture_bind_group_layout,
         )
     }
 
+    /// Sets the textures blended over the terrain, one per SplatLayer in order, returning the id of the
+    /// texture array holding them. Every layer is the size of the first. The terrain is drawn with the
+    /// missing texture until this is called.
+    pub fn set_terrain_textures(&mut self, layers: [TextureInitData; SplatLayer::COUNT]) -> u64 {
+        self.terrain_textures = self.textures.new_texture_array(
+            &mut self.device,
+            &mut self.queue,
+            &layers,
+            &self.terrain_texture_bind_group_layout,
+        );
+        self.terrain_textures
+    }
+
     /// Add mesh to the render module responsible for handling elements
     /// with a full transform as the instance and the default vertex type.
     pub fn add_mesh_instanced(
         &mut self,
         mesh: MeshInitData<DefaultVertexType>,
     ) -> Result<u64, mesh::MeshStorageError> {
-        self.render_module_transformed
+        let bounds = MeshBounds::from_points(
+            &mesh
+                .vertices
+                .iter()
+                .map(|v| Vector3::from(v.position))
+                .collect::<Vec<_>>(),
+        );
+        // every material's module draws it from the one copy
+        let mesh_id = self.entity_meshes.add_mesh(mesh)?;
+        if let Some(bounds) = bounds {
+            self.mesh_bounds.insert(mesh_id, bounds);
+        }
+        Ok(mesh_id)
+    }
+
+    /// Removes a mesh added with add_mesh_instanced, and every instance of it. Returns whether it existed.
+    /// Its id isn't reused.
+    pub fn remove_mesh_instanced(&mut self, mesh_id: u64) -> bool {
+        self.mesh_bounds.remove(&mesh_id);
+        let removed = self.entity_meshes.remove_meshes([mesh_id]);
+        for module in self.render_modules_transformed.iter_mut() {
+            module.remove_instances_of(&removed);
+        }
+        !removed.is_empty()
+    }
+
+    /// Add mesh to the render module drawn in screen space after the 3D scene.
+    pub fn add_ui_mesh(
+        &mut self,
+        mesh: MeshInitData<UiVertexType>,
+    ) -> Result<u64, mesh::MeshStorageError> {
+        self.render_module_ui
             .add_mesh(&self.device, &self.queue, mesh)
     }
 
     pub fn update_instances(&mut self, active_state: &mut ActiveState) {
-        self.render_module_transformed
-            .upsert_instances(active_state.entities())
+        for (_mesh_id, entity_id) in active_state.take_removed() {
+            if let Some(material) = self.entity_materials.remove(&entity_id) {
+                self.render_modules_transformed
+                    .get_mut(material)
+                    .remove_instance(&entity_id);
+            }
+        }
+        // Entities which changed material need to leave their old pass
+        for entity in active_state.entities() {
+            let previous = self.entity_materials.insert(*entity.id(), entity.material);
+            if let Some(previous) = previous.filter(|m| *m != entity.material) {
+                self.render_modules_transformed
+                    .get_mut(previous)
+                    .remove_instance(entity.id());
+            }
+        }
+        let camera = active_state.current_camera();
+        let (drawn, mut culled) = cull_by_distance(
+            active_state.entities(),
+            camera.position(),
+            self.max_render_distance,
+        );
+        let drawn = if self.frustum_culling {
+            let mut frustums = vec![Frustum::from_view_proj(camera.view_proj())];
+            // the minimap draws what's around the camera, including what's behind it
+            if let Some(minimap) = active_state.minimap() {
+                frustums.push(Frustum::from_view_proj(&minimap.camera.view_proj()));
+            }
+            let (drawn, outside) = cull_by_frustum(drawn, &frustums, &self.mesh_bounds);
+            culled.extend(outside);
+            drawn
+        } else {
+            drawn
+        };
+        for entity in culled {
+            self.render_modules_transformed
+                .get_mut(entity.material)
+                .remove_instance(entity.id());
+        }
+        let groups = group_by_material(drawn);
+        // blended in order, so the farthest has to be drawn first
+        let transparent = groups
+            .get(&MaterialKind::Transparent)
+            .map_or(vec![], |entities| {
+                back_to_front(entities, camera.position())
+            });
+        self.render_modules_transformed
+            .get_mut(MaterialKind::Transparent)
+            .set_draw_order(Some(transparent));
+        for (material, entities) in groups {
+            self.render_modules_transformed
+                .get_mut(material)
+                .upsert_textured_instances(&self.device, entities)
+                .unwrap();
+        }
+        self.render_module_ui
+            .upsert_instances(&self.device, active_state.ui_elements())
             .unwrap();
+        self.update_minimap(active_state.minimap().cloned());
 
         // temporary fix
         active_state
@@ -619,13 +1055,92 @@ impl Renderer {
             .update_gpu(&mut self.queue);
     }
 
+    /// Points the minimap's camera at [minimap] and places it on the window, creating its texture if the
+    /// size changed. None stops drawing it.
+    fn update_minimap(&mut self, minimap: Option<MinimapView>) {
+        let Some(minimap) = minimap else {
+            self.minimap = None;
+            return;
+        };
+        let size = (minimap.size.round() as u32).max(1);
+        if self.minimap.as_ref().is_none_or(|m| m.size != size) {
+            self.minimap = Some(MinimapTarget::new(
+                &self.device,
+                self.config.format,
+                size,
+                &self.camera_bind_group_layout,
+                &self.texture_bind_group_layout,
+            ));
+        }
+        if let Some(target) = &self.minimap {
+            self.queue.write_buffer(
+                &target.camera_buffer,
+                0,
+                bytemuck::cast_slice(&[Into::<[[f32; 4]; 4]>::into(minimap.camera.view_proj())]),
+            );
+        }
+        self.render_module_minimap
+            .upsert_instances(
+                &self.device,
+                &[UiElement {
+                    id: 0,
+                    mesh_id: self.minimap_mesh,
+                    offset: minimap.offset,
+                    scale: Vector2::repeat(minimap.size),
+                }],
+            )
+            .unwrap();
+    }
+
+    /// Builds meshes for the chunks [world] loaded since the last call, replacing the meshes of chunks
+    /// whose edges changed. Each chunk is drawn once, as it's already in world space.
+    pub fn update_terrain(&mut self, world: &mut World) {
+        let changed = world.take_changed_chunks();
+        // all at once, so the mesh storage is only compacted once
+        let stale: Vec<u64> = changed
+            .iter()
+            .filter_map(|key| self.terrain_chunks.remove(key))
+            .collect();
+        self.render_module_terrain.remove_meshes(stale);
+        for key in changed {
+            let Some(mesh) = world.chunk_mesh(key) else {
+                continue;
+            };
+            let mesh_id = match self
+                .render_module_terrain
+                .add_mesh(&self.device, &self.queue, mesh)
+            {
+                Ok(id) => id,
+                Err(e) => {
+                    warn!("Couldn't add the mesh of chunk {:?}: {:?}", key, e);
+                    continue;
+                }
+            };
+            self.terrain_chunks.insert(key, mesh_id);
+            if let Err(e) = self
+                .render_module_terrain
+                .upsert_instances(&self.device, &[TerrainChunk { mesh_id }])
+            {
+                warn!("Couldn't add the instance of chunk {:?}: {}", key, e);
+            }
+        }
+    }
+
     pub fn update_gpu(&mut self) {
-        self.render_module_transformed
+        self.entity_meshes.update_gpu(&self.device, &self.queue);
+        for module in self.render_modules_transformed.iter_mut() {
+            module.update_gpu(&self.device, &self.queue);
+        }
+        self.render_module_terrain
+            .update_gpu(&self.device, &self.queue);
+        self.render_module_ui.update_gpu(&self.device, &self.queue);
+        self.render_module_minimap
             .update_gpu(&self.device, &self.queue);
     }
 
     pub fn render(&mut self, state: &mut ActiveState) -> Result<(), SurfaceError> {
-        if !self.is_surface_configured {
+        self.apply_pending_resize();
+        if !self.is_ready() {
             return Ok(());
         }
 
@@ -634,26 +1149,85 @@ impl Renderer {
         let view = output
             .texture
             .create_view(&TextureViewDescriptor::default());
+        let encoder = self.encode_frame(state, &view);
+        self.queue.submit(std::iter::once(encoder.finish()));
+        output.present();
+
+        Ok(())
+    }
+
+    /// Renders a frame the same way Renderer::render does, into an offscreen texture instead of the window,
+    /// and copies it back, i.e. for screenshots and visual tests. Nothing is presented. Blocks until the
+    /// GPU is done.
+    pub fn capture_frame(&mut self, state: &mut ActiveState) -> Result<RgbaImage, CaptureError> {
+        self.apply_pending_resize();
+        if !self.is_ready() {
+            return Err(CaptureError::NotReady);
+        }
+
+        let texture = self.device.create_texture(&TextureDescriptor {
+            label: Some("Capture Texture"),
+            size: Extent3d {
+                width: self.config.width,
+                height: self.config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            // the pipelines are built for the surface's format
+            format: self.config.format,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&TextureViewDescriptor::default());
+        let encoder = self.encode_frame(state, &view);
+        self.queue.submit(std::iter::once(encoder.finish()));
+
+        capture_texture(&self.device, &self.queue, &texture)
+    }
 
+    /// Records the scene, UI and egui passes drawing to [view].
+    fn encode_frame(&mut self, state: &ActiveState, view: &TextureView) -> CommandEncoder {
         let mut encoder = self
             .device
             .create_command_encoder(&CommandEncoderDescriptor {
                 label: Some("Render Encoder"),
             });
 
+        if let Some(minimap) = &self.minimap {
+            let mut minimap_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                label: Some("Minimap Render Pass"),
+                color_attachments: &[Some(RenderPassColorAttachment {
+                    view: &minimap.view,
+                    resolve_target: None,
+                    ops: Operations {
+                        load: clear_load_op(self.clear_color, self.config.format),
+                        store: StoreOp::Store,
+                    },
+                    depth_slice: None,
+                })],
+                depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                    view: &minimap.depth_view,
+                    depth_ops: Some(Operations {
+                        load: LoadOp::Clear(1.0),
+                        store: StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+            self.draw_scene(&mut minimap_pass, &minimap.camera_bind_group);
+        }
         {
             let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
                 label: Some("Render Pass"),
                 color_attachments: &[Some(RenderPassColorAttachment {
-                    view: &view,
+                    view,
                     resolve_target: None,
                     ops: Operations {
-                        load: LoadOp::Clear(Color {
-                            r: 0.1,
-                            g: 0.2,
-                            b: 0.3,
-                            a: 1.0,
-                        }),
+                        load: clear_load_op(self.clear_color, self.config.format),
                         store: StoreOp::Store,
                     },
                     depth_slice: None,
@@ -669,32 +1243,42 @@ impl Renderer {
                 occlusion_query_set: None,
                 timestamp_writes: None,
             });
-            self.render_module_terrain.draw_all(
-                &mut render_pass,
-                [
-                    &state.current_camera().bind_group(),
-                    &&self.textures.get(&1).unwrap().3,
-                    &self.lights.bind_group(),
-                    &&self.depth_bind_group,
-                ]
-                .iter(),
-            );
-            self.render_module_transformed.draw_all(
-                &mut render_pass,
-                [
-                    &state.current_camera().bind_group(),
-                    &&self.textures.get(&1).unwrap().3,
-                    &self.lights.bind_group(),
-                    &&self.depth_bind_group,
-                ]
-                .iter(),
-            );
-            // Draw markers above everything else
-            self.render_module_markers.draw_all(
+            self.draw_scene(&mut render_pass, state.current_camera().bind_group());
+            // Draw markers above everything else
+            self.render_module_markers.draw_all(
                 &mut render_pass,
                 [&state.current_camera().bind_group()].iter(),
+                None,
             );
         }
+        {
+            // Drawn over the 3D scene, without depth testing
+            let mut ui_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                label: Some("UI Render Pass"),
+                color_attachments: &[Some(RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: Operations {
+                        load: LoadOp::Load,
+                        store: StoreOp::Store,
+                    },
+                    depth_slice: None,
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+            // under the UI elements, so the minimap's dots are drawn over it
+            if let Some(minimap) = &self.minimap {
+                self.render_module_minimap.draw_all(
+                    &mut ui_pass,
+                    [&&self.screen_bind_group, &&minimap.texture_bind_group].iter(),
+                    None,
+                );
+            }
+            self.render_module_ui
+                .draw_all(&mut ui_pass, [&&self.screen_bind_group].iter(), None);
+        }
         self.egui_renderer.render(
             &self.device,
             &self.queue,
@@ -703,18 +1287,50 @@ impl Renderer {
                 pixels_per_point: self.window.scale_factor() as f32 * 1.0,
             },
             &mut encoder,
-            &view,
+            view,
         );
-        self.queue.submit(std::iter::once(encoder.finish()));
-        output.present();
+        encoder
+    }
 
-        Ok(())
+    /// Draws the terrain and entities as seen by the camera bound with [camera_bind_group].
+    fn draw_scene(&self, render_pass: &mut RenderPass, camera_bind_group: &BindGroup) {
+        self.render_module_terrain.draw_all(
+            render_pass,
+            [
+                &camera_bind_group,
+                &&self.textures.get(&self.terrain_textures).unwrap().3,
+                &self.lights.bind_group(),
+                &&self.depth_bind_group,
+            ]
+            .iter(),
+            None,
+        );
+        // Transparent is last, so it blends over everything opaque
+        for module in self.render_modules_transformed.iter() {
+            module.draw_all_with(
+                render_pass,
+                &self.entity_meshes,
+                [
+                    &camera_bind_group,
+                    // replaced by each entity's own texture
+                    &&self.textures.get_or_missing(&MISSING_TEXTURE_ID).3,
+                    &self.lights.bind_group(),
+                    &&self.depth_bind_group,
+                ]
+                .iter(),
+                Some((1, &self.textures)),
+            );
+        }
     }
 
     pub fn device(&self) -> &Device {
         &self.device
     }
 
+    pub fn queue(&self) -> &Queue {
+        &self.queue
+    }
+
     pub fn camera_bind_group_layout(&self) -> &BindGroupLayout {
         &self.camera_bind_group_layout
     }
@@ -727,3 +1343,1077 @@ impl Renderer {
         &self.window
     }
 }
+
+/// Pipeline options shared by every render module, drawing to a surface of the given format.
+/// Layout of a texture and its sampler, bound at 0 and 1, viewed with [view_dimension].
+fn texture_bind_group_layout(
+    device: &Device,
+    view_dimension: TextureViewDimension,
+    label: &str,
+) -> BindGroupLayout {
+    device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+        entries: &[
+            BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Texture {
+                    sample_type: TextureSampleType::Float { filterable: true },
+                    view_dimension,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            BindGroupLayoutEntry {
+                binding: 1,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                count: None,
+            },
+        ],
+        label: Some(label),
+    })
+}
+
+fn pipeline_spec<'a>(format: TextureFormat, cull_mode: Option<Face>) -> RenderPipelineSpec<'a> {
+    RenderPipelineSpec {
+        primitive: PrimitiveState {
+            topology: PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: FrontFace::Ccw,
+            cull_mode,
+            polygon_mode: PolygonMode::Fill,
+            unclipped_depth: false,
+            conservative: false,
+        },
+        depth_stencil: Some(DepthStencilState {
+            format: TextureFormat::Depth32Float,
+            depth_write_enabled: true,
+            depth_compare: CompareFunction::Less,
+            stencil: StencilState::default(),
+            bias: DepthBiasState::default(),
+        }),
+        multisample: MultisampleState {
+            count: 1,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        multiview: None,
+        cache: None,
+        fragment_color_target_state: Some(ColorTargetState {
+            format,
+            blend: Some(BlendState::REPLACE),
+            write_mask: ColorWrites::ALL,
+        }),
+    }
+}
+
+/// Pipeline options for entities of the given material.
+fn material_pipeline_spec<'a>(
+    format: TextureFormat,
+    renderer_config: &RendererConfig,
+    material: MaterialKind,
+) -> RenderPipelineSpec<'a> {
+    let mut spec = pipeline_spec(format, renderer_config.cull_mode);
+    if material == MaterialKind::Transparent {
+        if let Some(target) = &mut spec.fragment_color_target_state {
+            target.blend = Some(BlendState::ALPHA_BLENDING);
+        }
+        if let Some(depth_stencil) = &mut spec.depth_stencil {
+            depth_stencil.depth_write_enabled = false;
+        }
+    }
+    spec
+}
+
+/// Fragment shader in default.wgsl used for entities of [material].
+fn fragment_entry_point(material: MaterialKind, shading_mode: ShadingMode) -> &'static str {
+    match (shading_mode, material) {
+        (ShadingMode::Normals, _) => "fs_normals",
+        (ShadingMode::Lit, MaterialKind::Unlit) => "fs_unlit",
+        (ShadingMode::Lit, MaterialKind::Opaque | MaterialKind::Transparent) => "fs_main",
+        // custom modules have their own shader
+        (ShadingMode::Lit, MaterialKind::Custom(_)) => "fs_main",
+    }
+}
+
+/// Texture the minimap is rendered into, along with the camera it's rendered with.
+struct MinimapTarget {
+    // Side length in pixels
+    size: u32,
+    view: TextureView,
+    depth_view: TextureView,
+    camera_buffer: Buffer,
+    camera_bind_group: BindGroup,
+    // The texture, for drawing it in the UI pass
+    texture_bind_group: BindGroup,
+}
+
+impl MinimapTarget {
+    /// A [size] by [size] texture of [format], which the scene pipelines are built for.
+    fn new(
+        device: &Device,
+        format: TextureFormat,
+        size: u32,
+        camera_layout: &BindGroupLayout,
+        texture_layout: &BindGroupLayout,
+    ) -> Self {
+        let extent = Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: 1,
+        };
+        let texture = device.create_texture(&TextureDescriptor {
+            label: Some("Minimap Texture"),
+            size: extent,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&TextureViewDescriptor::default());
+        let depth_view = device
+            .create_texture(&TextureDescriptor {
+                label: Some("Minimap Depth Texture"),
+                size: extent,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format: TextureFormat::Depth32Float,
+                usage: TextureUsages::RENDER_ATTACHMENT,
+                view_formats: &[],
+            })
+            .create_view(&TextureViewDescriptor::default());
+        let camera_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Minimap Camera Buffer"),
+            contents: bytemuck::cast_slice(&[Into::<[[f32; 4]; 4]>::into(
+                Matrix4::<f32>::identity(),
+            )]),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+        let camera_bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Minimap Camera Bind Group"),
+            layout: camera_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: camera_buffer.as_entire_binding(),
+            }],
+        });
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some("Minimap Sampler"),
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..Default::default()
+        });
+        let texture_bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Minimap Texture Bind Group"),
+            layout: texture_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(&view),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::Sampler(&sampler),
+                },
+            ],
+        });
+        Self {
+            size,
+            view,
+            depth_view,
+            camera_buffer,
+            camera_bind_group,
+            texture_bind_group,
+        }
+    }
+}
+
+/// Entity passes in draw order, each named and drawing the entities of one material. The built-in materials
+/// come first with Transparent last. Modules added later are drawn before Transparent, so it blends over them.
+struct RenderModules<M> {
+    modules: Vec<(String, MaterialKind, M)>,
+    custom_count: u32,
+}
+
+impl<M> RenderModules<M> {
+    /// The built-in materials' modules, each created by [module].
+    #[cfg(test)]
+    fn new(mut module: impl FnMut(MaterialKind) -> M) -> Self {
+        Self {
+            modules: MaterialKind::ALL
+                .map(|material| (format!("{:?}", material), material, module(material)))
+                .into(),
+            custom_count: 0,
+        }
+    }
+
+    /// Same as RenderModules::new, stopping at the first module which couldn't be created.
+    fn try_new<E>(mut module: impl FnMut(MaterialKind) -> Result<M, E>) -> Result<Self, E> {
+        let mut modules = Vec::with_capacity(MaterialKind::ALL.len());
+        for material in MaterialKind::ALL {
+            modules.push((format!("{:?}", material), material, module(material)?));
+        }
+        Ok(Self {
+            modules,
+            custom_count: 0,
+        })
+    }
+
+    /// Registers [module] under [name], returning the material which entities use to be drawn by it.
+    fn add(&mut self, name: &str, module: M) -> Result<MaterialKind, RenderModuleError> {
+        if self.material(name).is_some() {
+            return Err(RenderModuleError::NameTaken(name.to_owned()));
+        }
+        let material = MaterialKind::Custom(self.custom_count);
+        self.custom_count += 1;
+        let transparent = self.index(MaterialKind::Transparent);
+        self.modules
+            .insert(transparent, (name.to_owned(), material, module));
+        Ok(material)
+    }
+
+    fn material(&self, name: &str) -> Option<MaterialKind> {
+        self.modules
+            .iter()
+            .find(|(n, ..)| n == name)
+            .map(|(_, material, _)| *material)
+    }
+
+    /// Names of the modules in draw order.
+    fn names(&self) -> Vec<&str> {
+        self.modules
+            .iter()
+            .map(|(name, ..)| name.as_str())
+            .collect()
+    }
+
+    /// Position of [material]'s module in draw order. Custom materials which weren't added are drawn as
+    /// Opaque.
+    fn index(&self, material: MaterialKind) -> usize {
+        let position = |material| self.modules.iter().position(|(_, m, _)| *m == material);
+        position(material)
+            .or_else(|| position(MaterialKind::Opaque))
+            .unwrap()
+    }
+
+    fn get_mut(&mut self, material: MaterialKind) -> &mut M {
+        let index = self.index(material);
+        &mut self.modules[index].2
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &M> {
+        self.modules.iter().map(|(.., module)| module)
+    }
+
+    fn iter_mut(&mut self) -> impl Iterator<Item = &mut M> {
+        self.modules.iter_mut().map(|(.., module)| module)
+    }
+}
+
+/// Render module drawing entities with [shader], with the camera, texture, lights and depth layouts bound
+/// in that order.
+fn entity_render_module(
+    device: &Device,
+    name: &str,
+    shader: &ShaderSpec,
+    layouts: [&BindGroupLayout; 4],
+    pipeline: &RenderPipelineSpec,
+) -> Result<InstancedRenderModule<DefaultVertexType, DefaultInstanceType>, ShaderLoadError> {
+    InstancedRenderModule::new(
+        device,
+        Some(&format!("Main Render Module ({})", name)),
+        &VertexSpec {
+            vertex_layout: DefaultVertexType::vertex_desc(),
+            instance_layout: DefaultVertexType::instance_desc(),
+        },
+        shader,
+        layouts
+            .map(|layout| UniformSpec {
+                bind_group_layout: layout.clone(),
+            })
+            .iter(),
+        pipeline,
+    )
+}
+
+/// Splits entities into those within [max_distance] of [camera], which are drawn, and the rest.
+fn cull_by_distance<'a>(
+    entities: &'a [Entity],
+    camera: &Vector3<Float>,
+    max_distance: Option<f32>,
+) -> (Vec<&'a Entity>, Vec<&'a Entity>) {
+    let Some(max_distance) = max_distance else {
+        return (entities.iter().collect(), vec![]);
+    };
+    let max_distance_squared = max_distance * max_distance;
+    entities
+        .iter()
+        .partition(|e| (e.translation - camera).magnitude_squared() <= max_distance_squared)
+}
+
+/// Bounds of a mesh in its own space.
+struct MeshBounds {
+    sphere: BoundingSphere,
+    aabb: BoundingBox,
+}
+
+impl MeshBounds {
+    fn from_points(points: &[Vector3<f32>]) -> Option<Self> {
+        let sphere = BoundingSphere::from_points(points.iter().copied())?;
+        let min = points
+            .iter()
+            .fold(Vector3::repeat(f32::INFINITY), |m, p| m.inf(p));
+        let max = points
+            .iter()
+            .fold(Vector3::repeat(f32::NEG_INFINITY), |m, p| m.sup(p));
+        let size = max - min;
+        Some(Self {
+            sphere,
+            aabb: BoundingBox::new((min.x, min.y, min.z), (size.x, size.y, size.z)),
+        })
+    }
+}
+
+/// Splits entities into those whose mesh bounds, moved by the entity's transform, are at least partly
+/// within one of [frustums], which are drawn, and the rest. Entities of meshes without bounds are drawn.
+///
+/// The sphere is checked first since it's cheap, then the box, which is tighter for long, thin meshes.
+fn cull_by_frustum<'a>(
+    entities: Vec<&'a Entity>,
+    frustums: &[Frustum],
+    mesh_bounds: &HashMap<u64, MeshBounds>,
+) -> (Vec<&'a Entity>, Vec<&'a Entity>) {
+    entities.into_iter().partition(|e| {
+        mesh_bounds.get(&e.mesh_id).is_none_or(|bounds| {
+            let sphere = bounds
+                .sphere
+                .transformed(&e.scale, &e.rotation, &e.translation);
+            let aabb = bounds
+                .aabb
+                .transformed(&e.scale, &e.rotation, &e.translation);
+            frustums.iter().any(|frustum| {
+                frustum.contains_sphere(&sphere) && frustum.contains_aabb(&aabb.min(), &aabb.max())
+            })
+        })
+    })
+}
+
+/// Splits entities into the instance sets of each material's pass.
+fn group_by_material<'a>(
+    entities: impl IntoIterator<Item = &'a Entity>,
+) -> HashMap<MaterialKind, Vec<&'a Entity>> {
+    let mut groups: HashMap<MaterialKind, Vec<&Entity>> = HashMap::new();
+    for entity in entities {
+        groups.entry(entity.material).or_default().push(entity);
+    }
+    groups
+}
+
+/// Features to create the device with: the [desired] ones the adapter supports.
+fn negotiate_features(desired: Features, available: Features) -> Features {
+    desired & available
+}
+
+/// Copies [texture], a 2D texture of 4 byte pixels with COPY_SRC usage, into an image. Blue and red are
+/// swapped back for Bgra8 formats. Blocks until the GPU is done.
+pub(crate) fn capture_texture(
+    device: &Device,
+    queue: &Queue,
+    texture: &Texture,
+) -> Result<RgbaImage, CaptureError> {
+    let (width, height) = (texture.width(), texture.height());
+    let bytes_per_row = padded_bytes_per_row(width);
+    let buffer = device.create_buffer(&BufferDescriptor {
+        label: Some("Capture Buffer"),
+        size: bytes_per_row as u64 * height as u64,
+        usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+        label: Some("Capture Encoder"),
+    });
+    encoder.copy_texture_to_buffer(
+        texture.as_image_copy(),
+        TexelCopyBufferInfo {
+            buffer: &buffer,
+            layout: TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(bytes_per_row),
+                rows_per_image: Some(height),
+            },
+        },
+        texture.size(),
+    );
+    queue.submit(std::iter::once(encoder.finish()));
+
+    let slice = buffer.slice(..);
+    let (sender, receiver) = std::sync::mpsc::channel();
+    slice.map_async(MapMode::Read, move |result| {
+        let _ = sender.send(result);
+    });
+    device
+        .poll(PollType::wait_indefinitely())
+        .map_err(CaptureError::Poll)?;
+    receiver
+        .recv()
+        .expect("the buffer was mapped without calling back")
+        .map_err(CaptureError::Map)?;
+
+    let bgra = matches!(
+        texture.format(),
+        TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb
+    );
+    let image = frame_image(
+        &slice.get_mapped_range(),
+        width,
+        height,
+        bytes_per_row,
+        bgra,
+    );
+    buffer.unmap();
+    Ok(image)
+}
+
+/// Bytes per row of a texture [width] pixels wide when copied into a buffer, which wgpu requires to be a
+/// multiple of COPY_BYTES_PER_ROW_ALIGNMENT.
+fn padded_bytes_per_row(width: u32) -> u32 {
+    (width * 4).div_ceil(COPY_BYTES_PER_ROW_ALIGNMENT) * COPY_BYTES_PER_ROW_ALIGNMENT
+}
+
+/// Image from [data] laid out in rows of [bytes_per_row], of which only the first [width] pixels are
+/// kept. Swaps blue and red if [bgra].
+fn frame_image(data: &[u8], width: u32, height: u32, bytes_per_row: u32, bgra: bool) -> RgbaImage {
+    let mut image = RgbaImage::new(width, height);
+    for (y, row) in data
+        .chunks(bytes_per_row as usize)
+        .take(height as usize)
+        .enumerate()
+    {
+        for (x, pixel) in row[..width as usize * 4].chunks_exact(4).enumerate() {
+            let rgba = match bgra {
+                true => [pixel[2], pixel[1], pixel[0], pixel[3]],
+                false => [pixel[0], pixel[1], pixel[2], pixel[3]],
+            };
+            image.put_pixel(x as u32, y as u32, Rgba(rgba));
+        }
+    }
+    image
+}
+
+/// Polygon mode of the entity and terrain pipelines, None if [wireframe] needs a feature missing from
+/// [features].
+fn wireframe_polygon_mode(wireframe: bool, features: Features) -> Option<PolygonMode> {
+    match wireframe {
+        false => Some(PolygonMode::Fill),
+        true => features
+            .contains(Features::POLYGON_MODE_LINE)
+            .then_some(PolygonMode::Line),
+    }
+}
+
+/// Ids of [entities] ordered by decreasing distance from [camera_position]. Entities at the same distance
+/// keep their order.
+fn back_to_front(entities: &[&Entity], camera_position: &Vector3<f32>) -> Vec<u64> {
+    let mut by_distance: Vec<(f32, u64)> = entities
+        .iter()
+        .map(|e| {
+            (
+                (e.translation() - camera_position).magnitude_squared(),
+                *e.id(),
+            )
+        })
+        .collect();
+    by_distance.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+    by_distance.into_iter().map(|(_, id)| id).collect()
+}
+
+/// Pipeline options for screen-space overlays: alpha blended, double-sided and without a depth test.
+fn ui_pipeline_spec<'a>(format: TextureFormat) -> RenderPipelineSpec<'a> {
+    RenderPipelineSpec {
+        primitive: PrimitiveState {
+            topology: PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: FrontFace::Ccw,
+            cull_mode: None,
+            polygon_mode: PolygonMode::Fill,
+            unclipped_depth: false,
+            conservative: false,
+        },
+        depth_stencil: None,
+        multisample: MultisampleState {
+            count: 1,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        multiview: None,
+        cache: None,
+        fragment_color_target_state: Some(ColorTargetState {
+            format,
+            blend: Some(BlendState::ALPHA_BLENDING),
+            write_mask: ColorWrites::ALL,
+        }),
+    }
+}
+
+/// Maps window pixels, with (0, 0) at the top left, to clip space.
+fn screen_projection(width: u32, height: u32) -> [[f32; 4]; 4] {
+    Matrix4::new_orthographic(
+        0.0,
+        width.max(1) as f32,
+        height.max(1) as f32,
+        0.0,
+        -1.0,
+        1.0,
+    )
+    .into()
+}
+
+/// Latest size passed to Renderer::resize which hasn't been applied yet, and whether any size has been
+/// applied, which configures the surface.
+#[derive(Debug, Default)]
+struct SurfaceSize {
+    requested: Option<(u32, u32)>,
+    configured: bool,
+}
+
+impl SurfaceSize {
+    /// Replaces any earlier size that wasn't applied.
+    fn request(&mut self, width: u32, height: u32) {
+        self.requested = Some((width, height));
+    }
+
+    /// The size to apply this frame, if one was requested since the last frame. The surface counts as
+    /// configured once a size has been taken.
+    fn take(&mut self) -> Option<(u32, u32)> {
+        let size = self.requested.take();
+        self.configured |= size.is_some();
+        size
+    }
+
+    fn is_configured(&self) -> bool {
+        self.configured
+    }
+}
+
+/// Converts an sRGB channel in 0..=1 to linear, for colors which are written to an sRGB target
+/// (which converts back to sRGB on write).
+pub fn srgb_to_linear(channel: f64) -> f64 {
+    if channel <= 0.04045 {
+        channel / 12.92
+    } else {
+        ((channel + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Load op clearing a target of [format] to the sRGB [clear_color].
+fn clear_load_op(clear_color: Color, format: TextureFormat) -> LoadOp<Color> {
+    if format.is_srgb() {
+        LoadOp::Clear(Color {
+            r: srgb_to_linear(clear_color.r),
+            g: srgb_to_linear(clear_color.g),
+            b: srgb_to_linear(clear_color.b),
+            // alpha is always linear
+            a: clear_color.a,
+        })
+    } else {
+        LoadOp::Clear(clear_color)
+    }
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use wgpu::{
+        BlendState, Color, CommandEncoderDescriptor, Extent3d, Face, Features, LoadOp, Operations,
+        PolygonMode, RenderPassColorAttachment, RenderPassDescriptor, StoreOp, TextureDescriptor,
+        TextureDimension, TextureFormat, TextureUsages, TextureViewDescriptor,
+    };
+
+    use nalgebra::{Matrix4, UnitQuaternion, Vector2, Vector3};
+    use std::collections::HashMap;
+
+    use crate::{
+        core::{
+            Unique,
+            entity::{BoundingBox, CollisionResponse, Entity, EntityType, MaterialKind},
+            geometry::{BoundingSphere, Frustum},
+            prefabs::TopDownCamera,
+        },
+        render::{
+            module::load_shader,
+            renderer::{
+                MeshBounds, RenderModuleError, RenderModules, RendererError, ShadingMode,
+                SurfaceSize, back_to_front, capture_texture, clear_load_op, cull_by_distance,
+                cull_by_frustum, fragment_entry_point, group_by_material, negotiate_features,
+                padded_bytes_per_row, pipeline_spec, ui_pipeline_spec, wireframe_polygon_mode,
+            },
+            test_device,
+        },
+    };
+
+    #[test]
+    fn ui_pipeline_is_separate_from_main() {
+        let main = pipeline_spec(TextureFormat::Bgra8UnormSrgb, Some(Face::Back));
+        let ui = ui_pipeline_spec(TextureFormat::Bgra8UnormSrgb);
+
+        assert!(ui.depth_stencil.is_none());
+        assert_eq!(
+            ui.fragment_color_target_state.unwrap().blend,
+            Some(BlendState::ALPHA_BLENDING)
+        );
+
+        assert!(main.depth_stencil.is_some());
+        assert_eq!(
+            main.fragment_color_target_state.unwrap().blend,
+            Some(BlendState::REPLACE)
+        );
+    }
+
+    #[test]
+    fn entities_grouped_by_material() {
+        let entities: Vec<Entity> = [
+            MaterialKind::Opaque,
+            MaterialKind::Transparent,
+            MaterialKind::Opaque,
+            MaterialKind::Unlit,
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(i, material)| {
+            let mut entity = Entity::new(
+                i as u64,
+                0,
+                0,
+                Vector3::new(1.0, 1.0, 1.0),
+                UnitQuaternion::identity(),
+                Vector3::zeros(),
+                Vector3::zeros(),
+                Vector3::zeros(),
+                BoundingBox::ZERO,
+                EntityType::Object,
+                CollisionResponse::Immovable,
+                1.0,
+            );
+            entity.material = material;
+            entity
+        })
+        .collect();
+
+        let groups = group_by_material(&entities);
+        let ids = |material: MaterialKind| -> Vec<u64> {
+            groups[&material].iter().map(|e| *e.id()).collect()
+        };
+
+        assert_eq!(groups.len(), 3);
+        assert_eq!(ids(MaterialKind::Opaque), vec![0, 2]);
+        assert_eq!(ids(MaterialKind::Transparent), vec![1]);
+        assert_eq!(ids(MaterialKind::Unlit), vec![3]);
+    }
+
+    #[test]
+    fn missing_shader_reports_path() {
+        let path = "src/render/shaders/missing.wgsl";
+
+        let result: Result<RenderModules<String>, RendererError> =
+            RenderModules::try_new(|_| load_shader(path)).map_err(RendererError::Shader);
+
+        let Err(RendererError::Shader(error)) = &result else {
+            panic!("expected a shader error");
+        };
+        assert_eq!(error.path, path);
+        assert_eq!(error.error.kind(), std::io::ErrorKind::NotFound);
+        assert!(result.err().unwrap().to_string().contains(path));
+        assert!(load_shader("src/render/shaders/default.wgsl").is_ok());
+    }
+
+    #[test]
+    fn captured_frame_matches_clear_color() {
+        // 50 pixels take 200 bytes, padded to 256
+        assert_eq!(padded_bytes_per_row(50), 256);
+        assert_eq!(padded_bytes_per_row(64), 256);
+        assert_eq!(padded_bytes_per_row(65), 512);
+
+        let Some((device, queue)) = test_device() else {
+            return;
+        };
+        let format = TextureFormat::Bgra8UnormSrgb;
+        let texture = device.create_texture(&TextureDescriptor {
+            label: None,
+            size: Extent3d {
+                width: 50,
+                height: 30,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&TextureViewDescriptor::default());
+        let color = Color {
+            r: 0.2,
+            g: 0.4,
+            b: 0.8,
+            a: 1.0,
+        };
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor { label: None });
+        // a scene of nothing but the clear color
+        encoder.begin_render_pass(&RenderPassDescriptor {
+            label: None,
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: &view,
+                resolve_target: None,
+                ops: Operations {
+                    load: clear_load_op(color, format),
+                    store: StoreOp::Store,
+                },
+                depth_slice: None,
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        queue.submit(std::iter::once(encoder.finish()));
+
+        let image = capture_texture(&device, &queue, &texture).unwrap();
+
+        assert_eq!(image.dimensions(), (50, 30));
+        let center = image.get_pixel(25, 15);
+        for (channel, expected) in [color.r, color.g, color.b, color.a].iter().enumerate() {
+            assert!(
+                (center[channel] as f64 - expected * 255.0).abs() <= 1.0,
+                "{:?}",
+                center
+            );
+        }
+        // the padding at the end of each row isn't part of the image
+        assert_eq!(image.get_pixel(49, 29), center);
+    }
+
+    #[test]
+    fn features_negotiated_to_intersection() {
+        let desired =
+            Features::POLYGON_MODE_LINE | Features::DEPTH_CLIP_CONTROL | Features::TIMESTAMP_QUERY;
+        let available = Features::POLYGON_MODE_LINE
+            | Features::TIMESTAMP_QUERY
+            | Features::TEXTURE_COMPRESSION_BC;
+
+        assert_eq!(
+            negotiate_features(desired, available),
+            Features::POLYGON_MODE_LINE | Features::TIMESTAMP_QUERY
+        );
+        assert_eq!(
+            negotiate_features(desired, Features::empty()),
+            Features::empty()
+        );
+        assert_eq!(
+            negotiate_features(Features::empty(), available),
+            Features::empty()
+        );
+    }
+
+    #[test]
+    fn wireframe_needs_polygon_mode_line() {
+        assert_eq!(
+            wireframe_polygon_mode(false, Features::empty()),
+            Some(PolygonMode::Fill)
+        );
+        assert_eq!(wireframe_polygon_mode(true, Features::empty()), None);
+        assert_eq!(
+            wireframe_polygon_mode(true, Features::POLYGON_MODE_LINE),
+            Some(PolygonMode::Line)
+        );
+        // turning wireframe off never needs the feature
+        assert_eq!(
+            wireframe_polygon_mode(false, Features::POLYGON_MODE_LINE),
+            Some(PolygonMode::Fill)
+        );
+    }
+
+    #[test]
+    fn transparent_sorted_back_to_front() {
+        let entities: Vec<Entity> = [
+            Vector3::new(0.0, 0.0, 5.0),
+            Vector3::new(0.0, 0.0, -20.0),
+            Vector3::new(3.0, 0.0, 0.0),
+            // as far as the first, on the other side
+            Vector3::new(0.0, 0.0, -5.0),
+            Vector3::new(10.0, 10.0, 10.0),
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(i, translation)| {
+            Entity::new(
+                i as u64,
+                0,
+                0,
+                Vector3::new(1.0, 1.0, 1.0),
+                UnitQuaternion::identity(),
+                translation,
+                Vector3::zeros(),
+                Vector3::zeros(),
+                BoundingBox::ZERO,
+                EntityType::Object,
+                CollisionResponse::Immovable,
+                1.0,
+            )
+        })
+        .collect();
+        let entities: Vec<&Entity> = entities.iter().collect();
+
+        assert_eq!(
+            back_to_front(&entities, &Vector3::zeros()),
+            vec![1, 4, 0, 3, 2]
+        );
+        // from further along +z, the entities behind the origin are the far ones
+        assert_eq!(
+            back_to_front(&entities, &Vector3::new(0.0, 0.0, 30.0)),
+            vec![1, 3, 2, 0, 4]
+        );
+    }
+
+    #[test]
+    fn normals_mode_selects_debug_shader() {
+        let mode = ShadingMode::default();
+        assert_eq!(fragment_entry_point(MaterialKind::Opaque, mode), "fs_main");
+        assert_eq!(fragment_entry_point(MaterialKind::Unlit, mode), "fs_unlit");
+
+        let mode = mode.toggled();
+        for material in MaterialKind::ALL {
+            assert_eq!(fragment_entry_point(material, mode), "fs_normals");
+        }
+        assert_eq!(mode.toggled(), ShadingMode::Lit);
+    }
+
+    #[test]
+    fn srgb_clear_color_is_linearized() {
+        let gray = Color {
+            r: 0.5,
+            g: 0.5,
+            b: 0.5,
+            a: 0.5,
+        };
+
+        let LoadOp::Clear(linear) = clear_load_op(gray, TextureFormat::Bgra8UnormSrgb) else {
+            panic!("not a clear");
+        };
+        for channel in [linear.r, linear.g, linear.b] {
+            assert!((channel - 0.214041).abs() < 1.0e-6, "{}", channel);
+        }
+        assert_eq!(linear.a, 0.5);
+
+        // non-sRGB targets store the value as given
+        let LoadOp::Clear(unchanged) = clear_load_op(gray, TextureFormat::Bgra8Unorm) else {
+            panic!("not a clear");
+        };
+        assert_eq!(unchanged, gray);
+    }
+
+    #[test]
+    fn rapid_resizes_applied_once() {
+        let mut pending = SurfaceSize::default();
+        let mut applied = vec![];
+        let mut frame = |pending: &mut SurfaceSize| {
+            if let Some(size) = pending.take() {
+                applied.push(size);
+            }
+        };
+
+        // a drag fires many events between two frames
+        for width in 800..=860 {
+            pending.request(width, 600);
+        }
+        frame(&mut pending);
+        frame(&mut pending);
+
+        assert_eq!(applied, vec![(860, 600)]);
+    }
+
+    #[test]
+    fn ready_after_first_resize() {
+        let mut surface_size = SurfaceSize::default();
+
+        // frames before the window's first Resized event draw nothing
+        assert_eq!(surface_size.take(), None);
+        assert!(!surface_size.is_configured());
+
+        surface_size.request(800, 600);
+        assert!(!surface_size.is_configured());
+        assert_eq!(surface_size.take(), Some((800, 600)));
+        assert!(surface_size.is_configured());
+
+        // stays ready on frames without a resize
+        assert_eq!(surface_size.take(), None);
+        assert!(surface_size.is_configured());
+    }
+
+    #[test]
+    fn distant_entities_not_drawn() {
+        let entities: Vec<Entity> = [5.0, 99.0, 101.0, -150.0]
+            .into_iter()
+            .enumerate()
+            .map(|(i, z)| {
+                Entity::new(
+                    i as u64,
+                    0,
+                    0,
+                    Vector3::new(1.0, 1.0, 1.0),
+                    UnitQuaternion::identity(),
+                    Vector3::new(10.0, 0.0, z),
+                    Vector3::zeros(),
+                    Vector3::zeros(),
+                    BoundingBox::ZERO,
+                    EntityType::Object,
+                    CollisionResponse::Immovable,
+                    1.0,
+                )
+            })
+            .collect();
+        let camera = Vector3::new(10.0, 0.0, 0.0);
+        let ids =
+            |entities: Vec<&Entity>| -> Vec<u64> { entities.iter().map(|e| *e.id()).collect() };
+
+        let (drawn, culled) = cull_by_distance(&entities, &camera, Some(100.0));
+        assert_eq!(ids(drawn), vec![0, 1]);
+        assert_eq!(ids(culled), vec![2, 3]);
+
+        let (drawn, culled) = cull_by_distance(&entities, &camera, None);
+        assert_eq!(drawn.len(), 4);
+        assert!(culled.is_empty());
+    }
+
+    #[test]
+    fn entities_outside_frustum_not_drawn() {
+        // (mesh, z), looking down -z from the origin
+        let entities: Vec<Entity> = [(0, -10.0), (0, 10.0), (0, 1.5), (1, 10.0)]
+            .into_iter()
+            .enumerate()
+            .map(|(i, (mesh_id, z))| {
+                Entity::new(
+                    i as u64,
+                    mesh_id,
+                    0,
+                    Vector3::new(1.0, 1.0, 1.0),
+                    UnitQuaternion::identity(),
+                    Vector3::new(0.0, 0.0, z),
+                    Vector3::zeros(),
+                    Vector3::zeros(),
+                    BoundingBox::ZERO,
+                    EntityType::Object,
+                    CollisionResponse::Immovable,
+                    1.0,
+                )
+            })
+            .collect();
+        let mesh_bounds = HashMap::from([(
+            0,
+            MeshBounds {
+                sphere: BoundingSphere {
+                    center: Vector3::zeros(),
+                    radius: 2.0,
+                },
+                aabb: BoundingBox::new((-2.0, -2.0, -2.0), (4.0, 4.0, 4.0)),
+            },
+        )]);
+        let view = Matrix4::look_at_rh(
+            &Vector3::zeros().into(),
+            &Vector3::new(0.0, 0.0, -1.0).into(),
+            &Vector3::y(),
+        );
+        let frustum = Frustum::from_view_proj(
+            &(Matrix4::new_perspective(1.0, std::f32::consts::FRAC_PI_2, 0.1, 100.0) * view),
+        );
+        let ids =
+            |entities: Vec<&Entity>| -> Vec<u64> { entities.iter().map(|e| *e.id()).collect() };
+
+        let (drawn, culled) = cull_by_frustum(
+            entities.iter().collect(),
+            std::slice::from_ref(&frustum),
+            &mesh_bounds,
+        );
+        // the sphere behind the camera still reaches past the near plane, and mesh 1 has no bounds
+        assert_eq!(ids(drawn), vec![0, 2, 3]);
+        assert_eq!(ids(culled), vec![1]);
+
+        // seen from above by the minimap, which also draws what's behind the camera
+        let minimap = TopDownCamera {
+            center: Vector2::zeros(),
+            extent: 20.0,
+            height: 100.0,
+        };
+        let frustums = [frustum, Frustum::from_view_proj(&minimap.view_proj())];
+
+        let (drawn, culled) = cull_by_frustum(entities.iter().collect(), &frustums, &mesh_bounds);
+        assert_eq!(ids(drawn), vec![0, 1, 2, 3]);
+        assert!(culled.is_empty());
+    }
+
+    #[test]
+    fn custom_modules_drawn_before_transparent() {
+        // each module stands in for a render module, collecting the ids of the instances given to it
+        let mut modules: RenderModules<Vec<u64>> = RenderModules::new(|_| vec![]);
+        let toon = modules.add("toon", vec![]).unwrap();
+        let pbr = modules.add("pbr", vec![]).unwrap();
+        assert_eq!(toon, MaterialKind::Custom(0));
+        assert_eq!(pbr, MaterialKind::Custom(1));
+        assert_eq!(
+            modules.names(),
+            vec!["Opaque", "Unlit", "toon", "pbr", "Transparent"]
+        );
+        assert!(matches!(
+            modules.add("Opaque", vec![]),
+            Err(RenderModuleError::NameTaken(_))
+        ));
+        assert_eq!(modules.material("toon"), Some(toon));
+
+        let entities: Vec<Entity> = [
+            MaterialKind::Opaque,
+            toon,
+            MaterialKind::Transparent,
+            toon,
+            MaterialKind::Custom(7),
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(i, material)| {
+            let mut entity = Entity::new(
+                i as u64,
+                0,
+                0,
+                Vector3::new(1.0, 1.0, 1.0),
+                UnitQuaternion::identity(),
+                Vector3::zeros(),
+                Vector3::zeros(),
+                Vector3::zeros(),
+                BoundingBox::ZERO,
+                EntityType::Object,
+                CollisionResponse::Immovable,
+                1.0,
+            );
+            entity.material = material;
+            entity
+        })
+        .collect();
+        for (material, entities) in group_by_material(&entities) {
+            modules
+                .get_mut(material)
+                .extend(entities.iter().map(|e| *e.id()));
+        }
+
+        let drawn: Vec<Vec<u64>> = modules
+            .iter()
+            .map(|ids| {
+                let mut ids = ids.clone();
+                ids.sort();
+                ids
+            })
+            .collect();
+        // unregistered materials are drawn as opaque
+        assert_eq!(drawn, vec![vec![0, 4], vec![], vec![1, 3], vec![], vec![2]]);
+    }
+}
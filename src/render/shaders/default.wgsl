@@ -8,6 +8,12 @@ struct LightUniform {
     luminence: f32,
 }
 
+// Laid out by LightSourceStorage, only the first count lights are valid
+struct Lights {
+    count: u32,
+    lights: array<LightUniform>,
+}
+
 @group(0) @binding(0)
 var<uniform> camera: CameraUniform;
 
@@ -17,7 +23,7 @@ var texture: texture_2d<f32>;
 var s: sampler;
 
 @group(2) @binding(0)
-var<uniform> light: LightUniform;
+var<storage, read> lights: Lights;
 
 @group(3) @binding(0)
 var depth_texture: texture_depth_2d;
@@ -29,10 +35,11 @@ struct VertexInput {
 }
 
 struct InstanceInput {
-    @location(5) x: vec4<f32>,
-    @location(6) y: vec4<f32>,
-    @location(7) z: vec4<f32>,
-    @location(8) w: vec4<f32>,
+    @location(5) rotation: vec4<f32>,
+    @location(6) translation: vec3<f32>,
+    @location(7) scale: vec3<f32>,
+    @location(8) emissive: f32,
+    @location(9) tint: vec3<f32>,
 }
 
 struct VertexOutput {
@@ -40,6 +47,21 @@ struct VertexOutput {
     @location(0) tex_coords: vec2<f32>,
     @location(1) world_position: vec4<f32>,
     @location(2) normal: vec3<f32>,
+    @location(3) emissive: f32,
+    @location(4) tint: vec3<f32>,
+}
+
+// Rotation matrix of a unit quaternion (x, y, z, w). Mirrored by Instance::model_matrix.
+fn quat_to_mat3(q: vec4<f32>) -> mat3x3<f32> {
+    let x = q.x;
+    let y = q.y;
+    let z = q.z;
+    let w = q.w;
+    return mat3x3<f32>(
+        vec3<f32>(1.0 - 2.0 * (y * y + z * z), 2.0 * (x * y + w * z), 2.0 * (x * z - w * y)),
+        vec3<f32>(2.0 * (x * y - w * z), 1.0 - 2.0 * (x * x + z * z), 2.0 * (y * z + w * x)),
+        vec3<f32>(2.0 * (x * z + w * y), 2.0 * (y * z - w * x), 1.0 - 2.0 * (x * x + y * y)),
+    );
 }
 
 @vertex
@@ -47,30 +69,56 @@ fn vs_main(
     model: VertexInput,
     instance: InstanceInput,
 ) -> VertexOutput {
-    let transform = mat4x4<f32>(
-        instance.x,
-        instance.y,
-        instance.z,
-        instance.w
-    );
+    let rotation = quat_to_mat3(instance.rotation);
+    let world_position = vec4<f32>(rotation * (model.position * instance.scale) + instance.translation, 1.0);
     var out: VertexOutput;
-    out.world_position = transform * vec4<f32>(model.position, 1.0);
-    out.normal = mat3x3<f32>(transform[0].xyz, transform[1].xyz, transform[2].xyz) * model.normal;
-    out.clip_position = camera.view_proj * transform * vec4<f32>(model.position, 1.0);
+    out.world_position = world_position;
+    // inverse transpose of rotation * scale, so normals stay perpendicular under non-uniform scale.
+    // Mirrored by Instance::normal_matrix.
+    out.normal = normalize(rotation * (model.normal / instance.scale));
+    out.clip_position = camera.view_proj * world_position;
     out.tex_coords = model.tex_coords;
+    out.emissive = instance.emissive;
+    out.tint = instance.tint;
     return out;
 }
 
+// Fades the lighting out in favour of the base color as emissive goes to 1. Mirrored by Instance::shade.
+fn shade(base: vec3<f32>, lighting: vec3<f32>, emissive: f32) -> vec3<f32> {
+    return base * (lighting * (1.0 - emissive) + emissive);
+}
+
+// Sum of every light's contribution at a point
+fn light_sum(position: vec3<f32>, normal: vec3<f32>) -> vec3<f32> {
+    var total = vec3<f32>(0.0);
+    for (var i = 0u; i < min(lights.count, arrayLength(&lights.lights)); i++) {
+        let light = lights.lights[i];
+        let light_vec = position - light.pos.xyz;
+        let light_dist = length(light_vec);
+        let light_unit_vec = normalize(light_vec);
+        let brightness = light.luminence * 1.0 / max(light_dist * light_dist, 1.0);
+        total += light.colour.xyz * max(dot(normal, -light_unit_vec), 0.0) * brightness;
+    }
+    return total;
+}
+
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    let light_pos = light.pos.xyz;
-    let light_vec = in.world_position.xyz - light_pos;
-    let light_dist = length(light_vec);
-    let light_unit_vec = normalize(light_vec);
-    let brightness = light.luminence * 1.0 / max(light_dist * light_dist, 1.0);
-    let lighting = light.colour.xyz * max(dot(in.normal, -light_unit_vec), 0.0);
-
-    return vec4<f32>(lighting * brightness, 1.0) * textureSample(texture, s, in.tex_coords);
+    let lighting = light_sum(in.world_position.xyz, in.normal);
+
+    let base = textureSample(texture, s, in.tex_coords) * vec4<f32>(in.tint, 1.0);
+    return vec4<f32>(shade(base.rgb, lighting, in.emissive), base.a);
+}
+
+@fragment
+fn fs_unlit(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(texture, s, in.tex_coords) * vec4<f32>(in.tint, 1.0);
+}
+
+// Debug shading, world-space normals mapped to RGB
+@fragment
+fn fs_normals(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(normalize(in.normal) * 0.5 + 0.5, 1.0);
 }
 
 // @fragment
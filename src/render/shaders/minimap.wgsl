@@ -0,0 +1,44 @@
+struct ScreenUniform {
+    projection: mat4x4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> screen: ScreenUniform;
+
+@group(1) @binding(0)
+var minimap_texture: texture_2d<f32>;
+@group(1) @binding(1)
+var minimap_sampler: sampler;
+
+struct VertexInput {
+    // 0 to 1 on both axes, doubling as the texture coordinates
+    @location(0) position: vec2<f32>,
+    @location(1) color: vec4<f32>,
+}
+
+struct InstanceInput {
+    @location(5) offset: vec2<f32>,
+    @location(6) scale: vec2<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+}
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+    instance: InstanceInput,
+) -> VertexOutput {
+    let position = model.position * instance.scale + instance.offset;
+    var out: VertexOutput;
+    out.clip_position = screen.projection * vec4<f32>(position, 0.0, 1.0);
+    out.tex_coords = model.position;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(minimap_texture, minimap_sampler, in.tex_coords);
+}
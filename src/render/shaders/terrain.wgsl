@@ -8,16 +8,23 @@ struct LightUniform {
     luminence: f32,
 }
 
+// Laid out by LightSourceStorage, only the first count lights are valid
+struct Lights {
+    count: u32,
+    lights: array<LightUniform>,
+}
+
 @group(0) @binding(0)
 var<uniform> camera: CameraUniform;
 
+// One layer per SplatLayer
 @group(1) @binding(0)
-var texture: texture_2d<f32>;
+var textures: texture_2d_array<f32>;
 @group(1) @binding(1)
 var s: sampler;
 
 @group(2) @binding(0)
-var<uniform> light: LightUniform;
+var<storage, read> lights: Lights;
 
 @group(3) @binding(0)
 var depth_texture: texture_depth_2d;
@@ -26,6 +33,7 @@ struct VertexInput {
     @location(0) position: vec3<f32>,
     @location(1) normal: vec3<f32>,
     @location(2) tex_coords: vec2<f32>,
+    @location(3) splat: vec4<f32>,
 }
 
 struct InstanceInput {
@@ -37,6 +45,7 @@ struct VertexOutput {
     @location(0) tex_coords: vec2<f32>,
     @location(1) world_position: vec4<f32>,
     @location(2) normal: vec3<f32>,
+    @location(3) splat: vec4<f32>,
 }
 
 @vertex
@@ -55,17 +64,32 @@ fn vs_main(
     out.normal = mat3x3<f32>(transform[0].xyz, transform[1].xyz, transform[2].xyz) * model.normal;
     out.clip_position = camera.view_proj * transform * vec4<f32>(model.position, 1.0);
     out.tex_coords = model.tex_coords;
+    out.splat = model.splat;
     return out;
 }
 
+// Sum of every light's contribution at a point
+fn light_sum(position: vec3<f32>, normal: vec3<f32>) -> vec3<f32> {
+    var total = vec3<f32>(0.0);
+    for (var i = 0u; i < min(lights.count, arrayLength(&lights.lights)); i++) {
+        let light = lights.lights[i];
+        let light_vec = position - light.pos.xyz;
+        let light_dist = length(light_vec);
+        let light_unit_vec = normalize(light_vec);
+        let brightness = light.luminence * 1.0 / max(light_dist * light_dist, 1.0);
+        total += light.colour.xyz * max(dot(normal, -light_unit_vec), 0.0) * brightness;
+    }
+    return total;
+}
+
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    let light_pos = light.pos.xyz;
-    let light_vec = in.world_position.xyz - light_pos;
-    let light_dist = length(light_vec);
-    let light_unit_vec = normalize(light_vec);
-    let brightness = light.luminence * 1.0 / max(light_dist * light_dist, 1.0);
-    let lighting = light.colour.xyz * max(dot(in.normal, -light_unit_vec), 0.0);
+    let lighting = light_sum(in.world_position.xyz, in.normal);
 
-    return textureSample(texture, s, in.tex_coords);
+    // weighted sum of every layer, sampled outside of any branch so derivatives stay valid
+    var color = vec4<f32>(0.0);
+    for (var layer = 0; layer < 4; layer++) {
+        color += textureSample(textures, s, in.tex_coords, layer) * in.splat[layer];
+    }
+    return color;
 }
@@ -0,0 +1,38 @@
+struct ScreenUniform {
+    projection: mat4x4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> screen: ScreenUniform;
+
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+    @location(1) color: vec4<f32>,
+}
+
+struct InstanceInput {
+    @location(5) offset: vec2<f32>,
+    @location(6) scale: vec2<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+}
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+    instance: InstanceInput,
+) -> VertexOutput {
+    let position = model.position * instance.scale + instance.offset;
+    var out: VertexOutput;
+    out.clip_position = screen.projection * vec4<f32>(position, 0.0, 1.0);
+    out.color = model.color;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return in.color;
+}
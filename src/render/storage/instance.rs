@@ -1,19 +1,62 @@
+use std::{
+    borrow::Cow,
+    collections::{BTreeSet, HashMap},
+    ops::Range,
+};
+
 use bytemuck::{Pod, Zeroable};
 use log::debug;
 use wgpu::{
-    Buffer, BufferDescriptor, BufferSlice, BufferUsages, Device, Queue,
+    Buffer, BufferDescriptor, BufferSlice, BufferUsages, Device, Queue, VERTEX_ALIGNMENT,
     util::{BufferInitDescriptor, DeviceExt},
 };
 
-/// Maps an entity id to an index into a transform array. Once an entity is added, it can't be removed (for now).
+/// Smallest instance buffer allocated, in bytes.
+const MIN_BUFFER_SIZE: u64 = 100;
+
+/// The buffer is compacted once it's this many times larger than the live instances.
+const COMPACT_RATIO: u64 = 4;
+
+/// Why instances of a type can't be laid out a given stride apart, see InstanceStorage::with_stride.
+#[derive(Debug, PartialEq, Eq)]
+pub enum InstanceStrideError {
+    /// Shorter than the instance type, so instances would overlap.
+    TooSmall { stride: u64, size: u64 },
+    /// Not a multiple of VERTEX_ALIGNMENT.
+    Misaligned(u64),
+}
+
+/// Checks that instances of [I] can be read [stride] bytes apart from a vertex buffer.
+pub fn check_instance_stride<I>(stride: u64) -> Result<(), InstanceStrideError> {
+    let size = size_of::<I>() as u64;
+    if stride < size {
+        return Err(InstanceStrideError::TooSmall { stride, size });
+    }
+    if !stride.is_multiple_of(VERTEX_ALIGNMENT) {
+        return Err(InstanceStrideError::Misaligned(stride));
+    }
+    Ok(())
+}
+
+/// Maps an entity id to an index into a transform array.
+///
+/// Indirection is needed since instances are expected to be tightly packed. Removing an instance moves the
+/// last instance into its slot.
 ///
-/// Indirection is needed since instances are expected to have a specific ordering.
+/// Slots changed since the last update_gpu are tracked, and only those are written to the buffer.
 #[derive(Debug)]
 pub struct InstanceStorage<I>
 where
     I: Pod + Zeroable + Clone + Copy + std::fmt::Debug,
 {
     data: Vec<I>,
+    slots: HashMap<u64, usize>,
+    // Entity id of each slot in [data]
+    ids: Vec<u64>,
+    // Slots changed since the last upload
+    dirty: BTreeSet<usize>,
+    // Bytes between the starts of consecutive instances in the buffer
+    stride: u64,
 
     instance_buffer: Buffer,
 }
@@ -22,21 +65,39 @@ impl<I> InstanceStorage<I>
 where
     I: Pod + Zeroable + Clone + Copy + std::fmt::Debug,
 {
+    /// Instances packed size_of::<I>() bytes apart. Panics if that isn't a valid stride, see
+    /// check_instance_stride.
     pub fn new(device: &Device) -> Self {
+        Self::with_stride(device, size_of::<I>() as u64).unwrap()
+    }
+
+    /// Instances laid out [stride] bytes apart, zero padded after each instance. [stride] has to match
+    /// the array_stride of the instance VertexBufferLayout the buffer is drawn with.
+    pub fn with_stride(device: &Device, stride: u64) -> Result<Self, InstanceStrideError> {
+        check_instance_stride::<I>(stride)?;
         let instance_buffer = device.create_buffer_init(&BufferInitDescriptor {
             label: Some("Instance Buffer"),
-            contents: &[0 as u8; 100],
+            contents: &[0; MIN_BUFFER_SIZE as usize],
             usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
         });
 
-        Self {
+        Ok(Self {
             data: Vec::new(),
+            slots: HashMap::new(),
+            ids: Vec::new(),
+            dirty: BTreeSet::new(),
+            stride,
             instance_buffer,
-        }
+        })
     }
 
     pub fn get_instance(&self, entity_id: &u64) -> Option<&I> {
-        self.data.get(*entity_id as usize)
+        self.slots.get(entity_id).map(|slot| &self.data[*slot])
+    }
+
+    /// Index of the entity's instance in the buffer. Changes when another instance is removed.
+    pub fn slot(&self, entity_id: &u64) -> Option<usize> {
+        self.slots.get(entity_id).copied()
     }
 
     pub fn len(&self) -> u64 {
@@ -47,37 +108,328 @@ where
         self.instance_buffer.size()
     }
 
+    pub fn stride(&self) -> u64 {
+        self.stride
+    }
+
+    /// Bytes taken up by the instances, each InstanceStorage::stride long.
+    pub fn byte_len(&self) -> u64 {
+        self.len() * self.stride
+    }
+
     pub fn slice(&self) -> BufferSlice<'_> {
-        self.instance_buffer
-            .slice(0..self.len() * size_of::<[[f32; 4]; 4]>() as u64)
+        self.instance_buffer.slice(0..self.byte_len())
     }
 
-    /// Inserts a new instance if it wasn't in the buffer, updates existing one if it was.
+    /// Inserts a new instance if it wasn't in the buffer, updates existing one if it was. Instances set to
+    /// the data they already had aren't re-uploaded.
     pub fn upsert_instance(&mut self, entity_id: &u64, data: I) {
-        if *entity_id < self.len() {
-            self.data[*entity_id as usize] = data;
+        if let Some(slot) = self.slots.get(entity_id) {
+            if bytemuck::bytes_of(&self.data[*slot]) != bytemuck::bytes_of(&data) {
+                self.data[*slot] = data;
+                self.dirty.insert(*slot);
+            }
         } else {
+            self.slots.insert(*entity_id, self.data.len());
+            self.dirty.insert(self.data.len());
+            self.ids.push(*entity_id);
             self.data.push(data);
         }
     }
 
-    /// May re-allocate buffer.
-    pub fn update_gpu(&mut self, queue: &Queue, device: &Device) {
-        let bytes = bytemuck::cast_slice(&self.data);
-        if bytes.len() > self.capacity() as usize {
-            let new_size = (self.capacity() * 2).max(bytes.len() as u64);
-            debug!(
-                "re-allocating instance buffer to {:.8} MB",
-                new_size as f32 / (1024.0 * 1024.0)
+    /// Removes an instance, returning it if it existed.
+    pub fn remove_instance(&mut self, entity_id: &u64) -> Option<I> {
+        let slot = self.slots.remove(entity_id)?;
+        self.ids.swap_remove(slot);
+        if let Some(moved) = self.ids.get(slot) {
+            self.slots.insert(*moved, slot);
+            self.dirty.insert(slot);
+        }
+        Some(self.data.swap_remove(slot))
+    }
+
+    /// Uploads the instances changed since the last call, one write per run of adjacent changed slots.
+    /// Returns the number of writes issued.
+    ///
+    /// May re-allocate buffer, re-uploading every instance. Compacts it if most of it is unused after
+    /// removals.
+    pub fn update_gpu(&mut self, queue: &Queue, device: &Device) -> usize {
+        if self.byte_len() > self.capacity() {
+            let new_size = (self.capacity() * 2).max(self.byte_len());
+            self.reallocate(device, new_size);
+            self.dirty = (0..self.data.len()).collect();
+        } else if self.capacity() > MIN_BUFFER_SIZE
+            && self.capacity() > self.byte_len() * COMPACT_RATIO
+        {
+            self.compact(queue, device);
+            return 1;
+        }
+        let spans = dirty_spans(&self.dirty, self.data.len());
+        for span in spans.iter() {
+            queue.write_buffer(
+                &self.instance_buffer,
+                span.start as u64 * self.stride,
+                &padded_bytes(&self.data[span.clone()], self.stride),
             );
-            self.instance_buffer.destroy();
-            self.instance_buffer = device.create_buffer(&BufferDescriptor {
-                label: Some("Instance Buffer"),
-                size: new_size,
-                mapped_at_creation: false,
-                usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
-            });
         }
-        queue.write_buffer(&self.instance_buffer, 0, bytes);
+        self.dirty.clear();
+        spans.len()
+    }
+
+    /// Rebuilds the id to slot map, shrinks the buffer to fit the live instances and re-uploads them.
+    pub fn compact(&mut self, queue: &Queue, device: &Device) {
+        self.slots = self
+            .ids
+            .iter()
+            .enumerate()
+            .map(|(slot, id)| (*id, slot))
+            .collect();
+        let new_size = self.byte_len().max(MIN_BUFFER_SIZE);
+        if new_size != self.capacity() {
+            self.reallocate(device, new_size);
+        }
+        queue.write_buffer(
+            &self.instance_buffer,
+            0,
+            &padded_bytes(&self.data, self.stride),
+        );
+        self.dirty.clear();
+    }
+
+    fn reallocate(&mut self, device: &Device, size: u64) {
+        debug!(
+            "re-allocating instance buffer to {:.8} MB",
+            size as f32 / (1024.0 * 1024.0)
+        );
+        self.instance_buffer.destroy();
+        self.instance_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("Instance Buffer"),
+            size,
+            mapped_at_creation: false,
+            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+        });
+    }
+}
+
+/// Bytes of [instances] laid out [stride] bytes apart, zero padded.
+fn padded_bytes<I: Pod>(instances: &[I], stride: u64) -> Cow<'_, [u8]> {
+    let (size, stride) = (size_of::<I>(), stride as usize);
+    let bytes: &[u8] = bytemuck::cast_slice(instances);
+    if size == stride || size == 0 {
+        return Cow::Borrowed(bytes);
+    }
+    let mut padded = vec![0; instances.len() * stride];
+    for (i, instance) in bytes.chunks_exact(size).enumerate() {
+        padded[i * stride..i * stride + size].copy_from_slice(instance);
+    }
+    Cow::Owned(padded)
+}
+
+/// Merges [dirty] slots into ranges of adjacent slots, leaving out slots past [len] which were removed.
+fn dirty_spans(dirty: &BTreeSet<usize>, len: usize) -> Vec<Range<usize>> {
+    let mut spans: Vec<Range<usize>> = Vec::new();
+    for slot in dirty.range(..len) {
+        match spans.last_mut() {
+            Some(span) if span.end == *slot => span.end += 1,
+            _ => spans.push(*slot..*slot + 1),
+        }
+    }
+    spans
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use bytemuck::{Pod, Zeroable};
+    use std::collections::BTreeSet;
+
+    use crate::render::{
+        storage::instance::{
+            InstanceStorage, InstanceStrideError, check_instance_stride, dirty_spans, padded_bytes,
+        },
+        test_device,
+    };
+
+    // 5 floats, unlike any of the engine's instance types
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy, Pod, Zeroable)]
+    struct ColoredInstance {
+        offset: [f32; 2],
+        color: [f32; 3],
+    }
+
+    #[test]
+    fn sized_by_instance_type() {
+        let Some((device, queue)) = test_device() else {
+            return;
+        };
+        let mut storage = InstanceStorage::<ColoredInstance>::new(&device);
+        for id in 0..30 {
+            storage.upsert_instance(
+                &id,
+                ColoredInstance {
+                    offset: [id as f32, 0.0],
+                    color: [1.0, 0.0, 0.0],
+                },
+            );
+        }
+        storage.remove_instance(&3);
+
+        storage.update_gpu(&queue, &device);
+
+        assert_eq!(storage.byte_len(), 29 * 20);
+        assert_eq!(storage.slice().size().get(), storage.byte_len());
+        assert!(storage.capacity() >= storage.byte_len());
+    }
+
+    #[test]
+    fn padded_to_configured_stride() {
+        assert_eq!(check_instance_stride::<ColoredInstance>(20), Ok(()));
+        assert_eq!(check_instance_stride::<ColoredInstance>(32), Ok(()));
+        assert_eq!(
+            check_instance_stride::<ColoredInstance>(16),
+            Err(InstanceStrideError::TooSmall {
+                stride: 16,
+                size: 20
+            })
+        );
+        assert_eq!(
+            check_instance_stride::<ColoredInstance>(22),
+            Err(InstanceStrideError::Misaligned(22))
+        );
+        let instances = [1.0f32, 2.0];
+        let padded = padded_bytes(&instances, 8);
+        assert_eq!(padded.len(), 16);
+        assert_eq!(&padded[0..4], bytemuck::bytes_of(&1.0f32));
+        assert_eq!(&padded[4..8], &[0; 4]);
+        assert_eq!(&padded[8..12], bytemuck::bytes_of(&2.0f32));
+
+        let Some((device, queue)) = test_device() else {
+            return;
+        };
+        assert!(InstanceStorage::<ColoredInstance>::with_stride(&device, 16).is_err());
+        let mut storage = InstanceStorage::<ColoredInstance>::with_stride(&device, 32).unwrap();
+        for id in 0..10 {
+            storage.upsert_instance(
+                &id,
+                ColoredInstance {
+                    offset: [id as f32, 0.0],
+                    color: [0.0, 0.0, 1.0],
+                },
+            );
+        }
+
+        storage.update_gpu(&queue, &device);
+
+        assert_eq!(storage.stride(), 32);
+        assert_eq!(storage.byte_len(), 10 * 32);
+        assert_eq!(storage.slice().size().get(), storage.byte_len());
+    }
+
+    #[test]
+    fn compacted_to_live_instances() {
+        let Some((device, queue)) = test_device() else {
+            return;
+        };
+        let mut storage = InstanceStorage::<ColoredInstance>::new(&device);
+        let instance = |id: u64| ColoredInstance {
+            offset: [id as f32, -(id as f32)],
+            color: [0.0, 1.0, 0.0],
+        };
+        for id in 0..40 {
+            storage.upsert_instance(&id, instance(id));
+        }
+        storage.update_gpu(&queue, &device);
+        for id in (0..40).step_by(2) {
+            storage.remove_instance(&id);
+        }
+
+        storage.compact(&queue, &device);
+
+        assert_eq!(storage.len(), 20);
+        assert_eq!(storage.byte_len(), 20 * 20);
+        assert_eq!(storage.capacity(), storage.byte_len());
+        for id in 0..40 {
+            if id % 2 == 0 {
+                assert!(storage.get_instance(&id).is_none());
+            } else {
+                assert_eq!(
+                    storage.get_instance(&id).unwrap().offset,
+                    instance(id).offset
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn removed_instance_slot_reclaimed() {
+        let Some((device, queue)) = test_device() else {
+            return;
+        };
+        let mut storage = InstanceStorage::<ColoredInstance>::new(&device);
+        let instance = |id: u64| ColoredInstance {
+            offset: [id as f32, 2.0 * id as f32],
+            color: [1.0, 1.0, 0.0],
+        };
+        for id in 0..3 {
+            storage.upsert_instance(&id, instance(id));
+        }
+        storage.update_gpu(&queue, &device);
+
+        storage.remove_instance(&1);
+        storage.update_gpu(&queue, &device);
+
+        // the last instance fills the gap, so every slot drawn is live
+        assert_eq!(storage.len(), 2);
+        assert_eq!(storage.slice().size().get(), 2 * 20);
+        let drawn: Vec<[f32; 2]> = storage.data.iter().map(|i| i.offset).collect();
+        assert_eq!(drawn, vec![instance(0).offset, instance(2).offset]);
+        assert_eq!(storage.get_instance(&2).unwrap().offset, instance(2).offset);
+
+        // a new instance takes the next slot
+        storage.upsert_instance(&3, instance(3));
+        assert_eq!(storage.len(), 3);
+        assert_eq!(storage.data[2].offset, instance(3).offset);
+    }
+
+    #[test]
+    fn dirty_slots_merged_into_spans() {
+        let dirty = BTreeSet::from([0, 1, 2, 5, 7, 8, 12]);
+
+        assert_eq!(dirty_spans(&dirty, 20), vec![0..3, 5..6, 7..9, 12..13]);
+        // slots 8 and 12 were removed since they were changed
+        assert_eq!(dirty_spans(&dirty, 8), vec![0..3, 5..6, 7..8]);
+        assert!(dirty_spans(&BTreeSet::new(), 20).is_empty());
+    }
+
+    #[test]
+    fn unchanged_frame_issues_no_writes() {
+        let Some((device, queue)) = test_device() else {
+            return;
+        };
+        let mut storage = InstanceStorage::<ColoredInstance>::new(&device);
+        let instance = |id: u64, x: f32| ColoredInstance {
+            offset: [id as f32, x],
+            color: [0.0, 0.0, 1.0],
+        };
+        for id in 0..10 {
+            storage.upsert_instance(&id, instance(id, 0.0));
+        }
+        assert_eq!(storage.update_gpu(&queue, &device), 1);
+
+        // every instance upserted again as each frame does, but none moved
+        for id in 0..10 {
+            storage.upsert_instance(&id, instance(id, 0.0));
+        }
+        assert_eq!(storage.update_gpu(&queue, &device), 0);
+
+        storage.upsert_instance(&2, instance(2, 1.0));
+        storage.upsert_instance(&3, instance(3, 1.0));
+        storage.upsert_instance(&7, instance(7, 1.0));
+        assert_eq!(storage.update_gpu(&queue, &device), 2);
+
+        // the last instance moves into the removed one's slot
+        storage.remove_instance(&4);
+        assert_eq!(storage.update_gpu(&queue, &device), 1);
+        assert_eq!(storage.update_gpu(&queue, &device), 0);
     }
 }
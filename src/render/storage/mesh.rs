@@ -11,13 +11,15 @@ use crate::render::{GlobalIndexType, model::TobjModelError};
 
 /// Stores a vertex and index buffer on main memory, can be hashed into with a string id to get the start and end indices.
 ///
-/// Meshes can't be removed once added, for now.
+/// Ids are issued in increasing order and never reused, even after a mesh is removed. A removed mesh's vertices
+/// and indices stay in the buffers until MeshStorage::compact is called.
 #[derive(Debug)]
 pub struct MeshStorage<V>
 where
     V: Pod + Zeroable + Clone + Copy + std::fmt::Debug,
 {
     map: HashMap<u64, (usize, usize, usize, usize)>, // vertex inclusive start, exclusive end, index inclusive start, exclusive end
+    next_id: u64,
 
     vertex_storage: Vec<V>,
     vertex_buffer: Buffer,
@@ -46,6 +48,7 @@ where
         });
         Self {
             map: HashMap::new(),
+            next_id: 0,
 
             vertex_storage: Vec::new(),
             vertex_buffer,
@@ -58,18 +61,37 @@ where
     }
 
     /// [indices] should be relative to [vertices] locations in provided slice.
+    ///
+    /// Compacts the storage first if the mesh would only fit without removed meshes.
     pub fn add_mesh(
         &mut self,
         vertices: &[V],
         indices: &[GlobalIndexType],
     ) -> Result<u64, MeshStorageError> {
-        let before_count_vertices = self.vertex_storage.len();
-        let before_count_indexes = self.index_storage.len();
+        let id = self.next_id;
+        self.insert_mesh(id, vertices, indices)?;
+        self.next_id += 1;
+        Ok(id)
+    }
+
+    fn insert_mesh(
+        &mut self,
+        id: u64,
+        vertices: &[V],
+        indices: &[GlobalIndexType],
+    ) -> Result<(), MeshStorageError> {
+        if self.map.contains_key(&id) {
+            return Err(MeshStorageError::MeshExists);
+        }
         let n = vertices.len();
-        if before_count_vertices + n > GlobalIndexType::MAX as usize {
-            return Err(MeshStorageError::MaxVerticesExceeded);
+        if self.vertex_storage.len() + n > GlobalIndexType::MAX as usize {
+            self.compact();
+            if self.vertex_storage.len() + n > GlobalIndexType::MAX as usize {
+                return Err(MeshStorageError::MaxVerticesExceeded);
+            }
         }
-        let id = self.map.len() as u64;
+        let before_count_vertices = self.vertex_storage.len();
+        let before_count_indexes = self.index_storage.len();
 
         for i in indices {
             let i = *i as usize;
@@ -85,7 +107,6 @@ where
                 .map(|index| *index + before_count_vertices as GlobalIndexType),
         );
 
-        // TODO: Inserting a mesh with the same mesh_id multiple times will result in dead vertices/indices.
         self.map.insert(
             id,
             (
@@ -96,7 +117,53 @@ where
             ),
         );
 
-        Ok(id)
+        Ok(())
+    }
+
+    /// Removes a mesh, returning whether it existed. Its vertices and indices are only freed by compact.
+    pub fn remove_mesh(&mut self, id: u64) -> bool {
+        self.map.remove(&id).is_some()
+    }
+
+    /// Vertices and indices left behind by removed meshes.
+    pub fn dead_len(&self) -> (usize, usize) {
+        let (vertices, indices) = self
+            .map
+            .values()
+            .fold((0, 0), |(v, i), (s_v, e_v, s_i, e_i)| {
+                (v + e_v - s_v, i + e_i - s_i)
+            });
+        (
+            self.vertex_storage.len() - vertices,
+            self.index_storage.len() - indices,
+        )
+    }
+
+    /// Rebuilds the storage with only the meshes which weren't removed, packed in their current order, and
+    /// remaps their offsets. Ids don't change. The buffers are updated on the next update_gpu.
+    pub fn compact(&mut self) {
+        let mut meshes: Vec<(u64, (usize, usize, usize, usize))> =
+            self.map.iter().map(|(id, range)| (*id, *range)).collect();
+        meshes.sort_by_key(|(_, (s_v, ..))| *s_v);
+
+        let mut vertex_storage = Vec::with_capacity(self.vertex_storage.len());
+        let mut index_storage = Vec::with_capacity(self.index_storage.len());
+        for (id, (s_v, e_v, s_i, e_i)) in meshes {
+            let new_s_v = vertex_storage.len();
+            let new_s_i = index_storage.len();
+            vertex_storage.extend_from_slice(&self.vertex_storage[s_v..e_v]);
+            index_storage.extend(
+                self.index_storage[s_i..e_i]
+                    .iter()
+                    .map(|index| *index - s_v as GlobalIndexType + new_s_v as GlobalIndexType),
+            );
+            self.map.insert(
+                id,
+                (new_s_v, vertex_storage.len(), new_s_i, index_storage.len()),
+            );
+        }
+        self.vertex_storage = vertex_storage;
+        self.index_storage = index_storage;
     }
 
     pub fn vertex_slice<S: RangeBounds<u64>>(&self, bounds: S) -> BufferSlice<'_> {
@@ -155,6 +222,22 @@ where
         self.map.get(mesh_id).map(|(_, _, s_i, e_i)| (*s_i, *e_i))
     }
 
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Same as get_mesh, with indices relative to the mesh's vertices like those passed to add_mesh.
+    pub fn get_mesh_data(&self, mesh_id: &u64) -> Option<(&[V], Vec<GlobalIndexType>)> {
+        let (s_v, ..) = self.map.get(mesh_id)?;
+        let (vertices, indices) = self.get_mesh(mesh_id)?;
+        let offset = *s_v as GlobalIndexType;
+        Some((vertices, indices.iter().map(|i| i - offset).collect()))
+    }
+
     /// Returns a direct representation of a mesh.
     ///
     /// Likely not needed for draw calls. Use get_mesh_index_bounds instead.
@@ -176,7 +259,51 @@ pub enum MeshStorageError {
     /// >2^16 vertices were added.
     MaxVerticesExceeded,
 
+    /// A mesh with the id being inserted already exists.
     MeshExists,
 
     TobjModelError(TobjModelError),
 }
+
+#[allow(unused_imports)]
+mod tests {
+    use crate::render::{storage::mesh::MeshStorage, test_device};
+
+    #[test]
+    fn removed_middle_mesh_compacted_out() {
+        let Some((device, queue)) = test_device() else {
+            return;
+        };
+        let mut storage = MeshStorage::<[f32; 4]>::new(&device);
+        let triangle = |x: f32| [[x, 0.0, 0.0, 1.0], [x, 1.0, 0.0, 1.0], [x, 0.0, 1.0, 1.0]];
+        let quad = [[5.0; 4]; 4];
+        let a = storage.add_mesh(&triangle(1.0), &[0, 1, 2]).unwrap();
+        let b = storage.add_mesh(&quad, &[0, 1, 2, 2, 3, 0]).unwrap();
+        let c = storage.add_mesh(&triangle(2.0), &[2, 1, 0]).unwrap();
+
+        assert!(storage.remove_mesh(b));
+        assert!(!storage.remove_mesh(b));
+        assert_eq!(storage.dead_len(), (4, 6));
+        storage.compact();
+        assert_eq!(storage.dead_len(), (0, 0));
+        storage.update_gpu(&queue, &device);
+
+        // ids aren't reused
+        let d = storage.add_mesh(&triangle(3.0), &[0, 2, 1]).unwrap();
+        assert_eq!(d, 3);
+        assert!(storage.get_mesh(&b).is_none());
+
+        for (id, vertices, indices) in [
+            (a, triangle(1.0), vec![0, 1, 2]),
+            (c, triangle(2.0), vec![2, 1, 0]),
+            (d, triangle(3.0), vec![0, 2, 1]),
+        ] {
+            let (stored, stored_indices) = storage.get_mesh_data(&id).unwrap();
+            assert_eq!(stored, vertices);
+            assert_eq!(stored_indices, indices);
+            let (start, end) = storage.get_mesh_index_bounds(&id).unwrap();
+            assert_eq!(end - start, 3);
+        }
+        assert_eq!(storage.num_indices(), 9);
+    }
+}
@@ -1,18 +1,59 @@
 use std::collections::HashMap;
 
-use image::{DynamicImage, ImageBuffer, imageops::FilterType};
+use image::{
+    DynamicImage, RgbaImage,
+    imageops::{self, FilterType},
+};
 use wgpu::{
     AddressMode, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindingResource,
     Device, Extent3d, FilterMode, Origin3d, Queue, Sampler, SamplerDescriptor,
     TexelCopyBufferLayout, TexelCopyTextureInfoBase, Texture, TextureAspect, TextureDescriptor,
     TextureDimension, TextureFormat, TextureUsages, TextureView, TextureViewDescriptor,
+    TextureViewDimension,
 };
 
-use crate::core::MIPMAP_LEVELS;
+use crate::render::app::TextureInitData;
 
-#[derive(Clone)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum MipLevel {
     Square(u32),
+    /// Width, height.
+    Rect(u32, u32),
+}
+
+impl MipLevel {
+    pub fn width(&self) -> u32 {
+        match self {
+            MipLevel::Square(size) => *size,
+            MipLevel::Rect(width, _) => *width,
+        }
+    }
+
+    pub fn height(&self) -> u32 {
+        match self {
+            MipLevel::Square(size) => *size,
+            MipLevel::Rect(_, height) => *height,
+        }
+    }
+}
+
+/// Every mip level of a [width] x [height] texture, from full size down to 1x1, each half the size of
+/// the last (rounded down, at least 1).
+pub fn mip_chain(width: u32, height: u32) -> Vec<MipLevel> {
+    let (mut width, mut height) = (width.max(1), height.max(1));
+    let mut levels = vec![];
+    loop {
+        levels.push(if width == height {
+            MipLevel::Square(width)
+        } else {
+            MipLevel::Rect(width, height)
+        });
+        if width == 1 && height == 1 {
+            return levels;
+        }
+        width = (width / 2).max(1);
+        height = (height / 2).max(1);
+    }
 }
 
 pub enum Side2H {
@@ -33,17 +74,46 @@ pub enum ResizeStrategy {
     ShrinkToFit(FilterType),
 }
 
+/// How a texture's values are interpreted, which decides its format.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextureKind {
+    /// Colours, stored in sRGB and converted to linear when sampled.
+    Color,
+    /// Values sampled as-is, i.e. normal maps and heightmaps.
+    Data,
+}
+
+impl TextureKind {
+    pub fn format(&self) -> TextureFormat {
+        match self {
+            TextureKind::Color => TextureFormat::Rgba8UnormSrgb,
+            TextureKind::Data => TextureFormat::Rgba8Unorm,
+        }
+    }
+}
+
 type TextureEntry = (Texture, TextureView, Sampler, BindGroup);
 
+/// Reserved for the built-in missing texture, never returned by TextureStorage::new_texture.
+pub const MISSING_TEXTURE_ID: u64 = u64::MAX;
+
+/// Reserved for the built-in missing texture array, see TextureStorage::add_missing_texture_array.
+pub const MISSING_TEXTURE_ARRAY_ID: u64 = u64::MAX - 1;
+
+/// Color of the missing texture, picked to stand out.
+const MISSING_TEXTURE_COLOR: [u8; 4] = [255, 0, 255, 255];
+
 #[derive(Debug)]
 pub struct TextureStorage {
     textures: HashMap<u64, TextureEntry>,
+    next_id: u64,
 }
 
 impl TextureStorage {
     pub fn new() -> Self {
         Self {
             textures: HashMap::new(),
+            next_id: 0,
         }
     }
 
@@ -51,104 +121,478 @@ impl TextureStorage {
         self.textures.get(texture_id)
     }
 
-    // TODO: This currently ignores resize_strategy and just stretches.
+    /// Same as TextureStorage::get, falling back to the missing texture if [texture_id] wasn't added.
+    ///
+    /// Panics if TextureStorage::add_missing_texture hasn't been called.
+    pub fn get_or_missing(&self, texture_id: &u64) -> &TextureEntry {
+        self.textures
+            .get(texture_id)
+            .or_else(|| self.textures.get(&MISSING_TEXTURE_ID))
+            .expect("missing texture not added")
+    }
+
+    /// Adds the 1x1 magenta texture at MISSING_TEXTURE_ID, used in place of textures which don't exist.
+    pub fn add_missing_texture(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        bind_group_layout: &BindGroupLayout,
+    ) {
+        let entry = missing_texture(
+            device,
+            queue,
+            1,
+            TextureViewDimension::D2,
+            bind_group_layout,
+        );
+        self.textures.insert(MISSING_TEXTURE_ID, entry);
+    }
+
+    /// Adds a 1x1 magenta texture array with [layers] layers at MISSING_TEXTURE_ARRAY_ID, for bind group
+    /// layouts expecting an array (i.e. terrain textures).
+    pub fn add_missing_texture_array(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        layers: u32,
+        bind_group_layout: &BindGroupLayout,
+    ) {
+        let entry = missing_texture(
+            device,
+            queue,
+            layers,
+            TextureViewDimension::D2Array,
+            bind_group_layout,
+        );
+        self.textures.insert(MISSING_TEXTURE_ARRAY_ID, entry);
+    }
 
     /// Adds a new texture to storage and returns its ID.
     ///
-    /// This will generate all mipmap levels for the texture.
-    /// For [full_size_image], check crate::MIPMAP_LEVELS.
+    /// The texture is [data].size, or the image's own size if not given, limited to the device's maximum.
+    /// This will generate its full mip chain, each level fit to its size with [data].resize.
     pub fn new_texture(
         &mut self,
         device: &mut Device,
         queue: &mut Queue,
-        full_size_image: DynamicImage,
-        _resize_strategy: ResizeStrategy,
+        data: TextureInitData,
+        bind_group_layout: &BindGroupLayout,
+    ) -> u64 {
+        self.insert_texture(
+            device,
+            queue,
+            &[data],
+            TextureViewDimension::D2,
+            bind_group_layout,
+        )
+    }
+
+    /// Adds a texture array with a layer per entry of [layers], in order, and returns its ID. Bound as a
+    /// texture_2d_array.
+    ///
+    /// Every layer is the size of the first, see TextureStorage::new_texture, and each is fit to it with its
+    /// own resize strategy. The first layer's kind decides the format. Panics if [layers] is empty.
+    pub fn new_texture_array(
+        &mut self,
+        device: &mut Device,
+        queue: &mut Queue,
+        layers: &[TextureInitData],
+        bind_group_layout: &BindGroupLayout,
+    ) -> u64 {
+        self.insert_texture(
+            device,
+            queue,
+            layers,
+            TextureViewDimension::D2Array,
+            bind_group_layout,
+        )
+    }
+
+    fn insert_texture(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        layers: &[TextureInitData],
+        dimension: TextureViewDimension,
         bind_group_layout: &BindGroupLayout,
     ) -> u64 {
-        let texture_id = self.textures.len() as u64;
+        let first = layers.first().expect("a texture needs at least one layer");
+        let texture_id = self.next_id;
+        self.next_id += 1;
+        let max_size = device.limits().max_texture_dimension_2d;
+        let (width, height) = first
+            .size
+            .unwrap_or((first.image.width(), first.image.height()));
+        let levels = mip_chain(width.clamp(1, max_size), height.clamp(1, max_size));
         let texture = device.create_texture(&TextureDescriptor {
             label: Some(&format!("Texture: {}", texture_id)),
             size: Extent3d {
-                width: 2048,
-                height: 2048,
-                depth_or_array_layers: 1,
+                width: levels[0].width(),
+                height: levels[0].height(),
+                depth_or_array_layers: layers.len() as u32,
             },
-            mip_level_count: MIPMAP_LEVELS.len() as u32,
+            mip_level_count: levels.len() as u32,
             sample_count: 1,
             dimension: TextureDimension::D2,
-            format: TextureFormat::Rgba8UnormSrgb,
+            format: first.kind.format(),
             usage: TextureUsages::COPY_DST | TextureUsages::TEXTURE_BINDING,
             view_formats: &[],
         });
-        let images: Vec<(MipLevel, ImageBuffer<image::Rgba<u8>, Vec<u8>>)> = MIPMAP_LEVELS
-            .map(|level| match level {
-                MipLevel::Square(size) => {
-                    let start_width = full_size_image.width();
-                    let start_height = full_size_image.height();
-                    let _delta_w = size as i64 - start_width as i64;
-                    let _delta_w = size as i64 - start_height as i64;
-
-                    let image = full_size_image.resize_exact(size, size, FilterType::Gaussian);
-                    (level, image.to_rgba8())
-                }
-            })
-            .to_vec();
-
-        for i in 0..images.len() {
-            let level = i as u32;
-            let (level_desc, image) = &images[i];
-            queue.write_texture(
-                TexelCopyTextureInfoBase {
-                    texture: &texture,
-                    mip_level: level,
-                    origin: Origin3d::ZERO,
-                    aspect: TextureAspect::All,
-                },
-                image,
-                match level_desc {
-                    MipLevel::Square(s) => TexelCopyBufferLayout {
+
+        for (layer, data) in layers.iter().enumerate() {
+            for (i, level) in levels.iter().enumerate() {
+                let image = fit_to_size(&data.image, level.width(), level.height(), &data.resize);
+                queue.write_texture(
+                    TexelCopyTextureInfoBase {
+                        texture: &texture,
+                        mip_level: i as u32,
+                        origin: Origin3d {
+                            x: 0,
+                            y: 0,
+                            z: layer as u32,
+                        },
+                        aspect: TextureAspect::All,
+                    },
+                    &image,
+                    TexelCopyBufferLayout {
                         offset: 0,
-                        bytes_per_row: Some(*s * 4),
-                        rows_per_image: Some(*s),
+                        bytes_per_row: Some(image.width() * 4),
+                        rows_per_image: Some(image.height()),
                     },
-                },
-                match level_desc {
-                    MipLevel::Square(s) => Extent3d {
-                        width: *s,
-                        height: *s,
+                    Extent3d {
+                        width: image.width(),
+                        height: image.height(),
                         depth_or_array_layers: 1,
                     },
-                },
-            );
+                );
+            }
         }
-        let view = texture.create_view(&TextureViewDescriptor::default());
-        let sampler = device.create_sampler(&SamplerDescriptor {
-            label: Some(&format!("Texture Sampler: {}", texture_id)),
-            address_mode_u: AddressMode::ClampToEdge,
-            address_mode_v: AddressMode::ClampToEdge,
-            address_mode_w: AddressMode::ClampToEdge,
-            mag_filter: FilterMode::Linear,
-            min_filter: FilterMode::Linear,
-            mipmap_filter: FilterMode::Linear,
-            ..Default::default()
-        });
+        let entry = bind_texture(
+            device,
+            texture,
+            &texture_id.to_string(),
+            dimension,
+            bind_group_layout,
+        );
+        self.textures.insert(texture_id, entry);
+        texture_id
+    }
+}
+
+/// 1x1 magenta texture with [layers] layers.
+fn missing_texture(
+    device: &Device,
+    queue: &Queue,
+    layers: u32,
+    dimension: TextureViewDimension,
+    bind_group_layout: &BindGroupLayout,
+) -> TextureEntry {
+    let size = Extent3d {
+        width: 1,
+        height: 1,
+        depth_or_array_layers: layers,
+    };
+    let texture = device.create_texture(&TextureDescriptor {
+        label: Some("Texture: missing"),
+        size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: TextureDimension::D2,
+        format: TextureKind::Color.format(),
+        usage: TextureUsages::COPY_DST | TextureUsages::TEXTURE_BINDING,
+        view_formats: &[],
+    });
+    queue.write_texture(
+        TexelCopyTextureInfoBase {
+            texture: &texture,
+            mip_level: 0,
+            origin: Origin3d::ZERO,
+            aspect: TextureAspect::All,
+        },
+        &MISSING_TEXTURE_COLOR.repeat(layers as usize),
+        TexelCopyBufferLayout {
+            offset: 0,
+            bytes_per_row: Some(4),
+            rows_per_image: Some(1),
+        },
+        size,
+    );
+    bind_texture(device, texture, "missing", dimension, bind_group_layout)
+}
+
+/// [image] as a [width] x [height] image, resized or cropped according to [strategy]. Pixels not covered
+/// by the image are transparent.
+fn fit_to_size(
+    image: &DynamicImage,
+    width: u32,
+    height: u32,
+    strategy: &ResizeStrategy,
+) -> RgbaImage {
+    let (top, x, y) = match strategy {
+        ResizeStrategy::Stretch(filter) => {
+            return image.resize_exact(width, height, *filter).to_rgba8();
+        }
+        ResizeStrategy::ShrinkToFit(filter) => {
+            // centered, keeping the aspect ratio
+            let fitted = image.resize(width, height, *filter).to_rgba8();
+            let x = (width - fitted.width()) / 2;
+            let y = (height - fitted.height()) / 2;
+            (fitted, x as i64, y as i64)
+        }
+        ResizeStrategy::Crop(h, v) => {
+            // kept at full size, against the sides which aren't trimmed
+            let x = match h {
+                Side2H::Left => width as i64 - image.width() as i64,
+                Side2H::Right => 0,
+            };
+            let y = match v {
+                Side2V::Up => height as i64 - image.height() as i64,
+                Side2V::Down => 0,
+            };
+            (image.to_rgba8(), x, y)
+        }
+    };
+    let mut canvas = RgbaImage::new(width, height);
+    imageops::replace(&mut canvas, &top, x, y);
+    canvas
+}
+
+/// Creates the view, sampler and bind group of [texture].
+fn bind_texture(
+    device: &Device,
+    texture: Texture,
+    label: &str,
+    dimension: TextureViewDimension,
+    bind_group_layout: &BindGroupLayout,
+) -> TextureEntry {
+    let view = texture.create_view(&TextureViewDescriptor {
+        dimension: Some(dimension),
+        ..Default::default()
+    });
+    let sampler = device.create_sampler(&SamplerDescriptor {
+        label: Some(&format!("Texture Sampler: {}", label)),
+        address_mode_u: AddressMode::ClampToEdge,
+        address_mode_v: AddressMode::ClampToEdge,
+        address_mode_w: AddressMode::ClampToEdge,
+        mag_filter: FilterMode::Linear,
+        min_filter: FilterMode::Linear,
+        mipmap_filter: FilterMode::Linear,
+        ..Default::default()
+    });
+
+    let bind_group = device.create_bind_group(&BindGroupDescriptor {
+        label: Some(&format!("Texture Bind Group: {}", label)),
+        layout: bind_group_layout,
+        entries: &[
+            BindGroupEntry {
+                binding: 0,
+                resource: BindingResource::TextureView(&view),
+            },
+            BindGroupEntry {
+                binding: 1,
+                resource: BindingResource::Sampler(&sampler),
+            },
+        ],
+    });
+    (texture, view, sampler, bind_group)
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use image::{DynamicImage, Rgba, RgbaImage, imageops::FilterType};
+    use wgpu::{
+        BindGroupLayout, BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingType, Device,
+        SamplerBindingType, ShaderStages, TextureFormat, TextureSampleType, TextureViewDimension,
+    };
+
+    use crate::render::{
+        app::TextureInitData,
+        storage::textures::{
+            MISSING_TEXTURE_ID, MipLevel, ResizeStrategy, Side2H, Side2V, TextureKind,
+            TextureStorage, fit_to_size, mip_chain,
+        },
+        test_device,
+    };
 
-        let bind_group = device.create_bind_group(&BindGroupDescriptor {
-            label: Some(&format!("Texture Bind Group: {}", texture_id)),
-            layout: bind_group_layout,
+    #[cfg(test)]
+    fn texture_layout(device: &Device) -> BindGroupLayout {
+        device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: None,
             entries: &[
-                BindGroupEntry {
+                BindGroupLayoutEntry {
                     binding: 0,
-                    resource: BindingResource::TextureView(&view),
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
                 },
-                BindGroupEntry {
+                BindGroupLayoutEntry {
                     binding: 1,
-                    resource: BindingResource::Sampler(&sampler),
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
                 },
             ],
-        });
-        self.textures
-            .insert(texture_id, (texture, view, sampler, bind_group));
-        texture_id
+        })
+    }
+
+    #[test]
+    fn format_follows_kind() {
+        let Some((mut device, mut queue)) = test_device() else {
+            return;
+        };
+        let layout = texture_layout(&device);
+        let mut storage = TextureStorage::new();
+        let mut add = |kind| {
+            storage.new_texture(
+                &mut device,
+                &mut queue,
+                TextureInitData {
+                    image: DynamicImage::new_rgba8(4, 4),
+                    resize: ResizeStrategy::Stretch(FilterType::Nearest),
+                    kind,
+                    size: None,
+                },
+                &layout,
+            )
+        };
+        let color = add(TextureKind::Color);
+        let data = add(TextureKind::Data);
+
+        assert_eq!(
+            storage.get(&color).unwrap().0.format(),
+            TextureFormat::Rgba8UnormSrgb
+        );
+        assert_eq!(
+            storage.get(&data).unwrap().0.format(),
+            TextureFormat::Rgba8Unorm
+        );
+    }
+
+    #[test]
+    fn missing_textures_fall_back() {
+        let Some((mut device, mut queue)) = test_device() else {
+            return;
+        };
+        let layout = texture_layout(&device);
+        let mut storage = TextureStorage::new();
+        storage.add_missing_texture(&device, &queue, &layout);
+
+        // no user textures, the renderer's texture 1 is the fallback
+        assert!(storage.get(&1).is_none());
+        let fallback = &storage.get_or_missing(&1).0;
+        assert_eq!((fallback.width(), fallback.height()), (1, 1));
+
+        // user ids aren't shifted by the reserved one
+        let id = storage.new_texture(
+            &mut device,
+            &mut queue,
+            TextureInitData {
+                image: DynamicImage::new_rgba8(4, 4),
+                resize: ResizeStrategy::Stretch(FilterType::Nearest),
+                kind: TextureKind::Color,
+                size: None,
+            },
+            &layout,
+        );
+        assert_eq!(id, 0);
+        assert_ne!(id, MISSING_TEXTURE_ID);
+        assert_eq!(storage.get_or_missing(&id).0.width(), 4);
+    }
+
+    #[test]
+    fn mip_chain_halves_down_to_one_pixel() {
+        assert_eq!(
+            mip_chain(8, 2),
+            vec![
+                MipLevel::Rect(8, 2),
+                MipLevel::Rect(4, 1),
+                MipLevel::Rect(2, 1),
+                MipLevel::Square(1)
+            ]
+        );
+        assert_eq!(mip_chain(5, 5).len(), 3);
+        assert_eq!(mip_chain(1, 1), vec![MipLevel::Square(1)]);
+
+        let Some((mut device, mut queue)) = test_device() else {
+            return;
+        };
+        let layout = texture_layout(&device);
+        let mut storage = TextureStorage::new();
+        let mut add = |size| {
+            let id = storage.new_texture(
+                &mut device,
+                &mut queue,
+                TextureInitData {
+                    image: DynamicImage::new_rgba8(5, 3),
+                    resize: ResizeStrategy::ShrinkToFit(FilterType::Nearest),
+                    kind: TextureKind::Color,
+                    size,
+                },
+                &layout,
+            );
+            let texture = &storage.get(&id).unwrap().0;
+            (texture.width(), texture.height(), texture.mip_level_count())
+        };
+
+        // odd widths need no padding per row
+        assert_eq!(add(None), (5, 3, 3));
+        assert_eq!(add(Some((64, 16))), (64, 16, 7));
+    }
+
+    #[test]
+    fn resize_strategies_pad_with_transparency() {
+        const RED: Rgba<u8> = Rgba([255, 0, 0, 255]);
+        // 8 wide, 4 tall, opaque
+        let wide = DynamicImage::ImageRgba8(RgbaImage::from_pixel(8, 4, RED));
+        let transparent = |image: &RgbaImage, x, y| image.get_pixel(x, y)[3] == 0;
+
+        let stretched = fit_to_size(&wide, 16, 16, &ResizeStrategy::Stretch(FilterType::Nearest));
+        assert_eq!(stretched.dimensions(), (16, 16));
+        assert!(stretched.pixels().all(|p| *p == RED));
+
+        // scaled to 16x8, centered vertically
+        let shrunk = fit_to_size(
+            &wide,
+            16,
+            16,
+            &ResizeStrategy::ShrinkToFit(FilterType::Nearest),
+        );
+        assert_eq!(shrunk.dimensions(), (16, 16));
+        for y in 0..16 {
+            assert_eq!(
+                transparent(&shrunk, 0, y),
+                !(4..12).contains(&y),
+                "row {}",
+                y
+            );
+        }
+
+        // larger than the image, so nothing is trimmed and the image sits against the bottom right
+        let cropped = fit_to_size(
+            &wide,
+            10,
+            10,
+            &ResizeStrategy::Crop(Side2H::Left, Side2V::Up),
+        );
+        assert_eq!(cropped.dimensions(), (10, 10));
+        assert!(transparent(&cropped, 1, 9) && transparent(&cropped, 9, 5));
+        assert_eq!(*cropped.get_pixel(2, 6), RED);
+        assert_eq!(*cropped.get_pixel(9, 9), RED);
+
+        // smaller, so the left and top are trimmed
+        let mut marked = RgbaImage::from_pixel(8, 4, RED);
+        marked.put_pixel(7, 3, Rgba([0, 255, 0, 255]));
+        let trimmed = fit_to_size(
+            &DynamicImage::ImageRgba8(marked),
+            2,
+            2,
+            &ResizeStrategy::Crop(Side2H::Left, Side2V::Up),
+        );
+        assert_eq!(*trimmed.get_pixel(1, 1), Rgba([0, 255, 0, 255]));
+        assert!(trimmed.pixels().all(|p| p[3] == 255));
     }
 }
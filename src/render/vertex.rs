@@ -4,7 +4,7 @@
 
 pub mod default;
 pub type DefaultVertexType = default::Vertex;
-pub type DefaultInstanceType = [[f32; 4]; 4];
+pub type DefaultInstanceType = default::Instance;
 
 pub mod terrain;
 pub type TerrainVertexType = terrain::TerrainVertex;
@@ -13,3 +13,7 @@ pub type TerrainInstanceType = f32;
 pub mod marker;
 pub type MarkerVertexType = marker::MarkerVertex;
 pub type MarkerInstanceType = marker::MarkerInstance;
+
+pub mod ui;
+pub type UiVertexType = ui::UiVertex;
+pub type UiInstanceType = ui::UiInstance;
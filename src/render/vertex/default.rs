@@ -1,4 +1,5 @@
 use bytemuck::{Pod, Zeroable};
+use nalgebra::{Matrix3, Matrix4, Vector3};
 use wgpu::{BufferAddress, VertexAttribute, VertexBufferLayout, VertexFormat, VertexStepMode};
 
 #[repr(C)]
@@ -9,6 +10,63 @@ pub struct Vertex {
     pub tex_coords: [f32; 2],
 }
 
+/// Raw scale, rotation and translation components. The model matrix is assembled in the vertex shader,
+/// so nothing needs to be computed on the CPU when an entity moves.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Pod, Zeroable)]
+pub struct Instance {
+    /// Unit quaternion (x, y, z, w).
+    pub rotation: [f32; 4],
+    pub translation: [f32; 3],
+    pub scale: [f32; 3],
+    /// 0.0 is fully lit by the light source, 1.0 shows the texture's color regardless of lighting.
+    pub emissive: f32,
+    /// Multiplies the texture's color.
+    pub tint: [f32; 3],
+}
+
+impl Instance {
+    /// Host-side version of the matrix assembled in default.wgsl. Applies scale, then rotation, then translation.
+    pub fn model_matrix(&self) -> Matrix4<f32> {
+        let mut matrix = (self.rotation_matrix()
+            * Matrix3::from_diagonal(&Vector3::from(self.scale)))
+        .to_homogeneous();
+        matrix
+            .fixed_view_mut::<3, 1>(0, 3)
+            .copy_from(&Vector3::from(self.translation));
+        matrix
+    }
+
+    /// Host-side version of the normal transform in default.wgsl, the inverse transpose of the model matrix's
+    /// upper 3x3. Normals transformed by it stay perpendicular to their surface under non-uniform scale.
+    /// Isn't normalized.
+    pub fn normal_matrix(&self) -> Matrix3<f32> {
+        self.rotation_matrix() * Matrix3::from_diagonal(&Vector3::from(self.scale).map(|s| 1.0 / s))
+    }
+
+    fn rotation_matrix(&self) -> Matrix3<f32> {
+        let [x, y, z, w] = self.rotation;
+        // arguments are row-major
+        Matrix3::new(
+            1.0 - 2.0 * (y * y + z * z),
+            2.0 * (x * y - w * z),
+            2.0 * (x * z + w * y),
+            2.0 * (x * y + w * z),
+            1.0 - 2.0 * (x * x + z * z),
+            2.0 * (y * z - w * x),
+            2.0 * (x * z - w * y),
+            2.0 * (y * z + w * x),
+            1.0 - 2.0 * (x * x + y * y),
+        )
+    }
+
+    /// Host-side version of shade in default.wgsl. The lighting is faded out and replaced by the base
+    /// color as [emissive] goes from 0.0 to 1.0.
+    pub fn shade(base: Vector3<f32>, lighting: Vector3<f32>, emissive: f32) -> Vector3<f32> {
+        base.component_mul(&(lighting * (1.0 - emissive) + Vector3::repeat(emissive)))
+    }
+}
+
 impl Vertex {
     pub const fn vertex_desc() -> VertexBufferLayout<'static> {
         VertexBufferLayout {
@@ -47,17 +105,22 @@ impl Vertex {
                 VertexAttribute {
                     offset: std::mem::size_of::<[f32; 4]>() as BufferAddress,
                     shader_location: 6,
-                    format: VertexFormat::Float32x4,
+                    format: VertexFormat::Float32x3,
                 },
                 VertexAttribute {
-                    offset: std::mem::size_of::<[f32; 8]>() as BufferAddress,
+                    offset: std::mem::size_of::<[f32; 7]>() as BufferAddress,
                     shader_location: 7,
-                    format: VertexFormat::Float32x4,
+                    format: VertexFormat::Float32x3,
                 },
                 VertexAttribute {
-                    offset: std::mem::size_of::<[f32; 12]>() as BufferAddress,
+                    offset: std::mem::size_of::<[f32; 10]>() as BufferAddress,
                     shader_location: 8,
-                    format: VertexFormat::Float32x4,
+                    format: VertexFormat::Float32,
+                },
+                VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 11]>() as BufferAddress,
+                    shader_location: 9,
+                    format: VertexFormat::Float32x3,
                 },
             ],
         }
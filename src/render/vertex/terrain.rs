@@ -1,12 +1,41 @@
 use bytemuck::{Pod, Zeroable};
 use wgpu::{BufferAddress, VertexAttribute, VertexBufferLayout, VertexFormat, VertexStepMode};
 
+use crate::core::{Instanced, Meshed, Unique};
+
+/// The single instance of a terrain chunk's mesh. Chunk meshes are already in world space, so it isn't
+/// offset.
+pub struct TerrainChunk {
+    pub mesh_id: u64,
+}
+
+impl Instanced<super::TerrainInstanceType> for TerrainChunk {
+    fn instance(&self) -> super::TerrainInstanceType {
+        0.0
+    }
+}
+
+impl Unique<u64> for TerrainChunk {
+    fn id(&self) -> &u64 {
+        // one instance per mesh
+        &self.mesh_id
+    }
+}
+
+impl Meshed<u64> for TerrainChunk {
+    fn mesh_id(&self) -> &u64 {
+        &self.mesh_id
+    }
+}
+
 #[repr(C)]
 #[derive(Debug, Clone, Copy, Pod, Zeroable)]
 pub struct TerrainVertex {
     pub position: [f32; 3],
     pub normal: [f32; 3],
     pub tex_coords: [f32; 2],
+    /// Weight of each terrain texture, indexed by SplatLayer. Sums to 1.
+    pub splat: [f32; 4],
 }
 
 impl TerrainVertex {
@@ -30,6 +59,11 @@ impl TerrainVertex {
                     shader_location: 2,
                     format: VertexFormat::Float32x2,
                 },
+                VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 8]>() as BufferAddress,
+                    shader_location: 3,
+                    format: VertexFormat::Float32x4,
+                },
             ],
         }
     }
@@ -0,0 +1,94 @@
+use bytemuck::{Pod, Zeroable};
+use nalgebra::Vector2;
+use wgpu::{BufferAddress, VertexAttribute, VertexBufferLayout, VertexFormat, VertexStepMode};
+
+use crate::{
+    Float,
+    core::{Instanced, Meshed, Unique},
+};
+
+/// A screen-space element drawn in the UI pass, after the 3D scene.
+///
+/// Positions are in pixels, with (0, 0) at the top left of the window.
+pub struct UiElement {
+    pub id: u64,
+    pub mesh_id: u64,
+    pub offset: Vector2<Float>,
+    pub scale: Vector2<Float>,
+}
+
+impl Instanced<UiInstance> for UiElement {
+    fn instance(&self) -> UiInstance {
+        UiInstance {
+            offset: self.offset.into(),
+            scale: self.scale.into(),
+        }
+    }
+}
+
+impl Unique<u64> for UiElement {
+    fn id(&self) -> &u64 {
+        &self.id
+    }
+}
+
+impl Meshed<u64> for UiElement {
+    fn mesh_id(&self) -> &u64 {
+        &self.mesh_id
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+pub struct UiVertex {
+    pub position: [Float; 2],
+    /// Non-premultiplied RGBA.
+    pub color: [Float; 4],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+pub struct UiInstance {
+    pub offset: [Float; 2],
+    pub scale: [Float; 2],
+}
+
+impl UiVertex {
+    pub const fn vertex_desc() -> VertexBufferLayout<'static> {
+        VertexBufferLayout {
+            array_stride: std::mem::size_of::<super::UiVertexType>() as BufferAddress,
+            step_mode: VertexStepMode::Vertex,
+            attributes: &[
+                VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: VertexFormat::Float32x2,
+                },
+                VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 2]>() as BufferAddress,
+                    shader_location: 1,
+                    format: VertexFormat::Float32x4,
+                },
+            ],
+        }
+    }
+
+    pub const fn instance_desc() -> VertexBufferLayout<'static> {
+        VertexBufferLayout {
+            array_stride: std::mem::size_of::<super::UiInstanceType>() as BufferAddress,
+            step_mode: VertexStepMode::Instance,
+            attributes: &[
+                VertexAttribute {
+                    offset: 0,
+                    shader_location: 5,
+                    format: VertexFormat::Float32x2,
+                },
+                VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 2]>() as BufferAddress,
+                    shader_location: 6,
+                    format: VertexFormat::Float32x2,
+                },
+            ],
+        }
+    }
+}